@@ -0,0 +1,80 @@
+use std::{cell::RefCell, rc::Rc, thread};
+
+use ankara::{
+    builtin::get_builtin_environment::get_builtin_environment,
+    interpreter::{
+        environment::Environment,
+        evaluator::{call_stack_size, EvalOption, Evaluator, DEFAULT_MAX_CALL_DEPTH},
+    },
+    lexer::Peekable,
+    parser::parse,
+};
+use criterion::{black_box, criterion_group, Criterion};
+
+const PROGRAMS: [(&str, &str); 5] = [
+    ("numericLoop", include_str!("programs/numericLoop.ank")),
+    (
+        "stringBuilding",
+        include_str!("programs/stringBuilding.ank"),
+    ),
+    ("fibonacci", include_str!("programs/fibonacci.ank")),
+    ("deepRecursion", include_str!("programs/deepRecursion.ank")),
+    ("watch", include_str!("programs/watch.ank")),
+];
+
+fn bench_lexer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lexer");
+    for (name, source_code) in PROGRAMS {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut lexer = Peekable::new(black_box(source_code));
+                while lexer.next().is_some() {}
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_parser(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parser");
+    for (name, source_code) in PROGRAMS {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut lexer = Peekable::new(black_box(source_code));
+                parse(&mut lexer).unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_evaluator(c: &mut Criterion) {
+    let mut group = c.benchmark_group("evaluator");
+    for (name, source_code) in PROGRAMS {
+        let mut lexer = Peekable::new(source_code);
+        let program = parse(&mut lexer).unwrap();
+        let builtins = Rc::new(RefCell::new(get_builtin_environment()));
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let env = Rc::new(RefCell::new(Environment::fork_isolated(&builtins)));
+                program.eval(env, &mut EvalOption::new()).unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_lexer, bench_parser, bench_evaluator);
+
+// `deepRecursion` nests calls almost as deeply as the interpreter allows, so the benchmarks
+// run with the stack `ankara` gives scripts rather than the main thread's.
+fn main() {
+    let runner = thread::Builder::new()
+        .stack_size(call_stack_size(DEFAULT_MAX_CALL_DEPTH))
+        .spawn(|| {
+            benches();
+            Criterion::default().configure_from_args().final_summary();
+        })
+        .unwrap();
+    runner.join().unwrap();
+}
@@ -0,0 +1,469 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Display;
+
+use crate::ast::{self, Expression, Statement};
+use crate::printer::print_expression;
+
+// What `ankara analyze` found in a program: functions that no code which runs ever reaches,
+// imports that are never read, and code that can never execute.
+#[derive(Debug, PartialEq, Default)]
+pub struct Report {
+    pub unused_functions: Vec<String>,
+    pub unused_imports: Vec<String>,
+    pub unreachable_code: Vec<String>,
+}
+
+impl Report {
+    pub fn is_empty(&self) -> bool {
+        self.unused_functions.is_empty()
+            && self.unused_imports.is_empty()
+            && self.unreachable_code.is_empty()
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "no dead code found");
+        }
+        for name in &self.unused_functions {
+            writeln!(f, "function {} is never called", name)?;
+        }
+        for name in &self.unused_imports {
+            writeln!(f, "import {} is never used", name)?;
+        }
+        for description in &self.unreachable_code {
+            writeln!(f, "{}", description)?;
+        }
+        Ok(())
+    }
+}
+
+// A function bound with `let` or `const`, and the names its body refers to.
+struct FunctionNode {
+    name: String,
+    references: HashSet<String>,
+}
+
+// Names are not resolved to scopes: every function bound to the same name is one node of the
+// call graph, and a name counts as used wherever it appears. A function passed around as a
+// value therefore counts as called.
+#[derive(Default)]
+struct Analyzer {
+    functions: Vec<FunctionNode>,
+    function_indexes: HashMap<String, usize>,
+    imports: Vec<String>,
+    // truthiness of constants bound to a literal, for conditions like `if (DEBUG)`
+    constants: HashMap<String, bool>,
+    // names referred to by code outside of any named function
+    roots: HashSet<String>,
+    unreachable_code: Vec<String>,
+    // the function whose body is being walked
+    owner: Option<usize>,
+    // inside code that can never execute, where references do not count
+    dead: bool,
+}
+
+pub fn analyze(program: &ast::Program) -> Report {
+    let mut analyzer = Analyzer::default();
+    for statement in &program.statements {
+        let statement = match statement {
+            Statement::ExportStatement(export) => &*export.declaration,
+            statement => statement,
+        };
+        if let Statement::ConstantDeclaration(constant) = statement {
+            if let Some(truthy) = literal_truthiness(&constant.value) {
+                analyzer.constants.insert(constant.name.clone(), truthy);
+            }
+        }
+    }
+    analyzer.walk_statements(&program.statements);
+
+    // every name that code reachable from the top level refers to
+    let mut used = HashSet::new();
+    let mut reached = vec![false; analyzer.functions.len()];
+    let mut queue: VecDeque<&String> = analyzer.roots.iter().collect();
+    while let Some(name) = queue.pop_front() {
+        used.insert(name.clone());
+        if let Some(&index) = analyzer.function_indexes.get(name) {
+            if !reached[index] {
+                reached[index] = true;
+                queue.extend(analyzer.functions[index].references.iter());
+            }
+        }
+    }
+
+    Report {
+        unused_functions: analyzer
+            .functions
+            .iter()
+            .zip(reached)
+            .filter(|(_, reached)| !reached)
+            .map(|(function, _)| function.name.clone())
+            .collect(),
+        unused_imports: analyzer
+            .imports
+            .iter()
+            .filter(|name| !used.contains(*name))
+            .cloned()
+            .collect(),
+        unreachable_code: analyzer.unreachable_code,
+    }
+}
+
+// Whether a literal condition is always true or always false, following `Object::is_falsey`.
+fn literal_truthiness(expression: &Expression) -> Option<bool> {
+    match expression {
+        Expression::BooleanLiteral(boolean) => Some(boolean.value),
+        Expression::NullLiteral => Some(false),
+        Expression::NumberLiteral(number) => Some(number.value != 0),
+        Expression::FloatLiteral(float) => Some(float.value != 0.0),
+        Expression::StringLiteral(_) => Some(true),
+        _ => None,
+    }
+}
+
+impl Analyzer {
+    fn refer(&mut self, name: &str) {
+        if self.dead {
+            return;
+        }
+        match self.owner {
+            Some(index) => {
+                self.functions[index].references.insert(name.to_string());
+            }
+            None => {
+                self.roots.insert(name.to_string());
+            }
+        }
+    }
+
+    fn report(&mut self, description: String) {
+        if !self.dead {
+            self.unreachable_code.push(description);
+        }
+    }
+
+    fn condition_truthiness(&self, condition: &Expression) -> Option<bool> {
+        match condition {
+            Expression::Identifier(identifier) => self.constants.get(&identifier.value).copied(),
+            _ => literal_truthiness(condition),
+        }
+    }
+
+    fn walk_statements(&mut self, statements: &[Statement]) {
+        for (index, statement) in statements.iter().enumerate() {
+            self.walk_statement(statement);
+            let exit = match statement {
+                Statement::ReturnStatement(_) => "return",
+                Statement::ThrowStatement(_) => "throw",
+                Statement::BreakStatement(_) => "break",
+                Statement::ContinueStatement(_) => "continue",
+                _ => continue,
+            };
+            let rest = &statements[index + 1..];
+            if !rest.is_empty() {
+                self.report(format!(
+                    "{} statement(s) after {} never run",
+                    rest.len(),
+                    exit
+                ));
+                self.walk_dead(|analyzer| analyzer.walk_statements(rest));
+            }
+            return;
+        }
+    }
+
+    fn walk_dead(&mut self, walk: impl FnOnce(&mut Analyzer)) {
+        let dead = std::mem::replace(&mut self.dead, true);
+        walk(self);
+        self.dead = dead;
+    }
+
+    fn walk_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::VariableDeclaration(declaration) => {
+                self.walk_binding(&declaration.name, &declaration.value)
+            }
+            Statement::DeclarationList(declaration_list) => {
+                for declaration in &declaration_list.declarations {
+                    self.walk_binding(&declaration.name, &declaration.value);
+                }
+            }
+            Statement::ConstantDeclaration(declaration) => {
+                self.walk_binding(&declaration.name, &declaration.value)
+            }
+            Statement::DestructuringDeclaration(declaration) => {
+                self.walk_expression(&declaration.value);
+                if let Some(alternative) = &declaration.alternative {
+                    self.walk_statements(&alternative.statements);
+                }
+            }
+            Statement::Expression(expression) => self.walk_expression(expression),
+            Statement::MultipleAssign(multiple_assign) => {
+                for expression in multiple_assign
+                    .targets
+                    .iter()
+                    .chain(&multiple_assign.values)
+                {
+                    self.walk_expression(expression);
+                }
+            }
+            Statement::ReturnStatement(statement) => self.walk_expression(&statement.value),
+            Statement::ThrowStatement(statement) => self.walk_expression(&statement.value),
+            Statement::DeferStatement(statement) => self.walk_expression(&statement.value),
+            Statement::AssertStatement(statement) => {
+                self.walk_expression(&statement.condition);
+                if let Some(message) = &statement.message {
+                    self.walk_expression(message);
+                }
+            }
+            Statement::BlockReturnStatement(statement) => self.walk_expression(&statement.value),
+            Statement::BreakStatement(statement) => {
+                if let Some(value) = &statement.value {
+                    self.walk_expression(value);
+                }
+            }
+            Statement::ContinueStatement(_) => {}
+            Statement::WatchDeclaration(watch) => self.walk_statements(&watch.block.statements),
+            Statement::TestBlock(test) => self.walk_statements(&test.body.statements),
+            Statement::ImportStatement(import) => self.imports.push(import.name.clone()),
+            // an exported name is used by whoever imports the module
+            Statement::ExportStatement(export) => {
+                for name in export.names() {
+                    self.roots.insert(name.to_string());
+                }
+                self.walk_statement(&export.declaration);
+            }
+            Statement::StructDeclaration(declaration) => {
+                for method in &declaration.methods {
+                    self.walk_statements(&method.function.body.statements);
+                }
+            }
+        }
+    }
+
+    // `let name = fn ...` adds a function to the call graph, whose body refers to names on
+    // its own behalf. Any other value is walked like an expression.
+    fn walk_binding(&mut self, name: &str, value: &Expression) {
+        let function = match value {
+            Expression::FunctionLiteral(function) => function,
+            _ => return self.walk_expression(value),
+        };
+        let index = match self.function_indexes.get(name) {
+            Some(&index) => index,
+            None => {
+                self.functions.push(FunctionNode {
+                    name: name.to_string(),
+                    references: HashSet::new(),
+                });
+                self.function_indexes
+                    .insert(name.to_string(), self.functions.len() - 1);
+                self.functions.len() - 1
+            }
+        };
+        let owner = self.owner.replace(index);
+        self.walk_statements(&function.body.statements);
+        self.owner = owner;
+    }
+
+    fn walk_expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::InfixExpression(infix) => {
+                self.walk_expression(&infix.left);
+                self.walk_expression(&infix.right);
+            }
+            Expression::PrefixExpression(prefix) => self.walk_expression(&prefix.right),
+            Expression::UpdateExpression(update) => self.walk_expression(&update.target),
+            Expression::Identifier(identifier) => self.refer(&identifier.value),
+            Expression::FunctionLiteral(function) => {
+                self.walk_statements(&function.body.statements)
+            }
+            Expression::CallExpression(call) => {
+                self.walk_expression(&call.left);
+                for argument in &call.arguments {
+                    self.walk_expression(argument);
+                }
+            }
+            Expression::IfExpression(if_expression) => self.walk_if(if_expression),
+            Expression::TemplateLiteral(template) => {
+                for part in &template.parts {
+                    if let ast::TemplatePart::Expression(expression) = part {
+                        self.walk_expression(expression);
+                    }
+                }
+            }
+            Expression::ArrayLiteral(ast::ArrayLiteral { elements, .. })
+            | Expression::MapLiteral(ast::MapLiteral { entries: elements }) => {
+                for element in elements {
+                    match element {
+                        ast::ArrayMapValue::MapKeyValue(key_value) => {
+                            self.walk_expression(&key_value.value)
+                        }
+                        ast::ArrayMapValue::ComputedKeyValue(key_value) => {
+                            self.walk_expression(&key_value.key);
+                            self.walk_expression(&key_value.value);
+                        }
+                        ast::ArrayMapValue::Value(value) => self.walk_expression(value),
+                    }
+                }
+            }
+            Expression::ArrayComprehension(comprehension) => {
+                self.walk_expression(&comprehension.element);
+                self.walk_expression(&comprehension.iterable);
+                if let Some(condition) = &comprehension.condition {
+                    self.walk_expression(condition);
+                }
+            }
+            Expression::ElementAccessExpression(element_access) => {
+                self.walk_expression(&element_access.left);
+                self.walk_expression(&element_access.index);
+            }
+            Expression::MemberAccessExpression(member_access) => {
+                self.walk_expression(&member_access.left)
+            }
+            Expression::SliceExpression(slice) => {
+                self.walk_expression(&slice.left);
+                for bound in [&slice.start, &slice.end].into_iter().flatten() {
+                    self.walk_expression(bound);
+                }
+            }
+            Expression::ForExpression(for_expression) => {
+                self.walk_expression(&for_expression.iterable);
+                self.walk_statements(&for_expression.body.statements);
+            }
+            Expression::LoopExpression(loop_expression) => {
+                self.walk_statements(&loop_expression.body.statements)
+            }
+            Expression::DoWhileExpression(do_while) => {
+                self.walk_statements(&do_while.body.statements);
+                self.walk_expression(&do_while.condition);
+            }
+            Expression::SwitchExpression(switch_expression) => {
+                self.walk_expression(&switch_expression.expression);
+                for case in &switch_expression.cases {
+                    if let ast::CaseCondition::Value(value) = &case.condition {
+                        self.walk_expression(value);
+                    }
+                    self.walk_statements(&case.body.statements);
+                }
+                if let Some(default) = &switch_expression.default {
+                    self.walk_statements(&default.body.statements);
+                }
+            }
+            Expression::MatchExpression(match_expression) => {
+                self.walk_expression(&match_expression.expression);
+                for arm in &match_expression.arms {
+                    self.walk_expression(&arm.body);
+                }
+            }
+            Expression::TryExpression(try_expression) => {
+                self.walk_statements(&try_expression.body.statements);
+                if let Some(handler) = &try_expression.handler {
+                    self.walk_statements(&handler.body.statements);
+                }
+                if let Some(finalizer) = &try_expression.finalizer {
+                    self.walk_statements(&finalizer.statements);
+                }
+            }
+            Expression::Assign(assign) => {
+                self.walk_expression(&assign.left);
+                self.walk_expression(&assign.right);
+            }
+            Expression::BlockExpression(block) => self.walk_statements(&block.statements),
+            Expression::NumberLiteral(_)
+            | Expression::FloatLiteral(_)
+            | Expression::BooleanLiteral(_)
+            | Expression::NullLiteral
+            | Expression::LineLiteral(_)
+            | Expression::FileLiteral
+            | Expression::StringLiteral(_) => {}
+        }
+    }
+
+    // A branch behind a condition that is always true or always false is reported, and what
+    // it refers to does not count as used.
+    fn walk_if(&mut self, if_expression: &ast::IfExpression) {
+        self.walk_expression(&if_expression.condition);
+        let condition = print_expression(&if_expression.condition, 0);
+        match self.condition_truthiness(&if_expression.condition) {
+            Some(true) => {
+                self.walk_statements(&if_expression.consequence.statements);
+                if let Some(alternative) = &if_expression.alternative {
+                    self.report(format!("the else branch of if ({}) never runs", condition));
+                    self.walk_dead(|analyzer| analyzer.walk_statements(&alternative.statements));
+                }
+            }
+            Some(false) => {
+                self.report(format!("the body of if ({}) never runs", condition));
+                self.walk_dead(|analyzer| {
+                    analyzer.walk_statements(&if_expression.consequence.statements)
+                });
+                if let Some(alternative) = &if_expression.alternative {
+                    self.walk_statements(&alternative.statements);
+                }
+            }
+            None => {
+                self.walk_statements(&if_expression.consequence.statements);
+                if let Some(alternative) = &if_expression.alternative {
+                    self.walk_statements(&alternative.statements);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Peekable;
+    use crate::parser::parse;
+
+    fn analyze_source(source_code: &str) -> Report {
+        let mut lexer = Peekable::new(source_code);
+        analyze(&parse(&mut lexer).unwrap())
+    }
+
+    #[test]
+    fn test_analyze() {
+        let report = analyze_source(
+            r#"
+            import config from "config.json";
+            import unused from "unused.json";
+            const DEBUG = false;
+            let helper = fn(x) { x * 2 };
+            let main = fn() { helper(config["n"]) };
+            let orphan = fn() { cousin() };
+            let cousin = fn() { orphan() };
+            let trace = fn(x) { print(x) };
+            let apply = fn(f) { f(1) };
+            let callback = fn(x) { x };
+            if (DEBUG) { trace("start") }
+            let limit = fn(x) {
+                return x;
+                print("never");
+            };
+            if (true) { main() } else { helper(1) }
+            apply(callback);
+            limit(1);
+            "#,
+        );
+        assert_eq!(
+            report,
+            Report {
+                unused_functions: vec![
+                    "orphan".to_string(),
+                    "cousin".to_string(),
+                    "trace".to_string()
+                ],
+                unused_imports: vec!["unused".to_string()],
+                unreachable_code: vec![
+                    "the body of if (DEBUG) never runs".to_string(),
+                    "1 statement(s) after return never run".to_string(),
+                    "the else branch of if (true) never runs".to_string(),
+                ],
+            }
+        );
+        assert_eq!(analyze_source("let x = 1; print(x);"), Report::default());
+    }
+}
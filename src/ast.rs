@@ -1,41 +1,202 @@
 use std::{collections::HashMap, fmt::Display};
 
+use crate::interpreter::inline_cache::MethodCache;
+use crate::interpreter::pool::LiteralPool;
 use crate::token::{self, Token};
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Program {
     pub statements: Vec<Statement>,
+    pub lines: StatementLines,
+}
+
+impl Program {
+    // Statements following a top-level `return`, which can never be executed.
+    pub fn get_unreachable_statements(&self) -> &[Statement] {
+        match self
+            .statements
+            .iter()
+            .position(|statement| matches!(statement, Statement::ReturnStatement(_)))
+        {
+            Some(index) => &self.statements[index + 1..],
+            None => &[],
+        }
+    }
+
+    // Whether the program starts with `"use strict";`, which runs it in strict mode.
+    pub fn has_strict_pragma(&self) -> bool {
+        matches!(
+            self.statements.first(),
+            Some(Statement::Expression(Expression::StringLiteral(string))) if string.value == "use strict"
+        )
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Statement {
     VariableDeclaration(VariableDeclaration),
+    DeclarationList(DeclarationList),
+    DestructuringDeclaration(DestructuringDeclaration),
+    ConstantDeclaration(ConstantDeclaration),
     Expression(Expression),
+    MultipleAssign(MultipleAssign),
     ReturnStatement(ReturnStatement),
+    ThrowStatement(ThrowStatement),
+    DeferStatement(DeferStatement),
+    AssertStatement(AssertStatement),
     BlockReturnStatement(BlockReturnStatement),
+    BreakStatement(BreakStatement),
+    ContinueStatement(ContinueStatement),
     WatchDeclaration(WatchDeclaration),
+    TestBlock(TestBlock),
+    ImportStatement(ImportStatement),
+    ExportStatement(ExportStatement),
+    StructDeclaration(StructDeclaration),
+}
+
+// `import name from "path";`, which binds the parsed contents of a data file, or the exports
+// of an `.ank` module
+#[derive(Debug, PartialEq, Clone)]
+pub struct ImportStatement {
+    pub name: String,
+    pub path: String,
+}
+
+// `export let ...;` or `export const ...;` at the top level of a module
+#[derive(Debug, PartialEq, Clone)]
+pub struct ExportStatement {
+    pub declaration: Box<Statement>,
+}
+
+impl ExportStatement {
+    pub fn names(&self) -> Vec<&str> {
+        match &*self.declaration {
+            Statement::VariableDeclaration(declaration) => vec![&declaration.name],
+            Statement::DeclarationList(declaration_list) => declaration_list
+                .declarations
+                .iter()
+                .map(|declaration| declaration.name.as_str())
+                .collect(),
+            Statement::ConstantDeclaration(declaration) => vec![&declaration.name],
+            _ => Vec::new(),
+        }
+    }
+}
+
+// `struct Name { field, ..., fn method(...) { ... } ... }`. Calling `Name(...)` makes an
+// instance, a map of the fields, and `self` in a method is the instance it was called on.
+#[derive(Debug, PartialEq, Clone)]
+pub struct StructDeclaration {
+    pub name: String,
+    pub fields: Vec<String>,
+    pub methods: Vec<StructMethod>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct StructMethod {
+    pub name: String,
+    pub function: FunctionLiteral,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct VariableDeclaration {
     pub name: String,
+    // `number` in `let x: number = 1`. Type annotations are only kept for tools, the
+    // evaluator ignores them.
+    pub type_annotation: Option<String>,
+    pub value: Expression,
+    // the line the name is declared on
+    pub position: Position,
+}
+
+// `let x = 1, y = x;`, declared one after another
+#[derive(Debug, PartialEq, Clone)]
+pub struct DeclarationList {
+    pub declarations: Vec<VariableDeclaration>,
+}
+
+// `a, b = b, a;`, which evaluates every value before assigning any of them
+#[derive(Debug, PartialEq, Clone)]
+pub struct MultipleAssign {
+    pub targets: Vec<Expression>,
+    pub values: Vec<Expression>,
+    pub position: Position,
+}
+
+// `let [a, b] = value else { ... };`
+#[derive(Debug, PartialEq, Clone)]
+pub struct DestructuringDeclaration {
+    pub pattern: ArrayPattern,
+    pub value: Expression,
+    // runs when the value does not match the pattern, and has to return
+    pub alternative: Option<BlockExpression>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Pattern {
+    Identifier(Identifier),
+    Array(ArrayPattern),
+    // a number, string or boolean the element has to equal
+    Literal(Expression),
+    // `key: pattern`, only in patterns whose elements all have keys
+    Keyed(Box<KeyedPattern>),
+    // `_`, which matches anything and binds nothing
+    Wildcard,
+}
+
+// matches the value stored under `key`, wherever it is in the array
+#[derive(Debug, PartialEq, Clone)]
+pub struct KeyedPattern {
+    pub key: String,
+    pub value: Pattern,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ArrayPattern {
+    pub elements: Vec<Pattern>,
+    // `...rest`, the array of the elements after the other patterns
+    pub rest: Option<Identifier>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ConstantDeclaration {
+    pub name: String,
+    pub type_annotation: Option<String>,
     pub value: Expression,
+    // the line the name is declared on
+    pub position: Position,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Expression {
     InfixExpression(Box<InfixExpression>),
+    PrefixExpression(Box<PrefixExpression>),
+    UpdateExpression(Box<UpdateExpression>),
     NumberLiteral(NumberLiteral),
+    FloatLiteral(FloatLiteral),
     Identifier(Identifier),
-    FunctionLiteral(FunctionLiteral),
+    FunctionLiteral(Box<FunctionLiteral>),
     CallExpression(Box<CallExpression>),
     IfExpression(Box<IfExpression>),
     BooleanLiteral(BooleanLiteral),
+    NullLiteral,
+    // `__line` and `__file`, the place in the source they are written at
+    LineLiteral(Position),
+    FileLiteral,
     StringLiteral(StringLiteral),
+    TemplateLiteral(TemplateLiteral),
     ArrayLiteral(ArrayLiteral),
+    ArrayComprehension(Box<ArrayComprehension>),
+    MapLiteral(MapLiteral),
     ElementAccessExpression(Box<ElementAccessExpression>),
+    MemberAccessExpression(Box<MemberAccessExpression>),
+    SliceExpression(Box<SliceExpression>),
     ForExpression(Box<ForExpression>),
+    DoWhileExpression(Box<DoWhileExpression>),
+    LoopExpression(Box<LoopExpression>),
     SwitchExpression(Box<SwitchExpression>),
+    MatchExpression(Box<MatchExpression>),
+    TryExpression(Box<TryExpression>),
     Assign(Box<Assign>),
     BlockExpression(BlockExpression),
 }
@@ -49,7 +210,12 @@ pub struct InfixExpression {
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct NumberLiteral {
-    pub value: i32,
+    pub value: i64,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct FloatLiteral {
+    pub value: f64,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -73,6 +239,9 @@ pub enum Operator {
     And,
     Or,
     Bang,
+    Range,
+    In,
+    Coalesce,
 }
 
 impl Operator {
@@ -92,6 +261,9 @@ impl Operator {
             Token::And => Operator::And,
             Token::Or => Operator::Or,
             Token::Bang => Operator::Bang,
+            Token::DotDot => Operator::Range,
+            Token::In => Operator::In,
+            Token::QuestionQuestion => Operator::Coalesce,
             _ => panic!("unexpected token"),
         }
     }
@@ -114,6 +286,9 @@ impl Display for Operator {
             Operator::And => "&&",
             Operator::Or => "||",
             Operator::Bang => "!",
+            Operator::Range => "..",
+            Operator::In => "in",
+            Operator::Coalesce => "??",
         };
         write!(f, "{}", operator)
     }
@@ -122,11 +297,17 @@ impl Display for Operator {
 #[derive(Debug, PartialEq, Clone)]
 pub struct BlockExpression {
     pub statements: Vec<Statement>,
+    pub lines: StatementLines,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct FunctionLiteral {
     pub parameters: Vec<Identifier>,
+    // the type annotation of each parameter, as in `fn(a: number, b)`
+    pub parameter_types: Vec<Option<String>>,
+    pub return_type: Option<String>,
+    // `...rest`, the array of the arguments after the other parameters
+    pub rest: Option<Identifier>,
     pub body: BlockExpression,
 }
 
@@ -134,6 +315,40 @@ pub struct FunctionLiteral {
 pub struct CallExpression {
     pub left: Expression,
     pub arguments: Vec<Expression>,
+    pub position: Position,
+    // what `receiver.name(...)` found last, see `MethodCache`
+    pub cache: MethodCache,
+}
+
+// Where a node was written in the source. Positions never take part in comparisons, so
+// code that moved to another line is still equal to what it was.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Position {
+    pub line: usize,
+}
+
+impl PartialEq for Position {
+    fn eq(&self, _other: &Position) -> bool {
+        true
+    }
+}
+
+// The line each statement of a program or block starts on, so an error can say where it
+// happened. Blocks the parser makes up, like the body of `x => x + 1`, have none. Like
+// positions, they never take part in comparisons.
+#[derive(Debug, Clone, Default)]
+pub struct StatementLines(pub Vec<usize>);
+
+impl StatementLines {
+    pub fn get(&self, index: usize) -> Option<usize> {
+        self.0.get(index).copied()
+    }
+}
+
+impl PartialEq for StatementLines {
+    fn eq(&self, _other: &StatementLines) -> bool {
+        true
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -141,6 +356,26 @@ pub struct ReturnStatement {
     pub value: Expression,
 }
 
+// `throw value;`, which fails like a runtime error until a `catch` receives the value
+#[derive(Debug, PartialEq, Clone)]
+pub struct ThrowStatement {
+    pub value: Expression,
+}
+
+// `defer value;`, which evaluates the value when the enclosing block ends, however it ends
+#[derive(Debug, PartialEq, Clone)]
+pub struct DeferStatement {
+    pub value: Expression,
+}
+
+// `assert condition;` or `assert condition, message;`, a runtime error when the condition is
+// falsey. The message is only evaluated then.
+#[derive(Debug, PartialEq, Clone)]
+pub struct AssertStatement {
+    pub condition: Expression,
+    pub message: Option<Expression>,
+}
+
 pub struct BlockReturn {
     pub value: Expression,
 }
@@ -152,6 +387,23 @@ pub struct IfExpression {
     pub alternative: Option<BlockExpression>,
 }
 
+// `try { ... } catch (e) { ... } finally { ... }`, the value of the body, or of the handler
+// when the body fails. Either the handler or the finalizer can be left out.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TryExpression {
+    pub body: BlockExpression,
+    pub handler: Option<CatchHandler>,
+    // runs after the body and the handler however they end
+    pub finalizer: Option<BlockExpression>,
+}
+
+// `catch (e) { ... }`, with the thrown value, or the message of a runtime error, bound to `e`
+#[derive(Debug, PartialEq, Clone)]
+pub struct CatchHandler {
+    pub parameter: Identifier,
+    pub body: BlockExpression,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct BooleanLiteral {
     pub value: bool,
@@ -162,14 +414,45 @@ pub struct StringLiteral {
     pub value: String,
 }
 
+// A string with `${...}` in it. Text parts are never empty and never adjacent.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TemplateLiteral {
+    pub parts: Vec<TemplatePart>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum TemplatePart {
+    String(String),
+    Expression(Expression),
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct ArrayLiteral {
     pub elements: Vec<ArrayMapValue>,
+    pub pool: LiteralPool,
+}
+
+// `[:]`, or a bracket literal whose elements all have keys
+// `[element for variable in iterable if condition]`, the array of `element` for each value
+// `for` would walk, leaving out those the condition is false for
+#[derive(Debug, PartialEq, Clone)]
+pub struct ArrayComprehension {
+    pub element: Expression,
+    pub variable: Identifier,
+    pub iterable: Expression,
+    pub condition: Option<Expression>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct MapLiteral {
+    // only `MapKeyValue` and `ComputedKeyValue`
+    pub entries: Vec<ArrayMapValue>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum ArrayMapValue {
     MapKeyValue(MapKeyValue),
+    ComputedKeyValue(ComputedKeyValue),
     Value(Expression),
 }
 
@@ -179,10 +462,36 @@ pub struct MapKeyValue {
     pub value: Expression,
 }
 
+// `(expression): value`, with the key evaluated when the literal is
+#[derive(Debug, PartialEq, Clone)]
+pub struct ComputedKeyValue {
+    pub key: Expression,
+    pub value: Expression,
+}
+
+// `left[index]`, or `left?[index]` when `optional`, which is null instead of an error when
+// `left` is not an array or has nothing at `index`
 #[derive(Debug, PartialEq, Clone)]
 pub struct ElementAccessExpression {
     pub left: Expression,
     pub index: Expression,
+    pub optional: bool,
+}
+
+// `left.member`, the same as `left["member"]`, or `left?.member` like `left?["member"]`
+#[derive(Debug, PartialEq, Clone)]
+pub struct MemberAccessExpression {
+    pub left: Expression,
+    pub member: String,
+    pub optional: bool,
+}
+
+// `left[start:end]`, where a missing start is the first index and a missing end the length
+#[derive(Debug, PartialEq, Clone)]
+pub struct SliceExpression {
+    pub left: Expression,
+    pub start: Option<Expression>,
+    pub end: Option<Expression>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -190,19 +499,76 @@ pub struct BlockReturnStatement {
     pub value: Expression,
 }
 
+// `break;` or `break value;`, where the value becomes the value of the loop. `break outer;`
+// leaves the enclosing loop labeled `outer:` instead of the innermost one.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BreakStatement {
+    pub label: Option<String>,
+    pub value: Option<Expression>,
+}
+
+// `continue;`, or `continue outer;` for the enclosing loop labeled `outer:`
+#[derive(Debug, PartialEq, Clone)]
+pub struct ContinueStatement {
+    pub label: Option<String>,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct PrefixExpression {
     pub operator: Operator,
     pub right: Expression,
 }
 
+// `x++`, `x--`, `++x` or `--x`. The target is an identifier or an element access.
+#[derive(Debug, PartialEq, Clone)]
+pub struct UpdateExpression {
+    pub operator: UpdateOperator,
+    pub prefix: bool,
+    pub target: Expression,
+    pub position: Position,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum UpdateOperator {
+    Increment,
+    Decrement,
+}
+
+impl Display for UpdateOperator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UpdateOperator::Increment => write!(f, "++"),
+            UpdateOperator::Decrement => write!(f, "--"),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct ForExpression {
+    // `outer` in `outer: for (...)`, for `break outer;` and `continue outer;`
+    pub label: Option<String>,
+    // `i` in `for (i, value in iterable)`, the position of the element, or its key
+    pub index: Option<Identifier>,
     pub variable: Identifier,
     pub iterable: Expression,
     pub body: BlockExpression,
 }
 
+// `do { ... } while (condition)`, which checks the condition after each run of the body
+#[derive(Debug, PartialEq, Clone)]
+pub struct DoWhileExpression {
+    pub label: Option<String>,
+    pub body: BlockExpression,
+    pub condition: Expression,
+}
+
+// `loop { ... }`, which runs until a `break`, and takes the value of `break value;`
+#[derive(Debug, PartialEq, Clone)]
+pub struct LoopExpression {
+    pub label: Option<String>,
+    pub body: BlockExpression,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct SwitchExpression {
     pub expression: Expression,
@@ -212,8 +578,18 @@ pub struct SwitchExpression {
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Case {
-    pub condition: Expression,
+    pub condition: CaseCondition,
     pub body: BlockExpression,
+    // the body ends with `fallthrough;`, which goes on to run the next body as well
+    pub fallthrough: bool,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum CaseCondition {
+    // `case 1:` compares the switched value for equality
+    Value(Expression),
+    // `case [1, x]:` matches an array by shape and binds `x` for the body
+    Pattern(ArrayPattern),
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -221,6 +597,19 @@ pub struct Default {
     pub body: BlockExpression,
 }
 
+// `match (value) { pattern => body, ... }`, the body of the first arm whose pattern fits
+#[derive(Debug, PartialEq, Clone)]
+pub struct MatchExpression {
+    pub expression: Expression,
+    pub arms: Vec<MatchArm>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: Expression,
+}
+
 impl Display for Expression {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -229,7 +618,20 @@ impl Display for Expression {
                 "{}",
                 infix.left.to_string() + &infix.operator.to_string() + &infix.right.to_string()
             ),
+            Expression::PrefixExpression(prefix) => write!(
+                f,
+                "{}",
+                prefix.operator.to_string() + &prefix.right.to_string()
+            ),
+            Expression::UpdateExpression(update) => {
+                if update.prefix {
+                    write!(f, "{}{}", update.operator, update.target)
+                } else {
+                    write!(f, "{}{}", update.target, update.operator)
+                }
+            }
             Expression::NumberLiteral(number) => write!(f, "number Literal {}", number.value),
+            Expression::FloatLiteral(float) => write!(f, "float Literal {}", float.value),
             Expression::Identifier(identifier) => write!(f, "identifier {}", identifier.value),
             Expression::FunctionLiteral(function) => write!(f, "function",),
             Expression::CallExpression(call) => {
@@ -237,17 +639,31 @@ impl Display for Expression {
             }
             Expression::IfExpression(if_expression) => write!(f, "if"),
             Expression::BooleanLiteral(boolean) => write!(f, "boolean {}", boolean.value),
+            Expression::NullLiteral => write!(f, "null"),
+            Expression::LineLiteral(position) => write!(f, "line {}", position.line),
+            Expression::FileLiteral => write!(f, "file"),
             Expression::StringLiteral(string) => write!(f, "string {}", string.value),
+            Expression::TemplateLiteral(_) => write!(f, "template"),
             Expression::ArrayLiteral(array) => write!(f, "array"),
+            Expression::ArrayComprehension(_) => write!(f, "array comprehension"),
+            Expression::MapLiteral(_) => write!(f, "map"),
             Expression::ElementAccessExpression(element_access) => {
                 write!(f, "element access {}", element_access.left.to_string())
             }
+            Expression::MemberAccessExpression(member_access) => {
+                write!(f, "member access {}", member_access.left)
+            }
+            Expression::SliceExpression(slice) => write!(f, "slice {}", slice.left),
             Expression::ForExpression(for_expression) => {
                 write!(f, "for expression")
             }
+            Expression::DoWhileExpression(_) => write!(f, "do while expression"),
+            Expression::LoopExpression(_) => write!(f, "loop expression"),
             Expression::SwitchExpression(switch_expression) => {
                 write!(f, "switch expression")
             }
+            Expression::MatchExpression(_) => write!(f, "match expression"),
+            Expression::TryExpression(_) => write!(f, "try expression"),
             Expression::Assign(assign) => {
                 write!(f, "assign expression")
             }
@@ -262,6 +678,7 @@ impl Display for Expression {
 pub struct Assign {
     pub left: Expression,
     pub right: Expression,
+    pub position: Position,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -269,3 +686,9 @@ pub struct WatchDeclaration {
     pub name: String,
     pub block: BlockExpression,
 }
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TestBlock {
+    pub name: String,
+    pub body: BlockExpression,
+}
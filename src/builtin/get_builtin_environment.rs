@@ -1,18 +1,282 @@
+use std::rc::Rc;
+
 use crate::interpreter::{
     environment::Environment,
-    object::{BuiltInFunction, Object},
+    evaluator::{Error, EvalOption},
+    object::{Array, BuiltInFunction, Object},
+};
+
+use super::std::{
+    caller_location, contains, float, history, int, mem_stats, parse_number, print, range,
+    stdin_lines, str, to_array, watches,
 };
 
-use super::std::print;
+type Function = fn(Vec<Object>, &mut EvalOption) -> Result<Object, Error>;
+
+struct Builtin {
+    name: &'static str,
+    function: Function,
+    min_arity: usize,
+    max_arity: usize,
+    doc: &'static str,
+}
+
+// Builtins are grouped into map-valued namespaces, called as `io["print"]("hello")`.
+const NAMESPACES: &[(&str, &[Builtin])] = &[
+    (
+        "io",
+        &[
+            Builtin {
+                name: "print",
+                function: print,
+                min_arity: 1,
+                max_arity: 1,
+                doc: "Prints a value followed by a newline.",
+            },
+            Builtin {
+                name: "stdinLines",
+                function: stdin_lines,
+                min_arity: 0,
+                max_arity: 0,
+                doc: "Returns the lines of standard input, read one per loop step.",
+            },
+        ],
+    ),
+    (
+        "number",
+        &[Builtin {
+            name: "parseNumber",
+            function: parse_number,
+            min_arity: 1,
+            max_arity: 2,
+            doc: "Parses text in an optional radix into [ok, value] or [ok, error, position].",
+        }],
+    ),
+    (
+        "convert",
+        &[
+            Builtin {
+                name: "str",
+                function: str,
+                min_arity: 1,
+                max_arity: 2,
+                doc: "Converts a value to the string print would show, optionally with a number of decimal places.",
+            },
+            Builtin {
+                name: "int",
+                function: int,
+                min_arity: 1,
+                max_arity: 1,
+                doc: "Converts a float, numeric string or boolean to an integer.",
+            },
+            Builtin {
+                name: "float",
+                function: float,
+                min_arity: 1,
+                max_arity: 1,
+                doc: "Converts a number, numeric string or boolean to a float.",
+            },
+        ],
+    ),
+    (
+        "ranges",
+        &[
+            Builtin {
+                name: "range",
+                function: range,
+                min_arity: 2,
+                max_arity: 3,
+                doc: "Creates the range from start up to end, by an optional step.",
+            },
+            Builtin {
+                name: "contains",
+                function: contains,
+                min_arity: 2,
+                max_arity: 2,
+                doc: "Tells whether a range produces a value.",
+            },
+            Builtin {
+                name: "toArray",
+                function: to_array,
+                min_arity: 1,
+                max_arity: 1,
+                doc: "Builds the array of every value in a range.",
+            },
+        ],
+    ),
+    (
+        "debug",
+        &[
+            Builtin {
+                name: "history",
+                function: history,
+                min_arity: 1,
+                max_arity: 1,
+                doc: "Returns the recorded values of a watch.",
+            },
+            Builtin {
+                name: "watches",
+                function: watches,
+                min_arity: 0,
+                max_arity: 0,
+                doc: "Lists the watches in scope with the variables they read and their values.",
+            },
+            Builtin {
+                name: "memStats",
+                function: mem_stats,
+                min_arity: 0,
+                max_arity: 0,
+                doc: "Counts the live environments, arrays and functions.",
+            },
+            Builtin {
+                name: "callerLocation",
+                function: caller_location,
+                min_arity: 0,
+                max_arity: 0,
+                doc: "Returns the file and line the current function was called from.",
+            },
+            Builtin {
+                name: "builtins",
+                function: builtins_object,
+                min_arity: 0,
+                max_arity: 0,
+                doc: "Lists every builtin with its namespace, arity and documentation.",
+            },
+        ],
+    ),
+];
+
+// What tooling needs to know about a builtin, without having to call it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BuiltinInfo {
+    pub namespace: &'static str,
+    pub name: &'static str,
+    pub min_arity: usize,
+    pub max_arity: usize,
+    pub doc: &'static str,
+}
+
+// Every builtin in namespace order, as registered by `get_builtin_environment`.
+pub fn builtins() -> Vec<BuiltinInfo> {
+    let mut infos = Vec::new();
+    for (namespace, functions) in NAMESPACES {
+        for builtin in functions.iter() {
+            infos.push(BuiltinInfo {
+                namespace,
+                name: builtin.name,
+                min_arity: builtin.min_arity,
+                max_arity: builtin.max_arity,
+                doc: builtin.doc,
+            });
+        }
+    }
+    infos
+}
+
+// `builtins()` in scripts, the same list as an array of maps.
+fn builtins_object(vec: Vec<Object>, _option: &mut EvalOption) -> Result<Object, Error> {
+    if !vec.is_empty() {
+        return Err(Error {
+            message: format!("wrong number of arguments. got={}, want=0", vec.len()),
+            child: None,
+        });
+    }
+    let infos = builtins()
+        .into_iter()
+        .map(|info| {
+            let entries = vec![
+                (
+                    "namespace".to_string(),
+                    Object::StringLiteral(info.namespace.to_string()),
+                ),
+                (
+                    "name".to_string(),
+                    Object::StringLiteral(info.name.to_string()),
+                ),
+                (
+                    "minArity".to_string(),
+                    Object::Number(info.min_arity as i64),
+                ),
+                (
+                    "maxArity".to_string(),
+                    Object::Number(info.max_arity as i64),
+                ),
+                (
+                    "doc".to_string(),
+                    Object::StringLiteral(info.doc.to_string()),
+                ),
+            ];
+            Object::Array(Rc::new(Array::from_entries(entries)))
+        })
+        .collect();
+    Ok(Object::Array(Rc::new(Array::from_objects(infos))))
+}
 
+// Keeps every builtin reachable by its bare name as well, as scripts written before the
+// namespaces existed expect.
 pub fn get_builtin_environment() -> Environment {
+    get_builtin_environment_with(true)
+}
+
+pub fn get_builtin_environment_with(flat_aliases: bool) -> Environment {
     let mut env = Environment::new(None);
-    env.define(
-        "print".to_string(),
-        Object::BuiltInFunction(BuiltInFunction {
-            name: "print".to_string(),
-            function: print,
-        }),
-    );
+    for (namespace, functions) in NAMESPACES {
+        let mut entries = Vec::new();
+        for builtin in functions.iter() {
+            let function = Object::BuiltInFunction(BuiltInFunction {
+                name: builtin.name.to_string(),
+                function: builtin.function,
+            });
+            if flat_aliases {
+                env.define(builtin.name.to_string(), function.clone());
+            }
+            entries.push((builtin.name.to_string(), function));
+        }
+        // the namespaces are shared by every program forked from this scope
+        let namespace_map = Array {
+            frozen: Some(format!("builtin namespace {}", namespace)),
+            ..Array::from_entries(entries)
+        };
+        env.define(namespace.to_string(), Object::Array(Rc::new(namespace_map)));
+    }
     env
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the registered arities have to agree with the checks inside the builtins
+    #[test]
+    fn test_builtin_arities() {
+        for (_, functions) in NAMESPACES {
+            for builtin in functions.iter() {
+                let mut counts = vec![builtin.max_arity + 1];
+                if builtin.min_arity > 0 {
+                    counts.push(builtin.min_arity - 1);
+                }
+                for count in counts {
+                    let error =
+                        (builtin.function)(vec![Object::Null; count], &mut EvalOption::new())
+                            .unwrap_err();
+                    assert!(
+                        error.message.starts_with("wrong number of arguments"),
+                        "{} with {} arguments: {}",
+                        builtin.name,
+                        count,
+                        error.message
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_builtins_object() {
+        let list = builtins_object(Vec::new(), &mut EvalOption::new()).unwrap();
+        assert!(list.to_string().starts_with(
+            "[[namespace:io,name:print,minArity:1,maxArity:1,doc:Prints a value followed by a newline.,],"
+        ));
+        assert_eq!(builtins().len(), 14);
+    }
+}
@@ -0,0 +1,258 @@
+use std::rc::Rc;
+
+use crate::interpreter::{
+    evaluator::{Error, EvalOption},
+    object::{Array, ArrayElement, Object},
+};
+
+use super::std::{contains, to_array};
+
+// Called with the receiver first, then the arguments of the call.
+type Function = fn(Vec<Object>, &mut EvalOption) -> Result<Object, Error>;
+
+pub struct Method {
+    pub name: &'static str,
+    function: Function,
+    // not counting the receiver
+    pub arity: usize,
+    pub doc: &'static str,
+}
+
+const ARRAY_METHODS: &[Method] = &[
+    Method {
+        name: "len",
+        function: len,
+        arity: 0,
+        doc: "Counts the elements, keyed ones included.",
+    },
+    Method {
+        name: "push",
+        function: push,
+        arity: 1,
+        doc: "Appends a value to the end.",
+    },
+    Method {
+        name: "pop",
+        function: pop,
+        arity: 0,
+        doc: "Removes the last element and returns its value.",
+    },
+    Method {
+        name: "keys",
+        function: keys,
+        arity: 0,
+        doc: "Lists the keys in insertion order.",
+    },
+    Method {
+        name: "values",
+        function: values,
+        arity: 0,
+        doc: "Lists the values of every element in order.",
+    },
+];
+
+const STRING_METHODS: &[Method] = &[
+    Method {
+        name: "len",
+        function: len,
+        arity: 0,
+        doc: "Counts the characters.",
+    },
+    Method {
+        name: "upper",
+        function: upper,
+        arity: 0,
+        doc: "Converts to upper case.",
+    },
+    Method {
+        name: "lower",
+        function: lower,
+        arity: 0,
+        doc: "Converts to lower case.",
+    },
+    Method {
+        name: "trim",
+        function: trim,
+        arity: 0,
+        doc: "Removes whitespace from both ends.",
+    },
+    Method {
+        name: "split",
+        function: split,
+        arity: 1,
+        doc: "Splits around a separator, or into characters when it is empty.",
+    },
+];
+
+const RANGE_METHODS: &[Method] = &[
+    Method {
+        name: "len",
+        function: len,
+        arity: 0,
+        doc: "Counts the values the range produces.",
+    },
+    Method {
+        name: "contains",
+        function: contains,
+        arity: 1,
+        doc: "Tells whether the range produces a value.",
+    },
+    Method {
+        name: "toArray",
+        function: to_array,
+        arity: 0,
+        doc: "Builds the array of every value in the range.",
+    },
+];
+
+// The method `name` of the type of `receiver`, if it has one. `receiver.name(...)` calls it
+// instead of looking `name` up as a key.
+pub fn find_method(receiver: &Object, name: &str) -> Option<&'static Method> {
+    let methods = match receiver {
+        Object::Array(_) => ARRAY_METHODS,
+        Object::StringLiteral(_) => STRING_METHODS,
+        Object::Range(_) => RANGE_METHODS,
+        _ => return None,
+    };
+    methods.iter().find(|method| method.name == name)
+}
+
+impl Method {
+    pub fn call(
+        &self,
+        receiver: Object,
+        arguments: Vec<Object>,
+        option: &mut EvalOption,
+    ) -> Result<Object, Error> {
+        if arguments.len() != self.arity {
+            return Err(Error {
+                message: format!(
+                    "wrong number of arguments to {}. got={}, want={}",
+                    self.name,
+                    arguments.len(),
+                    self.arity
+                ),
+                child: None,
+            });
+        }
+        let mut vec = vec![receiver];
+        vec.extend(arguments);
+        (self.function)(vec, option)
+    }
+}
+
+fn len(vec: Vec<Object>, _option: &mut EvalOption) -> Result<Object, Error> {
+    let len = match &vec[0] {
+        Object::Array(array) => array.elements.borrow().len(),
+        Object::StringLiteral(value) => value.chars().count(),
+        Object::Range(range) => range.len(),
+        _ => unreachable!(),
+    };
+    Ok(Object::Number(len as i64))
+}
+
+fn push(vec: Vec<Object>, _option: &mut EvalOption) -> Result<Object, Error> {
+    let array = expect_array(&vec[0]);
+    array.check_writable()?;
+    array
+        .elements_mut()
+        .push(ArrayElement::Object(vec[1].clone()));
+    Ok(Object::Null)
+}
+
+fn pop(vec: Vec<Object>, _option: &mut EvalOption) -> Result<Object, Error> {
+    let array = expect_array(&vec[0]);
+    array.check_writable()?;
+    let element = array.elements_mut().pop();
+    match element {
+        Some(ArrayElement::Object(value)) => Ok(value),
+        Some(ArrayElement::Key(key)) => Ok(array.map.borrow_mut().remove(&key).unwrap()),
+        None => Err(Error {
+            message: "cannot pop from an empty array".to_string(),
+            child: None,
+        }),
+    }
+}
+
+fn keys(vec: Vec<Object>, _option: &mut EvalOption) -> Result<Object, Error> {
+    let array = expect_array(&vec[0]);
+    let keys = array
+        .elements
+        .borrow()
+        .iter()
+        .filter_map(|element| match element {
+            ArrayElement::Key(key) => Some(Object::StringLiteral(key.clone())),
+            ArrayElement::Object(_) => None,
+        })
+        .collect();
+    Ok(Object::Array(Rc::new(Array::from_objects(keys))))
+}
+
+fn values(vec: Vec<Object>, _option: &mut EvalOption) -> Result<Object, Error> {
+    let array = expect_array(&vec[0]);
+    let map = array.map.borrow();
+    let values = array
+        .elements
+        .borrow()
+        .iter()
+        .map(|element| match element {
+            ArrayElement::Object(value) => value.clone(),
+            ArrayElement::Key(key) => map.get(key).unwrap().clone(),
+        })
+        .collect();
+    Ok(Object::Array(Rc::new(Array::from_objects(values))))
+}
+
+fn upper(vec: Vec<Object>, _option: &mut EvalOption) -> Result<Object, Error> {
+    Ok(Object::StringLiteral(expect_string(&vec[0]).to_uppercase()))
+}
+
+fn lower(vec: Vec<Object>, _option: &mut EvalOption) -> Result<Object, Error> {
+    Ok(Object::StringLiteral(expect_string(&vec[0]).to_lowercase()))
+}
+
+fn trim(vec: Vec<Object>, _option: &mut EvalOption) -> Result<Object, Error> {
+    Ok(Object::StringLiteral(
+        expect_string(&vec[0]).trim().to_string(),
+    ))
+}
+
+fn split(vec: Vec<Object>, _option: &mut EvalOption) -> Result<Object, Error> {
+    let value = expect_string(&vec[0]);
+    let separator = match &vec[1] {
+        Object::StringLiteral(separator) => separator,
+        obj => {
+            return Err(Error {
+                message: format!("split expects a string separator, got {}", obj),
+                child: None,
+            })
+        }
+    };
+    let parts = if separator.is_empty() {
+        value
+            .chars()
+            .map(|c| Object::StringLiteral(c.to_string()))
+            .collect()
+    } else {
+        value
+            .split(separator.as_str())
+            .map(|part| Object::StringLiteral(part.to_string()))
+            .collect()
+    };
+    Ok(Object::Array(Rc::new(Array::from_objects(parts))))
+}
+
+// The tables only hold methods of the receiver's own type.
+fn expect_array(obj: &Object) -> &Array {
+    match obj {
+        Object::Array(array) => array,
+        _ => unreachable!(),
+    }
+}
+
+fn expect_string(obj: &Object) -> &str {
+    match obj {
+        Object::StringLiteral(value) => value,
+        _ => unreachable!(),
+    }
+}
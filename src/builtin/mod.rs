@@ -1,2 +1,3 @@
 pub mod get_builtin_environment;
+pub mod methods;
 mod std;
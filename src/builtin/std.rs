@@ -1,8 +1,20 @@
-use crate::interpreter::object::Object;
+use crate::interpreter::{
+    environment::Environment,
+    evaluator::{Error, EvalOption},
+    object::{
+        get_float_format, with_float_format, Array, FloatFormat, Lines, Object, Range,
+        MAX_PRECISION,
+    },
+    stats::get_mem_stats,
+};
+use std::rc::Rc;
 
-pub fn print(vec: Vec<Object>) -> Object {
+pub fn print(vec: Vec<Object>, option: &mut EvalOption) -> Result<Object, Error> {
     if vec.len() != 1 {
-        panic!("wrong number of arguments. got={}, want=1", vec.len());
+        return Err(Error {
+            message: format!("wrong number of arguments. got={}, want=1", vec.len()),
+            child: None,
+        });
     }
     let text = match &vec[0] {
         Object::Number(value) => value.to_string(),
@@ -10,6 +22,407 @@ pub fn print(vec: Vec<Object>) -> Object {
         obj => obj.to_string(),
     };
 
+    if let Some(output_limit) = &option.output_limit {
+        // the trailing newline counts too
+        output_limit.borrow_mut().write(text.len() + 1)?;
+    }
     println!("{}", text);
-    Object::Null
+    Ok(Object::Null)
+}
+
+pub fn history(vec: Vec<Object>, option: &mut EvalOption) -> Result<Object, Error> {
+    if vec.len() != 1 {
+        return Err(Error {
+            message: format!("wrong number of arguments. got={}, want=1", vec.len()),
+            child: None,
+        });
+    }
+    let name = match &vec[0] {
+        Object::StringLiteral(name) => name,
+        obj => {
+            return Err(Error {
+                message: format!("history expects the name of a watch, got {}", obj),
+                child: None,
+            })
+        }
+    };
+    match &option.watch_history {
+        Some(watch_history) => Ok(watch_history.borrow().to_object(name)),
+        None => Ok(Object::Array(Rc::new(Array::from_objects(Vec::new())))),
+    }
+}
+
+// Counts of live environments, arrays and closures, taken before the result array is built.
+pub fn mem_stats(vec: Vec<Object>, _option: &mut EvalOption) -> Result<Object, Error> {
+    if !vec.is_empty() {
+        return Err(Error {
+            message: format!("wrong number of arguments. got={}, want=0", vec.len()),
+            child: None,
+        });
+    }
+    Ok(get_mem_stats().to_object())
+}
+
+// `[name: n, dependencies: [...], value: v]` for every watch declared in the scope
+// `watches()` is called from or a scope around it, sorted by name.
+pub fn watches(vec: Vec<Object>, option: &mut EvalOption) -> Result<Object, Error> {
+    if !vec.is_empty() {
+        return Err(Error {
+            message: format!("wrong number of arguments. got={}, want=0", vec.len()),
+            child: None,
+        });
+    }
+    let env = match &option.caller_env {
+        Some(env) => env,
+        None => return Ok(Object::Array(Rc::new(Array::from_objects(Vec::new())))),
+    };
+    let watches = Environment::active_watches(env)
+        .into_iter()
+        .map(|watch| {
+            let dependencies = watch
+                .dependencies
+                .into_iter()
+                .map(Object::StringLiteral)
+                .collect();
+            Object::Array(Rc::new(Array::from_entries(vec![
+                ("name".to_string(), Object::StringLiteral(watch.name)),
+                (
+                    "dependencies".to_string(),
+                    Object::Array(Rc::new(Array::from_objects(dependencies))),
+                ),
+                ("value".to_string(), watch.value.unwrap_or(Object::Null)),
+            ])))
+        })
+        .collect();
+    Ok(Object::Array(Rc::new(Array::from_objects(watches))))
+}
+
+// `[file: f, line: n]` of the call to the function that calls `callerLocation()`, so logging
+// and assertion helpers can tell where they were used. `null` outside of any function.
+pub fn caller_location(vec: Vec<Object>, option: &mut EvalOption) -> Result<Object, Error> {
+    if !vec.is_empty() {
+        return Err(Error {
+            message: format!("wrong number of arguments. got={}, want=0", vec.len()),
+            child: None,
+        });
+    }
+    let line = match option.call_lines.last() {
+        Some(line) => *line,
+        None => return Ok(Object::Null),
+    };
+    let file = match &option.file {
+        Some(file) => Object::StringLiteral(file.clone()),
+        None => Object::Null,
+    };
+    Ok(Object::Array(Rc::new(Array::from_entries(vec![
+        ("file".to_string(), file),
+        ("line".to_string(), Object::Number(line as i64)),
+    ]))))
+}
+
+// The lines of standard input, read as a `for` loop asks for them, so a filter can answer
+// each line of an interactive pipe before the next one arrives.
+pub fn stdin_lines(vec: Vec<Object>, _option: &mut EvalOption) -> Result<Object, Error> {
+    if !vec.is_empty() {
+        return Err(Error {
+            message: format!("wrong number of arguments. got={}, want=0", vec.len()),
+            child: None,
+        });
+    }
+    Ok(Object::Lines(Rc::new(Lines::stdin())))
+}
+
+// `parseNumber(text)` or `parseNumber(text, radix)`. Returns `[ok: true, value: n]`, or
+// `[ok: false, error: message, position: i]` where `i` is the index of the offending
+// character, so scripts can validate input without failing.
+pub fn parse_number(vec: Vec<Object>, _option: &mut EvalOption) -> Result<Object, Error> {
+    if vec.is_empty() || vec.len() > 2 {
+        return Err(Error {
+            message: format!("wrong number of arguments. got={}, want=1 or 2", vec.len()),
+            child: None,
+        });
+    }
+    let text = match &vec[0] {
+        Object::StringLiteral(text) => text,
+        obj => {
+            return Err(Error {
+                message: format!("parseNumber expects a string, got {}", obj),
+                child: None,
+            })
+        }
+    };
+    let radix = match vec.get(1) {
+        None => 10,
+        Some(Object::Number(radix)) if (2..=36).contains(radix) => *radix as u32,
+        Some(obj) => {
+            return Err(Error {
+                message: format!("radix must be a number from 2 to 36, got {}", obj),
+                child: None,
+            })
+        }
+    };
+    let entries = match parse_number_text(text, radix) {
+        Ok(value) => vec![
+            ("ok".to_string(), Object::Boolean(true)),
+            ("value".to_string(), value),
+        ],
+        Err((error, position)) => vec![
+            ("ok".to_string(), Object::Boolean(false)),
+            ("error".to_string(), Object::StringLiteral(error)),
+            ("position".to_string(), Object::Number(position as i64)),
+        ],
+    };
+    Ok(Object::Array(Rc::new(Array::from_entries(entries))))
+}
+
+// The value, or the error message and the index of the character it is about.
+type ParsedNumber = Result<Object, (String, usize)>;
+
+// Accepts an optional sign, digits of the radix with `_` between them, and in radix 10 a
+// fractional part, which makes the result a float.
+fn parse_number_text(text: &str, radix: u32) -> ParsedNumber {
+    let chars: Vec<char> = text.chars().collect();
+    let mut digits = String::new();
+    let mut index = 0;
+    if let Some(sign @ ('-' | '+')) = chars.first() {
+        digits.push(*sign);
+        index = 1;
+    }
+    let mut is_float = false;
+    let mut has_digits = false;
+    while index < chars.len() {
+        let c = chars[index];
+        let between_digits = index > 0
+            && chars[index - 1].is_digit(radix)
+            && chars
+                .get(index + 1)
+                .is_some_and(|next| next.is_digit(radix));
+        if c.is_digit(radix) {
+            has_digits = true;
+            digits.push(c);
+        } else if c == '.' && radix == 10 && !is_float && between_digits {
+            is_float = true;
+            digits.push(c);
+        } else if c != '_' || !between_digits {
+            return Err((format!("unexpected character `{}`", c), index));
+        }
+        index += 1;
+    }
+    if !has_digits {
+        return Err(("expected a digit".to_string(), index));
+    }
+    if is_float {
+        return Ok(Object::Float(digits.parse::<f64>().unwrap()));
+    }
+    match i64::from_str_radix(&digits, radix) {
+        Ok(value) => Ok(Object::Number(value)),
+        Err(_) => Err(("number does not fit in 64 bits".to_string(), 0)),
+    }
+}
+
+// `str(value)`, the text `print` would show for the value, or `str(value, digits)` to show
+// its floats with that many digits after the decimal point.
+pub fn str(mut vec: Vec<Object>, _option: &mut EvalOption) -> Result<Object, Error> {
+    if vec.is_empty() || vec.len() > 2 {
+        return Err(Error {
+            message: format!("wrong number of arguments. got={}, want=1 or 2", vec.len()),
+            child: None,
+        });
+    }
+    let precision = match vec.get(1) {
+        Some(Object::Number(digits)) if (0..=MAX_PRECISION as i64).contains(digits) => {
+            Some(*digits as usize)
+        }
+        Some(obj) => {
+            return Err(Error {
+                message: format!(
+                    "str expects from 0 to {} digits after the decimal point, got {}",
+                    MAX_PRECISION, obj
+                ),
+                child: None,
+            })
+        }
+        None => None,
+    };
+    let value = vec.remove(0);
+    Ok(match (value, precision) {
+        (Object::StringLiteral(text), _) => Object::StringLiteral(text),
+        (value, None) => Object::StringLiteral(value.to_string()),
+        (value, Some(precision)) => {
+            let format = FloatFormat {
+                precision: Some(precision),
+                ..get_float_format()
+            };
+            Object::StringLiteral(with_float_format(format, || value.to_string()))
+        }
+    })
+}
+
+// `int(value)` truncates a float toward zero, parses a string like `parseNumber` does and
+// turns booleans into 1 and 0. Unlike `parseNumber` it fails the script on bad input.
+pub fn int(vec: Vec<Object>, _option: &mut EvalOption) -> Result<Object, Error> {
+    let value = expect_one(vec)?;
+    let converted = match &value {
+        Object::Number(_) => Some(value.clone()),
+        Object::Float(float) if float.is_finite() && float.abs() < i64::MAX as f64 => {
+            Some(Object::Number(float.trunc() as i64))
+        }
+        Object::StringLiteral(text) => match parse_number_text(text, 10) {
+            Ok(Object::Number(number)) => Some(Object::Number(number)),
+            _ => None,
+        },
+        Object::Boolean(boolean) => Some(Object::Number(*boolean as i64)),
+        _ => None,
+    };
+    converted.ok_or_else(|| cannot_convert(&value, "int"))
+}
+
+// `float(value)`, like `int` but keeping the fractional part.
+pub fn float(vec: Vec<Object>, _option: &mut EvalOption) -> Result<Object, Error> {
+    let value = expect_one(vec)?;
+    let converted = match &value {
+        Object::Number(number) => Some(Object::Float(*number as f64)),
+        Object::Float(_) => Some(value.clone()),
+        Object::StringLiteral(text) => match parse_number_text(text, 10) {
+            Ok(Object::Number(number)) => Some(Object::Float(number as f64)),
+            Ok(float) => Some(float),
+            Err(_) => None,
+        },
+        Object::Boolean(boolean) => Some(Object::Float(*boolean as i64 as f64)),
+        _ => None,
+    };
+    converted.ok_or_else(|| cannot_convert(&value, "float"))
+}
+
+fn expect_one(mut vec: Vec<Object>) -> Result<Object, Error> {
+    if vec.len() != 1 {
+        return Err(Error {
+            message: format!("wrong number of arguments. got={}, want=1", vec.len()),
+            child: None,
+        });
+    }
+    Ok(vec.remove(0))
+}
+
+fn cannot_convert(value: &Object, to: &str) -> Error {
+    Error {
+        message: match value {
+            Object::StringLiteral(text) => format!("cannot convert string \"{}\" to {}", text, to),
+            value => format!("cannot convert {} to {}", value.type_name(), to),
+        },
+        child: None,
+    }
+}
+
+// `range(start, end)` or `range(start, end, step)`, for the steps `start..end` cannot express.
+pub fn range(vec: Vec<Object>, _option: &mut EvalOption) -> Result<Object, Error> {
+    if vec.len() < 2 || vec.len() > 3 {
+        return Err(Error {
+            message: format!("wrong number of arguments. got={}, want=2 or 3", vec.len()),
+            child: None,
+        });
+    }
+    let mut bounds = Vec::new();
+    for obj in vec.iter() {
+        match obj {
+            Object::Number(value) => bounds.push(*value),
+            obj => {
+                return Err(Error {
+                    message: format!("range bounds must be integers, got {}", obj),
+                    child: None,
+                })
+            }
+        }
+    }
+    let step = bounds.get(2).copied().unwrap_or(1);
+    if step == 0 {
+        return Err(Error {
+            message: "range step cannot be 0".to_string(),
+            child: None,
+        });
+    }
+    Ok(Object::Range(Range {
+        start: bounds[0],
+        end: bounds[1],
+        step,
+    }))
+}
+
+// `contains(range, value)`: whether iterating the range would produce the value.
+pub fn contains(vec: Vec<Object>, _option: &mut EvalOption) -> Result<Object, Error> {
+    if vec.len() != 2 {
+        return Err(Error {
+            message: format!("wrong number of arguments. got={}, want=2", vec.len()),
+            child: None,
+        });
+    }
+    let range = expect_range(&vec[0], "contains")?;
+    match &vec[1] {
+        Object::Number(value) => Ok(Object::Boolean(range.contains(*value))),
+        _ => Ok(Object::Boolean(false)),
+    }
+}
+
+// `toArray(range)` builds the array of every value in the range.
+pub fn to_array(vec: Vec<Object>, _option: &mut EvalOption) -> Result<Object, Error> {
+    if vec.len() != 1 {
+        return Err(Error {
+            message: format!("wrong number of arguments. got={}, want=1", vec.len()),
+            child: None,
+        });
+    }
+    let range = expect_range(&vec[0], "toArray")?;
+    let values = range.iter().map(Object::Number).collect();
+    Ok(Object::Array(Rc::new(Array::from_objects(values))))
+}
+
+fn expect_range<'a>(obj: &'a Object, name: &str) -> Result<&'a Range, Error> {
+    match obj {
+        Object::Range(range) => Ok(range),
+        obj => Err(Error {
+            message: format!("{} expects a range, got {}", name, obj),
+            child: None,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::interpreter::iterator::ForIterator;
+
+    #[test]
+    fn test_lines() {
+        let lines = Object::Lines(Rc::new(Lines::new(Cursor::new("first\r\nsecond\n\nlast"))));
+        let mut iterator = ForIterator::new(lines, &mut EvalOption::new()).unwrap();
+        let mut read = Vec::new();
+        while let Some((index, line)) = iterator.next_element(&mut EvalOption::new()).unwrap() {
+            read.push(format!("{}:{}", index, line));
+        }
+        assert_eq!(read, vec!["0:first", "1:second", "2:", "3:last"]);
+    }
+
+    #[test]
+    fn test_parse_number_text() {
+        let cases: Vec<(&str, u32, ParsedNumber)> = vec![
+            ("-1_000", 10, Ok(Object::Number(-1000))),
+            ("ff", 16, Ok(Object::Number(255))),
+            ("+2.5", 10, Ok(Object::Float(2.5))),
+            ("12g4", 16, Err(("unexpected character `g`".to_string(), 2))),
+            ("1__0", 10, Err(("unexpected character `_`".to_string(), 1))),
+            ("1.5", 2, Err(("unexpected character `.`".to_string(), 1))),
+            ("-", 10, Err(("expected a digit".to_string(), 1))),
+            ("", 10, Err(("expected a digit".to_string(), 0))),
+            (
+                "99999999999999999999",
+                10,
+                Err(("number does not fit in 64 bits".to_string(), 0)),
+            ),
+        ];
+        for (text, radix, expected) in cases {
+            assert_eq!(parse_number_text(text, radix), expected, "{}", text);
+        }
+    }
 }
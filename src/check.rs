@@ -0,0 +1,698 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::{self, Expression, Pattern, Statement};
+use crate::printer::print_expression;
+
+// What `check` found in a program: errors it would fail with at runtime, and warnings about
+// `let` and `const` bindings that are never read.
+#[derive(Debug, PartialEq, Default)]
+pub struct Report {
+    pub errors: Vec<String>,
+    pub warnings: Vec<Warning>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Warning {
+    pub line: usize,
+    pub message: String,
+}
+
+// Finds the mistakes a program would fail with at runtime that can be seen from its text:
+// names that are never declared or are read before their declaration runs, parameters
+// declared twice, and assignments to constants, builtins, undeclared names or values that
+// cannot be assigned to. `globals` are the names the program starts with, its builtins.
+// Bindings whose name starts with `_` are never warned about.
+//
+// Scopes follow the runtime: blocks of `if` and `try` share the enclosing scope, while
+// functions, loop bodies, cases, arms, handlers and comprehensions get their own. A function
+// body may refer to a name its enclosing scopes declare after it, since it only runs once
+// it is called.
+pub fn check(program: &ast::Program, globals: &HashSet<String>) -> Report {
+    let mut checker = Checker {
+        globals,
+        scopes: Vec::new(),
+        late: false,
+        report: Report::default(),
+    };
+    checker.push_scope(&program.statements, false);
+    checker.walk_statements(&program.statements);
+    checker.pop_scope();
+    let mut report = checker.report;
+    report
+        .warnings
+        .sort_by(|a, b| (a.line, &a.message).cmp(&(b.line, &b.message)));
+    report
+}
+
+#[derive(Default)]
+struct Scope {
+    // names declared so far
+    declared: HashMap<String, Binding>,
+    // every name the statements of the scope declare, wherever they are, and whether they
+    // are constants
+    hoisted: HashMap<String, bool>,
+    // names read by code that runs after their declaration, but is written before it
+    read_early: HashSet<String>,
+    // the body of a function or a test, which runs after the scopes around it
+    function: bool,
+}
+
+struct Binding {
+    constant: bool,
+    // the warning reported if the scope ends before the binding is read
+    unread: Option<Warning>,
+}
+
+enum Resolution {
+    Variable,
+    Constant,
+    Builtin,
+    // declared later in the code running before the name is read
+    NotYetDeclared,
+    Undeclared,
+}
+
+struct Checker<'a> {
+    globals: &'a HashSet<String>,
+    scopes: Vec<Scope>,
+    // inside a deferred expression, which runs once its block is done
+    late: bool,
+    report: Report,
+}
+
+impl Checker<'_> {
+    fn push_scope(&mut self, statements: &[Statement], function: bool) {
+        let mut scope = Scope {
+            function,
+            ..Scope::default()
+        };
+        hoist_statements(statements, &mut scope.hoisted);
+        self.scopes.push(scope);
+    }
+
+    fn pop_scope(&mut self) {
+        if let Some(scope) = self.scopes.pop() {
+            let unread = scope
+                .declared
+                .into_values()
+                .filter_map(|binding| binding.unread);
+            self.report.warnings.extend(unread);
+        }
+    }
+
+    fn declare(&mut self, name: &str, constant: bool) {
+        self.declare_binding(name, constant, None);
+    }
+
+    // Declares what `let` or `const` binds, which is warned about if it is never read.
+    fn declare_variable(&mut self, name: &str, constant: bool, value: &Expression, line: usize) {
+        let kind = match value {
+            Expression::FunctionLiteral(_) => "function",
+            _ if constant => "constant",
+            _ => "variable",
+        };
+        let warning = match name.starts_with('_') {
+            true => None,
+            false => Some(Warning {
+                line,
+                message: format!("{} {} is never used", kind, name),
+            }),
+        };
+        self.declare_binding(name, constant, warning);
+    }
+
+    fn declare_binding(&mut self, name: &str, constant: bool, unread: Option<Warning>) {
+        let scope = self.scopes.last_mut().unwrap();
+        let unread = unread.filter(|_| !scope.read_early.contains(name));
+        let previous = scope
+            .declared
+            .insert(name.to_string(), Binding { constant, unread });
+        let previous = match previous {
+            Some(previous) => previous,
+            None => return,
+        };
+        if previous.constant {
+            self.report
+                .errors
+                .push(format!("cannot redeclare constant {}", name));
+        } else if constant {
+            self.report
+                .errors
+                .push(format!("cannot redeclare {} as a constant", name));
+        }
+        self.report.warnings.extend(previous.unread);
+    }
+
+    fn declare_pattern(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Identifier(identifier) => self.declare(&identifier.value, false),
+            Pattern::Array(array_pattern) => self.declare_array_pattern(array_pattern),
+            Pattern::Keyed(keyed) => self.declare_pattern(&keyed.value),
+            Pattern::Literal(_) | Pattern::Wildcard => {}
+        }
+    }
+
+    fn declare_array_pattern(&mut self, array_pattern: &ast::ArrayPattern) {
+        for element in &array_pattern.elements {
+            self.declare_pattern(element);
+        }
+        if let Some(rest) = &array_pattern.rest {
+            self.declare(&rest.value, false);
+        }
+    }
+
+    fn resolve(&self, name: &str) -> Resolution {
+        let mut late = self.late;
+        for scope in self.scopes.iter().rev() {
+            if let Some(binding) = scope.declared.get(name) {
+                return if binding.constant {
+                    Resolution::Constant
+                } else {
+                    Resolution::Variable
+                };
+            }
+            if let Some(&constant) = scope.hoisted.get(name) {
+                return match (late, constant) {
+                    (false, _) => Resolution::NotYetDeclared,
+                    (true, true) => Resolution::Constant,
+                    (true, false) => Resolution::Variable,
+                };
+            }
+            late |= scope.function;
+        }
+        if self.globals.contains(name) {
+            Resolution::Builtin
+        } else {
+            Resolution::Undeclared
+        }
+    }
+
+    fn read(&mut self, name: &str) {
+        match self.resolve(name) {
+            Resolution::NotYetDeclared => self
+                .report
+                .errors
+                .push(format!("{} is used before it is declared", name)),
+            Resolution::Undeclared => self.report.errors.push(format!("{} is not defined", name)),
+            _ => self.mark_read(name),
+        }
+    }
+
+    fn mark_read(&mut self, name: &str) {
+        for scope in self.scopes.iter_mut().rev() {
+            if let Some(binding) = scope.declared.get_mut(name) {
+                binding.unread = None;
+                return;
+            }
+            if scope.hoisted.contains_key(name) {
+                scope.read_early.insert(name.to_string());
+                return;
+            }
+        }
+    }
+
+    fn assign(&mut self, target: &Expression) {
+        match target {
+            Expression::Identifier(identifier) => {
+                let name = &identifier.value;
+                let problem = match self.resolve(name) {
+                    Resolution::Variable => return,
+                    Resolution::Constant => format!("cannot assign to constant {}", name),
+                    Resolution::Builtin => format!("cannot assign to builtin {}", name),
+                    Resolution::NotYetDeclared => {
+                        format!("{} is assigned before it is declared", name)
+                    }
+                    Resolution::Undeclared => {
+                        format!("cannot assign to undeclared variable {}", name)
+                    }
+                };
+                self.report.errors.push(problem);
+            }
+            Expression::ElementAccessExpression(_) | Expression::MemberAccessExpression(_) => {
+                self.walk_expression(target)
+            }
+            _ => {
+                self.walk_expression(target);
+                self.report
+                    .errors
+                    .push(format!("cannot assign to {}", print_expression(target, 0)));
+            }
+        }
+    }
+
+    fn walk_statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.walk_statement(statement);
+        }
+    }
+
+    // Walks a block that runs in a scope of its own, after declaring what `declare` adds.
+    fn walk_scope(
+        &mut self,
+        block: &ast::BlockExpression,
+        function: bool,
+        declare: impl FnOnce(&mut Checker),
+    ) {
+        self.push_scope(&block.statements, function);
+        declare(self);
+        self.walk_statements(&block.statements);
+        self.pop_scope();
+    }
+
+    fn walk_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::VariableDeclaration(declaration) => {
+                self.walk_expression(&declaration.value);
+                self.declare_variable(
+                    &declaration.name,
+                    false,
+                    &declaration.value,
+                    declaration.position.line,
+                );
+            }
+            Statement::DeclarationList(declaration_list) => {
+                for declaration in &declaration_list.declarations {
+                    self.walk_expression(&declaration.value);
+                    self.declare_variable(
+                        &declaration.name,
+                        false,
+                        &declaration.value,
+                        declaration.position.line,
+                    );
+                }
+            }
+            Statement::ConstantDeclaration(declaration) => {
+                self.walk_expression(&declaration.value);
+                self.declare_variable(
+                    &declaration.name,
+                    true,
+                    &declaration.value,
+                    declaration.position.line,
+                );
+            }
+            Statement::DestructuringDeclaration(declaration) => {
+                self.walk_expression(&declaration.value);
+                if let Some(alternative) = &declaration.alternative {
+                    self.walk_scope(alternative, false, |_| {});
+                }
+                self.declare_array_pattern(&declaration.pattern);
+            }
+            Statement::Expression(expression) => self.walk_expression(expression),
+            Statement::MultipleAssign(multiple_assign) => {
+                for value in &multiple_assign.values {
+                    self.walk_expression(value);
+                }
+                for target in &multiple_assign.targets {
+                    self.assign(target);
+                }
+            }
+            Statement::ReturnStatement(statement) => self.walk_expression(&statement.value),
+            Statement::ThrowStatement(statement) => self.walk_expression(&statement.value),
+            Statement::DeferStatement(statement) => {
+                let late = std::mem::replace(&mut self.late, true);
+                self.walk_expression(&statement.value);
+                self.late = late;
+            }
+            Statement::AssertStatement(statement) => {
+                self.walk_expression(&statement.condition);
+                if let Some(message) = &statement.message {
+                    self.walk_expression(message);
+                }
+            }
+            Statement::BlockReturnStatement(statement) => self.walk_expression(&statement.value),
+            Statement::BreakStatement(statement) => {
+                if let Some(value) = &statement.value {
+                    self.walk_expression(value);
+                }
+            }
+            Statement::ContinueStatement(_) => {}
+            Statement::WatchDeclaration(watch) => {
+                self.walk_statements(&watch.block.statements);
+                self.declare(&watch.name, false);
+            }
+            // tests run once the whole program has
+            Statement::TestBlock(test) => self.walk_scope(&test.body, true, |_| {}),
+            Statement::ImportStatement(import) => self.declare(&import.name, false),
+            // an exported name is read by whoever imports the module
+            Statement::ExportStatement(export) => {
+                self.walk_statement(&export.declaration);
+                for name in export.names() {
+                    self.mark_read(name);
+                }
+            }
+            Statement::StructDeclaration(declaration) => {
+                self.declare(&declaration.name, false);
+                for method in &declaration.methods {
+                    self.walk_function(&method.function, true);
+                }
+            }
+        }
+    }
+
+    fn walk_function(&mut self, function: &ast::FunctionLiteral, method: bool) {
+        let mut parameters = HashSet::new();
+        for parameter in function.parameters.iter().chain(&function.rest) {
+            if !parameters.insert(&parameter.value) {
+                self.report
+                    .errors
+                    .push(format!("parameter {} is declared twice", parameter.value));
+            }
+        }
+        self.walk_scope(&function.body, true, |checker| {
+            if method {
+                checker.declare("self", false);
+            }
+            for parameter in &parameters {
+                checker.declare(parameter, false);
+            }
+        });
+    }
+
+    fn walk_expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::InfixExpression(infix) => {
+                self.walk_expression(&infix.left);
+                self.walk_expression(&infix.right);
+            }
+            Expression::PrefixExpression(prefix) => self.walk_expression(&prefix.right),
+            // the target is read before it is assigned
+            Expression::UpdateExpression(update) => {
+                if let Expression::Identifier(identifier) = &update.target {
+                    if matches!(
+                        self.resolve(&identifier.value),
+                        Resolution::NotYetDeclared | Resolution::Undeclared
+                    ) {
+                        return self.read(&identifier.value);
+                    }
+                    self.mark_read(&identifier.value);
+                }
+                self.assign(&update.target)
+            }
+            Expression::Identifier(identifier) => self.read(&identifier.value),
+            Expression::FunctionLiteral(function) => self.walk_function(function, false),
+            Expression::CallExpression(call) => {
+                self.walk_expression(&call.left);
+                for argument in &call.arguments {
+                    self.walk_expression(argument);
+                }
+            }
+            Expression::IfExpression(if_expression) => {
+                self.walk_expression(&if_expression.condition);
+                self.walk_statements(&if_expression.consequence.statements);
+                if let Some(alternative) = &if_expression.alternative {
+                    self.walk_statements(&alternative.statements);
+                }
+            }
+            Expression::TemplateLiteral(template) => {
+                for part in &template.parts {
+                    if let ast::TemplatePart::Expression(expression) = part {
+                        self.walk_expression(expression);
+                    }
+                }
+            }
+            Expression::ArrayLiteral(ast::ArrayLiteral { elements, .. })
+            | Expression::MapLiteral(ast::MapLiteral { entries: elements }) => {
+                for element in elements {
+                    match element {
+                        ast::ArrayMapValue::MapKeyValue(key_value) => {
+                            self.walk_expression(&key_value.value)
+                        }
+                        ast::ArrayMapValue::ComputedKeyValue(key_value) => {
+                            self.walk_expression(&key_value.key);
+                            self.walk_expression(&key_value.value);
+                        }
+                        ast::ArrayMapValue::Value(value) => self.walk_expression(value),
+                    }
+                }
+            }
+            Expression::ArrayComprehension(comprehension) => {
+                self.walk_expression(&comprehension.iterable);
+                self.push_scope(&[], false);
+                self.declare(&comprehension.variable.value, false);
+                if let Some(condition) = &comprehension.condition {
+                    self.walk_expression(condition);
+                }
+                self.walk_expression(&comprehension.element);
+                self.pop_scope();
+            }
+            Expression::ElementAccessExpression(element_access) => {
+                self.walk_expression(&element_access.left);
+                self.walk_expression(&element_access.index);
+            }
+            Expression::MemberAccessExpression(member_access) => {
+                self.walk_expression(&member_access.left)
+            }
+            Expression::SliceExpression(slice) => {
+                self.walk_expression(&slice.left);
+                for bound in [&slice.start, &slice.end].into_iter().flatten() {
+                    self.walk_expression(bound);
+                }
+            }
+            Expression::ForExpression(for_expression) => {
+                self.walk_expression(&for_expression.iterable);
+                self.walk_scope(&for_expression.body, false, |checker| {
+                    if let Some(index) = &for_expression.index {
+                        checker.declare(&index.value, false);
+                    }
+                    checker.declare(&for_expression.variable.value, false);
+                });
+            }
+            Expression::LoopExpression(loop_expression) => {
+                self.walk_scope(&loop_expression.body, false, |_| {})
+            }
+            Expression::DoWhileExpression(do_while) => {
+                self.walk_scope(&do_while.body, false, |_| {});
+                self.walk_expression(&do_while.condition);
+            }
+            Expression::SwitchExpression(switch_expression) => {
+                self.walk_expression(&switch_expression.expression);
+                for case in &switch_expression.cases {
+                    match &case.condition {
+                        ast::CaseCondition::Value(value) => {
+                            self.walk_expression(value);
+                            self.walk_scope(&case.body, false, |_| {});
+                        }
+                        ast::CaseCondition::Pattern(pattern) => {
+                            self.walk_scope(&case.body, false, |checker| {
+                                checker.declare_array_pattern(pattern)
+                            })
+                        }
+                    }
+                }
+                if let Some(default) = &switch_expression.default {
+                    self.walk_scope(&default.body, false, |_| {});
+                }
+            }
+            Expression::MatchExpression(match_expression) => {
+                self.walk_expression(&match_expression.expression);
+                for arm in &match_expression.arms {
+                    self.push_scope(&[], false);
+                    self.declare_pattern(&arm.pattern);
+                    self.walk_expression(&arm.body);
+                    self.pop_scope();
+                }
+            }
+            Expression::TryExpression(try_expression) => {
+                self.walk_statements(&try_expression.body.statements);
+                if let Some(handler) = &try_expression.handler {
+                    self.walk_scope(&handler.body, false, |checker| {
+                        checker.declare(&handler.parameter.value, false)
+                    });
+                }
+                if let Some(finalizer) = &try_expression.finalizer {
+                    self.walk_statements(&finalizer.statements);
+                }
+            }
+            Expression::Assign(assign) => {
+                self.walk_expression(&assign.right);
+                self.assign(&assign.left);
+            }
+            Expression::BlockExpression(block) => self.walk_statements(&block.statements),
+            Expression::NumberLiteral(_)
+            | Expression::FloatLiteral(_)
+            | Expression::BooleanLiteral(_)
+            | Expression::NullLiteral
+            | Expression::LineLiteral(_)
+            | Expression::FileLiteral
+            | Expression::StringLiteral(_) => {}
+        }
+    }
+}
+
+// Adds the names `statements` declare to `names`, with whether they are constants, including
+// those declared in the blocks of an `if` or a `try`, which share the scope.
+fn hoist_statements(statements: &[Statement], names: &mut HashMap<String, bool>) {
+    for statement in statements {
+        match statement {
+            Statement::VariableDeclaration(declaration) => {
+                names.insert(declaration.name.clone(), false);
+            }
+            Statement::DeclarationList(declaration_list) => {
+                for declaration in &declaration_list.declarations {
+                    names.insert(declaration.name.clone(), false);
+                }
+            }
+            Statement::ConstantDeclaration(declaration) => {
+                names.insert(declaration.name.clone(), true);
+            }
+            Statement::DestructuringDeclaration(declaration) => {
+                hoist_array_pattern(&declaration.pattern, names)
+            }
+            Statement::WatchDeclaration(watch) => {
+                names.insert(watch.name.clone(), false);
+            }
+            Statement::ImportStatement(import) => {
+                names.insert(import.name.clone(), false);
+            }
+            Statement::StructDeclaration(declaration) => {
+                names.insert(declaration.name.clone(), false);
+            }
+            Statement::ExportStatement(export) => {
+                hoist_statements(std::slice::from_ref(&*export.declaration), names)
+            }
+            Statement::Expression(expression)
+            | Statement::BlockReturnStatement(ast::BlockReturnStatement { value: expression }) => {
+                match expression {
+                    Expression::IfExpression(if_expression) => {
+                        hoist_statements(&if_expression.consequence.statements, names);
+                        if let Some(alternative) = &if_expression.alternative {
+                            hoist_statements(&alternative.statements, names);
+                        }
+                    }
+                    Expression::TryExpression(try_expression) => {
+                        hoist_statements(&try_expression.body.statements, names);
+                        if let Some(finalizer) = &try_expression.finalizer {
+                            hoist_statements(&finalizer.statements, names);
+                        }
+                    }
+                    Expression::BlockExpression(block) => {
+                        hoist_statements(&block.statements, names)
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn hoist_array_pattern(array_pattern: &ast::ArrayPattern, names: &mut HashMap<String, bool>) {
+    for element in &array_pattern.elements {
+        hoist_pattern(element, names);
+    }
+    if let Some(rest) = &array_pattern.rest {
+        names.insert(rest.value.clone(), false);
+    }
+}
+
+fn hoist_pattern(pattern: &Pattern, names: &mut HashMap<String, bool>) {
+    match pattern {
+        Pattern::Identifier(identifier) => {
+            names.insert(identifier.value.clone(), false);
+        }
+        Pattern::Array(array_pattern) => hoist_array_pattern(array_pattern, names),
+        Pattern::Keyed(keyed) => hoist_pattern(&keyed.value, names),
+        Pattern::Literal(_) | Pattern::Wildcard => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Peekable;
+    use crate::parser::parse;
+
+    fn check_source(source_code: &str) -> Report {
+        let mut lexer = Peekable::new(source_code);
+        let globals = HashSet::from(["print".to_string(), "len".to_string()]);
+        check(&parse(&mut lexer).unwrap(), &globals)
+    }
+
+    #[test]
+    fn test_check() {
+        assert_eq!(
+            check_source(
+                r#"
+                print(missing);
+                print(later);
+                let later = 1;
+                const LIMIT = 10;
+                LIMIT = 20;
+                LIMIT++;
+                len = 1;
+                nowhere = 1;
+                let add = fn(a, b, a) { a + b };
+                1 = 2;
+                let LIMIT = 3;
+                "#
+            )
+            .errors,
+            vec![
+                "missing is not defined",
+                "later is used before it is declared",
+                "cannot assign to constant LIMIT",
+                "cannot assign to constant LIMIT",
+                "cannot assign to builtin len",
+                "cannot assign to undeclared variable nowhere",
+                "parameter a is declared twice",
+                "cannot assign to 1",
+                "cannot redeclare constant LIMIT",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_scopes() {
+        assert_eq!(
+            check_source(
+                r#"
+                let fact = fn(n) { if (n < 2) { return 1; }; n * fact(n - 1) };
+                let greet = fn() { print(name) };
+                let name = "ankara";
+                if (true) { let shared = 1; };
+                print(shared);
+                for (i, x in [1, 2]) { let inner = x; print(i + inner); };
+                print(inner);
+                let doubled = [x * 2 for x in [1, 2] if x > 1];
+                let y = match ([1, 2]) { [a, ...rest] => a, _ => 0 };
+                try { throw 1; } catch (error) { print(error); };
+                print(error);
+                struct Point { x, fn get() { self.x } }
+                test "uses" { print(fact(3) + y + later); }
+                let later = 1;
+                let f = fn() { defer print(after); let after = 1; };
+                "#
+            )
+            .errors,
+            vec!["inner is not defined", "error is not defined"]
+        );
+    }
+
+    #[test]
+    fn test_check_unused() {
+        let warning = |line, message: &str| Warning {
+            line,
+            message: message.to_string(),
+        };
+        assert_eq!(
+            check_source(
+                "let unused = 1;\n\
+                 const LIMIT = 2;\n\
+                 let helper = fn(x) { let temp = x; x };\n\
+                 let counter = 0;\n\
+                 counter++;\n\
+                 let _ignored = 3;\n\
+                 let later = fn() { value };\n\
+                 let value = later();\n\
+                 export let shared = 4;\n"
+            )
+            .warnings,
+            vec![
+                warning(1, "variable unused is never used"),
+                warning(2, "constant LIMIT is never used"),
+                warning(3, "function helper is never used"),
+                warning(3, "variable temp is never used"),
+            ]
+        );
+    }
+}
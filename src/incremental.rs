@@ -0,0 +1,154 @@
+use crate::ast;
+use crate::lexer::Peekable;
+use crate::parser::{parse_statement, ParseError, Span};
+
+// A top-level statement and the bytes of the source it was parsed from.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParsedStatement {
+    pub statement: ast::Statement,
+    pub span: Span,
+}
+
+// A program kept parsed while its source is edited, for editor diagnostics. An edit only
+// parses the top-level statements around it again: the ones before it are kept as they are,
+// and the ones after it are kept with shifted spans as soon as parsing reaches the start of
+// one of them again.
+#[derive(Debug, Default)]
+pub struct IncrementalProgram {
+    source: String,
+    statements: Vec<ParsedStatement>,
+}
+
+impl IncrementalProgram {
+    pub fn new() -> IncrementalProgram {
+        IncrementalProgram::default()
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn statements(&self) -> &[ParsedStatement] {
+        &self.statements
+    }
+
+    pub fn program(&self) -> ast::Program {
+        ast::Program {
+            statements: self
+                .statements
+                .iter()
+                .map(|parsed| parsed.statement.clone())
+                .collect(),
+            lines: ast::StatementLines(
+                self.statements
+                    .iter()
+                    .map(|parsed| self.source[..parsed.span.start].matches('\n').count() + 1)
+                    .collect(),
+            ),
+        }
+    }
+
+    // Replaces `range` of the source with `replacement` and parses what changed. After an
+    // error the source is still updated, and the next edit parses it from the start.
+    pub fn edit(&mut self, range: Span, replacement: &str) -> Result<(), ParseError> {
+        self.source.replace_range(range.clone(), replacement);
+        let mut statements = std::mem::take(&mut self.statements);
+
+        // statements that start after the edit, moved to where they are now
+        let removed = range.end - range.start;
+        let mut suffix: Vec<ParsedStatement> = statements
+            .iter()
+            .filter(|parsed| parsed.span.start >= range.end)
+            .map(|parsed| ParsedStatement {
+                statement: parsed.statement.clone(),
+                span: parsed.span.start - removed + replacement.len()
+                    ..parsed.span.end - removed + replacement.len(),
+            })
+            .collect();
+
+        // The statement before the first one the edit touches is parsed again as well,
+        // because the parser looked at the token after it.
+        let touched = statements
+            .iter()
+            .position(|parsed| parsed.span.end >= range.start)
+            .unwrap_or(statements.len());
+        statements.truncate(touched.saturating_sub(1));
+        let start = statements.last().map_or(0, |parsed| parsed.span.end);
+
+        let mut lexer = Peekable::new(&self.source);
+        lexer.start_at(start);
+        while lexer.peek().is_some() {
+            let statement_start = lexer.peeked_start;
+            suffix.retain(|parsed| parsed.span.start >= statement_start);
+            if suffix.first().map(|parsed| parsed.span.start) == Some(statement_start) {
+                statements.append(&mut suffix);
+                break;
+            }
+            let statement = parse_statement(&mut lexer)?;
+            statements.push(ParsedStatement {
+                statement,
+                span: statement_start..lexer.consumed,
+            });
+        }
+        self.statements = statements;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn parse_whole(source: &str) -> ast::Program {
+        parse(&mut Peekable::new(source)).unwrap()
+    }
+
+    #[test]
+    fn test_edit_matches_full_parse() {
+        let mut program = IncrementalProgram::new();
+        program
+            .edit(0..0, "let a = 1;\nif (a) { 1 }\nlet b = 2;\nlet c = 3;\n")
+            .unwrap();
+        let edits: Vec<(Span, &str)> = vec![
+            (8..9, "10"),
+            (24..24, " else { 2 };"),
+            (11..11, "// note\n"),
+            (0..11, ""),
+        ];
+        for (range, replacement) in edits {
+            program.edit(range, replacement).unwrap();
+            assert_eq!(program.program(), parse_whole(program.source()));
+            for parsed in program.statements() {
+                let mut lexer = Peekable::new(&program.source()[parsed.span.clone()]);
+                assert_eq!(parse_statement(&mut lexer).unwrap(), parsed.statement);
+            }
+        }
+    }
+
+    #[test]
+    fn test_edit_reuses_statements_after_the_edit() {
+        let mut program = IncrementalProgram::new();
+        program
+            .edit(0..0, "let a = 1;\nlet b = 2;\nlet c = 3;")
+            .unwrap();
+        program.edit(8..9, "100").unwrap();
+        assert_eq!(
+            program
+                .statements()
+                .iter()
+                .map(|parsed| parsed.span.clone())
+                .collect::<Vec<Span>>(),
+            vec![0..12, 13..23, 24..34]
+        );
+    }
+
+    #[test]
+    fn test_edit_recovers_after_error() {
+        let mut program = IncrementalProgram::new();
+        program.edit(0..0, "let a = 1;").unwrap();
+        assert!(program.edit(9..10, "").is_err());
+        program.edit(9..9, ";").unwrap();
+        assert_eq!(program.program(), parse_whole("let a = 1;"));
+    }
+}
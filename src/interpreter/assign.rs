@@ -2,7 +2,7 @@ use core::borrow;
 use std::rc::Rc;
 use std::{borrow::BorrowMut, cell::RefCell};
 
-use crate::ast::{ElementAccessExpression, Identifier};
+use crate::ast::{ElementAccessExpression, Identifier, MemberAccessExpression};
 
 use super::evaluator::EvalOption;
 use super::{
@@ -11,11 +11,13 @@ use super::{
     object::{ArrayElement, Object},
 };
 
+// Assigning to a target written on `line`, which watches record as where they were triggered.
 pub trait EvalAssign {
     fn assign(
         &self,
         env: Rc<RefCell<Environment>>,
         value: Object,
+        line: usize,
         option: &mut EvalOption,
     ) -> Result<Object, Error>;
 }
@@ -25,40 +27,47 @@ impl EvalAssign for ElementAccessExpression {
         &self,
         env: Rc<RefCell<Environment>>,
         value: Object,
+        _line: usize,
         option: &mut EvalOption,
     ) -> Result<Object, Error> {
-        let left = self.left.eval(env.clone(), option);
-        let index = self.index.eval(env, option);
+        if self.optional {
+            return Err(Error {
+                message: "cannot assign through ?[ or ?.".to_string(),
+                child: None,
+            });
+        }
+        let left = self.left.eval(env.clone(), option)?;
+        let index = self.index.eval(env, option)?;
 
         let array = match left {
-            Ok(Object::Array(array)) => array.clone(),
-            _ => {
+            Object::Array(array) => array,
+            left => {
                 return Err(Error {
-                    message: format!("{} is not an array", left.unwrap()),
+                    message: format!("{} is not an array", left),
                     child: None,
                 })
             }
         };
+        array.check_writable()?;
 
         match index {
-            Ok(Object::Number(index)) => {
-                let index = index as usize;
-                let mut elements = array.elements.borrow_mut();
-                if index < elements.len() {
-                    elements[index] = ArrayElement::Object(value.clone());
-                } else {
+            Object::Number(index) => match array.resolve_index(index) {
+                Some(position) => {
+                    array.elements_mut()[position] = ArrayElement::Object(value.clone());
+                }
+                None => {
                     return Err(Error {
                         message: format!("index out of range: {}", index),
                         child: None,
                     });
                 }
+            },
+            Object::StringLiteral(index) => {
+                array.set(index, value.clone());
             }
-            Ok(Object::StringLiteral(index)) => {
-                array.map.borrow_mut().insert(index, value.clone());
-            }
-            _ => {
+            index => {
                 return Err(Error {
-                    message: format!("{} is not a valid index", index.unwrap()),
+                    message: format!("{} is not a valid index", index),
                     child: None,
                 })
             }
@@ -68,28 +77,75 @@ impl EvalAssign for ElementAccessExpression {
     }
 }
 
+impl EvalAssign for MemberAccessExpression {
+    fn assign(
+        &self,
+        env: Rc<RefCell<Environment>>,
+        value: Object,
+        _line: usize,
+        option: &mut EvalOption,
+    ) -> Result<Object, Error> {
+        if self.optional {
+            return Err(Error {
+                message: "cannot assign through ?[ or ?.".to_string(),
+                child: None,
+            });
+        }
+        match self.left.eval(env, option)? {
+            Object::Array(array) => {
+                array.check_writable()?;
+                array.set(self.member.clone(), value.clone());
+                Ok(value)
+            }
+            left => Err(Error {
+                message: format!("{} is not an array", left),
+                child: None,
+            }),
+        }
+    }
+}
+
 impl EvalAssign for Identifier {
     fn assign(
         &self,
         env: Rc<RefCell<Environment>>,
         value: Object,
+        line: usize,
         option: &mut EvalOption,
     ) -> Result<Object, Error> {
         let name = self.value.clone();
+        if env.borrow().is_constant(&name) {
+            return Err(Error {
+                message: "cannot assign to constant ".to_string() + &name,
+                child: None,
+            });
+        }
+        if env.borrow().is_frozen(&name) {
+            return Err(Error {
+                message: "cannot assign to builtin ".to_string() + &name,
+                child: None,
+            });
+        }
         let ret = value.clone();
         Environment::assign(env.clone(), &name, value);
-        let borrowed_env = (*env).borrow();
-        let watch = match borrowed_env.watch.get(&name) {
-            Some(watch) => watch,
+        let scope = match Environment::find_scope(env, &name) {
+            Some(scope) => scope,
             None => return Ok(ret),
         };
-        let mut watch_env = watch.env.clone();
-        if env == watch_env {
-            watch_env = env.clone();
+        let watches = (*scope).borrow_mut().get_watches(&name);
+        for (expression, watch_env) in watches {
+            expression.borrow().eval(watch_env.clone(), option)?;
+            if let Some(watch_history) = option.watch_history.clone() {
+                let watch_name = expression.borrow().name.clone();
+                if let Some(value) = watch_env.borrow().get(&watch_name) {
+                    (*watch_history).borrow_mut().record(
+                        &watch_name,
+                        value,
+                        Some((name.clone(), line)),
+                    );
+                }
+            }
         }
-        let expression = watch.expressions.clone();
-        drop(borrowed_env);
-        expression.borrow().eval(watch_env, option);
         Ok(ret)
     }
 }
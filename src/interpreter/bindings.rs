@@ -0,0 +1,145 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use super::object::Object;
+
+// Scopes with up to this many variables keep them in a vector, where a linear search is
+// cheaper than hashing. Function calls almost always stay below it.
+pub const INLINE_CAPACITY: usize = 8;
+
+// How many emptied vectors are kept for the next scopes to reuse.
+const POOL_SIZE: usize = 256;
+
+thread_local! {
+    static POOL: RefCell<Vec<Vec<(String, Object)>>> = const { RefCell::new(Vec::new()) };
+}
+
+// The variables of one scope.
+#[derive(Debug, Clone)]
+pub enum Bindings {
+    Inline(Vec<(String, Object)>),
+    Map(HashMap<String, Object>),
+}
+
+impl Bindings {
+    // Empty bindings, reusing the vector of a dropped scope when there is one.
+    pub fn new() -> Bindings {
+        let inline = POOL
+            .try_with(|pool| pool.borrow_mut().pop())
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+        Bindings::Inline(inline)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Object> {
+        match self {
+            Bindings::Inline(inline) => inline
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value),
+            Bindings::Map(map) => map.get(name),
+        }
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    pub fn insert(&mut self, name: String, value: Object) {
+        match self {
+            Bindings::Inline(inline) => {
+                if let Some(binding) = inline.iter_mut().find(|(key, _)| *key == name) {
+                    binding.1 = value;
+                } else if inline.len() < INLINE_CAPACITY {
+                    inline.push((name, value));
+                } else {
+                    let mut map: HashMap<String, Object> = inline.drain(..).collect();
+                    map.insert(name, value);
+                    *self = Bindings::Map(map);
+                }
+            }
+            Bindings::Map(map) => {
+                map.insert(name, value);
+            }
+        }
+    }
+
+    pub fn keys(&self) -> Vec<&String> {
+        match self {
+            Bindings::Inline(inline) => inline.iter().map(|(key, _)| key).collect(),
+            Bindings::Map(map) => map.keys().collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Bindings::Inline(inline) => inline.len(),
+            Bindings::Map(map) => map.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Bindings::new()
+    }
+}
+
+// The values are dropped before the vector goes back to the pool, as dropping them can drop
+// other scopes that return their own vectors.
+impl Drop for Bindings {
+    fn drop(&mut self) {
+        let mut inline = match self {
+            Bindings::Inline(inline) if inline.capacity() > 0 => std::mem::take(inline),
+            _ => return,
+        };
+        inline.clear();
+        let _ = POOL.try_with(|pool| {
+            let mut pool = pool.borrow_mut();
+            if pool.len() < POOL_SIZE {
+                pool.push(inline);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bindings_spill_into_a_map() {
+        let mut bindings = Bindings::new();
+        for index in 0..=INLINE_CAPACITY {
+            bindings.insert(format!("x{}", index), Object::Number(index as i64));
+            bindings.insert("x0".to_string(), Object::Number(-1));
+        }
+        assert!(matches!(bindings, Bindings::Map(_)));
+        assert_eq!(bindings.len(), INLINE_CAPACITY + 1);
+        assert_eq!(bindings.get("x0"), Some(&Object::Number(-1)));
+        assert_eq!(
+            bindings.get(&format!("x{}", INLINE_CAPACITY)),
+            Some(&Object::Number(INLINE_CAPACITY as i64))
+        );
+        assert!(!bindings.contains_key("y"));
+    }
+
+    #[test]
+    fn test_dropped_bindings_are_reused() {
+        let mut bindings = Bindings::new();
+        bindings.insert("a".to_string(), Object::Number(1));
+        drop(bindings);
+        match &Bindings::new() {
+            Bindings::Inline(inline) => {
+                assert!(inline.is_empty());
+                assert!(inline.capacity() > 0);
+            }
+            Bindings::Map(_) => panic!("expected inline bindings"),
+        }
+    }
+}
@@ -0,0 +1,404 @@
+use std::path::Path;
+use std::rc::Rc;
+
+use super::{
+    evaluator::Error,
+    object::{Array, Object},
+};
+use crate::read_file::read_file;
+
+// Reads a `.json` or `.csv` file into a value for `import name from "path";`. Relative paths
+// are resolved against the working directory, like the script passed on the command line.
+pub fn load_data_file(path: &str) -> Result<Object, Error> {
+    let extension = match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+        Some(extension) => extension.to_lowercase(),
+        None => String::new(),
+    };
+    if extension != "json" && extension != "csv" {
+        return Err(Error {
+            message: format!(
+                "cannot import {}, only .ank, .json and .csv files can be imported",
+                path
+            ),
+            child: None,
+        });
+    }
+    let text = match read_file(path) {
+        Ok(text) => text,
+        Err(error) => {
+            return Err(Error {
+                message: format!("cannot import {}", path),
+                child: Some(Box::new(Error {
+                    message: error.to_string(),
+                    child: None,
+                })),
+            })
+        }
+    };
+    let parsed = if extension == "json" {
+        parse_json(&text)
+    } else {
+        parse_csv(&text)
+    };
+    match parsed {
+        Ok(value) => Ok(value),
+        Err((message, offset)) => {
+            let (line, column) = get_line_column(&text, offset);
+            Err(Error {
+                message: format!("cannot import {}", path),
+                child: Some(Box::new(Error {
+                    message: format!("{} at line {} column {}", message, line, column),
+                    child: None,
+                })),
+            })
+        }
+    }
+}
+
+// The message and the byte offset it is about.
+type ParseResult<T> = Result<T, (String, usize)>;
+
+fn get_line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = match before.rfind('\n') {
+        Some(start) => before[start + 1..].chars().count() + 1,
+        None => before.chars().count() + 1,
+    };
+    (line, column)
+}
+
+// Objects become maps, keeping the order of their keys, and `null` becomes `null`. Integers
+// that fit in 64 bits become numbers and every other number a float.
+pub fn parse_json(text: &str) -> ParseResult<Object> {
+    let mut parser = JsonParser { text, offset: 0 };
+    let value = parser.parse_value()?;
+    parser.skip_whitespace();
+    if parser.offset < text.len() {
+        return Err(("unexpected trailing characters".to_string(), parser.offset));
+    }
+    Ok(value)
+}
+
+struct JsonParser<'a> {
+    text: &'a str,
+    offset: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.offset..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(' ' | '\t' | '\n' | '\r') = self.peek() {
+            self.offset += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> ParseResult<()> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.offset += 1;
+                Ok(())
+            }
+            Some(c) => Err((
+                format!("expected `{}`, found `{}`", expected, c),
+                self.offset,
+            )),
+            None => Err((format!("expected `{}`", expected), self.offset)),
+        }
+    }
+
+    fn parse_value(&mut self) -> ParseResult<Object> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.parse_object(),
+            Some('[') => self.parse_array(),
+            Some('"') => Ok(Object::StringLiteral(self.parse_string()?)),
+            Some('-' | '0'..='9') => self.parse_number(),
+            Some(_) => {
+                for (word, value) in [
+                    ("true", Object::Boolean(true)),
+                    ("false", Object::Boolean(false)),
+                    ("null", Object::Null),
+                ] {
+                    if self.text[self.offset..].starts_with(word) {
+                        self.offset += word.len();
+                        return Ok(value);
+                    }
+                }
+                Err(("expected a value".to_string(), self.offset))
+            }
+            None => Err(("unexpected end of input".to_string(), self.offset)),
+        }
+    }
+
+    fn parse_object(&mut self) -> ParseResult<Object> {
+        self.expect('{')?;
+        let mut entries: Vec<(String, Object)> = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.offset += 1;
+            return Ok(Object::Array(Rc::new(Array::from_entries(entries))));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err(("expected a string key".to_string(), self.offset));
+            }
+            let key = self.parse_string()?;
+            self.expect(':')?;
+            // the last value of a repeated key wins, as in JavaScript
+            entries.push((key, self.parse_value()?));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.offset += 1,
+                _ => break,
+            }
+        }
+        self.expect('}')?;
+        Ok(Object::Array(Rc::new(Array::from_entries(entries))))
+    }
+
+    fn parse_array(&mut self) -> ParseResult<Object> {
+        self.expect('[')?;
+        let mut elements = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.offset += 1;
+            return Ok(Object::Array(Rc::new(Array::from_objects(elements))));
+        }
+        loop {
+            elements.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.offset += 1,
+                _ => break,
+            }
+        }
+        self.expect(']')?;
+        Ok(Object::Array(Rc::new(Array::from_objects(elements))))
+    }
+
+    fn parse_string(&mut self) -> ParseResult<String> {
+        let start = self.offset;
+        self.offset += 1;
+        let mut value = String::new();
+        loop {
+            let c = match self.peek() {
+                Some(c) => c,
+                None => return Err(("unterminated string".to_string(), start)),
+            };
+            let escape_offset = self.offset;
+            self.offset += c.len_utf8();
+            match c {
+                '"' => return Ok(value),
+                '\\' => {
+                    let escaped = match self.peek() {
+                        Some(escaped) => escaped,
+                        None => return Err(("unterminated string".to_string(), start)),
+                    };
+                    self.offset += escaped.len_utf8();
+                    match escaped {
+                        '"' | '\\' | '/' => value.push(escaped),
+                        'b' => value.push('\u{8}'),
+                        'f' => value.push('\u{c}'),
+                        'n' => value.push('\n'),
+                        'r' => value.push('\r'),
+                        't' => value.push('\t'),
+                        'u' => value.push(self.parse_unicode_escape(escape_offset)?),
+                        _ => {
+                            return Err((
+                                format!("unknown escape sequence \\{}", escaped),
+                                escape_offset,
+                            ))
+                        }
+                    }
+                }
+                c if c < ' ' => {
+                    return Err((
+                        "control characters must be escaped".to_string(),
+                        escape_offset,
+                    ))
+                }
+                c => value.push(c),
+            }
+        }
+    }
+
+    // `\uXXXX` after the `u`, or a surrogate pair written as two of them.
+    fn parse_unicode_escape(&mut self, escape_offset: usize) -> ParseResult<char> {
+        let high = self.parse_hex4(escape_offset)?;
+        let code = if (0xD800..0xDC00).contains(&high) {
+            if !self.text[self.offset..].starts_with("\\u") {
+                return Err(("unpaired surrogate".to_string(), escape_offset));
+            }
+            self.offset += 2;
+            let low = self.parse_hex4(escape_offset)?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(("unpaired surrogate".to_string(), escape_offset));
+            }
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+        match char::from_u32(code) {
+            Some(c) => Ok(c),
+            None => Err(("unpaired surrogate".to_string(), escape_offset)),
+        }
+    }
+
+    fn parse_hex4(&mut self, escape_offset: usize) -> ParseResult<u32> {
+        let digits = self.text.get(self.offset..self.offset + 4);
+        match digits.and_then(|digits| u32::from_str_radix(digits, 16).ok()) {
+            Some(code) => {
+                self.offset += 4;
+                Ok(code)
+            }
+            None => Err(("expected 4 hex digits after \\u".to_string(), escape_offset)),
+        }
+    }
+
+    fn parse_number(&mut self) -> ParseResult<Object> {
+        let start = self.offset;
+        let rest = &self.text[start..];
+        let length = rest
+            .find(|c: char| !matches!(c, '0'..='9' | '-' | '+' | '.' | 'e' | 'E'))
+            .unwrap_or(rest.len());
+        let literal = &rest[..length];
+        self.offset += length;
+        if let Ok(value) = literal.parse::<i64>() {
+            return Ok(Object::Number(value));
+        }
+        match literal.parse::<f64>() {
+            Ok(value) if value.is_finite() => Ok(Object::Float(value)),
+            _ => Err((format!("invalid number {}", literal), start)),
+        }
+    }
+}
+
+// The first row names the columns, and every other row becomes a map from those names to
+// its fields, which stay strings. Quoted fields may hold commas, line breaks and `""`.
+pub fn parse_csv(text: &str) -> ParseResult<Object> {
+    let mut rows = parse_csv_rows(text)?.into_iter();
+    let header = match rows.next() {
+        Some((header, _)) => header,
+        None => return Ok(Object::Array(Rc::new(Array::from_objects(Vec::new())))),
+    };
+    for (index, name) in header.iter().enumerate() {
+        if header[..index].contains(name) {
+            return Err((format!("duplicate column {}", name), 0));
+        }
+    }
+    let mut records = Vec::new();
+    for (fields, offset) in rows {
+        if fields.len() != header.len() {
+            return Err((
+                format!("expected {} fields, found {}", header.len(), fields.len()),
+                offset,
+            ));
+        }
+        let entries = header
+            .iter()
+            .cloned()
+            .zip(fields.into_iter().map(Object::StringLiteral))
+            .collect();
+        records.push(Object::Array(Rc::new(Array::from_entries(entries))));
+    }
+    Ok(Object::Array(Rc::new(Array::from_objects(records))))
+}
+
+// Every non-empty row with the offset it starts at.
+fn parse_csv_rows(text: &str) -> ParseResult<Vec<(Vec<String>, usize)>> {
+    let mut rows = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut row_start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((offset, c)) = chars.next() {
+        match c {
+            '"' if field.is_empty() => loop {
+                match chars.next() {
+                    Some((_, '"')) if chars.peek().map(|(_, c)| *c) == Some('"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    Some((_, '"')) => match chars.peek() {
+                        None | Some((_, ',' | '\n' | '\r')) => break,
+                        Some((offset, _)) => {
+                            return Err(("expected `,` after a quoted field".to_string(), *offset))
+                        }
+                    },
+                    Some((_, c)) => field.push(c),
+                    None => return Err(("unterminated quoted field".to_string(), offset)),
+                }
+            },
+            ',' => fields.push(std::mem::take(&mut field)),
+            '\r' if chars.peek().map(|(_, c)| *c) == Some('\n') => {}
+            '\n' => {
+                fields.push(std::mem::take(&mut field));
+                if fields.len() > 1 || !fields[0].is_empty() {
+                    rows.push((std::mem::take(&mut fields), row_start));
+                }
+                fields.clear();
+                row_start = offset + 1;
+            }
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    if fields.len() > 1 || !fields[0].is_empty() {
+        rows.push((fields, row_start));
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_json() {
+        let value = parse_json(
+            r#" {"name": "ankara", "version": 1, "ratio": 2.5e0, "tags": ["a\n", "é😀"],
+                "nested": {"ok": true, "none": null}, "name": "again"} "#,
+        )
+        .unwrap();
+        assert_eq!(
+            value.to_string(),
+            "[name:again,version:1,ratio:2.5,tags:[a\n,é😀,],nested:[ok:true,none:null,],]"
+        );
+
+        let cases = vec![
+            ("[1, 2", ("expected `]`".to_string(), 5)),
+            ("{1: 2}", ("expected a string key".to_string(), 1)),
+            ("\"a\\q\"", ("unknown escape sequence \\q".to_string(), 2)),
+            ("[1] 2", ("unexpected trailing characters".to_string(), 4)),
+            ("1e999", ("invalid number 1e999".to_string(), 0)),
+            ("nul", ("expected a value".to_string(), 0)),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(parse_json(text), Err(expected), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let value = parse_csv("name,note\r\nankara,\"a, \"\"quoted\"\"\nline\"\n\nx,\n").unwrap();
+        assert_eq!(
+            value.to_string(),
+            "[[name:ankara,note:a, \"quoted\"\nline,],[name:x,note:,],]"
+        );
+        assert_eq!(
+            parse_csv("a,b\n1,2\n3\n"),
+            Err(("expected 2 fields, found 1".to_string(), 8))
+        );
+        assert_eq!(
+            parse_csv("a\n\"1\"2\n"),
+            Err(("expected `,` after a quoted field".to_string(), 5))
+        );
+        assert_eq!(get_line_column("a\nbc", 3), (2, 2));
+    }
+}
@@ -1,51 +1,123 @@
-extern crate rand;
 use crate::{
     ast::{BlockExpression, BlockReturnStatement, Expression, WatchDeclaration},
+    interpreter::bindings::Bindings,
     interpreter::object::Object,
+    interpreter::stats::{LiveKind, LiveToken},
 };
 use core::borrow;
-use std::{borrow::BorrowMut, cell::RefCell, collections::HashMap, path::Display, rc::Rc};
+use std::{
+    borrow::BorrowMut,
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    path::Display,
+    rc::{Rc, Weak},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+// Ids are handed out in order and never reused, so no two scopes share one.
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
 
 #[derive(Debug, Clone)]
 pub struct Environment {
-    pub values: HashMap<String, Object>,
-    pub watch: HashMap<String, Watch>,
+    pub values: Bindings,
+    pub constants: HashSet<String>,
+    // watches that depend on a variable defined in this scope, by variable name
+    pub watch: HashMap<String, Vec<Watch>>,
     pub parent: Option<Rc<RefCell<Environment>>>,
     pub children: Vec<Rc<RefCell<Environment>>>,
-    pub id: u32,
+    // identifies the scope; copies of it keep the id
+    pub id: u64,
+    pub live: LiveToken,
+    // Set on scopes shared by several programs, such as the builtins. See `fork_isolated`.
+    pub frozen: bool,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+// The scope a watch is declared in is held weakly: the watch ends with that scope instead
+// of keeping it alive from the scope of a variable it reads.
+#[derive(Debug, Clone)]
 pub struct Watch {
     pub expressions: Rc<RefCell<WatchDeclaration>>,
-    pub env: Rc<RefCell<Environment>>,
+    pub env: Weak<RefCell<Environment>>,
+}
+
+// What `watches()` reports about a watch.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ActiveWatch {
+    pub name: String,
+    pub dependencies: Vec<String>,
+    // none while the watch is being computed for the first time
+    pub value: Option<Object>,
+}
+
+impl PartialEq for Watch {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.expressions, &other.expressions) && self.env.ptr_eq(&other.env)
+    }
 }
 
 impl Environment {
     pub fn new(parent: Option<Rc<RefCell<Environment>>>) -> Environment {
         let env = Environment {
-            values: HashMap::new(),
+            values: Bindings::new(),
+            constants: HashSet::new(),
             watch: HashMap::new(),
             parent: parent.clone(),
             children: Vec::new(),
-            id: rand::random(),
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            live: LiveToken::new(LiveKind::Environment),
+            frozen: false,
         };
         match parent {
-            Some(parent) => {
+            Some(parent) if !parent.borrow().frozen => {
                 (*parent)
                     .borrow_mut()
                     .children
                     .push(Rc::new(RefCell::new(env.clone())));
             }
-            None => {}
+            _ => {}
         }
         env
     }
 
+    // Freezes `shared` and returns a new scope on top of it for one program. The program can
+    // read everything in `shared` and shadow it with its own definitions, but assigning to a
+    // name defined there is an error, so nothing one program does is seen by the next.
+    pub fn fork_isolated(shared: &Rc<RefCell<Environment>>) -> Environment {
+        (**shared).borrow_mut().frozen = true;
+        Environment::new(Some(shared.clone()))
+    }
+
+    // Whether `name` resolves to a binding of a frozen scope.
+    pub fn is_frozen(&self, name: &str) -> bool {
+        if self.values.contains_key(name) {
+            return self.frozen;
+        }
+        match &self.parent {
+            Some(parent) => parent.borrow().is_frozen(name),
+            None => false,
+        }
+    }
+
     pub fn define(&mut self, name: String, value: Object) {
         self.values.insert(name, value);
     }
 
+    pub fn define_constant(&mut self, name: String, value: Object) {
+        self.constants.insert(name.clone());
+        self.values.insert(name, value);
+    }
+
+    // Whether `name` resolves to a binding declared with `const`.
+    pub fn is_constant(&self, name: &str) -> bool {
+        if self.values.contains_key(name) {
+            return self.constants.contains(name);
+        }
+        match &self.parent {
+            Some(parent) => parent.borrow().is_constant(name),
+            None => false,
+        }
+    }
+
     pub fn get(&self, name: &str) -> Option<Object> {
         match self.values.get(name) {
             Some(value) => Some(value.clone()),
@@ -56,6 +128,15 @@ impl Environment {
         }
     }
 
+    // Every name visible from this scope, including shadowed ones.
+    pub fn visible_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.values.keys().into_iter().cloned().collect();
+        if let Some(parent) = &self.parent {
+            names.extend(parent.borrow().visible_names());
+        }
+        names
+    }
+
     pub fn assign(env: Rc<RefCell<Environment>>, name: &str, value: Object) -> Option<Object> {
         let mut cloned_env = env.clone();
         let mut borrowed_env = (*cloned_env).borrow_mut();
@@ -71,18 +152,110 @@ impl Environment {
         }
     }
 
+    // The scope that defines `name`, which is where watches on it are registered.
+    pub fn find_scope(
+        env: Rc<RefCell<Environment>>,
+        name: &str,
+    ) -> Option<Rc<RefCell<Environment>>> {
+        if env.borrow().values.contains_key(name) {
+            return Some(env);
+        }
+        let parent = env.borrow().parent.clone();
+        match parent {
+            Some(parent) => Environment::find_scope(parent, name),
+            None => None,
+        }
+    }
+
     pub fn set_watch(
         &mut self,
         expressions: Rc<RefCell<WatchDeclaration>>,
-        env: Rc<RefCell<Environment>>,
+        env: &Rc<RefCell<Environment>>,
         name: &str,
     ) {
-        self.watch
-            .insert(name.to_string(), Watch { expressions, env });
+        let watch = Watch {
+            expressions,
+            env: Rc::downgrade(env),
+        };
+        let watches = self.watch.entry(name.to_string()).or_default();
+        watches.retain(|watch| watch.env.strong_count() > 0);
+        if !watches.contains(&watch) {
+            watches.push(watch);
+        }
+    }
+
+    // The watches on `name` whose scope is still alive, with that scope. Ended ones are
+    // forgotten here.
+    pub fn get_watches(
+        &mut self,
+        name: &str,
+    ) -> Vec<(Rc<RefCell<WatchDeclaration>>, Rc<RefCell<Environment>>)> {
+        let watches = match self.watch.get_mut(name) {
+            Some(watches) => watches,
+            None => return Vec::new(),
+        };
+        watches.retain(|watch| watch.env.strong_count() > 0);
+        watches
+            .iter()
+            .filter_map(|watch| Some((watch.expressions.clone(), watch.env.upgrade()?)))
+            .collect()
     }
+    // The watches declared in `env` or a scope around it, sorted by name, with the variables
+    // each one reads. A watch is registered on the scope of every variable it reads, and
+    // those are all scopes around the one it is declared in.
+    pub fn active_watches(env: &Rc<RefCell<Environment>>) -> Vec<ActiveWatch> {
+        let mut chain = vec![env.clone()];
+        loop {
+            let parent = chain.last().unwrap().borrow().parent.clone();
+            match parent {
+                Some(parent) => chain.push(parent),
+                None => break,
+            }
+        }
+        let mut active: Vec<(Rc<RefCell<WatchDeclaration>>, ActiveWatch)> = Vec::new();
+        for scope in &chain {
+            for (variable, watches) in scope.borrow().watch.iter() {
+                for watch in watches {
+                    let watch_env = match watch.env.upgrade() {
+                        Some(watch_env) if chain.iter().any(|env| Rc::ptr_eq(env, &watch_env)) => {
+                            watch_env
+                        }
+                        _ => continue,
+                    };
+                    let index = match active
+                        .iter()
+                        .position(|(declaration, _)| Rc::ptr_eq(declaration, &watch.expressions))
+                    {
+                        Some(index) => index,
+                        None => {
+                            let name = watch.expressions.borrow().name.clone();
+                            let value = watch_env.borrow().get(&name);
+                            active.push((
+                                watch.expressions.clone(),
+                                ActiveWatch {
+                                    name,
+                                    dependencies: Vec::new(),
+                                    value,
+                                },
+                            ));
+                            active.len() - 1
+                        }
+                    };
+                    active[index].1.dependencies.push(variable.clone());
+                }
+            }
+        }
+        let mut active: Vec<ActiveWatch> = active.into_iter().map(|(_, watch)| watch).collect();
+        for watch in active.iter_mut() {
+            watch.dependencies.sort();
+        }
+        active.sort_by(|left, right| left.name.cmp(&right.name));
+        active
+    }
+
     pub fn to_string(&self) -> String {
         let mut result = String::new();
-        let mut keys: Vec<&String> = self.values.keys().collect();
+        let mut keys = self.values.keys();
         keys.sort();
         for key in keys {
             if let Some(value) = self.values.get(key) {
@@ -104,3 +277,20 @@ impl PartialEq for Environment {
         self.id == other.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_environments_are_distinct() {
+        let environments: Vec<Environment> = (0..10_000).map(|_| Environment::new(None)).collect();
+        for (index, env) in environments.iter().enumerate() {
+            assert_eq!(env, &env.clone());
+            if index > 0 {
+                assert!(env.id > environments[index - 1].id);
+                assert_ne!(env, &environments[index - 1]);
+            }
+        }
+    }
+}
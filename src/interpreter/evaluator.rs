@@ -3,21 +3,71 @@ use std::borrow::BorrowMut;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::ops::Add;
+use std::path::PathBuf;
 use std::rc::Rc;
 
-use crate::ast::{
-    self, ArrayMapValue, Assign, BlockExpression, ElementAccessExpression, Expression, Identifier,
-    Program, Statement, WatchDeclaration,
-};
+use crate::ast::{self, ArrayMapValue, Expression, Program, Statement, WatchDeclaration};
 use crate::interpreter::environment::Environment;
 use crate::interpreter::object::{Function, Object};
+use crate::printer::print_expression;
 
 use super::assign::EvalAssign;
-use super::object::{Array, ArrayElement, BlockReturn, Return};
+use super::data_file::load_data_file;
+use super::history::WatchHistory;
+use super::inline_cache::MethodLookup;
+use super::iterator::ForIterator;
+use super::module::{
+    is_bare_module, is_module_path, load_module, resolve_module, resolve_module_path,
+};
+use super::object::{
+    find_struct_method, resolve_index, Array, ArrayElement, BlockReturn, Break, Range, Return,
+    StructType,
+};
+use super::output::OutputLimit;
+use super::pattern::BindPattern;
+use super::stats::{LiveKind, LiveToken};
+use super::suggest::suggest;
+use super::trace::{error_object, ErrorKind, ErrorTrace};
+
+// How many function calls may be nested before a script fails, unless `--max-depth` says
+// otherwise.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 10_000;
+
+// The stack a thread needs to reach `max_depth` nested calls before it runs out, with room for
+// the parser and the script around them.
+pub fn call_stack_size(max_depth: usize) -> usize {
+    8 * 1024 * 1024 + max_depth * 128 * 1024
+}
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct EvalOption {
     pub watch: Option<Watch>,
+    // Set when watched values should be recorded for `history(name)` and `--record-watch`.
+    pub watch_history: Option<Rc<RefCell<WatchHistory>>>,
+    // Set by `--max-output` and `ankara test`; shared so nested options count together.
+    pub output_limit: Option<Rc<RefCell<OutputLimit>>>,
+    // The script being run, for `__file` and `callerLocation()`.
+    pub file: Option<String>,
+    // Lines of the calls to the functions being evaluated, innermost last.
+    pub call_lines: Vec<usize>,
+    // The scope a builtin is called from, set only while it runs.
+    pub caller_env: Option<Rc<RefCell<Environment>>>,
+    // The value of a `throw` while its error goes up to the `catch` that takes it.
+    pub thrown: Option<Object>,
+    // Where the error going up to a `catch` happened, once a call has seen it.
+    pub error_trace: Option<ErrorTrace>,
+    // The exports of every module imported so far, by path; shared so each is run only once.
+    pub modules: Rc<RefCell<HashMap<PathBuf, Object>>>,
+    // The modules being run by an import, outermost first, by path and as they were written.
+    pub importing: Vec<(PathBuf, String)>,
+    // Set by `--strict` or a leading `"use strict";`: numbers are not conditions, a
+    // declaration cannot shadow a variable, `?.` and `?[` do not hide a missing key, and
+    // only the last statement of a block may leave out its semicolon.
+    pub strict: bool,
+    // The calls being run, and how many may be nested before a call fails instead of
+    // overflowing the stack.
+    pub depth: usize,
+    pub max_depth: usize,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -28,7 +78,83 @@ pub struct Watch {
 
 impl EvalOption {
     pub fn new() -> EvalOption {
-        EvalOption { watch: None }
+        EvalOption {
+            watch: None,
+            watch_history: None,
+            output_limit: None,
+            file: None,
+            call_lines: Vec::new(),
+            caller_env: None,
+            thrown: None,
+            error_trace: None,
+            modules: Rc::new(RefCell::new(HashMap::new())),
+            importing: Vec::new(),
+            strict: false,
+            depth: 0,
+            max_depth: DEFAULT_MAX_CALL_DEPTH,
+        }
+    }
+
+    pub fn with_watch_history(capacity: usize) -> EvalOption {
+        EvalOption {
+            watch: None,
+            watch_history: Some(Rc::new(RefCell::new(WatchHistory::new(capacity)))),
+            output_limit: None,
+            file: None,
+            call_lines: Vec::new(),
+            caller_env: None,
+            thrown: None,
+            error_trace: None,
+            modules: Rc::new(RefCell::new(HashMap::new())),
+            importing: Vec::new(),
+            strict: false,
+            depth: 0,
+            max_depth: DEFAULT_MAX_CALL_DEPTH,
+        }
+    }
+
+    pub fn set_output_limit(&mut self, max_bytes: usize) {
+        self.output_limit = Some(Rc::new(RefCell::new(OutputLimit::new(max_bytes))));
+    }
+
+    // The error being raised, taken out while cleanup code runs so a `try` there cannot
+    // catch it. Give it back with `restore_raised` when the error goes on.
+    pub fn take_raised(&mut self) -> (Option<Object>, Option<ErrorTrace>) {
+        (self.thrown.take(), self.error_trace.take())
+    }
+
+    pub fn restore_raised(&mut self, raised: (Option<Object>, Option<ErrorTrace>)) {
+        (self.thrown, self.error_trace) = raised;
+    }
+
+    // Records where an error happened and the calls being run, unless a statement or call
+    // inside them already did. `line` is the call to a builtin that failed; without one the
+    // error is placed at the innermost call being run.
+    fn trace_error(&mut self, kind: ErrorKind, line: Option<usize>) {
+        if self.error_trace.is_some() {
+            return;
+        }
+        let mut calls = self.call_lines.clone();
+        calls.extend(line);
+        self.error_trace = Some(ErrorTrace {
+            kind,
+            line: calls.last().copied(),
+            file: self.file.clone(),
+            calls,
+        });
+    }
+
+    // Places an error at the statement on `line`, unless a statement inside it already did.
+    fn locate_error(&mut self, line: Option<usize>) {
+        if self.error_trace.is_some() || line.is_none() {
+            return;
+        }
+        self.error_trace = Some(ErrorTrace {
+            kind: ErrorKind::Runtime,
+            line,
+            file: self.file.clone(),
+            calls: self.call_lines.clone(),
+        });
     }
 }
 
@@ -49,17 +175,46 @@ impl Evaluator for Program {
         env: Rc<RefCell<Environment>>,
         option: &mut EvalOption,
     ) -> Result<Object, Error> {
-        let statements = &self.statements;
-        let mut value = Object::None;
-        let mut iter = statements.iter();
-        let mut option_statement = iter.next();
-        while option_statement.is_some() && value == Object::None {
-            let statement = option_statement.unwrap();
+        let strict = option.strict;
+        option.strict |= self.has_strict_pragma();
+        let value = eval_program_statements(self, env, option);
+        option.strict = strict;
+        value
+    }
+}
 
-            value = (*statement).eval(env.clone(), option).unwrap();
+// The statements of a program, which run until one of them returns or fails.
+fn eval_program_statements(
+    program: &Program,
+    env: Rc<RefCell<Environment>>,
+    option: &mut EvalOption,
+) -> Result<Object, Error> {
+    let statements = &program.statements;
+    check_semicolons(statements, option)?;
+    let mut value = Ok(Object::None);
+    let mut deferred = Vec::new();
+    let mut iter = statements.iter().enumerate();
+    let mut option_statement = iter.next();
+    while option_statement.is_some() && value == Ok(Object::None) {
+        let (index, statement) = option_statement.unwrap();
+        if let Statement::DeferStatement(defer_statement) = statement {
+            deferred.push(&defer_statement.value);
             option_statement = iter.next();
+            continue;
         }
-        Ok(value)
+        value = (*statement).eval(env.clone(), option);
+        if value.is_err() {
+            option.locate_error(program.lines.get(index));
+        }
+        option_statement = iter.next();
+    }
+    let value = run_deferred(&deferred, env, option, value)?;
+    match value {
+        Object::Break(_) | Object::Continue(_) => Err(Error {
+            message: format!("{} outside of a loop", value),
+            child: None,
+        }),
+        _ => Ok(value),
     }
 }
 
@@ -81,33 +236,177 @@ impl Evaluator for Statement {
                     Err(error) => return Err(error),
                 }
             }
+            Statement::DeclarationList(declaration_list) => {
+                for declaration in &declaration_list.declarations {
+                    let obj = declaration.eval(env.clone(), option)?;
+                    if let Object::Return(_) | Object::BlockReturn(_) = obj {
+                        return Ok(obj);
+                    }
+                }
+                Ok(Object::None)
+            }
+            Statement::MultipleAssign(multiple_assign) => {
+                match multiple_assign.eval(env, option)? {
+                    obj @ Object::Return(_) => Ok(obj),
+                    _ => Ok(Object::None),
+                }
+            }
+            Statement::DestructuringDeclaration(destructuring_declaration) => {
+                match destructuring_declaration.eval(env, option) {
+                    Ok(obj) => match obj {
+                        Object::Return(_) => return Ok(obj),
+                        Object::BlockReturn(_) => return Ok(obj),
+                        _ => return Ok(Object::None),
+                    },
+                    Err(error) => return Err(error),
+                }
+            }
+            Statement::ConstantDeclaration(constant_declaration) => {
+                match constant_declaration.eval(env, option) {
+                    Ok(obj) => match obj {
+                        Object::Return(_) => return Ok(obj),
+                        Object::BlockReturn(_) => return Ok(obj),
+                        _ => return Ok(Object::None),
+                    },
+                    Err(error) => return Err(error),
+                }
+            }
             Statement::Expression(expression) => match expression.eval(env, option) {
                 Ok(obj) => match obj {
                     Object::Return(_) => return Ok(obj),
                     Object::BlockReturn(_) => return Ok(obj),
+                    Object::Break(_) | Object::Continue(_) => return Ok(obj),
                     _ => return Ok(Object::None),
                 },
                 Err(error) => return Err(error),
             },
             Statement::ReturnStatement(return_statement) => {
                 match return_statement.eval(env, option) {
+                    // the returned expression already returned on its own
+                    Ok(Object::Return(value)) => return Ok(Object::Return(value)),
                     Ok(value) => return Ok(Object::Return(Box::new(Return { value: value }))),
                     Err(error) => return Err(error),
                 }
             }
+            // blocks collect these themselves, to run when they end
+            Statement::DeferStatement(_) => Err(Error {
+                message: "defer outside of a block".to_string(),
+                child: None,
+            }),
+            Statement::AssertStatement(assert_statement) => assert_statement.eval(env, option),
+            Statement::ThrowStatement(throw_statement) => {
+                let value = throw_statement.value.eval(env, option)?;
+                if let Object::Return(_) = value {
+                    return Ok(value);
+                }
+                let message = format!("uncaught {}", value);
+                option.thrown = Some(value);
+                Err(Error {
+                    message,
+                    child: None,
+                })
+            }
             Statement::BlockReturnStatement(block_return) => match block_return.eval(env, option) {
+                // an `if` that did not run has no value to return, so
+                // `if (n == 0) { return 1; } return n * f(n - 1);` goes on to the next statement
+                Ok(Object::None) => Ok(Object::None),
                 Ok(value) => {
                     return Ok(Object::BlockReturn(Box::new(BlockReturn { value: value })))
                 }
                 Err(error) => return Err(error),
             },
+            Statement::BreakStatement(break_statement) => {
+                // a bare `break` leaves the loop without a value, like a loop that runs out,
+                // so the loop does not end the block it is the last statement of
+                let value = match &break_statement.value {
+                    Some(value) => value.eval(env, option)?,
+                    None => Object::None,
+                };
+                match value {
+                    Object::Return(_) => return Ok(value),
+                    _ => {
+                        return Ok(Object::Break(Box::new(Break {
+                            label: break_statement.label.clone(),
+                            value,
+                        })))
+                    }
+                }
+            }
+            Statement::ContinueStatement(continue_statement) => {
+                Ok(Object::Continue(continue_statement.label.clone()))
+            }
             Statement::WatchDeclaration(watch_declaration) => {
                 match watch_declaration.eval(env, option) {
                     Ok(value) => return Ok(value),
                     Err(error) => return Err(error),
                 }
             }
+            // tests only run under `ankara test`, see `test_runner::run_tests`
+            Statement::TestBlock(_) => Ok(Object::None),
+            Statement::ImportStatement(import_statement) => {
+                match import_statement.eval(env, option) {
+                    Ok(_) => Ok(Object::None),
+                    Err(error) => Err(error),
+                }
+            }
+            Statement::ExportStatement(export_statement) => {
+                export_statement.declaration.eval(env, option)
+            }
+            Statement::StructDeclaration(struct_declaration) => {
+                struct_declaration.eval(env, option)
+            }
+        }
+    }
+}
+
+impl Evaluator for crate::ast::ImportStatement {
+    fn eval(
+        &self,
+        env: Rc<RefCell<Environment>>,
+        option: &mut EvalOption,
+    ) -> Result<Object, Error> {
+        let value = if is_module_path(&self.path) {
+            let path = resolve_module_path(&self.path, option);
+            load_module(&path, &env, option)?
+        } else if is_bare_module(&self.path) {
+            let path = resolve_module(&self.path, option)?;
+            load_module(&path, &env, option)?
+        } else {
+            load_data_file(&self.path)?
+        };
+        let mut env_borrowed = (*env).borrow_mut();
+        if env_borrowed.constants.contains(&self.name) {
+            return Err(Error {
+                message: "cannot redeclare constant ".to_string() + &self.name,
+                child: None,
+            });
+        }
+        env_borrowed.define(self.name.clone(), value);
+        Ok(Object::Null)
+    }
+}
+
+impl Evaluator for crate::ast::StructDeclaration {
+    fn eval(
+        &self,
+        env: Rc<RefCell<Environment>>,
+        _option: &mut EvalOption,
+    ) -> Result<Object, Error> {
+        let struct_type = Object::Struct(Rc::new(StructType {
+            name: self.name.clone(),
+            fields: self.fields.clone(),
+            methods: self.methods.clone(),
+            env: env.clone(),
+        }));
+        let mut env_borrowed = (*env).borrow_mut();
+        if env_borrowed.constants.contains(&self.name) {
+            return Err(Error {
+                message: "cannot redeclare constant ".to_string() + &self.name,
+                child: None,
+            });
         }
+        env_borrowed.define(self.name.clone(), struct_type);
+        Ok(Object::None)
     }
 }
 
@@ -124,11 +423,91 @@ impl Evaluator for crate::ast::VariableDeclaration {
             _ => {}
         }
         let mut env_borrowed = (*env).borrow_mut();
+        if env_borrowed.constants.contains(&name) {
+            return Err(Error {
+                message: "cannot redeclare constant ".to_string() + &name,
+                child: None,
+            });
+        }
+        check_shadowing(&env_borrowed, &name, option)?;
         env_borrowed.define(name, value);
         Ok(Object::Null)
     }
 }
 
+impl Evaluator for crate::ast::DestructuringDeclaration {
+    fn eval(
+        &self,
+        env: Rc<RefCell<Environment>>,
+        option: &mut EvalOption,
+    ) -> Result<Object, Error> {
+        let value = self.value.eval(env.clone(), option)?;
+        match value {
+            Object::Return(_) => return Ok(value),
+            _ => {}
+        }
+        let mut bindings = Vec::new();
+        if let Err(error) = self.pattern.bind(value, env.clone(), option, &mut bindings) {
+            let alternative = match &self.alternative {
+                Some(alternative) => alternative,
+                None => return Err(error),
+            };
+            let alternative_env = Rc::new(RefCell::new(Environment::new(Some(env))));
+            return match alternative.eval(alternative_env, option)? {
+                Object::Return(value) => Ok(Object::Return(value)),
+                _ => Err(Error {
+                    message: "the else block of a let pattern must return".to_string(),
+                    child: Some(Box::new(error)),
+                }),
+            };
+        }
+        let mut env_borrowed = (*env).borrow_mut();
+        for (name, value) in bindings {
+            if env_borrowed.constants.contains(&name) {
+                return Err(Error {
+                    message: "cannot redeclare constant ".to_string() + &name,
+                    child: None,
+                });
+            }
+            check_shadowing(&env_borrowed, &name, option)?;
+            env_borrowed.define(name, value);
+        }
+        Ok(Object::Null)
+    }
+}
+
+impl Evaluator for crate::ast::ConstantDeclaration {
+    fn eval(
+        &self,
+        env: Rc<RefCell<Environment>>,
+        option: &mut EvalOption,
+    ) -> Result<Object, Error> {
+        let name = self.name.clone();
+        let value = self.value.eval(env.clone(), option)?;
+        match value {
+            Object::Return(_) => return Ok(value),
+            _ => {}
+        }
+        let mut env_borrowed = (*env).borrow_mut();
+        if env_borrowed.values.contains_key(&name) {
+            return Err(Error {
+                message: "cannot redeclare ".to_string() + &name + " as a constant",
+                child: None,
+            });
+        }
+        check_shadowing(&env_borrowed, &name, option)?;
+        // the value cannot be changed either, not only the binding
+        let value = match value {
+            Object::Array(array) => {
+                Object::Array(Array::freeze(&array, &format!("constant {}", name)))
+            }
+            _ => value,
+        };
+        env_borrowed.define_constant(name, value);
+        Ok(Object::Null)
+    }
+}
+
 impl Evaluator for Expression {
     fn eval(
         &self,
@@ -137,7 +516,10 @@ impl Evaluator for Expression {
     ) -> Result<Object, Error> {
         match &self {
             Expression::NumberLiteral(integer_literal) => integer_literal.eval(env, option),
+            Expression::FloatLiteral(float_literal) => float_literal.eval(env, option),
             Expression::InfixExpression(infix_expression) => infix_expression.eval(env, option),
+            Expression::PrefixExpression(prefix_expression) => prefix_expression.eval(env, option),
+            Expression::UpdateExpression(update_expression) => update_expression.eval(env, option),
             Expression::Identifier(identifier) => identifier.eval(env, option),
             Expression::FunctionLiteral(function_declaration) => {
                 function_declaration.eval(env, option)
@@ -145,13 +527,30 @@ impl Evaluator for Expression {
             Expression::CallExpression(call_expression) => call_expression.eval(env, option),
             Expression::IfExpression(if_expression) => if_expression.eval(env, option),
             Expression::BooleanLiteral(boolean_literal) => boolean_literal.eval(env, option),
+            Expression::NullLiteral => Ok(Object::Null),
+            Expression::LineLiteral(position) => Ok(Object::Number(position.line as i64)),
+            Expression::FileLiteral => Ok(match &option.file {
+                Some(file) => Object::StringLiteral(file.clone()),
+                None => Object::Null,
+            }),
             Expression::StringLiteral(string_literal) => string_literal.eval(env, option),
+            Expression::TemplateLiteral(template_literal) => template_literal.eval(env, option),
             Expression::ArrayLiteral(array_literal) => array_literal.eval(env, option),
+            Expression::ArrayComprehension(comprehension) => comprehension.eval(env, option),
+            Expression::MapLiteral(map_literal) => map_literal.eval(env, option),
             Expression::ElementAccessExpression(element_access_expression) => {
                 element_access_expression.eval(env, option)
             }
+            Expression::MemberAccessExpression(member_access_expression) => {
+                member_access_expression.eval(env, option)
+            }
+            Expression::SliceExpression(slice_expression) => slice_expression.eval(env, option),
             Expression::ForExpression(for_expression) => for_expression.eval(env, option),
+            Expression::DoWhileExpression(do_while) => do_while.eval(env, option),
+            Expression::LoopExpression(loop_expression) => loop_expression.eval(env, option),
             Expression::SwitchExpression(switch_expression) => switch_expression.eval(env, option),
+            Expression::MatchExpression(match_expression) => match_expression.eval(env, option),
+            Expression::TryExpression(try_expression) => try_expression.eval(env, option),
             Expression::Assign(assign) => assign.eval(env, option),
             Expression::BlockExpression(block) => block.eval(env, option),
         }
@@ -162,12 +561,22 @@ impl Evaluator for crate::ast::NumberLiteral {
     fn eval(
         &self,
         _env: Rc<RefCell<Environment>>,
-        option: &mut EvalOption,
+        _option: &mut EvalOption,
     ) -> Result<Object, Error> {
         Ok(Object::Number(self.value))
     }
 }
 
+impl Evaluator for crate::ast::FloatLiteral {
+    fn eval(
+        &self,
+        _env: Rc<RefCell<Environment>>,
+        _option: &mut EvalOption,
+    ) -> Result<Object, Error> {
+        Ok(Object::Float(self.value))
+    }
+}
+
 impl Evaluator for crate::ast::InfixExpression {
     fn eval(
         &self,
@@ -175,15 +584,41 @@ impl Evaluator for crate::ast::InfixExpression {
         option: &mut EvalOption,
     ) -> Result<Object, Error> {
         let left = self.left.eval(env.clone(), option)?;
+        // the right side of `??` is only evaluated when it is needed
+        if self.operator == crate::ast::Operator::Coalesce {
+            return match left {
+                Object::Null | Object::None => self.right.eval(env, option),
+                left => Ok(left),
+            };
+        }
         let right = self.right.eval(env, option)?;
         let operator = self.operator.clone();
+        if operator == crate::ast::Operator::In {
+            return eval_in(&left, &right);
+        }
         match (left, right) {
             (Object::Number(left_value), Object::Number(right_value)) => match operator {
-                crate::ast::Operator::Plus => Ok(Object::Number(left_value + right_value)),
-                crate::ast::Operator::Minus => Ok(Object::Number(left_value - right_value)),
-                crate::ast::Operator::Asterisk => Ok(Object::Number(left_value * right_value)),
-                crate::ast::Operator::Slash => Ok(Object::Number(left_value / right_value)),
-                crate::ast::Operator::Percent => Ok(Object::Number(left_value % right_value)),
+                crate::ast::Operator::Plus => {
+                    checked_number(left_value.checked_add(right_value), &operator)
+                }
+                crate::ast::Operator::Minus => {
+                    checked_number(left_value.checked_sub(right_value), &operator)
+                }
+                crate::ast::Operator::Asterisk => {
+                    checked_number(left_value.checked_mul(right_value), &operator)
+                }
+                crate::ast::Operator::Slash | crate::ast::Operator::Percent if right_value == 0 => {
+                    Err(Error {
+                        message: "division by zero".to_string(),
+                        child: None,
+                    })
+                }
+                crate::ast::Operator::Slash => {
+                    checked_number(left_value.checked_div(right_value), &operator)
+                }
+                crate::ast::Operator::Percent => {
+                    checked_number(left_value.checked_rem(right_value), &operator)
+                }
                 crate::ast::Operator::Equal => Ok(Object::Boolean(left_value == right_value)),
                 crate::ast::Operator::NotEqual => Ok(Object::Boolean(left_value != right_value)),
                 crate::ast::Operator::LessThan => Ok(Object::Boolean(left_value < right_value)),
@@ -194,6 +629,9 @@ impl Evaluator for crate::ast::InfixExpression {
                 crate::ast::Operator::GreaterThanOrEqual => {
                     Ok(Object::Boolean(left_value >= right_value))
                 }
+                crate::ast::Operator::And | crate::ast::Operator::Or if option.strict => {
+                    Err(strict_error(format!("{} does not take numbers", operator)))
+                }
                 crate::ast::Operator::And => {
                     Ok(Object::Boolean(left_value != 0 && right_value != 0))
                 }
@@ -201,7 +639,46 @@ impl Evaluator for crate::ast::InfixExpression {
                     Ok(Object::Boolean(left_value != 0 || right_value != 0))
                 }
                 crate::ast::Operator::Bang => Ok(Object::Boolean(left_value == 0)),
+                crate::ast::Operator::Range => Ok(Object::Range(Range {
+                    start: left_value,
+                    end: right_value,
+                    step: 1,
+                })),
+                crate::ast::Operator::In | crate::ast::Operator::Coalesce => unreachable!(),
             },
+            // mixing an integer with a float promotes the integer
+            (Object::Float(left_value), Object::Float(right_value)) => {
+                eval_float_infix(operator, left_value, right_value)
+            }
+            (Object::Number(left_value), Object::Float(right_value)) => {
+                eval_float_infix(operator, left_value as f64, right_value)
+            }
+            (Object::Float(left_value), Object::Number(right_value)) => {
+                eval_float_infix(operator, left_value, right_value as f64)
+            }
+            (Object::StringLiteral(value), Object::Number(times))
+                if operator == crate::ast::Operator::Asterisk =>
+            {
+                let times = repeat_count(times)?;
+                match value.len().checked_mul(times) {
+                    Some(_) => Ok(Object::StringLiteral(value.repeat(times))),
+                    None => Err(Error {
+                        message: "repeated string is too long".to_string(),
+                        child: None,
+                    }),
+                }
+            }
+            // an instance of a struct is left to its operator methods
+            (Object::Array(left_value), Object::Array(right_value))
+                if operator == crate::ast::Operator::Plus && left_value.struct_type.is_none() =>
+            {
+                Ok(concat_arrays(&left_value, &right_value))
+            }
+            (Object::Array(array), Object::Number(times))
+                if operator == crate::ast::Operator::Asterisk && array.struct_type.is_none() =>
+            {
+                repeat_array(&array, repeat_count(times)?)
+            }
             (Object::StringLiteral(left_value), Object::StringLiteral(right_value)) => {
                 match operator {
                     crate::ast::Operator::Plus => {
@@ -211,12 +688,31 @@ impl Evaluator for crate::ast::InfixExpression {
                     crate::ast::Operator::NotEqual => {
                         Ok(Object::Boolean(left_value != right_value))
                     }
+                    // strings are ordered by their characters' code points
+                    crate::ast::Operator::LessThan => Ok(Object::Boolean(left_value < right_value)),
+                    crate::ast::Operator::LessThanOrEqual => {
+                        Ok(Object::Boolean(left_value <= right_value))
+                    }
+                    crate::ast::Operator::GreaterThan => {
+                        Ok(Object::Boolean(left_value > right_value))
+                    }
+                    crate::ast::Operator::GreaterThanOrEqual => {
+                        Ok(Object::Boolean(left_value >= right_value))
+                    }
                     _ => Err(Error {
                         message: "invalid operator".to_string(),
                         child: None,
                     }),
                 }
             }
+            (Object::Range(left_value), Object::Range(right_value)) => match operator {
+                crate::ast::Operator::Equal => Ok(Object::Boolean(left_value == right_value)),
+                crate::ast::Operator::NotEqual => Ok(Object::Boolean(left_value != right_value)),
+                _ => Err(Error {
+                    message: "invalid operator".to_string(),
+                    child: None,
+                }),
+            },
             (Object::Boolean(left_value), Object::Boolean(right_value)) => match operator {
                 crate::ast::Operator::Equal => Ok(Object::Boolean(left_value == right_value)),
                 crate::ast::Operator::NotEqual => Ok(Object::Boolean(left_value != right_value)),
@@ -225,119 +721,497 @@ impl Evaluator for crate::ast::InfixExpression {
                     child: None,
                 }),
             },
-            _ => Err(Error {
-                message: "invalid operator".to_string(),
-                child: None,
-            }),
+            // any value can be compared with null, which only equals itself
+            (left_value @ Object::Null, right_value) | (left_value, right_value @ Object::Null) => {
+                match operator {
+                    crate::ast::Operator::Equal => {
+                        Ok(Object::Boolean(left_value.is_equal_to(&right_value)))
+                    }
+                    crate::ast::Operator::NotEqual => {
+                        Ok(Object::Boolean(!left_value.is_equal_to(&right_value)))
+                    }
+                    _ => Err(Error {
+                        message: "invalid operator".to_string(),
+                        child: None,
+                    }),
+                }
+            }
+            (left_value, right_value) => {
+                let message = invalid_operator_message(&left_value, &operator, &right_value);
+                match eval_operator_method(&left_value, &operator, right_value, option) {
+                    Some(result) => result,
+                    None => Err(Error {
+                        message,
+                        child: None,
+                    }),
+                }
+            }
         }
     }
 }
 
-impl Evaluator for crate::ast::Identifier {
-    fn eval(
-        &self,
-        env: Rc<RefCell<Environment>>,
-        option: &mut EvalOption,
-    ) -> Result<Object, Error> {
-        let cloned_env = env.clone();
-        match option.watch {
-            Some(ref watch) => {
-                let watch_declaration = watch.declaration.clone();
-                let watch_env = watch.env.clone();
-                let mut borrowed = (*cloned_env).borrow_mut();
-                borrowed.set_watch(watch_declaration.clone(), watch_env.clone(), &self.value);
-            }
-            None => {}
+// Why `left op right` has no meaning. Adding a number to a string is a common slip, so the
+// error names both types and how to convert the number.
+fn invalid_operator_message(
+    left: &Object,
+    operator: &crate::ast::Operator,
+    right: &Object,
+) -> String {
+    if *operator != crate::ast::Operator::Plus {
+        return "invalid operator".to_string();
+    }
+    let number = match (left, right) {
+        (Object::StringLiteral(_), number @ (Object::Number(_) | Object::Float(_)))
+        | (number @ (Object::Number(_) | Object::Float(_)), Object::StringLiteral(_)) => number,
+        _ => return "invalid operator".to_string(),
+    };
+    format!(
+        "cannot add {} and {}, convert the number with str({}) first",
+        left.type_name(),
+        right.type_name(),
+        number
+    )
+}
+
+// `left op right` through the method a struct defines for the operator, like `__add` for `+`,
+// when `left` is an instance of one. `!=` is the opposite of `__eq`.
+fn eval_operator_method(
+    left: &Object,
+    operator: &crate::ast::Operator,
+    right: Object,
+    option: &mut EvalOption,
+) -> Option<Result<Object, Error>> {
+    let name = match operator {
+        crate::ast::Operator::Plus => "__add",
+        crate::ast::Operator::Minus => "__sub",
+        crate::ast::Operator::Asterisk => "__mul",
+        crate::ast::Operator::Slash => "__div",
+        crate::ast::Operator::Percent => "__mod",
+        crate::ast::Operator::Equal | crate::ast::Operator::NotEqual => "__eq",
+        _ => return None,
+    };
+    let method = find_struct_method(left, name)?;
+    let result = call_hook(&method, name, vec![right], option);
+    Some(match operator {
+        crate::ast::Operator::Equal => result.map(|equal| Object::Boolean(!equal.is_falsey())),
+        crate::ast::Operator::NotEqual => result.map(|equal| Object::Boolean(equal.is_falsey())),
+        _ => result,
+    })
+}
+
+// `value in collection`: whether an array holds the value as an element, a map has it as a
+// key, or a range contains it.
+fn eval_in(value: &Object, collection: &Object) -> Result<Object, Error> {
+    let found = match collection {
+        Object::Array(array) => {
+            let is_key = match value {
+                Object::StringLiteral(key) => array.map.borrow().contains_key(key),
+                Object::Number(key) => array.map.borrow().contains_key(&key.to_string()),
+                _ => false,
+            };
+            is_key
+                || array.elements.borrow().iter().any(|element| match element {
+                    ArrayElement::Object(element) => element.is_equal_to(value),
+                    ArrayElement::Key(_) => false,
+                })
         }
-        let value = cloned_env.borrow().get(&self.value);
-        match value {
-            Some(value) => Ok(value),
-            None => Err(Error {
-                message: "variable not found ".to_string() + &self.value,
+        Object::Range(range) => match value {
+            Object::Number(value) => range.contains(*value),
+            _ => false,
+        },
+        _ => {
+            return Err(Error {
+                message: format!("in needs an array, a map or a range, got {}", collection),
                 child: None,
-            }),
+            })
         }
+    };
+    Ok(Object::Boolean(found))
+}
+
+// Integer arithmetic never wraps: a result outside of i64 is a runtime error.
+fn checked_number(value: Option<i64>, operator: &crate::ast::Operator) -> Result<Object, Error> {
+    match value {
+        Some(value) => Ok(Object::Number(value)),
+        None => Err(Error {
+            message: format!("integer overflow in {}", operator),
+            child: None,
+        }),
     }
 }
 
-impl Evaluator for crate::ast::FunctionLiteral {
-    fn eval(
-        &self,
-        env: Rc<RefCell<Environment>>,
-        option: &mut EvalOption,
-    ) -> Result<Object, Error> {
-        let parameters = self.parameters.clone();
-        let body = self.body.clone();
-        let function = Object::Function(Function {
-            parameters,
-            body,
-            env: env,
-        });
-        Ok(function)
+fn eval_float_infix(
+    operator: crate::ast::Operator,
+    left_value: f64,
+    right_value: f64,
+) -> Result<Object, Error> {
+    match operator {
+        crate::ast::Operator::Plus => Ok(Object::Float(left_value + right_value)),
+        crate::ast::Operator::Minus => Ok(Object::Float(left_value - right_value)),
+        crate::ast::Operator::Asterisk => Ok(Object::Float(left_value * right_value)),
+        crate::ast::Operator::Slash => Ok(Object::Float(left_value / right_value)),
+        crate::ast::Operator::Percent => Ok(Object::Float(left_value % right_value)),
+        crate::ast::Operator::Equal => Ok(Object::Boolean(left_value == right_value)),
+        crate::ast::Operator::NotEqual => Ok(Object::Boolean(left_value != right_value)),
+        crate::ast::Operator::LessThan => Ok(Object::Boolean(left_value < right_value)),
+        crate::ast::Operator::LessThanOrEqual => Ok(Object::Boolean(left_value <= right_value)),
+        crate::ast::Operator::GreaterThan => Ok(Object::Boolean(left_value > right_value)),
+        crate::ast::Operator::GreaterThanOrEqual => Ok(Object::Boolean(left_value >= right_value)),
+        crate::ast::Operator::Range => Err(Error {
+            message: "range bounds must be integers".to_string(),
+            child: None,
+        }),
+        _ => Err(Error {
+            message: "invalid operator".to_string(),
+            child: None,
+        }),
     }
 }
 
-impl Evaluator for crate::ast::CallExpression {
+impl Evaluator for crate::ast::PrefixExpression {
     fn eval(
         &self,
         env: Rc<RefCell<Environment>>,
         option: &mut EvalOption,
     ) -> Result<Object, Error> {
-        let function = self.left.eval(env.clone(), option)?;
-        let arguments = self.arguments.clone();
-        match function {
-            Object::Function(function) => {
-                let mut function_env = Environment::new(Some(function.env.clone()));
-                for (index, parameter) in function.parameters.iter().enumerate() {
-                    let argument = arguments.get(index).unwrap();
-                    let value = argument.eval(env.clone(), option)?;
-                    function_env.define(parameter.value.clone(), value);
-                }
-                let result = function
-                    .body
-                    .eval(Rc::new(RefCell::new(function_env)), option);
-                match result {
-                    Ok(Object::Return(return_value)) => Ok(return_value.value),
-                    Ok(value) => Ok(value),
-                    Err(error) => Err(error),
-                }
+        let right = self.right.eval(env, option)?;
+        match (&self.operator, right) {
+            (crate::ast::Operator::Minus, Object::Number(value)) => {
+                checked_number(value.checked_neg(), &self.operator)
             }
-            Object::BuiltInFunction(buildin) => {
-                let mut args = Vec::new();
-                for argument in arguments {
-                    let value = argument.eval(env.clone(), option)?;
-                    args.push(value);
-                }
-                let function = buildin.function;
-                function(args);
-                Ok(Object::Null)
+            (crate::ast::Operator::Minus, Object::Float(value)) => Ok(Object::Float(-value)),
+            (crate::ast::Operator::Bang, Object::Boolean(value)) => Ok(Object::Boolean(!value)),
+            (crate::ast::Operator::Bang, Object::Number(value)) => {
+                is_truthy(&Object::Number(value), option).map(|truthy| Object::Boolean(!truthy))
             }
             _ => Err(Error {
-                message: "not a function".to_string() + &self.left.to_string(),
+                message: "invalid operator".to_string(),
                 child: None,
             }),
         }
     }
 }
 
-impl Evaluator for crate::ast::BlockExpression {
+// Assigns the target one more or one less, and evaluates to the new value when the
+// operator comes first and to the old value otherwise.
+impl Evaluator for crate::ast::UpdateExpression {
     fn eval(
         &self,
         env: Rc<RefCell<Environment>>,
         option: &mut EvalOption,
     ) -> Result<Object, Error> {
-        let statements = &self.statements;
-        let mut value = Ok(Object::None);
-        let mut iter = statements.iter();
-        let mut option_statement = iter.next();
+        let old = self.target.eval(env.clone(), option)?;
+        let step = match self.operator {
+            crate::ast::UpdateOperator::Increment => 1,
+            crate::ast::UpdateOperator::Decrement => -1,
+        };
+        let new = match &old {
+            Object::Number(value) => match value.checked_add(step) {
+                Some(value) => Object::Number(value),
+                None => {
+                    return Err(Error {
+                        message: format!("integer overflow in {}", self.operator),
+                        child: None,
+                    })
+                }
+            },
+            Object::Float(value) => Object::Float(value + step as f64),
+            _ => {
+                return Err(Error {
+                    message: format!("cannot apply {} to {}", self.operator, old),
+                    child: None,
+                })
+            }
+        };
+        match &self.target {
+            Expression::Identifier(identifier) => {
+                identifier.assign(env, new.clone(), self.position.line, option)?;
+            }
+            Expression::ElementAccessExpression(element_access_expression) => {
+                element_access_expression.assign(env, new.clone(), self.position.line, option)?;
+            }
+            Expression::MemberAccessExpression(member_access_expression) => {
+                member_access_expression.assign(env, new.clone(), self.position.line, option)?;
+            }
+            _ => {
+                return Err(Error {
+                    message: "invalid assignment".to_string(),
+                    child: None,
+                })
+            }
+        }
+        if self.prefix {
+            Ok(new)
+        } else {
+            Ok(old)
+        }
+    }
+}
+
+impl Evaluator for crate::ast::Identifier {
+    fn eval(
+        &self,
+        env: Rc<RefCell<Environment>>,
+        option: &mut EvalOption,
+    ) -> Result<Object, Error> {
+        let cloned_env = env.clone();
+        if let Some(ref watch) = option.watch {
+            // frozen scopes cannot be assigned to, so they are not watched either
+            let scope = Environment::find_scope(env.clone(), &self.value)
+                .filter(|scope| !scope.borrow().frozen);
+            if let Some(scope) = scope {
+                (*scope)
+                    .borrow_mut()
+                    .set_watch(watch.declaration.clone(), &watch.env, &self.value);
+            }
+        }
+        let value = cloned_env.borrow().get(&self.value);
+        match value {
+            Some(value) => Ok(value),
+            None => {
+                let names = cloned_env.borrow().visible_names();
+                let message = match suggest(&self.value, names.iter()) {
+                    Some(name) => format!(
+                        "variable not found {}, did you mean `{}`?",
+                        self.value, name
+                    ),
+                    None => "variable not found ".to_string() + &self.value,
+                };
+                Err(Error {
+                    message,
+                    child: None,
+                })
+            }
+        }
+    }
+}
+
+impl Evaluator for crate::ast::FunctionLiteral {
+    fn eval(
+        &self,
+        env: Rc<RefCell<Environment>>,
+        _option: &mut EvalOption,
+    ) -> Result<Object, Error> {
+        let parameters = self.parameters.clone();
+        let body = self.body.clone();
+        let function = Object::Function(Function {
+            parameters,
+            rest: self.rest.clone(),
+            body,
+            env: env,
+            live: Rc::new(LiveToken::new(LiveKind::Function)),
+        });
+        Ok(function)
+    }
+}
+
+impl Evaluator for crate::ast::CallExpression {
+    fn eval(
+        &self,
+        env: Rc<RefCell<Environment>>,
+        option: &mut EvalOption,
+    ) -> Result<Object, Error> {
+        let function = match &self.left {
+            // a method of the receiver's type comes before a function stored under its name
+            Expression::MemberAccessExpression(member_access) => {
+                let receiver = member_access.left.eval(env.clone(), option)?;
+                // `null?.name(...)` is null without calling anything
+                if member_access.optional && matches!(receiver, Object::Null) {
+                    return Ok(Object::Null);
+                }
+                match self.cache.lookup(&receiver, &member_access.member) {
+                    MethodLookup::Struct(method) => Object::Function(method),
+                    MethodLookup::Builtin(method) => {
+                        let mut args = Vec::new();
+                        for argument in &self.arguments {
+                            args.push(argument.eval(env.clone(), option)?);
+                        }
+                        let result = method.call(receiver, args, option);
+                        if result.is_err() {
+                            option.trace_error(ErrorKind::Builtin, Some(self.position.line));
+                        }
+                        return result;
+                    }
+                    MethodLookup::Member => get_member(member_access, receiver, option)?,
+                }
+            }
+            left => left.eval(env.clone(), option)?,
+        };
+        let arguments = self.arguments.clone();
+        match function {
+            Object::Function(function) => {
+                let mut args = Vec::new();
+                for argument in &arguments {
+                    args.push(argument.eval(env.clone(), option)?);
+                }
+                option.call_lines.push(self.position.line);
+                let result = call_function(&function, args, option);
+                option.call_lines.pop();
+                result
+            }
+            Object::BuiltInFunction(buildin) => {
+                let mut args = Vec::new();
+                for argument in arguments {
+                    let value = argument.eval(env.clone(), option)?;
+                    args.push(value);
+                }
+                let function = buildin.function;
+                option.caller_env = Some(env);
+                let result = function(args, option);
+                option.caller_env = None;
+                if result.is_err() {
+                    option.trace_error(ErrorKind::Builtin, Some(self.position.line));
+                }
+                result
+            }
+            Object::Struct(struct_type) => {
+                let mut args = Vec::new();
+                for argument in arguments {
+                    args.push(argument.eval(env.clone(), option)?);
+                }
+                struct_type.instantiate(args)
+            }
+            _ => Err(Error {
+                message: "not a function".to_string() + &self.left.to_string(),
+                child: None,
+            }),
+        }
+    }
+}
+
+// Calls a method the interpreter looks up by name, like `__add`, with its fixed arguments.
+pub fn call_hook(
+    method: &Function,
+    name: &str,
+    arguments: Vec<Object>,
+    option: &mut EvalOption,
+) -> Result<Object, Error> {
+    let parameters = method.parameters.len();
+    if parameters > arguments.len() || (parameters < arguments.len() && method.rest.is_none()) {
+        return Err(Error {
+            message: format!(
+                "{} must take {} parameter{}, not {}",
+                name,
+                arguments.len(),
+                if arguments.len() == 1 { "" } else { "s" },
+                parameters
+            ),
+            child: None,
+        });
+    }
+    call_function(method, arguments, option)
+}
+
+// Runs the body of `function` with arguments that are already evaluated.
+pub fn call_function(
+    function: &Function,
+    arguments: Vec<Object>,
+    option: &mut EvalOption,
+) -> Result<Object, Error> {
+    if arguments.len() < function.parameters.len() {
+        return Err(Error {
+            message: format!(
+                "wrong number of arguments. got={}, want={}",
+                arguments.len(),
+                function.parameters.len()
+            ),
+            child: None,
+        });
+    }
+    if option.depth >= option.max_depth {
+        let mut message = format!("maximum call depth {} exceeded", option.max_depth);
+        // methods the interpreter calls itself, like `__add`, have no line to list
+        if !option.call_lines.is_empty() {
+            message += &format!(
+                ", calls from lines {}",
+                describe_call_lines(&option.call_lines)
+            );
+        }
+        let error = Error {
+            message,
+            child: None,
+        };
+        option.trace_error(ErrorKind::Runtime, None);
+        return Err(error);
+    }
+    let mut function_env = Environment::new(Some(function.env.clone()));
+    let mut arguments = arguments.into_iter();
+    for parameter in &function.parameters {
+        function_env.define(parameter.value.clone(), arguments.next().unwrap());
+    }
+    if let Some(rest) = &function.rest {
+        let values = Object::Array(Rc::new(Array::from_objects(arguments.collect())));
+        function_env.define(rest.value.clone(), values);
+    }
+    option.depth += 1;
+    let result = function
+        .body
+        .eval(Rc::new(RefCell::new(function_env)), option);
+    option.depth -= 1;
+    if result.is_err() {
+        option.trace_error(ErrorKind::Runtime, None);
+    }
+    match result {
+        Ok(Object::Return(return_value)) => Ok(return_value.value),
+        Ok(value @ (Object::Break(_) | Object::Continue(_))) => Err(Error {
+            message: format!("{} outside of a loop", value),
+            child: None,
+        }),
+        Ok(value) => Ok(value),
+        Err(error) => Err(error),
+    }
+}
+
+// `1, 4 (x9998), 2`, the lines of the calls being run, outermost first, with a line that
+// calls itself over and over written once.
+fn describe_call_lines(lines: &[usize]) -> String {
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for line in lines {
+        match runs.last_mut() {
+            Some((last, count)) if last == line => *count += 1,
+            _ => runs.push((*line, 1)),
+        }
+    }
+    runs.iter()
+        .map(|(line, count)| match count {
+            1 => line.to_string(),
+            count => format!("{} (x{})", line, count),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl Evaluator for crate::ast::BlockExpression {
+    fn eval(
+        &self,
+        env: Rc<RefCell<Environment>>,
+        option: &mut EvalOption,
+    ) -> Result<Object, Error> {
+        let statements = &self.statements;
+        check_semicolons(statements, option)?;
+        let mut value = Ok(Object::None);
+        let mut deferred = Vec::new();
+        let mut iter = statements.iter().enumerate();
+        let mut option_statement = iter.next();
         while option_statement.is_some() {
-            let statement = option_statement.unwrap();
+            let (index, statement) = option_statement.unwrap();
+            if let Statement::DeferStatement(defer_statement) = statement {
+                deferred.push(&defer_statement.value);
+                option_statement = iter.next();
+                continue;
+            }
             value = (*statement).eval(env.clone(), option);
-            if value.is_ok() && value.clone().unwrap().is_return_like() {
-                break;
+            if value.is_err() {
+                option.locate_error(self.lines.get(index));
+            }
+            // an error stops the block, so a later statement cannot hide it
+            match &value {
+                Ok(object) if !object.is_return_like() => {}
+                _ => break,
             }
             option_statement = iter.next();
         }
+        let value = run_deferred(&deferred, env, option, value);
         match value {
             Ok(Object::BlockReturn(block_return)) => Ok(block_return.value),
             Ok(value) => match value {
@@ -349,6 +1223,58 @@ impl Evaluator for crate::ast::BlockExpression {
     }
 }
 
+// Evaluates what a block deferred, last first, after the block has its result. An error in a
+// deferred expression becomes the result unless the block already failed.
+fn run_deferred(
+    deferred: &[&Expression],
+    env: Rc<RefCell<Environment>>,
+    option: &mut EvalOption,
+    result: Result<Object, Error>,
+) -> Result<Object, Error> {
+    if deferred.is_empty() {
+        return result;
+    }
+    let raised = option.take_raised();
+    let failed = result.is_err();
+    let mut result = result;
+    for expression in deferred.iter().rev() {
+        if let Err(error) = expression.eval(env.clone(), option) {
+            if result.is_ok() {
+                result = Err(error);
+            }
+        }
+    }
+    if failed {
+        option.restore_raised(raised);
+    }
+    result
+}
+
+impl Evaluator for crate::ast::AssertStatement {
+    fn eval(
+        &self,
+        env: Rc<RefCell<Environment>>,
+        option: &mut EvalOption,
+    ) -> Result<Object, Error> {
+        let condition = self.condition.eval(env.clone(), option)?;
+        if is_truthy(&condition, option)? {
+            return Ok(Object::None);
+        }
+        let condition = print_expression(&self.condition, 0);
+        let message = match &self.message {
+            Some(message) => {
+                let message = message.eval(env, option)?;
+                format!("assertion failed: {} ({})", condition, message)
+            }
+            None => format!("assertion failed: {}", condition),
+        };
+        Err(Error {
+            message,
+            child: None,
+        })
+    }
+}
+
 impl Evaluator for crate::ast::ReturnStatement {
     fn eval(
         &self,
@@ -367,7 +1293,7 @@ impl Evaluator for crate::ast::IfExpression {
         option: &mut EvalOption,
     ) -> Result<Object, Error> {
         let condition = self.condition.eval(env.clone(), option)?;
-        if !condition.is_falsey() {
+        if is_truthy(&condition, option)? {
             self.consequence.eval(env.clone(), option)
         } else {
             match self.alternative.clone() {
@@ -378,11 +1304,54 @@ impl Evaluator for crate::ast::IfExpression {
     }
 }
 
+impl Evaluator for crate::ast::TryExpression {
+    fn eval(
+        &self,
+        env: Rc<RefCell<Environment>>,
+        option: &mut EvalOption,
+    ) -> Result<Object, Error> {
+        // nothing is being thrown when a try starts, so what an uncaught error left behind
+        // cannot reach this handler
+        option.thrown = None;
+        option.error_trace = None;
+        let result = match (self.body.eval(env.clone(), option), &self.handler) {
+            (Err(error), Some(handler)) => {
+                let trace = option.error_trace.take();
+                let value = match option.thrown.take() {
+                    Some(value) => value,
+                    None => error_object(error, trace),
+                };
+                let mut handler_env = Environment::new(Some(env.clone()));
+                handler_env.define(handler.parameter.value.clone(), value);
+                handler
+                    .body
+                    .eval(Rc::new(RefCell::new(handler_env)), option)
+            }
+            (result, _) => result,
+        };
+        let finalizer = match &self.finalizer {
+            Some(finalizer) => finalizer,
+            None => return result,
+        };
+        let raised = option.take_raised();
+        // `return`, `break` and `continue` in the finalizer replace the result
+        let value = finalizer.eval(env, option)?;
+        if matches!(
+            value,
+            Object::Return(_) | Object::Break(_) | Object::Continue(_)
+        ) {
+            return Ok(value);
+        }
+        option.restore_raised(raised);
+        result
+    }
+}
+
 impl Evaluator for crate::ast::BooleanLiteral {
     fn eval(
         &self,
         _env: Rc<RefCell<Environment>>,
-        option: &mut EvalOption,
+        _option: &mut EvalOption,
     ) -> Result<Object, Error> {
         Ok(Object::Boolean(self.value))
     }
@@ -392,38 +1361,102 @@ impl Evaluator for crate::ast::StringLiteral {
     fn eval(
         &self,
         _env: Rc<RefCell<Environment>>,
-        option: &mut EvalOption,
+        _option: &mut EvalOption,
     ) -> Result<Object, Error> {
         Ok(Object::StringLiteral(self.value.to_string()))
     }
 }
 
-impl Evaluator for crate::ast::ArrayLiteral {
+impl Evaluator for crate::ast::TemplateLiteral {
     fn eval(
         &self,
         env: Rc<RefCell<Environment>>,
         option: &mut EvalOption,
     ) -> Result<Object, Error> {
-        let mut elements: Vec<ArrayElement> = Vec::new();
-        let mut map_elements: HashMap<String, Object> = HashMap::new();
-        for element in &self.elements {
-            match element {
-                ArrayMapValue::Value(val) => {
-                    let value = val.eval(env.clone(), option)?;
-                    elements.push(ArrayElement::Object(value));
+        let mut result = String::new();
+        for part in &self.parts {
+            match part {
+                crate::ast::TemplatePart::String(value) => result.push_str(value),
+                crate::ast::TemplatePart::Expression(expression) => {
+                    let value = expression.eval(env.clone(), option)?;
+                    if value.is_return_like() {
+                        return Ok(value);
+                    }
+                    result.push_str(&value.to_string());
                 }
-                ArrayMapValue::MapKeyValue(val) => {
-                    let value = val.value.eval(env.clone(), option)?;
-                    map_elements.insert(val.key.clone(), value);
+            }
+        }
+        Ok(Object::StringLiteral(result))
+    }
+}
+
+impl Evaluator for crate::ast::ArrayLiteral {
+    fn eval(
+        &self,
+        env: Rc<RefCell<Environment>>,
+        option: &mut EvalOption,
+    ) -> Result<Object, Error> {
+        if let Some(elements) = &self.pool.elements {
+            return Ok(Object::Array(Rc::new(Array::from_pool(elements.clone()))));
+        }
+        let (elements, map_elements) = eval_elements(&self.elements, env, option)?;
+        Ok(Object::Array(Rc::new(Array::new(elements, map_elements))))
+    }
+}
+
+impl Evaluator for crate::ast::MapLiteral {
+    fn eval(
+        &self,
+        env: Rc<RefCell<Environment>>,
+        option: &mut EvalOption,
+    ) -> Result<Object, Error> {
+        let (elements, map_elements) = eval_elements(&self.entries, env, option)?;
+        Ok(Object::Array(Rc::new(Array::new_map(
+            elements,
+            map_elements,
+        ))))
+    }
+}
+
+fn eval_elements(
+    literal_elements: &[ArrayMapValue],
+    env: Rc<RefCell<Environment>>,
+    option: &mut EvalOption,
+) -> Result<(Vec<ArrayElement>, HashMap<String, Object>), Error> {
+    let mut elements: Vec<ArrayElement> = Vec::new();
+    let mut map_elements: HashMap<String, Object> = HashMap::new();
+    for element in literal_elements {
+        match element {
+            ArrayMapValue::Value(val) => {
+                let value = val.eval(env.clone(), option)?;
+                elements.push(ArrayElement::Object(value));
+            }
+            // a repeated key keeps its first position, as in `Array::set`
+            ArrayMapValue::MapKeyValue(val) => {
+                let value = val.value.eval(env.clone(), option)?;
+                if map_elements.insert(val.key.clone(), value).is_none() {
                     elements.push(ArrayElement::Key(val.key.clone()));
                 }
             }
+            ArrayMapValue::ComputedKeyValue(val) => {
+                let key = match val.key.eval(env.clone(), option)? {
+                    Object::StringLiteral(key) => key,
+                    key @ (Object::Number(_) | Object::Float(_)) => key.to_string(),
+                    key => {
+                        return Err(Error {
+                            message: format!("map keys must be strings or numbers, got {}", key),
+                            child: None,
+                        })
+                    }
+                };
+                let value = val.value.eval(env.clone(), option)?;
+                if map_elements.insert(key.clone(), value).is_none() {
+                    elements.push(ArrayElement::Key(key));
+                }
+            }
         }
-        Ok(Object::Array(Rc::new(Array {
-            elements: RefCell::new(elements),
-            map: RefCell::new(map_elements),
-        })))
     }
+    Ok((elements, map_elements))
 }
 
 impl Evaluator for crate::ast::ElementAccessExpression {
@@ -434,32 +1467,25 @@ impl Evaluator for crate::ast::ElementAccessExpression {
     ) -> Result<Object, Error> {
         let left = self.left.eval(env.clone(), option)?;
         let index = self.index.eval(env, option)?;
+        // a struct with `__index` decides what indexing its instances means
+        if let Some(method) = find_struct_method(&left, "__index") {
+            return call_hook(&method, "__index", vec![index], option);
+        }
         match left {
             Object::Array(array) => match index {
                 Object::Number(val) => {
                     let elements = array.elements.borrow();
                     let map = array.map.borrow();
-                    let index = val as usize;
-                    let element = match elements.get(index) {
+                    let element = match array.resolve_index(val).and_then(|i| elements.get(i)) {
                         Some(ArrayElement::Object(val)) => val,
                         Some(ArrayElement::Key(val)) => {
                             let key = val.clone();
                             match map.get(&key) {
                                 Some(val) => val,
-                                None => {
-                                    return Err(Error {
-                                        message: "key not found".to_string(),
-                                        child: None,
-                                    })
-                                }
+                                None => return missing(self.optional, "key not found"),
                             }
                         }
-                        None => {
-                            return Err(Error {
-                                message: "index out of bounds".to_string(),
-                                child: None,
-                            })
-                        }
+                        None => return missing(self.optional, "index out of bounds"),
                     };
                     Ok(element.clone())
                 }
@@ -467,14 +1493,10 @@ impl Evaluator for crate::ast::ElementAccessExpression {
                     let key = val.clone();
                     match array.map.borrow().get(&key) {
                         Some(val) => Ok(val.clone()),
-                        None => {
-                            return Err(Error {
-                                message: "key not found".to_string(),
-                                child: None,
-                            })
-                        }
+                        None => missing(self.optional && !option.strict, "key not found"),
                     }
                 }
+                Object::Range(range) => slice_array(&array, &range),
                 _ => {
                     return Err(Error {
                         message: "not a number".to_string() + &self.index.to_string(),
@@ -482,16 +1504,305 @@ impl Evaluator for crate::ast::ElementAccessExpression {
                     })
                 }
             },
+            Object::Range(range) => match index {
+                Object::Number(val) => match usize::try_from(val).ok().and_then(|i| range.get(i)) {
+                    Some(value) => Ok(Object::Number(value)),
+                    None => missing(self.optional, "index out of bounds"),
+                },
+                Object::StringLiteral(key) => match range.field(&key) {
+                    Some(value) => Ok(Object::Number(value)),
+                    None => missing(self.optional, "key not found"),
+                },
+                _ => Err(Error {
+                    message: "not a number".to_string() + &self.index.to_string(),
+                    child: None,
+                }),
+            },
+            // a character of a string is a string of its own
+            Object::StringLiteral(value) => match index {
+                Object::Number(val) => {
+                    let character = resolve_index(val, value.chars().count())
+                        .and_then(|i| value.chars().nth(i));
+                    match character {
+                        Some(character) => Ok(Object::StringLiteral(character.to_string())),
+                        None => missing(self.optional, "index out of bounds"),
+                    }
+                }
+                _ => Err(Error {
+                    message: "not a number".to_string() + &self.index.to_string(),
+                    child: None,
+                }),
+            },
+            _ => missing(
+                self.optional,
+                &("not an array".to_string() + &self.left.to_string()),
+            ),
+        }
+    }
+}
+
+impl Evaluator for crate::ast::MemberAccessExpression {
+    fn eval(
+        &self,
+        env: Rc<RefCell<Environment>>,
+        option: &mut EvalOption,
+    ) -> Result<Object, Error> {
+        let left = self.left.eval(env, option)?;
+        get_member(self, left, option)
+    }
+}
+
+// The value of the member of `left`, which `member_access.left` evaluated to.
+fn get_member(
+    member_access: &crate::ast::MemberAccessExpression,
+    left: Object,
+    option: &EvalOption,
+) -> Result<Object, Error> {
+    let array = match left {
+        Object::Array(array) => array,
+        Object::Range(range) => {
+            return match range.field(&member_access.member) {
+                Some(value) => Ok(Object::Number(value)),
+                None => missing(member_access.optional && !option.strict, "key not found"),
+            }
+        }
+        _ => {
+            return missing(
+                member_access.optional,
+                &("not an array".to_string() + &member_access.left.to_string()),
+            )
+        }
+    };
+    let value = array.map.borrow().get(&member_access.member).cloned();
+    match value {
+        Some(value) => Ok(value),
+        None => match find_struct_method(&Object::Array(array), &member_access.member) {
+            Some(method) => Ok(Object::Function(method)),
+            None => missing(member_access.optional && !option.strict, "key not found"),
+        },
+    }
+}
+
+// What a lookup with nothing to find evaluates to: null through `?[` or `?.`, an error
+// otherwise.
+fn missing(optional: bool, message: &str) -> Result<Object, Error> {
+    if optional {
+        return Ok(Object::Null);
+    }
+    Err(Error {
+        message: message.to_string(),
+        child: None,
+    })
+}
+
+// Whether a condition holds. In strict mode a number is not a condition.
+pub fn is_truthy(value: &Object, option: &EvalOption) -> Result<bool, Error> {
+    if option.strict && matches!(value, Object::Number(_) | Object::Float(_)) {
+        return Err(strict_error(format!(
+            "{} is a number, not a condition",
+            value
+        )));
+    }
+    Ok(!value.is_falsey())
+}
+
+// In strict mode, a statement without a semicolon has to be the last of its block, since
+// it ends the block with its value.
+fn check_semicolons(statements: &[Statement], option: &EvalOption) -> Result<(), Error> {
+    if !option.strict || statements.is_empty() {
+        return Ok(());
+    }
+    for statement in &statements[..statements.len() - 1] {
+        if let Statement::BlockReturnStatement(block_return) = statement {
+            return Err(strict_error(format!(
+                "expected a semicolon after {}",
+                print_expression(&block_return.value, 0)
+            )));
+        }
+    }
+    Ok(())
+}
+
+// In strict mode, a declaration cannot hide a variable of a scope around it. Builtins can
+// still be shadowed.
+fn check_shadowing(env: &Environment, name: &str, option: &EvalOption) -> Result<(), Error> {
+    if option.strict
+        && !env.values.contains_key(name)
+        && env.get(name).is_some()
+        && !env.is_frozen(name)
+    {
+        return Err(strict_error(format!(
+            "{} shadows a variable of an enclosing scope",
+            name
+        )));
+    }
+    Ok(())
+}
+
+fn strict_error(message: String) -> Error {
+    Error {
+        message: format!("strict mode: {}", message),
+        child: None,
+    }
+}
+
+impl Evaluator for crate::ast::SliceExpression {
+    fn eval(
+        &self,
+        env: Rc<RefCell<Environment>>,
+        option: &mut EvalOption,
+    ) -> Result<Object, Error> {
+        let left = self.left.eval(env.clone(), option)?;
+        let length = match &left {
+            Object::Array(array) => array.elements.borrow().len(),
+            Object::StringLiteral(value) => value.chars().count(),
             _ => {
                 return Err(Error {
-                    message: "not an array".to_string() + &self.left.to_string(),
+                    message: "cannot slice ".to_string() + &self.left.to_string(),
                     child: None,
                 })
             }
+        };
+        let range = Range {
+            start: eval_slice_bound(&self.start, 0, env.clone(), option)?,
+            end: eval_slice_bound(&self.end, length as i64, env, option)?,
+            step: 1,
+        };
+        match left {
+            Object::Array(array) => slice_array(&array, &range),
+            // strings are sliced by characters, like arrays of one-character strings
+            Object::StringLiteral(value) => {
+                if range.is_empty() {
+                    return Ok(Object::StringLiteral(String::new()));
+                }
+                if range.start < 0 || range.end > length as i64 {
+                    return Err(Error {
+                        message: "index out of bounds".to_string(),
+                        child: None,
+                    });
+                }
+                let sliced = value
+                    .chars()
+                    .skip(range.start as usize)
+                    .take(range.len())
+                    .collect();
+                Ok(Object::StringLiteral(sliced))
+            }
+            _ => unreachable!(),
         }
     }
 }
 
+fn eval_slice_bound(
+    bound: &Option<Expression>,
+    default: i64,
+    env: Rc<RefCell<Environment>>,
+    option: &mut EvalOption,
+) -> Result<i64, Error> {
+    let bound = match bound {
+        Some(bound) => bound.eval(env, option)?,
+        None => return Ok(default),
+    };
+    match bound {
+        Object::Number(value) => Ok(value),
+        obj => Err(Error {
+            message: format!("slice bounds must be integers, got {}", obj),
+            child: None,
+        }),
+    }
+}
+
+// The elements at the indices of the range, which all have to be in bounds. Keyed elements
+// keep their keys.
+fn slice_array(array: &Array, range: &Range) -> Result<Object, Error> {
+    let elements = array.elements.borrow();
+    let map = array.map.borrow();
+    let mut sliced = Vec::new();
+    let mut sliced_map = HashMap::new();
+    for index in range.iter() {
+        let element = match usize::try_from(index).ok().and_then(|i| elements.get(i)) {
+            Some(element) => element,
+            None => {
+                return Err(Error {
+                    message: "index out of bounds".to_string(),
+                    child: None,
+                })
+            }
+        };
+        if let ArrayElement::Key(key) = element {
+            match map.get(key) {
+                Some(value) => sliced_map.insert(key.clone(), value.clone()),
+                None => {
+                    return Err(Error {
+                        message: "key not found".to_string(),
+                        child: None,
+                    })
+                }
+            };
+        }
+        sliced.push(element.clone());
+    }
+    if array.is_map {
+        return Ok(Object::Array(Rc::new(Array::new_map(sliced, sliced_map))));
+    }
+    Ok(Object::Array(Rc::new(Array::new(sliced, sliced_map))))
+}
+
+// `left + right`, a new array of the elements of `left` followed by those of `right`. A key
+// both have keeps its position in `left` and takes its value in `right`.
+fn concat_arrays(left: &Array, right: &Array) -> Object {
+    let elements = left.elements.borrow().to_vec();
+    let map = left.map.borrow().clone();
+    let concatenated = match left.is_map && right.is_map {
+        true => Array::new_map(elements, map),
+        false => Array::new(elements, map),
+    };
+    let right_map = right.map.borrow();
+    for element in right.elements.borrow().iter() {
+        match element {
+            ArrayElement::Object(value) => concatenated
+                .elements_mut()
+                .push(ArrayElement::Object(value.clone())),
+            ArrayElement::Key(key) => {
+                if let Some(value) = right_map.get(key) {
+                    concatenated.set(key.clone(), value.clone());
+                }
+            }
+        }
+    }
+    Object::Array(Rc::new(concatenated))
+}
+
+// `array * times`, a new array of the elements of `array` over and over. The elements
+// themselves are not copied, so `[[0]] * 2` holds the same inner array twice.
+fn repeat_array(array: &Array, times: usize) -> Result<Object, Error> {
+    if !array.map.borrow().is_empty() {
+        return Err(Error {
+            message: "cannot repeat an array with keys".to_string(),
+            child: None,
+        });
+    }
+    let elements = array.elements.borrow();
+    let len = match elements.len().checked_mul(times) {
+        Some(len) => len,
+        None => {
+            return Err(Error {
+                message: "repeated array is too long".to_string(),
+                child: None,
+            })
+        }
+    };
+    let repeated = elements.iter().cycle().take(len).cloned().collect();
+    Ok(Object::Array(Rc::new(Array::new(repeated, HashMap::new()))))
+}
+
+fn repeat_count(times: i64) -> Result<usize, Error> {
+    usize::try_from(times).map_err(|_| Error {
+        message: format!("cannot repeat a value {} times", times),
+        child: None,
+    })
+}
+
 impl Evaluator for crate::ast::BlockReturnStatement {
     fn eval(
         &self,
@@ -503,64 +1814,123 @@ impl Evaluator for crate::ast::BlockReturnStatement {
     }
 }
 
+impl Evaluator for crate::ast::ArrayComprehension {
+    fn eval(
+        &self,
+        env: Rc<RefCell<Environment>>,
+        option: &mut EvalOption,
+    ) -> Result<Object, Error> {
+        let iterable = self.iterable.eval(env.clone(), option)?;
+        let mut iterator = ForIterator::new(iterable, option)?;
+        let mut values = Vec::new();
+        while let Some((_, element)) = iterator.next_element(option)? {
+            let mut element_env = Environment::new(Some(env.clone()));
+            element_env.define(self.variable.value.clone(), element);
+            let element_env = Rc::new(RefCell::new(element_env));
+            if let Some(condition) = &self.condition {
+                let condition = condition.eval(element_env.clone(), option)?;
+                if !is_truthy(&condition, option)? {
+                    continue;
+                }
+            }
+            values.push(self.element.eval(element_env, option)?);
+        }
+        Ok(Object::Array(Rc::new(Array::from_objects(values))))
+    }
+}
+
 impl Evaluator for crate::ast::ForExpression {
     fn eval(
         &self,
         env: Rc<RefCell<Environment>>,
         option: &mut EvalOption,
     ) -> Result<Object, Error> {
-        let mut value = Ok(Object::None);
-        let mut return_array = Array {
-            elements: RefCell::new(Vec::new()),
-            map: RefCell::new(HashMap::new()),
-        };
-        let iter = self.iterable.eval(env.clone(), option);
-        let mut obj = match iter {
-            Ok(obj) => obj,
-            Err(error) => return Err(error),
-        };
-        let array = match obj {
-            Object::Array(array) => array,
-            _ => {
-                return Err(Error {
-                    message: "not an array".to_string(),
-                    child: None,
-                })
+        let iterable = self.iterable.eval(env.clone(), option)?;
+        let mut iterator = ForIterator::new(iterable, option)?;
+        while let Some((index, element)) = iterator.next_element(option)? {
+            if let Some(result) = self.eval_iteration(&env, index, element, option) {
+                return result;
             }
-        };
-        let elements = array.elements.borrow();
-        let mut iter = elements.iter();
-        let mut option_array_value = iter.next();
-
-        while option_array_value.is_some() {
-            let map = array.map.borrow();
-            let array_value = match option_array_value.unwrap() {
-                ArrayElement::Object(val) => val,
-                ArrayElement::Key(key) => {
-                    let key = key.clone();
-                    match map.get(&key) {
-                        Some(val) => val,
-                        None => {
-                            return Err(Error {
-                                message: "key not found".to_string(),
-                                child: None,
-                            })
-                        }
-                    }
+        }
+        Ok(Object::None)
+    }
+}
+
+impl crate::ast::ForExpression {
+    // Runs the body for one element, and returns the value of the loop once it ends. `index`
+    // is the position of the element, or the key of a keyed one.
+    fn eval_iteration(
+        &self,
+        env: &Rc<RefCell<Environment>>,
+        index: Object,
+        element: Object,
+        option: &mut EvalOption,
+    ) -> Option<Result<Object, Error>> {
+        let mut for_env = Environment::new(Some(env.clone()));
+        if let Some(index_variable) = &self.index {
+            for_env.define(index_variable.value.clone(), index);
+        }
+        for_env.define(self.variable.value.clone(), element);
+        match self.body.eval(Rc::new(RefCell::new(for_env)), option) {
+            Ok(Object::Break(break_value)) if targets(&self.label, &break_value.label) => {
+                Some(Ok(break_value.value))
+            }
+            Ok(Object::Continue(label)) if targets(&self.label, &label) => None,
+            Ok(Object::None) => None,
+            value => Some(value),
+        }
+    }
+}
+
+// Whether a `break` or `continue` with `label` is meant for the loop labeled `loop_label`.
+// Without a label it is meant for the innermost loop, and labeled ones pass through the
+// loops inside the one they name.
+fn targets(loop_label: &Option<String>, label: &Option<String>) -> bool {
+    label.is_none() || label == loop_label
+}
+
+impl Evaluator for crate::ast::DoWhileExpression {
+    fn eval(
+        &self,
+        env: Rc<RefCell<Environment>>,
+        option: &mut EvalOption,
+    ) -> Result<Object, Error> {
+        loop {
+            let body_env = Rc::new(RefCell::new(Environment::new(Some(env.clone()))));
+            match self.body.eval(body_env, option) {
+                Ok(Object::Break(break_value)) if targets(&self.label, &break_value.label) => {
+                    return Ok(break_value.value)
                 }
-            };
-            let mut for_env = Environment::new(Some(env.clone()));
-            for_env.define(self.variable.value.clone(), array_value.clone());
-            value = self.body.eval(Rc::new(RefCell::new(for_env)), option);
-            match value {
-                Ok(Object::Return(_)) => return value,
+                // `continue` still checks the condition before the next run
+                Ok(Object::Continue(label)) if targets(&self.label, &label) => {}
                 Ok(Object::None) => {}
-                Ok(obj) => return Ok(obj),
-                Err(error) => return Err(error),
+                value => return value,
+            }
+            let condition = self.condition.eval(env.clone(), option)?;
+            if !is_truthy(&condition, option)? {
+                return Ok(Object::None);
+            }
+        }
+    }
+}
+
+impl Evaluator for crate::ast::LoopExpression {
+    fn eval(
+        &self,
+        env: Rc<RefCell<Environment>>,
+        option: &mut EvalOption,
+    ) -> Result<Object, Error> {
+        loop {
+            let body_env = Rc::new(RefCell::new(Environment::new(Some(env.clone()))));
+            match self.body.eval(body_env, option) {
+                Ok(Object::Break(break_value)) if targets(&self.label, &break_value.label) => {
+                    return Ok(break_value.value)
+                }
+                Ok(Object::Continue(label)) if targets(&self.label, &label) => {}
+                Ok(Object::None) => {}
+                value => return value,
             }
-            option_array_value = iter.next();
         }
-        Ok(Object::None)
     }
 }
 
@@ -572,20 +1942,41 @@ impl Evaluator for crate::ast::SwitchExpression {
     ) -> Result<Object, Error> {
         let value = self.expression.eval(env.clone(), option)?;
         let cases = &self.cases;
+        // the previous body ended with `fallthrough;`, so this one runs unconditionally
+        let mut falling = false;
         for case in cases {
-            let condition = match case.condition.eval(env.clone(), option) {
-                Ok(condition) => condition,
-                Err(error) => return Err(error),
+            let case_env = match &case.condition {
+                // the parser only lets `fallthrough` reach cases without a pattern
+                _ if falling => env.clone(),
+                ast::CaseCondition::Value(condition) => {
+                    let condition = condition.eval(env.clone(), option)?;
+                    if !condition.is_equal_to(&value) {
+                        continue;
+                    }
+                    env.clone()
+                }
+                // a value that does not fit the pattern just moves on to the next case
+                ast::CaseCondition::Pattern(pattern) => {
+                    let mut bindings = Vec::new();
+                    if pattern
+                        .bind(value.clone(), env.clone(), option, &mut bindings)
+                        .is_err()
+                    {
+                        continue;
+                    }
+                    let mut case_env = Environment::new(Some(env.clone()));
+                    for (name, value) in bindings {
+                        case_env.define(name, value);
+                    }
+                    Rc::new(RefCell::new(case_env))
+                }
             };
-
-            if condition.is_equal_to(&value) {
-                let body = case.body.eval(env.clone(), option)?;
-                match body {
-                    Object::Return(_) => return Ok(body),
-                    Object::None => {}
-                    _ => return Ok(body),
-                };
+            let body = case.body.eval(case_env, option)?;
+            if case.fallthrough && !body.is_return_like() {
+                falling = true;
+                continue;
             }
+            return Ok(body);
         }
         let default = match &self.default {
             Some(default) => default,
@@ -605,48 +1996,126 @@ impl Evaluator for crate::ast::SwitchExpression {
     }
 }
 
-impl Evaluator for crate::ast::Assign {
+impl Evaluator for crate::ast::MatchExpression {
     fn eval(
         &self,
         env: Rc<RefCell<Environment>>,
         option: &mut EvalOption,
     ) -> Result<Object, Error> {
-        let left = self.left.clone();
-        match left {
-            Expression::Identifier(identifier) => {
-                let value = self.right.eval(env.clone(), option)?;
-                identifier.assign(env.clone(), value, option)
+        let value = self.expression.eval(env.clone(), option)?;
+        for arm in &self.arms {
+            let mut bindings = Vec::new();
+            if arm
+                .pattern
+                .bind(value.clone(), env.clone(), option, &mut bindings)
+                .is_err()
+            {
+                continue;
             }
-            Expression::ElementAccessExpression(element_access_expression) => {
-                let value = self.right.eval(env.clone(), option)?;
-                element_access_expression.assign(env, value, option)
+            let mut arm_env = Environment::new(Some(env.clone()));
+            for (name, value) in bindings {
+                arm_env.define(name, value);
             }
-            _ => Err(Error {
-                message: "invalid assignment".to_string(),
-                child: None,
-            }),
+            return arm.body.eval(Rc::new(RefCell::new(arm_env)), option);
         }
+        Err(Error {
+            message: format!("no match arm for {}", value),
+            child: None,
+        })
     }
 }
 
-impl Evaluator for crate::ast::WatchDeclaration {
+impl Evaluator for crate::ast::Assign {
+    fn eval(
+        &self,
+        env: Rc<RefCell<Environment>>,
+        option: &mut EvalOption,
+    ) -> Result<Object, Error> {
+        let value = self.right.eval(env.clone(), option)?;
+        assign_to(&self.left, env, value, self.position.line, option)
+    }
+}
+
+impl Evaluator for crate::ast::MultipleAssign {
     fn eval(
         &self,
         env: Rc<RefCell<Environment>>,
         option: &mut EvalOption,
+    ) -> Result<Object, Error> {
+        let mut values = Vec::new();
+        for value in &self.values {
+            let value = value.eval(env.clone(), option)?;
+            if let Object::Return(_) = value {
+                return Ok(value);
+            }
+            values.push(value);
+        }
+        for (target, value) in self.targets.iter().zip(values) {
+            assign_to(target, env.clone(), value, self.position.line, option)?;
+        }
+        Ok(Object::Null)
+    }
+}
+
+fn assign_to(
+    target: &Expression,
+    env: Rc<RefCell<Environment>>,
+    value: Object,
+    line: usize,
+    option: &mut EvalOption,
+) -> Result<Object, Error> {
+    match target {
+        Expression::Identifier(identifier) => identifier.assign(env, value, line, option),
+        Expression::ElementAccessExpression(element_access_expression) => {
+            element_access_expression.assign(env, value, line, option)
+        }
+        Expression::MemberAccessExpression(member_access_expression) => {
+            member_access_expression.assign(env, value, line, option)
+        }
+        _ => Err(Error {
+            message: "invalid assignment".to_string(),
+            child: None,
+        }),
+    }
+}
+
+impl Evaluator for crate::ast::WatchDeclaration {
+    fn eval(
+        &self,
+        env: Rc<RefCell<Environment>>,
+        outer_option: &mut EvalOption,
     ) -> Result<Object, Error> {
         let block = Rc::new(RefCell::new(self.block.clone()));
-        let mut option = if env.borrow().get(&self.name).is_some() {
-            EvalOption { watch: None }
+        let is_initial = env.borrow().get(&self.name).is_none();
+        let mut option = if !is_initial {
+            EvalOption {
+                watch: None,
+                ..outer_option.clone()
+            }
         } else {
             EvalOption {
                 watch: Some(Watch {
                     declaration: Rc::new(RefCell::new(self.clone())),
                     env: env.clone(),
                 }),
+                ..outer_option.clone()
+            }
+        };
+        let value = match block.borrow().eval(env.clone(), &mut option) {
+            Ok(value) => value,
+            Err(error) => {
+                let raised = option.take_raised();
+                outer_option.restore_raised(raised);
+                return Err(error);
             }
         };
-        let value = block.borrow().eval(env.clone(), &mut option)?;
+        if is_initial {
+            if let Some(watch_history) = option.watch_history.clone() {
+                (*watch_history)
+                    .borrow_mut()
+                    .record(&self.name, value.clone(), None);
+            }
+        }
         (*env).borrow_mut().define(self.name.clone(), value);
         return Ok(Object::None);
     }
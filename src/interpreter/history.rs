@@ -0,0 +1,118 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::rc::Rc;
+
+use super::object::{Array, Object};
+
+// How many values are kept per watched binding before the oldest are dropped.
+pub const WATCH_HISTORY_CAPACITY: usize = 64;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct WatchRecord {
+    pub value: Object,
+    // The variable whose assignment caused the recomputation and the line of that
+    // assignment, `None` for the initial value.
+    pub trigger: Option<(String, usize)>,
+}
+
+// Ring buffers of every value a watched binding has taken, in order of declaration.
+#[derive(Debug, PartialEq, Clone)]
+pub struct WatchHistory {
+    pub names: Vec<String>,
+    pub records: HashMap<String, VecDeque<WatchRecord>>,
+    pub capacity: usize,
+}
+
+impl WatchHistory {
+    pub fn new(capacity: usize) -> WatchHistory {
+        WatchHistory {
+            names: Vec::new(),
+            records: HashMap::new(),
+            capacity,
+        }
+    }
+
+    pub fn record(&mut self, name: &str, value: Object, trigger: Option<(String, usize)>) {
+        if !self.records.contains_key(name) {
+            self.names.push(name.to_string());
+        }
+        let records = self.records.entry(name.to_string()).or_default();
+        if records.len() == self.capacity {
+            records.pop_front();
+        }
+        records.push_back(WatchRecord { value, trigger });
+    }
+
+    pub fn get(&self, name: &str) -> Vec<WatchRecord> {
+        match self.records.get(name) {
+            Some(records) => records.iter().cloned().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // `history(name)` exposes every record as `[value: ..., trigger: ..., line: ...]`.
+    pub fn to_object(&self, name: &str) -> Object {
+        let records = self
+            .get(name)
+            .into_iter()
+            .map(|record| {
+                let (trigger, line) = match record.trigger {
+                    Some((trigger, line)) => {
+                        (Object::StringLiteral(trigger), Object::Number(line as i64))
+                    }
+                    None => (Object::Null, Object::Null),
+                };
+                Object::Array(Rc::new(Array::from_entries(vec![
+                    ("value".to_string(), record.value),
+                    ("trigger".to_string(), trigger),
+                    ("line".to_string(), line),
+                ])))
+            })
+            .collect();
+        Object::Array(Rc::new(Array::from_objects(records)))
+    }
+}
+
+impl fmt::Display for WatchHistory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for name in &self.names {
+            writeln!(f, "{}:", name)?;
+            for record in self.get(name) {
+                match record.trigger {
+                    Some((trigger, line)) => {
+                        writeln!(f, "  {} ({} on line {})", record.value, trigger, line)?
+                    }
+                    None => writeln!(f, "  {} (initial)", record.value)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_drops_oldest_when_full() {
+        let mut history = WatchHistory::new(2);
+        history.record("sum", Object::Number(1), None);
+        history.record("sum", Object::Number(2), Some(("x".to_string(), 3)));
+        history.record("sum", Object::Number(3), Some(("y".to_string(), 4)));
+        assert_eq!(
+            history.get("sum"),
+            vec![
+                WatchRecord {
+                    value: Object::Number(2),
+                    trigger: Some(("x".to_string(), 3)),
+                },
+                WatchRecord {
+                    value: Object::Number(3),
+                    trigger: Some(("y".to_string(), 4)),
+                },
+            ]
+        );
+        assert_eq!(history.get("missing"), vec![]);
+    }
+}
@@ -0,0 +1,145 @@
+use std::{
+    cell::RefCell,
+    fmt::Debug,
+    rc::{Rc, Weak},
+};
+
+use crate::builtin::methods::{find_method, Method};
+
+use super::object::{bind_struct_method, Function, Object, StructType};
+
+// What a call `receiver.name(...)` runs.
+pub enum MethodLookup {
+    // a method of the receiver's struct, with `self` bound
+    Struct(Function),
+    // a method of the receiver's type, like `push` of arrays
+    Builtin(&'static Method),
+    // neither, so `name` is looked up as a key of the receiver
+    Member,
+}
+
+// The inline cache of a call site `receiver.name(...)`. It keeps what the name found for the
+// last receiver together with the shape of that receiver, so a call in a loop that gets
+// receivers of the same shape does not search methods by name again. A receiver of another
+// shape, like an instance of another struct, looks the name up again and replaces the entry.
+// Copies of the call site, as in the body of a method bound to an instance, share the cache.
+#[derive(Clone, Default)]
+pub struct MethodCache {
+    entry: Rc<RefCell<Option<Entry>>>,
+}
+
+struct Entry {
+    shape: Shape,
+    found: Found,
+}
+
+// What decides the method a name finds: the struct of an instance, or the type of any other
+// value. A struct is held weakly, so its address is not reused while it is cached.
+enum Shape {
+    Struct(Weak<StructType>),
+    Array,
+    String,
+    Range,
+    Other,
+}
+
+#[derive(Clone, Copy)]
+enum Found {
+    // the position of the method in its struct
+    Struct(usize),
+    Builtin(&'static Method),
+    Member,
+}
+
+impl MethodCache {
+    pub fn lookup(&self, receiver: &Object, name: &str) -> MethodLookup {
+        let cached = match &*self.entry.borrow() {
+            Some(entry) if entry.shape.matches(receiver) => Some(entry.found),
+            _ => None,
+        };
+        let found = cached.unwrap_or_else(|| {
+            let found = find(receiver, name);
+            *self.entry.borrow_mut() = Some(Entry {
+                shape: Shape::of(receiver),
+                found,
+            });
+            found
+        });
+        match (found, struct_type(receiver)) {
+            (Found::Struct(index), Some(struct_type)) => MethodLookup::Struct(bind_struct_method(
+                receiver,
+                struct_type,
+                &struct_type.methods[index],
+            )),
+            (Found::Builtin(method), _) => MethodLookup::Builtin(method),
+            _ => MethodLookup::Member,
+        }
+    }
+}
+
+// A method of the struct comes before a method of the type, and both before a key.
+fn find(receiver: &Object, name: &str) -> Found {
+    let struct_method = struct_type(receiver).and_then(|struct_type| {
+        struct_type
+            .methods
+            .iter()
+            .position(|method| method.name == name)
+    });
+    if let Some(index) = struct_method {
+        return Found::Struct(index);
+    }
+    match find_method(receiver, name) {
+        Some(method) => Found::Builtin(method),
+        None => Found::Member,
+    }
+}
+
+fn struct_type(receiver: &Object) -> Option<&Rc<StructType>> {
+    match receiver {
+        Object::Array(array) => array.struct_type.as_ref(),
+        _ => None,
+    }
+}
+
+impl Shape {
+    fn of(receiver: &Object) -> Shape {
+        match receiver {
+            Object::Array(array) => match &array.struct_type {
+                Some(struct_type) => Shape::Struct(Rc::downgrade(struct_type)),
+                None => Shape::Array,
+            },
+            Object::StringLiteral(_) => Shape::String,
+            Object::Range(_) => Shape::Range,
+            _ => Shape::Other,
+        }
+    }
+
+    fn matches(&self, receiver: &Object) -> bool {
+        match (self, receiver) {
+            (Shape::Struct(cached), Object::Array(array)) => match &array.struct_type {
+                Some(struct_type) => Weak::as_ptr(cached) == Rc::as_ptr(struct_type),
+                None => false,
+            },
+            (Shape::Array, Object::Array(array)) => array.struct_type.is_none(),
+            (Shape::String, Object::StringLiteral(_)) | (Shape::Range, Object::Range(_)) => true,
+            (Shape::Other, receiver) => !matches!(
+                receiver,
+                Object::Array(_) | Object::StringLiteral(_) | Object::Range(_)
+            ),
+            _ => false,
+        }
+    }
+}
+
+// A cache follows from running the call site, so it never takes part in comparisons.
+impl PartialEq for MethodCache {
+    fn eq(&self, _other: &MethodCache) -> bool {
+        true
+    }
+}
+
+impl Debug for MethodCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MethodCache")
+    }
+}
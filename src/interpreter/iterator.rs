@@ -0,0 +1,111 @@
+use std::rc::Rc;
+
+use super::{
+    evaluator::{call_hook, Error, EvalOption},
+    object::{find_struct_method, Array, ArrayElement, Function, Lines, Object, Range},
+};
+
+// What `for (i, x in value)` walks: the elements of an array, with the keys of keyed ones as
+// their index, the values of a range, the characters of a string, or what the `__iter` method
+// of a struct instance returns. An instance with a `__next` method is walked by calling it
+// until it returns null, and the lines of `stdinLines()` are read one per step.
+pub enum ForIterator {
+    Array { array: Rc<Array>, position: usize },
+    Range { range: Range, position: usize },
+    String { chars: Vec<char>, position: usize },
+    Next { next: Function, position: usize },
+    Lines { lines: Rc<Lines>, position: usize },
+}
+
+impl ForIterator {
+    pub fn new(value: Object, option: &mut EvalOption) -> Result<ForIterator, Error> {
+        if let Some(iter) = find_struct_method(&value, "__iter") {
+            let iterable = call_hook(&iter, "__iter", Vec::new(), option)?;
+            // an instance returning itself would call `__iter` forever
+            if find_struct_method(&iterable, "__iter").is_some() {
+                return Err(Error {
+                    message: "__iter must not return a value with __iter".to_string(),
+                    child: None,
+                });
+            }
+            return ForIterator::new(iterable, option);
+        }
+        if let Some(next) = find_struct_method(&value, "__next") {
+            return Ok(ForIterator::Next { next, position: 0 });
+        }
+        match value {
+            Object::Array(array) => Ok(ForIterator::Array { array, position: 0 }),
+            Object::Range(range) => Ok(ForIterator::Range { range, position: 0 }),
+            Object::StringLiteral(value) => Ok(ForIterator::String {
+                chars: value.chars().collect(),
+                position: 0,
+            }),
+            Object::Lines(lines) => Ok(ForIterator::Lines { lines, position: 0 }),
+            value => Err(Error {
+                message: format!("cannot iterate over {}", value),
+                child: None,
+            }),
+        }
+    }
+
+    // The index and value of the next element, or none once every element was walked. An
+    // array is read as it is at each step, so elements pushed by the loop are walked too.
+    pub fn next_element(
+        &mut self,
+        option: &mut EvalOption,
+    ) -> Result<Option<(Object, Object)>, Error> {
+        let element = match self {
+            ForIterator::Array { array, position } => {
+                let element = array.elements.borrow().get(*position).cloned();
+                let element = match element {
+                    Some(ArrayElement::Object(value)) => (Object::Number(*position as i64), value),
+                    Some(ArrayElement::Key(key)) => match array.map.borrow().get(&key) {
+                        Some(value) => (Object::StringLiteral(key), value.clone()),
+                        None => {
+                            return Err(Error {
+                                message: "key not found".to_string(),
+                                child: None,
+                            })
+                        }
+                    },
+                    None => return Ok(None),
+                };
+                *position += 1;
+                element
+            }
+            ForIterator::Range { range, position } => {
+                let value = match range.get(*position) {
+                    Some(value) => Object::Number(value),
+                    None => return Ok(None),
+                };
+                *position += 1;
+                (Object::Number(*position as i64 - 1), value)
+            }
+            ForIterator::String { chars, position } => {
+                let value = match chars.get(*position) {
+                    Some(char) => Object::StringLiteral(char.to_string()),
+                    None => return Ok(None),
+                };
+                *position += 1;
+                (Object::Number(*position as i64 - 1), value)
+            }
+            ForIterator::Next { next, position } => {
+                let value = match call_hook(next, "__next", Vec::new(), option)? {
+                    Object::Null => return Ok(None),
+                    value => value,
+                };
+                *position += 1;
+                (Object::Number(*position as i64 - 1), value)
+            }
+            ForIterator::Lines { lines, position } => {
+                let value = match lines.next_line()? {
+                    Some(line) => Object::StringLiteral(line),
+                    None => return Ok(None),
+                };
+                *position += 1;
+                (Object::Number(*position as i64 - 1), value)
+            }
+        };
+        Ok(Some(element))
+    }
+}
@@ -1,5 +1,18 @@
 pub mod assign;
+pub mod bindings;
+pub mod data_file;
 pub mod environment;
 pub mod evaluator;
+pub mod history;
+pub mod inline_cache;
+pub mod iterator;
+pub mod module;
 pub mod object;
+pub mod output;
+pub mod pattern;
+pub mod pool;
+pub mod stats;
+pub mod suggest;
+pub mod test_runner;
 pub mod tests;
+pub mod trace;
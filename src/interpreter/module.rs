@@ -0,0 +1,174 @@
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use super::{
+    environment::Environment,
+    evaluator::{Error, EvalOption, Evaluator},
+    object::{Array, Object},
+};
+use crate::ast::Statement;
+use crate::lexer::Peekable;
+use crate::parser::parse;
+use crate::read_file::read_file;
+
+pub fn is_module_path(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("ank"))
+}
+
+// A path without an extension, like `json` or `text/csv`, names a module to look for.
+pub fn is_bare_module(path: &str) -> bool {
+    Path::new(path).extension().is_none()
+}
+
+// The `.ank` file a bare module name refers to: the first one found in the importing script's
+// directory, then in `lib/` under that directory, then in each directory of `ANKARA_PATH`.
+pub fn resolve_module(name: &str, option: &EvalOption) -> Result<String, Error> {
+    let file_name = format!("{}.ank", name);
+    let script_dir = script_dir(option);
+    let mut dirs = vec![script_dir.clone(), script_dir.join("lib")];
+    if let Some(ankara_path) = std::env::var_os("ANKARA_PATH") {
+        dirs.extend(std::env::split_paths(&ankara_path));
+    }
+    let candidates: Vec<PathBuf> = dirs.iter().map(|dir| dir.join(&file_name)).collect();
+    match candidates.iter().find(|candidate| candidate.is_file()) {
+        Some(found) => Ok(found.display().to_string()),
+        None => Err(Error {
+            message: format!(
+                "module not found: {}, searched: {}",
+                name,
+                candidates
+                    .iter()
+                    .map(|candidate| candidate.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            child: None,
+        }),
+    }
+}
+
+// The path of a `.ank` file named by an import, where a relative path is resolved against the
+// importing script's directory.
+pub fn resolve_module_path(path: &str, option: &EvalOption) -> String {
+    let path = Path::new(path);
+    let dir = script_dir(option);
+    if path.is_absolute() || dir == Path::new(".") {
+        return path.display().to_string();
+    }
+    dir.join(path.strip_prefix(".").unwrap_or(path))
+        .display()
+        .to_string()
+}
+
+// The directory of the script being run, or the working directory when there is no file, as
+// in the REPL.
+fn script_dir(option: &EvalOption) -> PathBuf {
+    match option
+        .file
+        .as_ref()
+        .and_then(|file| Path::new(file).parent())
+    {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+// Runs the `.ank` file at `path` in a scope of its own and returns a map of its exports.
+// A module imported again, from any script, is not run again. Importing a module that is still being
+// run is an error naming the cycle.
+pub fn load_module(
+    path: &str,
+    env: &Rc<RefCell<Environment>>,
+    option: &mut EvalOption,
+) -> Result<Object, Error> {
+    let cannot_import = |message: String| Error {
+        message: format!("cannot import {}", path),
+        child: Some(Box::new(Error {
+            message,
+            child: None,
+        })),
+    };
+    let source = read_file(path).map_err(|error| cannot_import(error.to_string()))?;
+    let key = std::fs::canonicalize(path).map_err(|error| cannot_import(error.to_string()))?;
+    if let Some(exports) = option.modules.borrow().get(&key) {
+        return Ok(exports.clone());
+    }
+    let program =
+        parse(&mut Peekable::new(&source)).map_err(|error| cannot_import(error.message))?;
+
+    // the script run from the command line is the first module of every import chain
+    let root = match &option.file {
+        Some(file) if option.importing.is_empty() => std::fs::canonicalize(file)
+            .ok()
+            .map(|root| (root, file.clone())),
+        _ => None,
+    };
+    let depth = option.importing.len();
+    option.importing.extend(root);
+    if let Some(start) = option
+        .importing
+        .iter()
+        .position(|(module, _)| *module == key)
+    {
+        let mut cycle: Vec<&str> = option.importing[start..]
+            .iter()
+            .map(|(_, path)| path.as_str())
+            .collect();
+        cycle.push(path);
+        let message = format!("circular import: {}", cycle.join(" -> "));
+        option.importing.truncate(depth);
+        return Err(Error {
+            message,
+            child: None,
+        });
+    }
+
+    let module_env = Rc::new(RefCell::new(module_environment(env)));
+    let file = option.file.replace(path.to_string());
+    option.importing.push((key.clone(), path.to_string()));
+    let result = program.eval(module_env.clone(), option);
+    option.importing.truncate(depth);
+    option.file = file;
+    if let Err(error) = result {
+        return Err(Error {
+            message: format!("cannot import {}", path),
+            child: Some(Box::new(error)),
+        });
+    }
+
+    let mut exports = Vec::new();
+    for statement in &program.statements {
+        if let Statement::ExportStatement(export) = statement {
+            for name in export.names() {
+                let value = module_env.borrow().get(name).unwrap_or(Object::Null);
+                exports.push((name.to_string(), value));
+            }
+        }
+    }
+    // every importer shares the exports, so none of them may change what the others see
+    let exports = Object::Array(Rc::new(Array {
+        frozen: Some(format!("module {}", path)),
+        ..Array::from_entries(exports)
+    }));
+    option.modules.borrow_mut().insert(key, exports.clone());
+    Ok(exports)
+}
+
+// A module sees the builtins but none of the importing script's own bindings, so it starts
+// from the frozen builtin scope the script was forked from.
+fn module_environment(env: &Rc<RefCell<Environment>>) -> Environment {
+    let mut scope = env.clone();
+    loop {
+        if scope.borrow().frozen {
+            return Environment::fork_isolated(&scope);
+        }
+        let parent = scope.borrow().parent.clone();
+        match parent {
+            Some(parent) => scope = parent,
+            None => return Environment::new(None),
+        }
+    }
+}
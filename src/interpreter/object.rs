@@ -1,22 +1,35 @@
 use crate::ast::{BlockReturnStatement, Expression};
-use crate::{ast, interpreter::environment::Environment};
+use crate::{
+    ast,
+    interpreter::environment::Environment,
+    interpreter::evaluator::{Error, EvalOption},
+    interpreter::stats::{LiveKind, LiveToken},
+};
 use std::ops::Deref;
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell, RefMut},
     collections::HashMap,
     fmt::{Debug, Display},
+    io::{self, BufRead},
     rc::Rc,
 };
 #[derive(PartialEq, Clone)]
 pub enum Object {
-    Number(i32),
+    Number(i64),
+    Float(f64),
     Boolean(bool),
     Function(Function),
     BuiltInFunction(BuiltInFunction),
     StringLiteral(String),
     Array(Rc<Array>),
+    Range(Range),
+    Lines(Rc<Lines>),
+    Struct(Rc<StructType>),
     Return(Box<Return>),
     BlockReturn(Box<BlockReturn>),
+    Break(Box<Break>),
+    // the label of the loop to continue, if not the innermost one
+    Continue(Option<String>),
     None,
     Null,
     Void,
@@ -29,12 +42,32 @@ impl Object {
             _ => false,
         }
     }
-    pub fn unwrap_number(&self) -> i32 {
+    pub fn unwrap_number(&self) -> i64 {
         match self {
             Object::Number(value) => *value,
             _ => panic!("unwrap_number called on non-number"),
         }
     }
+    // The kind of value, for error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Number(_) => "number",
+            Object::Float(_) => "float",
+            Object::Boolean(_) => "boolean",
+            Object::Function(_) | Object::BuiltInFunction(_) => "function",
+            Object::StringLiteral(_) => "string",
+            Object::Array(array) if array.struct_type.is_some() => "instance",
+            Object::Array(_) => "array",
+            Object::Range(_) => "range",
+            Object::Lines(_) => "lines",
+            Object::Struct(_) => "struct",
+            Object::Null => "null",
+            Object::Void | Object::None => "nothing",
+            Object::Return(_) | Object::BlockReturn(_) | Object::Break(_) | Object::Continue(_) => {
+                "control flow"
+            }
+        }
+    }
     pub fn is_falsey(&self) -> bool {
         match self {
             Object::Boolean(value) => !value,
@@ -42,6 +75,7 @@ impl Object {
             Object::Void => true,
             Object::None => true,
             Object::Number(value) => *value == 0,
+            Object::Float(value) => *value == 0.0,
             _ => false,
         }
     }
@@ -55,6 +89,8 @@ impl Object {
         match self {
             Object::Return(_) => true,
             Object::BlockReturn(_) => true,
+            Object::Break(_) => true,
+            Object::Continue(_) => true,
             _ => false,
         }
     }
@@ -73,8 +109,12 @@ impl Object {
     pub fn is_equal_to(&self, other: &Object) -> bool {
         match (self, other) {
             (Object::Number(left), Object::Number(right)) => left == right,
+            (Object::Float(left), Object::Float(right)) => left == right,
+            (Object::Number(left), Object::Float(right)) => *left as f64 == *right,
+            (Object::Float(left), Object::Number(right)) => *left == *right as f64,
             (Object::Boolean(left), Object::Boolean(right)) => left == right,
             (Object::StringLiteral(left), Object::StringLiteral(right)) => left == right,
+            (Object::Range(left), Object::Range(right)) => left == right,
             (Object::Null, Object::Null) => true,
             (Object::Void, Object::Void) => true,
             (Object::None, Object::None) => true,
@@ -87,6 +127,7 @@ impl Display for Object {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Object::Number(value) => write!(f, "{}", value),
+            Object::Float(value) => write!(f, "{}", get_float_format().format(*value)),
             Object::Boolean(value) => write!(f, "{}", value),
             Object::Function(_) => write!(f, "function"),
             Object::BuiltInFunction(_) => write!(f, "builtin function"),
@@ -105,13 +146,24 @@ impl Display for Object {
                         }
                     }
                 }
+                if let Some(struct_type) = &array.struct_type {
+                    write!(f, "{}", struct_type.name)?;
+                }
+                if array.is_map && elements.is_empty() {
+                    return write!(f, "[:]");
+                }
                 write!(f, "[{}]", elements)
             }
+            Object::Range(range) => write!(f, "{}", range),
+            Object::Lines(_) => write!(f, "lines"),
+            Object::Struct(struct_type) => write!(f, "struct {}", struct_type.name),
             Object::Null => write!(f, "null"),
             Object::Void => write!(f, "void"),
             Object::None => write!(f, "none"),
             Object::Return(_) => write!(f, "return"),
             Object::BlockReturn(_) => write!(f, "block return"),
+            Object::Break(_) => write!(f, "break"),
+            Object::Continue(_) => write!(f, "continue"),
         }
     }
 }
@@ -120,6 +172,7 @@ impl Debug for Object {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Object::Number(value) => write!(f, "{}", value),
+            Object::Float(value) => write!(f, "{}", format_float(*value)),
             Object::Boolean(value) => write!(f, "{}", value),
             Object::Function(_) => write!(f, "function"),
             Object::BuiltInFunction(_) => write!(f, "builtin function"),
@@ -138,34 +191,472 @@ impl Debug for Object {
                         }
                     }
                 }
+                if let Some(struct_type) = &array.struct_type {
+                    write!(f, "{}", struct_type.name)?;
+                }
+                if array.is_map && elements.is_empty() {
+                    return write!(f, "[:]");
+                }
                 write!(f, "[{}]", elements)
             }
+            Object::Range(range) => write!(f, "{}", range),
+            Object::Lines(_) => write!(f, "lines"),
+            Object::Struct(struct_type) => write!(f, "struct {}", struct_type.name),
             Object::Null => write!(f, "null"),
             Object::Void => write!(f, "void"),
             Object::None => write!(f, "none"),
             Object::Return(_) => write!(f, "return"),
             Object::BlockReturn(_) => write!(f, "block return"),
+            Object::Break(_) => write!(f, "break"),
+            Object::Continue(_) => write!(f, "continue"),
+        }
+    }
+}
+
+// Keeps a decimal point on whole floats so `2.0` does not print like the integer `2`.
+pub fn format_float(value: f64) -> String {
+    let formatted = value.to_string();
+    if value.is_finite() && !formatted.contains('.') {
+        formatted + ".0"
+    } else {
+        formatted
+    }
+}
+
+// The most digits after the decimal point `--precision` and `str(value, digits)` accept.
+pub const MAX_PRECISION: usize = 20;
+
+// How floats are shown by `print` and string conversion. Source code, JSON and
+// debug dumps always get `format_float`, so they never lose digits.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FloatFormat {
+    // Digits after the decimal point, or as many as it takes to read the float back.
+    pub precision: Option<usize>,
+    // Floats of 10^n and up, or below 10^-n, are written like `1.5e21`.
+    pub exponent_threshold: Option<i32>,
+}
+
+impl FloatFormat {
+    pub fn format(&self, value: f64) -> String {
+        if !value.is_finite() {
+            return format_float(value);
+        }
+        let scientific = match self.exponent_threshold {
+            Some(threshold) => {
+                let magnitude = value.abs();
+                value != 0.0
+                    && (magnitude >= 10f64.powi(threshold) || magnitude < 10f64.powi(-threshold))
+            }
+            None => false,
+        };
+        match (scientific, self.precision) {
+            (true, Some(precision)) => format!("{:.*e}", precision, value),
+            (true, None) => format!("{:e}", value),
+            (false, Some(precision)) => format!("{:.*}", precision, value),
+            (false, None) => format_float(value),
         }
     }
 }
 
+thread_local! {
+    static FLOAT_FORMAT: Cell<FloatFormat> = Cell::new(FloatFormat::default());
+}
+
+// Set once by `--precision` and `--exponent-threshold` for the whole run.
+pub fn set_float_format(format: FloatFormat) {
+    FLOAT_FORMAT.with(|current| current.set(format));
+}
+
+pub fn get_float_format() -> FloatFormat {
+    FLOAT_FORMAT.with(|current| current.get())
+}
+
+// Shows floats with `format` while `f` runs, like `str(value, digits)` does.
+pub fn with_float_format<T>(format: FloatFormat, f: impl FnOnce() -> T) -> T {
+    let previous = get_float_format();
+    set_float_format(format);
+    let result = f();
+    set_float_format(previous);
+    result
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Function {
     pub parameters: Vec<ast::Identifier>,
+    pub rest: Option<ast::Identifier>,
     pub body: ast::BlockExpression,
     pub env: Rc<RefCell<Environment>>,
+    // shared by every copy of the same closure
+    pub live: Rc<LiveToken>,
+}
+
+// The lines of a reader without their terminators, read one at a time as `for` walks them,
+// like what `stdinLines()` returns. A line is read only once, so a second loop goes on where
+// the first one stopped.
+pub struct Lines {
+    read_line: RefCell<ReadLine>,
 }
 
+// Appends the next line to the string, like `BufRead::read_line`.
+type ReadLine = Box<dyn FnMut(&mut String) -> io::Result<usize>>;
+
+impl Lines {
+    pub fn new(mut reader: impl BufRead + 'static) -> Lines {
+        Lines {
+            read_line: RefCell::new(Box::new(move |line| reader.read_line(line))),
+        }
+    }
+
+    // Standard input is locked only while a line is read, so other readers of it, like the
+    // REPL or a second `stdinLines()`, are not blocked while the lines are alive.
+    pub fn stdin() -> Lines {
+        Lines {
+            read_line: RefCell::new(Box::new(|line| io::stdin().read_line(line))),
+        }
+    }
+
+    // The next line, or none at the end of the input.
+    pub fn next_line(&self) -> Result<Option<String>, Error> {
+        let mut line = String::new();
+        match (self.read_line.borrow_mut())(&mut line) {
+            Ok(0) => return Ok(None),
+            Ok(_) => {}
+            Err(error) => {
+                return Err(Error {
+                    message: format!("failed to read stdin: {}", error),
+                    child: None,
+                })
+            }
+        }
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Ok(Some(line))
+    }
+}
+
+// Every call to `stdinLines()` reads the same input, but is a value of its own.
+impl PartialEq for Lines {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Debug for Lines {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "lines")
+    }
+}
+
+// `start..end`, or `range(start, end, step)` for other steps. The end is excluded, and
+// nothing is allocated until the range is turned into an array.
 #[derive(Debug, PartialEq, Clone)]
+pub struct Range {
+    pub start: i64,
+    pub end: i64,
+    pub step: i64,
+}
+
+impl Range {
+    pub fn len(&self) -> usize {
+        let distance = self.end as i128 - self.start as i128;
+        let step = self.step as i128;
+        if distance == 0 || (distance > 0) != (step > 0) {
+            return 0;
+        }
+        ((distance + step - step.signum()) / step) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // `start`, `end` or `step`, read as `r.start` or `r["start"]`.
+    pub fn field(&self, name: &str) -> Option<i64> {
+        match name {
+            "start" => Some(self.start),
+            "end" => Some(self.end),
+            "step" => Some(self.step),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<i64> {
+        if index >= self.len() {
+            return None;
+        }
+        Some((self.start as i128 + index as i128 * self.step as i128) as i64)
+    }
+
+    pub fn contains(&self, value: i64) -> bool {
+        let offset = value as i128 - self.start as i128;
+        let in_bounds = if self.step > 0 {
+            value >= self.start && value < self.end
+        } else {
+            value <= self.start && value > self.end
+        };
+        in_bounds && offset % self.step as i128 == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = i64> + '_ {
+        (0..self.len()).map(|index| self.get(index).unwrap())
+    }
+}
+
+impl Display for Range {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.step == 1 {
+            write!(f, "{}..{}", self.start, self.end)
+        } else {
+            write!(f, "{}..{} step {}", self.start, self.end, self.step)
+        }
+    }
+}
+
+// What a `struct` declaration defines. Calling it makes an instance: a map of the fields, in
+// the order they were declared, that knows its type so its methods can be found.
+#[derive(Debug, PartialEq, Clone)]
+pub struct StructType {
+    pub name: String,
+    pub fields: Vec<String>,
+    pub methods: Vec<ast::StructMethod>,
+    pub env: Rc<RefCell<Environment>>,
+}
+
+impl StructType {
+    // Fields without an argument are null.
+    pub fn instantiate(self: &Rc<Self>, arguments: Vec<Object>) -> Result<Object, Error> {
+        if arguments.len() > self.fields.len() {
+            return Err(Error {
+                message: format!(
+                    "{} has {} fields, got {} arguments",
+                    self.name,
+                    self.fields.len(),
+                    arguments.len()
+                ),
+                child: None,
+            });
+        }
+        let mut arguments = arguments.into_iter();
+        let entries = self
+            .fields
+            .iter()
+            .map(|field| (field.clone(), arguments.next().unwrap_or(Object::Null)))
+            .collect();
+        Ok(Object::Array(Rc::new(Array {
+            struct_type: Some(self.clone()),
+            ..Array::from_entries(entries)
+        })))
+    }
+}
+
+// The method `name` of the struct `receiver` is an instance of, as a function with `self`
+// bound to `receiver`.
+pub fn find_struct_method(receiver: &Object, name: &str) -> Option<Function> {
+    let struct_type = match receiver {
+        Object::Array(array) => array.struct_type.as_ref()?,
+        _ => return None,
+    };
+    let method = struct_type
+        .methods
+        .iter()
+        .find(|method| method.name == name)?;
+    Some(bind_struct_method(receiver, struct_type, method))
+}
+
+// `method` of `struct_type` as a function with `self` bound to `receiver`.
+pub fn bind_struct_method(
+    receiver: &Object,
+    struct_type: &StructType,
+    method: &ast::StructMethod,
+) -> Function {
+    let mut env = Environment::new(Some(struct_type.env.clone()));
+    env.define("self".to_string(), receiver.clone());
+    Function {
+        parameters: method.function.parameters.clone(),
+        rest: method.function.rest.clone(),
+        body: method.function.body.clone(),
+        env: Rc::new(RefCell::new(env)),
+        live: Rc::new(LiveToken::new(LiveKind::Function)),
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct BuiltInFunction {
     pub name: String,
-    pub function: fn(Vec<Object>) -> Object,
+    pub function: fn(Vec<Object>, &mut EvalOption) -> Result<Object, Error>,
+}
+
+// Builtins are told apart by name, since the address of a function is not guaranteed to be
+// the same everywhere it is taken.
+impl PartialEq for BuiltInFunction {
+    fn eq(&self, other: &BuiltInFunction) -> bool {
+        self.name == other.name
+    }
 }
 
+// `elements` holds the order of everything in the array, keyed entries included, and `map`
+// only their values. Keys stay where they were first inserted, so printing, `for` and JSON
+// output follow insertion order; add keys with `set` to keep the two in step.
 #[derive(Debug, PartialEq, Clone)]
 pub struct Array {
-    pub elements: RefCell<Vec<ArrayElement>>,
+    // shared with the literal pool and the other arrays made from the same literal until the
+    // array is changed, see `elements_mut`
+    pub elements: RefCell<Rc<Vec<ArrayElement>>>,
     pub map: RefCell<HashMap<String, Object>>,
+    // made by a map literal or from entries, so it is still a map while it has no keys
+    pub is_map: bool,
+    // the struct this is an instance of
+    pub struct_type: Option<Rc<StructType>>,
+    // What the array is when it is shared and cannot be changed, like `module lib/math.ank`
+    // for the exports of a module, which every importer sees.
+    pub frozen: Option<String>,
+    pub live: LiveToken,
+}
+
+impl Array {
+    pub fn new(elements: Vec<ArrayElement>, map: HashMap<String, Object>) -> Array {
+        Array {
+            elements: RefCell::new(Rc::new(elements)),
+            map: RefCell::new(map),
+            is_map: false,
+            struct_type: None,
+            frozen: None,
+            live: LiveToken::new(LiveKind::Array),
+        }
+    }
+
+    pub fn new_map(elements: Vec<ArrayElement>, map: HashMap<String, Object>) -> Array {
+        Array {
+            is_map: true,
+            ..Array::new(elements, map)
+        }
+    }
+
+    pub fn from_objects(objects: Vec<Object>) -> Array {
+        Array::new(
+            objects.into_iter().map(ArrayElement::Object).collect(),
+            HashMap::new(),
+        )
+    }
+
+    // An array of the pooled elements of a constant literal.
+    pub fn from_pool(elements: Rc<Vec<ArrayElement>>) -> Array {
+        Array {
+            elements: RefCell::new(elements),
+            ..Array::new(Vec::new(), HashMap::new())
+        }
+    }
+
+    // The elements to change, copied first while other arrays still share them.
+    pub fn elements_mut(&self) -> RefMut<'_, Vec<ArrayElement>> {
+        RefMut::map(self.elements.borrow_mut(), Rc::make_mut)
+    }
+
+    // A repeated key keeps its first position and takes the last value.
+    pub fn from_entries(entries: Vec<(String, Object)>) -> Array {
+        let array = Array::new_map(Vec::new(), HashMap::new());
+        for (key, value) in entries {
+            array.set(key, value);
+        }
+        array
+    }
+
+    // Replaces the value of a key, or appends the key when it is new.
+    pub fn set(&self, key: String, value: Object) {
+        let mut map = self.map.borrow_mut();
+        if !map.contains_key(&key) {
+            self.elements_mut().push(ArrayElement::Key(key.clone()));
+        }
+        map.insert(key, value);
+    }
+
+    pub fn resolve_index(&self, index: i64) -> Option<usize> {
+        resolve_index(index, self.elements.borrow().len())
+    }
+
+    // Fails when the elements of the array cannot be assigned, added or removed.
+    pub fn check_writable(&self) -> Result<(), Error> {
+        match &self.frozen {
+            Some(what) => Err(Error {
+                message: format!("cannot modify {}", what),
+                child: None,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    // A copy that cannot be changed, with the arrays it holds copied the same way, for a value
+    // bound by `const`. An array that is already frozen is shared as it is.
+    pub fn freeze(array: &Rc<Array>, what: &str) -> Rc<Array> {
+        freeze_array(array, what, &mut HashMap::new())
+    }
+}
+
+// `copies` maps the arrays already copied to their copies, so an array that holds itself is
+// copied once.
+fn freeze_array(
+    array: &Rc<Array>,
+    what: &str,
+    copies: &mut HashMap<*const Array, Rc<Array>>,
+) -> Rc<Array> {
+    if array.frozen.is_some() {
+        return array.clone();
+    }
+    if let Some(copy) = copies.get(&Rc::as_ptr(array)) {
+        return copy.clone();
+    }
+    let copy = Rc::new(Array {
+        is_map: array.is_map,
+        struct_type: array.struct_type.clone(),
+        frozen: Some(what.to_string()),
+        ..Array::new(Vec::new(), HashMap::new())
+    });
+    copies.insert(Rc::as_ptr(array), copy.clone());
+    let elements = array
+        .elements
+        .borrow()
+        .iter()
+        .map(|element| match element {
+            ArrayElement::Object(value) => ArrayElement::Object(freeze_object(value, what, copies)),
+            ArrayElement::Key(key) => ArrayElement::Key(key.clone()),
+        })
+        .collect();
+    let map = array
+        .map
+        .borrow()
+        .iter()
+        .map(|(key, value)| (key.clone(), freeze_object(value, what, copies)))
+        .collect();
+    *copy.elements.borrow_mut() = Rc::new(elements);
+    *copy.map.borrow_mut() = map;
+    copy
+}
+
+fn freeze_object(
+    value: &Object,
+    what: &str,
+    copies: &mut HashMap<*const Array, Rc<Array>>,
+) -> Object {
+    match value {
+        Object::Array(array) => Object::Array(freeze_array(array, what, copies)),
+        _ => value.clone(),
+    }
+}
+
+// The position an index refers to in a value of `len` elements, where a negative index
+// counts back from the end.
+pub fn resolve_index(index: i64, len: usize) -> Option<usize> {
+    let len = len as i64;
+    let index = if index < 0 { index + len } else { index };
+    if (0..len).contains(&index) {
+        Some(index as usize)
+    } else {
+        None
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -183,3 +674,9 @@ pub struct BlockReturn {
 pub struct Return {
     pub value: Object,
 }
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Break {
+    pub label: Option<String>,
+    pub value: Object,
+}
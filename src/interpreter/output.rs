@@ -0,0 +1,50 @@
+use super::evaluator::Error;
+
+// How much `ankara test` lets a script print before aborting it.
+pub const TEST_MAX_OUTPUT_BYTES: usize = 1024 * 1024;
+
+// Caps the bytes a script may print, so a runaway loop cannot flood the terminal.
+#[derive(Debug, PartialEq, Clone)]
+pub struct OutputLimit {
+    pub max_bytes: usize,
+    pub written: usize,
+}
+
+impl OutputLimit {
+    pub fn new(max_bytes: usize) -> OutputLimit {
+        OutputLimit {
+            max_bytes,
+            written: 0,
+        }
+    }
+
+    // Accounts for `bytes` about to be written, failing without counting them when they
+    // would go over the limit.
+    pub fn write(&mut self, bytes: usize) -> Result<(), Error> {
+        if self.written + bytes > self.max_bytes {
+            return Err(Error {
+                message: format!("output limit of {} bytes exceeded", self.max_bytes),
+                child: None,
+            });
+        }
+        self.written += bytes;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_stops_at_limit() {
+        let mut limit = OutputLimit::new(10);
+        assert!(limit.write(6).is_ok());
+        assert!(limit.write(4).is_ok());
+        assert_eq!(
+            limit.write(1).unwrap_err().message,
+            "output limit of 10 bytes exceeded"
+        );
+        assert_eq!(limit.written, 10);
+    }
+}
@@ -0,0 +1,144 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::ast::{ArrayPattern, Pattern};
+
+use super::{
+    environment::Environment,
+    evaluator::{Error, EvalOption, Evaluator},
+    object::{Array, ArrayElement, Object},
+};
+
+pub trait BindPattern {
+    // Collects the bindings a match would introduce without defining any of them, so a
+    // failed match leaves the environment untouched.
+    fn bind(
+        &self,
+        value: Object,
+        env: Rc<RefCell<Environment>>,
+        option: &mut EvalOption,
+        bindings: &mut Vec<(String, Object)>,
+    ) -> Result<(), Error>;
+}
+
+impl BindPattern for Pattern {
+    fn bind(
+        &self,
+        value: Object,
+        env: Rc<RefCell<Environment>>,
+        option: &mut EvalOption,
+        bindings: &mut Vec<(String, Object)>,
+    ) -> Result<(), Error> {
+        match self {
+            Pattern::Identifier(identifier) => {
+                bindings.push((identifier.value.clone(), value));
+                Ok(())
+            }
+            Pattern::Array(array_pattern) => array_pattern.bind(value, env, option, bindings),
+            Pattern::Wildcard => Ok(()),
+            Pattern::Keyed(_) => unreachable!("keyed patterns are bound by their array pattern"),
+            Pattern::Literal(literal) => {
+                let expected = literal.eval(env, option)?;
+                if expected.is_equal_to(&value) {
+                    Ok(())
+                } else {
+                    Err(Error {
+                        message: format!("expected {}, got {}", expected, value),
+                        child: None,
+                    })
+                }
+            }
+        }
+    }
+}
+
+impl BindPattern for ArrayPattern {
+    fn bind(
+        &self,
+        value: Object,
+        env: Rc<RefCell<Environment>>,
+        option: &mut EvalOption,
+        bindings: &mut Vec<(String, Object)>,
+    ) -> Result<(), Error> {
+        let keyed = matches!(self.elements.first(), Some(Pattern::Keyed(_)));
+        let array = match value {
+            Object::Array(array) => array,
+            value if keyed => {
+                return Err(Error {
+                    message: format!("expected an array with keys, got {}", value),
+                    child: None,
+                })
+            }
+            value => {
+                return Err(Error {
+                    message: format!(
+                        "expected an array of {} elements, got {}",
+                        self.elements.len(),
+                        value
+                    ),
+                    child: None,
+                })
+            }
+        };
+        // the parser keeps keyed elements apart from the others
+        if keyed {
+            for element in &self.elements {
+                let keyed = match element {
+                    Pattern::Keyed(keyed) => keyed,
+                    _ => unreachable!(),
+                };
+                let value = array.map.borrow().get(&keyed.key).cloned();
+                let value = match value {
+                    Some(value) => value,
+                    None => {
+                        return Err(Error {
+                            message: format!("key not found: {}", keyed.key),
+                            child: None,
+                        })
+                    }
+                };
+                keyed.value.bind(value, env.clone(), option, bindings)?;
+            }
+            return Ok(());
+        }
+        let values: Vec<Object> = {
+            let elements = array.elements.borrow();
+            let map = array.map.borrow();
+            elements
+                .iter()
+                .map(|element| match element {
+                    ArrayElement::Object(value) => value.clone(),
+                    ArrayElement::Key(key) => map.get(key).cloned().unwrap_or(Object::Null),
+                })
+                .collect()
+        };
+        if self.rest.is_none() && values.len() != self.elements.len() {
+            return Err(Error {
+                message: format!(
+                    "expected {} elements, got {}",
+                    self.elements.len(),
+                    values.len()
+                ),
+                child: None,
+            });
+        }
+        if values.len() < self.elements.len() {
+            return Err(Error {
+                message: format!(
+                    "expected at least {} elements, got {}",
+                    self.elements.len(),
+                    values.len()
+                ),
+                child: None,
+            });
+        }
+        let mut values = values.into_iter();
+        for (pattern, value) in self.elements.iter().zip(values.by_ref()) {
+            pattern.bind(value, env.clone(), option, bindings)?;
+        }
+        if let Some(rest) = &self.rest {
+            let rest_array = Array::from_objects(values.collect());
+            bindings.push((rest.value.clone(), Object::Array(Rc::new(rest_array))));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,58 @@
+use std::{fmt::Debug, rc::Rc};
+
+use crate::ast::{ArrayMapValue, Expression};
+
+use super::object::{ArrayElement, Object};
+
+// The elements of an array literal made only of constants, like `[1, 2, 3]`, built once when
+// the literal is parsed rather than every time it runs. The arrays made from it share the
+// elements until one of them is changed, which copies them first, so a literal in the body of
+// a loop does not rebuild its elements on every iteration.
+#[derive(Clone, Default)]
+pub struct LiteralPool {
+    pub elements: Option<Rc<Vec<ArrayElement>>>,
+}
+
+impl LiteralPool {
+    pub fn new(literal_elements: &[ArrayMapValue]) -> LiteralPool {
+        let elements = literal_elements
+            .iter()
+            .map(|element| match element {
+                ArrayMapValue::Value(value) => constant(value).map(ArrayElement::Object),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>();
+        LiteralPool {
+            elements: elements.map(Rc::new),
+        }
+    }
+}
+
+// Nested arrays are not constants: changing one through an array made from the outer literal
+// would change it in every other one.
+fn constant(expression: &Expression) -> Option<Object> {
+    match expression {
+        Expression::NumberLiteral(number) => Some(Object::Number(number.value)),
+        Expression::FloatLiteral(float) => Some(Object::Float(float.value)),
+        Expression::BooleanLiteral(boolean) => Some(Object::Boolean(boolean.value)),
+        Expression::StringLiteral(string) => Some(Object::StringLiteral(string.value.clone())),
+        Expression::NullLiteral => Some(Object::Null),
+        _ => None,
+    }
+}
+
+// A pool follows from the elements of its literal, so it never takes part in comparisons.
+impl PartialEq for LiteralPool {
+    fn eq(&self, _other: &LiteralPool) -> bool {
+        true
+    }
+}
+
+impl Debug for LiteralPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.elements {
+            Some(elements) => write!(f, "LiteralPool({})", elements.len()),
+            None => write!(f, "LiteralPool"),
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use super::object::{Array, Object};
+
+// Counts of interpreter values that are currently alive on this thread.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct MemStats {
+    pub environments: usize,
+    pub arrays: usize,
+    pub functions: usize,
+}
+
+impl MemStats {
+    // `memStats()` exposes the counts as `[environments: ..., arrays: ..., functions: ...]`.
+    pub fn to_object(&self) -> Object {
+        Object::Array(Rc::new(Array::from_entries(vec![
+            (
+                "environments".to_string(),
+                Object::Number(self.environments as i64),
+            ),
+            ("arrays".to_string(), Object::Number(self.arrays as i64)),
+            (
+                "functions".to_string(),
+                Object::Number(self.functions as i64),
+            ),
+        ])))
+    }
+}
+
+impl fmt::Display for MemStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "environments: {}\narrays: {}\nfunctions: {}\n",
+            self.environments, self.arrays, self.functions
+        )
+    }
+}
+
+thread_local! {
+    static MEM_STATS: RefCell<MemStats> = RefCell::new(MemStats::default());
+}
+
+pub fn get_mem_stats() -> MemStats {
+    MEM_STATS.with(|stats| stats.borrow().clone())
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum LiveKind {
+    Environment,
+    Array,
+    Function,
+}
+
+fn update(kind: LiveKind, delta: isize) {
+    MEM_STATS.with(|stats| {
+        let mut stats = stats.borrow_mut();
+        let count = match kind {
+            LiveKind::Environment => &mut stats.environments,
+            LiveKind::Array => &mut stats.arrays,
+            LiveKind::Function => &mut stats.functions,
+        };
+        *count = count.saturating_add_signed(delta);
+    });
+}
+
+// Embedded in a tracked value: counted when created or cloned, uncounted when dropped.
+#[derive(Debug)]
+pub struct LiveToken(LiveKind);
+
+impl LiveToken {
+    pub fn new(kind: LiveKind) -> LiveToken {
+        update(kind, 1);
+        LiveToken(kind)
+    }
+}
+
+impl Clone for LiveToken {
+    fn clone(&self) -> Self {
+        LiveToken::new(self.0)
+    }
+}
+
+impl Drop for LiveToken {
+    fn drop(&mut self) {
+        update(self.0, -1);
+    }
+}
+
+// Tokens carry no data, so they never make two values unequal.
+impl PartialEq for LiveToken {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_live_token_counts() {
+        let before = get_mem_stats();
+        let token = LiveToken::new(LiveKind::Array);
+        let cloned = token.clone();
+        assert_eq!(get_mem_stats().arrays, before.arrays + 2);
+        drop(token);
+        drop(cloned);
+        assert_eq!(get_mem_stats(), before);
+    }
+}
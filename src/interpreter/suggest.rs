@@ -0,0 +1,50 @@
+// Number of single-character insertions, deletions and substitutions turning `a` into `b`.
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + if a_char == *b_char { 0 } else { 1 };
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+// The candidate closest to `name`, if it is close enough to be a plausible typo: at most
+// one edit for short names and a third of the name for longer ones.
+pub fn suggest<'a>(name: &str, candidates: impl Iterator<Item = &'a String>) -> Option<String> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    candidates
+        .filter(|candidate| candidate.as_str() != name)
+        .map(|candidate| (levenshtein(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by(|(a_distance, a), (b_distance, b)| a_distance.cmp(b_distance).then(a.cmp(b)))
+        .map(|(_, candidate)| candidate.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(levenshtein("count", "count"), 0);
+    }
+
+    #[test]
+    fn test_suggest() {
+        let names = [
+            "count".to_string(),
+            "counter".to_string(),
+            "print".to_string(),
+        ];
+        assert_eq!(suggest("cout", names.iter()), Some("count".to_string()));
+        assert_eq!(suggest("prnt", names.iter()), Some("print".to_string()));
+        assert_eq!(suggest("total", names.iter()), None);
+    }
+}
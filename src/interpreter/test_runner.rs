@@ -0,0 +1,89 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::ast::{Program, Statement};
+
+use super::{
+    environment::Environment,
+    evaluator::{Error, EvalOption, Evaluator},
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TestResult {
+    pub name: String,
+    // `None` when the test passed
+    pub error: Option<Error>,
+}
+
+// Runs the program once for its top-level definitions, then every top-level `test` block
+// in its own scope on top of them, so one test cannot see another's variables.
+pub fn run_tests(
+    program: &Program,
+    env: Rc<RefCell<Environment>>,
+    option: &mut EvalOption,
+) -> Result<Vec<TestResult>, Error> {
+    program.eval(env.clone(), option)?;
+    let mut results = Vec::new();
+    for statement in &program.statements {
+        if let Statement::TestBlock(test_block) = statement {
+            let test_env = Environment::new(Some(env.clone()));
+            let error = test_block
+                .body
+                .eval(Rc::new(RefCell::new(test_env)), option)
+                .err();
+            results.push(TestResult {
+                name: test_block.name.clone(),
+                error,
+            });
+        }
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtin::get_builtin_environment::get_builtin_environment;
+    use crate::lexer::Peekable;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_run_tests() {
+        let mut lexer = Peekable::new(
+            "\
+            let add = fn(a, b) { a + b };
+            test \"add\" {
+                let x = add(1, 2);
+                assert x == 3, \"1 + 2\";
+            }
+            test \"broken add\" {
+                assert add(1, 2) == 4, \"1 + 2\";
+            }
+            test \"isolated\" {
+                x;
+            }
+            ",
+        );
+        let program = parse(&mut lexer).unwrap();
+        let builtins = Rc::new(RefCell::new(get_builtin_environment()));
+        let env = Rc::new(RefCell::new(Environment::fork_isolated(&builtins)));
+        let results = run_tests(&program, env, &mut EvalOption::new()).unwrap();
+        let results: Vec<(String, Option<String>)> = results
+            .into_iter()
+            .map(|result| (result.name, result.error.map(|error| error.message)))
+            .collect();
+        assert_eq!(
+            results,
+            vec![
+                ("add".to_string(), None),
+                (
+                    "broken add".to_string(),
+                    Some("assertion failed: add(1, 2) == 4 (1 + 2)".to_string())
+                ),
+                (
+                    "isolated".to_string(),
+                    Some("variable not found x".to_string())
+                ),
+            ]
+        );
+    }
+}
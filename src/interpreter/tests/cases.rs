@@ -1,95 +1,215 @@
-use std::fs;
-use std::io::Write;
-use std::path::Path;
-
 #[cfg(test)]
 mod tests {
     use std::{
         cell::RefCell,
-        rc::{self, Rc},
+        fs,
+        io::Write,
+        path::Path,
+        rc::Rc,
+        sync::atomic::{AtomicUsize, Ordering},
     };
 
     use crate::{
         builtin::get_builtin_environment::get_builtin_environment,
+        interpreter::environment::Environment,
         interpreter::evaluator::{EvalOption, Evaluator},
         lexer::Peekable,
         parser::parse,
-        read_file::read_file,
+        read_file::{has_ank_extension, read_file},
     };
 
-    use super::*;
+    /// The error a case file declares it should fail with.
+    ///
+    /// Declared in the leading comment block of a case file:
+    ///
+    /// ```text
+    /// // expect-error: parse: expected semicolon
+    /// // expect-error: runtime: variable not found y
+    /// ```
+    #[derive(Debug, PartialEq, Clone)]
+    enum ExpectedError {
+        Parse(String),
+        Runtime(String),
+    }
+
+    fn get_expected_error(code: &str) -> Option<ExpectedError> {
+        for line in code.lines() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let comment = match line.strip_prefix("//") {
+                Some(comment) => comment.trim(),
+                None => return None,
+            };
+            let header = match comment.strip_prefix("expect-error:") {
+                Some(header) => header.trim(),
+                None => continue,
+            };
+            if let Some(message) = header.strip_prefix("parse:") {
+                return Some(ExpectedError::Parse(message.trim().to_string()));
+            }
+            if let Some(message) = header.strip_prefix("runtime:") {
+                return Some(ExpectedError::Runtime(message.trim().to_string()));
+            }
+        }
+        None
+    }
 
     #[test]
-    fn test_write_or_check_file() -> std::io::Result<()> {
+    fn test_write_or_check_file() {
         let all_case_file_path = get_all_case_file_path();
+        let failures = run_cases(&all_case_file_path);
+        assert!(
+            failures.is_empty(),
+            "{} of {} cases failed\n{}",
+            failures.len(),
+            all_case_file_path.len(),
+            failures.join("\n")
+        );
+    }
 
-        for file_path in all_case_file_path {
-            let code = read_file(&file_path)?;
-            let mut env = get_builtin_environment();
-            let rc_env = Rc::new(RefCell::new(env));
-            let mut lexer = Peekable::new(&code);
-            let program = parse(&mut lexer);
-            match program
-                .unwrap()
-                .eval(rc_env.clone(), &mut EvalOption::new())
-            {
-                Ok(_) => {}
-                Err(error) => {
-                    println!("{:?}", error);
-                    return Ok(());
+    // Runs the cases on a few threads that each take the next case until none are left, and
+    // returns the failures sorted by file name. Environments hold `Rc`s, so every thread makes
+    // its own builtins.
+    fn run_cases(file_paths: &[String]) -> Vec<String> {
+        let threads = std::thread::available_parallelism()
+            .map_or(1, |threads| threads.get())
+            .min(file_paths.len().max(1));
+        let next = AtomicUsize::new(0);
+        let mut failures: Vec<String> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let builtins = Rc::new(RefCell::new(get_builtin_environment()));
+                        let mut failures = Vec::new();
+                        while let Some(file_path) =
+                            file_paths.get(next.fetch_add(1, Ordering::Relaxed))
+                        {
+                            if let Err(failure) = run_case(file_path, &builtins) {
+                                failures.push(failure);
+                            }
+                        }
+                        failures
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect()
+        });
+        failures.sort();
+        failures
+    }
+
+    // Every case runs in its own scope on the builtins, and only that scope is dumped.
+    fn run_case(file_path: &str, builtins: &Rc<RefCell<Environment>>) -> Result<(), String> {
+        // last of path
+        let file_name = file_path
+            .split("/")
+            .last()
+            .unwrap()
+            .split(".")
+            .next()
+            .unwrap();
+        let code = read_file(file_path).map_err(|error| format!("{}: {}", file_name, error))?;
+        let expected_error = get_expected_error(&code);
+        let rc_env = Rc::new(RefCell::new(Environment::fork_isolated(builtins)));
+        let mut lexer = Peekable::new(&code);
+
+        let program = match parse(&mut lexer) {
+            Ok(program) => program,
+            Err(error) => {
+                let actual = ExpectedError::Parse(error.message);
+                if expected_error.as_ref() != Some(&actual) {
+                    return Err(format!(
+                        "{}: expected {:?}, got {:?}",
+                        file_name, expected_error, actual
+                    ));
                 }
+                return Ok(());
             }
+        };
+        // imports in a case are relative to its file
+        let mut option = EvalOption::new();
+        option.file = Some(file_path.to_string());
+        match program.eval(rc_env.clone(), &mut option) {
+            Ok(_) => {
+                if expected_error.is_some() {
+                    return Err(format!(
+                        "{}: expected {:?}, but it succeeded",
+                        file_name, expected_error
+                    ));
+                }
+            }
+            Err(error) => {
+                let actual = ExpectedError::Runtime(error.message);
+                if expected_error.as_ref() != Some(&actual) {
+                    return Err(format!(
+                        "{}: expected {:?}, got {:?}",
+                        file_name, expected_error, actual
+                    ));
+                }
+                return Ok(());
+            }
+        }
 
-            // last of path
-            let file_name = file_path
-                .split("/")
-                .last()
-                .unwrap()
-                .split(".")
-                .next()
-                .unwrap();
-            let text = (*rc_env.clone()).borrow_mut().to_string();
-            let result = write_or_check_file(&file_name, &text)?;
-            print!("{} \n", file_name);
-            assert!(result);
-            print!("\n")
+        let text = (*rc_env).borrow_mut().to_string();
+        match write_or_check_file(file_name, &text) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(format!("{}: output does not match", file_name)),
+            Err(error) => Err(format!("{}: {}", file_name, error)),
         }
+    }
 
-        Ok(())
+    #[test]
+    fn test_get_expected_error() {
+        assert_eq!(
+            get_expected_error("// expect-error: parse: expected semicolon\nlet x = 1"),
+            Some(ExpectedError::Parse("expected semicolon".to_string()))
+        );
+        assert_eq!(
+            get_expected_error("// a comment\n// expect-error: runtime: variable not found y\n"),
+            Some(ExpectedError::Runtime("variable not found y".to_string()))
+        );
+        assert_eq!(get_expected_error("let x = 1;\n"), None);
+        assert_eq!(
+            get_expected_error("let x = 1;\n// expect-error: runtime: ignored\n"),
+            None
+        );
     }
-}
 
-fn get_all_case_file_path() -> Vec<String> {
-    let case_dir = fs::read_dir("./src/interpreter/tests/cases").unwrap();
-    let mut file_paths = Vec::new();
-    for entry in case_dir {
-        let entry = entry.unwrap();
-        let path = entry.path();
-        if path.is_file() {
-            file_paths.push(path.to_str().unwrap().to_string());
+    fn get_all_case_file_path() -> Vec<String> {
+        let case_dir = fs::read_dir("./src/interpreter/tests/cases").unwrap();
+        let mut file_paths = Vec::new();
+        for entry in case_dir {
+            let entry = entry.unwrap();
+            let path = entry.path();
+            if path.is_file() && has_ank_extension(&path) {
+                file_paths.push(path.to_str().unwrap().to_string());
+            }
         }
+        file_paths
     }
-    file_paths
-}
 
-fn write_or_check_file(file_name: &str, content: &str) -> std::io::Result<bool> {
-    let out_dir = "./src/interpreter/tests/outputs";
-    let file_path = Path::new(out_dir).join(file_name).with_extension("txt");
+    fn write_or_check_file(file_name: &str, content: &str) -> std::io::Result<bool> {
+        let out_dir = "./src/interpreter/tests/outputs";
+        let file_path = Path::new(out_dir).join(file_name).with_extension("txt");
 
-    if file_path.exists() {
-        let existing_content = fs::read_to_string(file_path)?;
-        dbg!(&existing_content);
-        dbg!(&content);
-        Ok(existing_content == content)
-    } else {
-        let mut file = fs::File::create(file_path)?;
-        match file.write_all(content.as_bytes()) {
-            Ok(_) => {}
-            Err(error) => {
-                println!("{:?}", error);
-                return Ok(false);
+        if file_path.exists() {
+            let existing_content = fs::read_to_string(file_path)?;
+            Ok(existing_content == content)
+        } else {
+            let mut file = fs::File::create(file_path)?;
+            match file.write_all(content.as_bytes()) {
+                Ok(_) => {}
+                Err(error) => {
+                    println!("{:?}", error);
+                    return Ok(false);
+                }
             }
+            Ok(true)
         }
-        Ok(true)
     }
 }
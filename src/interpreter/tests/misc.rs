@@ -4,6 +4,9 @@ mod tests {
 
     use crate::{
         ast::{self, Expression, Operator},
+        builtin::get_builtin_environment::{get_builtin_environment, get_builtin_environment_with},
+        interpreter::history::WATCH_HISTORY_CAPACITY,
+        interpreter::stats::get_mem_stats,
         interpreter::{
             environment::Environment,
             evaluator::Evaluator,
@@ -34,6 +37,26 @@ mod tests {
         return Object::Return(Box::new(Return { value: obj }));
     }
 
+    #[test]
+    fn test_variable_not_found_suggestion() {
+        let mut lexer = Peekable::new(
+            "\
+            let count = 1;
+            let f = fn(total) {
+                cout + totl
+            };
+            f(2);
+            ",
+        );
+        let program = parse(&mut lexer).unwrap();
+        let env = Rc::new(RefCell::new(get_builtin_environment()));
+        let error = program.eval(env, &mut EvalOption::new()).unwrap_err();
+        assert_eq!(
+            error.message,
+            "variable not found cout, did you mean `count`?"
+        );
+    }
+
     #[test]
     fn test_element_access_expression() {
         let val = get_result(
@@ -45,6 +68,117 @@ mod tests {
         assert_eq!(val.unwrap_return(), Object::Number(1));
     }
 
+    #[test]
+    fn test_integer_overflow() {
+        let val = get_result("return 2147483647 + 1;");
+        assert_eq!(val.unwrap_return(), Object::Number(2147483648));
+
+        for (source, message) in [
+            ("9223372036854775807 + 1;", "integer overflow in +"),
+            ("-9223372036854775807 - 2;", "integer overflow in -"),
+            ("4611686018427387904 * 2;", "integer overflow in *"),
+            ("1 / 0;", "division by zero"),
+            ("1 % 0;", "division by zero"),
+        ] {
+            let mut lexer = Peekable::new(source);
+            let program = parse(&mut lexer).unwrap();
+            let error = program
+                .eval(
+                    Rc::new(RefCell::new(Environment::new(None))),
+                    &mut EvalOption::new(),
+                )
+                .unwrap_err();
+            assert_eq!(error.message, message);
+        }
+    }
+
+    #[test]
+    fn test_float() {
+        let val = get_result(
+            "\
+            let radius = 1.5;
+            return [radius * 2, radius * radius * 4, 7 / 2, 7 / 2.0, -0.5 < 0, 2.0];
+            ",
+        );
+        assert_eq!(val.unwrap_return().to_string(), "[3.0,9.0,3,3.5,true,2.0,]");
+    }
+
+    #[test]
+    fn test_prefix_expression() {
+        let val = get_result(
+            "\
+            let x = -5;
+            let flag = false;
+            if (!flag) {
+                return [x, -x * 2, !true, !0];
+            };
+            ",
+        );
+        assert_eq!(val.unwrap_return().to_string(), "[-5,10,false,true,]");
+    }
+
+    #[test]
+    fn test_destructuring_declaration() {
+        let val = get_result(
+            "\
+            let [a, [b, c]] = [1, [2, key: 3]];
+            return a + b + c;
+            ",
+        );
+        assert_eq!(val.unwrap_return(), Object::Number(6));
+    }
+
+    #[test]
+    fn test_destructuring_declaration_else() {
+        let val = get_result(
+            "\
+            let first = fn(pair) {
+                let [a, b] = pair else {
+                    return -1;
+                };
+                a
+            };
+            return [first([1, 2]), first([1, 2, 3]), first(5)];
+            ",
+        );
+        assert_eq!(val.unwrap_return().to_string(), "[1,-1,-1,]");
+
+        let mut lexer = Peekable::new("let [a] = 1 else { 2 };");
+        let program = parse(&mut lexer).unwrap();
+        let error = program
+            .eval(
+                Rc::new(RefCell::new(Environment::new(None))),
+                &mut EvalOption::new(),
+            )
+            .unwrap_err();
+        assert_eq!(error.message, "the else block of a let pattern must return");
+        assert_eq!(
+            error.child.unwrap().message,
+            "expected an array of 1 elements, got 1"
+        );
+    }
+
+    #[test]
+    fn test_else_if() {
+        let val = get_result(
+            "\
+            let grade = fn(score) {
+                if (score >= 90) {
+                    \"A\"
+                } else if (score >= 70) {
+                    \"B\"
+                } else if (score >= 50) {
+                    \"C\"
+                } else {
+                    \"D\"
+                }
+            };
+            return [grade(95), grade(75), grade(55), grade(10)];
+            ",
+        );
+        assert_eq!(val.unwrap_return().to_string(), "[A,B,C,D,]");
+    }
+
     #[test]
     fn test_for_loop() {
         let val = get_result(
@@ -61,6 +195,68 @@ mod tests {
         assert_eq!(val.unwrap_return(), Object::Number(3));
     }
 
+    #[test]
+    fn test_switch_array_pattern() {
+        let val = get_result(
+            "\
+            let describe = fn(point) {
+                switch (point) {
+                    case [0, 0]: {\"origin\"}
+                    case [0, y]: {\"y axis \" + y}
+                    case [x, [1, z]]: {\"nested \" + z}
+                    case 5: {\"five\"}
+                    default: {\"other\"}
+                }
+            };
+            return [
+                describe([0, 0]),
+                describe([0, \"2\"]),
+                describe([3, [1, \"4\"]]),
+                describe([3, [2, \"4\"]]),
+                describe(5),
+                describe([1, 2, 3]),
+            ];
+            ",
+        );
+        assert_eq!(
+            val.unwrap_return().to_string(),
+            "[origin,y axis 2,nested 4,other,five,other,]"
+        );
+    }
+
+    #[test]
+    fn test_break_and_continue() {
+        let val = get_result(
+            "\
+            let sum = 0;
+            let found = for (value in [1, 2, 3, 4, 5, 6]) {
+                if (value % 2 == 0) {
+                    continue;
+                };
+                if (value > 4) {
+                    break value;
+                };
+                sum = sum + value;
+            };
+            return [sum, found];
+            ",
+        );
+        assert_eq!(val.unwrap_return().to_string(), "[4,5,]");
+    }
+
+    #[test]
+    fn test_break_outside_of_loop() {
+        let mut lexer = Peekable::new("let f = fn() { break; }; for (x in [1]) { f(); };");
+        let program = parse(&mut lexer).unwrap();
+        let error = program
+            .eval(
+                Rc::new(RefCell::new(Environment::new(None))),
+                &mut EvalOption::new(),
+            )
+            .unwrap_err();
+        assert_eq!(error.message, "break outside of a loop");
+    }
+
     #[test]
     fn test_switch_expression() {
         let val = get_result(
@@ -274,4 +470,1697 @@ mod tests {
         );
         assert_eq!(val.unwrap_return(), Object::StringLiteral("a".to_string()));
     }
+
+    #[test]
+    fn test_constant() {
+        let val = get_result(
+            "\
+            const PI = 314;
+            let area = fn(r) {
+                PI * r * r
+            };
+            return area(2);
+            ",
+        );
+        assert_eq!(val.unwrap_return(), Object::Number(1256));
+    }
+
+    #[test]
+    fn test_constant_cannot_be_reassigned() {
+        let mut lexer = Peekable::new(
+            "\
+            const PI = 314;
+            let fnc = fn() {
+                PI = 3;
+            };
+            fnc();
+            ",
+        );
+        let program = parse(&mut lexer).unwrap();
+        let env = Rc::new(RefCell::new(Environment::new(None)));
+        let error = program.eval(env, &mut EvalOption::new()).unwrap_err();
+        assert_eq!(error.message, "cannot assign to constant PI");
+    }
+
+    #[test]
+    fn test_constant_can_be_shadowed_in_inner_scope() {
+        let val = get_result(
+            "\
+            const x = 1;
+            let fnc = fn() {
+                let x = 2;
+                x = 3;
+                x
+            };
+            return fnc() + x;
+            ",
+        );
+        assert_eq!(val.unwrap_return(), Object::Number(4));
+    }
+
+    #[test]
+    fn test_watch_history() {
+        let mut lexer = Peekable::new(
+            "\
+            let x = 1;
+            watch double = {
+                x * 2
+            };
+            x = 2;
+            x = 3;
+            return history(\"double\");
+            ",
+        );
+        let program = parse(&mut lexer).unwrap();
+        let env = Rc::new(RefCell::new(get_builtin_environment()));
+        let mut option = EvalOption::with_watch_history(WATCH_HISTORY_CAPACITY);
+        let val = program.eval(env, &mut option).unwrap();
+        assert_eq!(
+            val.unwrap_return().to_string(),
+            "[[value:2,trigger:null,line:null,],[value:4,trigger:x,line:5,],[value:6,trigger:x,line:6,],]"
+        );
+    }
+
+    #[test]
+    fn test_mem_stats() {
+        let mut lexer = Peekable::new(
+            "\
+            let make = fn(x) {
+                fn() { x }
+            };
+            let a = make(1);
+            let b = a;
+            let list = [1, 2];
+            return memStats();
+            ",
+        );
+        let program = parse(&mut lexer).unwrap();
+        let env = Rc::new(RefCell::new(get_builtin_environment()));
+        let before = get_mem_stats();
+        let val = program.eval(env, &mut EvalOption::new()).unwrap();
+        // the call frame and body block captured by `a`; `list`;
+        // `make` and `a`, which `b` shares
+        assert_eq!(
+            val.unwrap_return().to_string(),
+            format!(
+                "[environments:{},arrays:{},functions:{},]",
+                before.environments + 2,
+                before.arrays + 1,
+                before.functions + 2
+            )
+        );
+    }
+
+    #[test]
+    fn test_builtin_namespaces() {
+        let mut lexer = Peekable::new("return [io[\"print\"], debug[\"memStats\"]];");
+        let program = parse(&mut lexer).unwrap();
+        let env = Rc::new(RefCell::new(get_builtin_environment_with(false)));
+        let val = program.eval(env.clone(), &mut EvalOption::new()).unwrap();
+        assert_eq!(
+            val.unwrap_return().to_string(),
+            "[builtin function,builtin function,]"
+        );
+        assert_eq!((*env).borrow().get("print"), None);
+    }
+
+    #[test]
+    fn test_try_expression() {
+        let result = get_result(
+            "\
+            let divide = fn(a, b) {
+                try { a / b } catch (e) { \"failed: \" + e.message }
+            };
+            let early = fn() {
+                try { return 1; } catch (e) { 2 }
+            };
+            let nested = try {
+                try { [1][5] } catch (e) { missing }
+            } catch (outer) { outer.message };
+            return [divide(6, 3), divide(1, 0), early(), nested];
+            ",
+        );
+        assert_eq!(
+            result.unwrap_return().to_string(),
+            "[2,failed: division by zero,1,variable not found missing,]"
+        );
+    }
+
+    #[test]
+    fn test_throw_and_finally() {
+        let result = get_result(
+            "\
+            let log = [];
+            let check = fn(n) {
+                if (n < 0) { throw [code: 400, reason: \"negative\"]; };
+                n
+            };
+            let caught = try { check(-1) } catch (e) { e.reason };
+            let cleaned = fn() {
+                try {
+                    return check(2);
+                } finally {
+                    log.push(\"cleanup\");
+                }
+            };
+            let rethrown = try {
+                try { throw \"inner\"; } finally { log.push(\"finally\"); }
+            } catch (e) { e };
+            let overridden = fn() {
+                try { throw 1; } catch (e) { return e; } finally { return 2; }
+            };
+            return [caught, cleaned(), rethrown, overridden(), log];
+            ",
+        );
+        assert_eq!(
+            result.unwrap_return().to_string(),
+            "[negative,2,inner,2,[finally,cleanup,],]"
+        );
+
+        let mut lexer = Peekable::new("try { 1 };");
+        assert_eq!(
+            parse(&mut lexer).unwrap_err().message,
+            "expected catch or finally"
+        );
+    }
+
+    #[test]
+    fn test_error_object() {
+        let result = get_result(
+            "\
+            let get = fn(xs, i) {
+                xs[i]
+            };
+            let outer = fn() { get([1], 5) };
+            let e = try { outer() } catch (e) { e };
+            let b = try {
+                [].pop()
+            } catch (e) { e };
+            let top = try { 1 / 0 } catch (e) { e };
+            return [e.message, e.kind, e.line, e.calls, b.message, b.kind, b.line, top.line, top.calls];
+            ",
+        );
+        assert_eq!(
+            result.unwrap_return().to_string(),
+            "[index out of bounds,runtime,2,[5,4,],cannot pop from an empty array,builtin,7,9,[],]"
+        );
+
+        // the error is placed where it happened in the file being run, even outside of calls
+        let mut lexer = Peekable::new(
+            "\
+            let located = try {
+                let x = 1;
+                x / 0
+            } catch (e) { [e.file, e.line] };
+            throw \"boom\";
+            ",
+        );
+        let program = parse(&mut lexer).unwrap();
+        let env = Rc::new(RefCell::new(Environment::new(None)));
+        let mut option = EvalOption::new();
+        option.file = Some("main.ank".to_string());
+        let error = program.eval(env.clone(), &mut option).unwrap_err();
+        assert_eq!(error.message, "uncaught boom");
+        assert_eq!(
+            env.borrow().get("located").unwrap().to_string(),
+            "[main.ank,3,]"
+        );
+        let trace = option.error_trace.unwrap();
+        assert_eq!(
+            (trace.line, trace.file, trace.calls),
+            (Some(5), Some("main.ank".to_string()), vec![])
+        );
+    }
+
+    #[test]
+    fn test_defer() {
+        let result = get_result(
+            "\
+            let log = [];
+            let work = fn(fail) {
+                defer log.push(\"close\");
+                defer log.push(\"flush\");
+                if (fail) { throw \"failed\"; };
+                log.push(\"work\");
+                return \"done\";
+                defer log.push(\"never\");
+            };
+            let ok = work(false);
+            let failed = try { work(true) } catch (e) { e };
+            for (i in 0..2) {
+                defer log.push(i);
+            };
+            return [ok, failed, log];
+            ",
+        );
+        assert_eq!(
+            result.unwrap_return().to_string(),
+            "[done,failed,[work,flush,close,flush,close,0,1,],]"
+        );
+    }
+
+    #[test]
+    fn test_assert_statement() {
+        let result = get_result(
+            "\
+            let x = 3;
+            assert x > 2;
+            let failed = try { assert x + 1 == 5, \"x is off\"; } catch (e) { e.message };
+            let plain = try { assert x < 0; } catch (e) { e.message };
+            let grouped = try { assert (x > 5), \"too small\"; } catch (e) { e.message };
+            assert (x + 1) * 2 == 8;
+            return [failed, plain, grouped];
+            ",
+        );
+        assert_eq!(
+            result.unwrap_return().to_string(),
+            "[assertion failed: x + 1 == 5 (x is off),assertion failed: x < 0,assertion failed: x > 5 (too small),]"
+        );
+    }
+
+    #[test]
+    fn test_in_operator() {
+        let result = get_result(
+            "\
+            let arr = [1, 2, \"three\"];
+            let map = [name: \"ankara\", 7: true];
+            let found = [];
+            if (3 in arr) { found.push(\"3\"); };
+            if (2 in arr) { found.push(\"2\"); };
+            if (\"name\" in map) { found.push(\"name\"); };
+            if (\"ankara\" in map) { found.push(\"ankara\"); };
+            if (7 in map) { found.push(\"7\"); };
+            if (4 in 0..10) { found.push(\"4\"); };
+            if (10 in 0..10) { found.push(\"10\"); };
+            let wrong = try { 1 in \"abc\" } catch (e) { e.message };
+            return [found, 1 + 1 in arr, wrong];
+            ",
+        );
+        assert_eq!(
+            result.unwrap_return().to_string(),
+            "[[2,name,7,4,],true,in needs an array, a map or a range, got abc,]"
+        );
+    }
+
+    #[test]
+    fn test_null_coalescing() {
+        let result = get_result(
+            "\
+            let calls = [];
+            let log = fn(x) { calls.push(x); return x; };
+            let nothing = fn() {};
+            let settings = [theme: null, size: 0];
+            return [
+                settings.theme ?? \"light\",
+                settings.size ?? log(12),
+                nothing() ?? null ?? log(1),
+                calls,
+            ];
+            ",
+        );
+        assert_eq!(result.unwrap_return().to_string(), "[light,0,1,[1,],]");
+    }
+
+    #[test]
+    fn test_optional_chaining() {
+        let result = get_result(
+            "\
+            let user = [name: \"ann\", address: [city: \"oslo\"], tags: [\"a\"]];
+            let guest = null;
+            let assign = try { user?.name = \"bob\"; } catch (e) { e.message };
+            let strict = try { user.phone.number } catch (e) { e.message };
+            return [
+                user?.address?.city,
+                user?.phone?.number,
+                user?[\"tags\"]?[3],
+                guest?.name,
+                guest?.keys(),
+                user.name?.upper,
+                strict,
+                assign,
+            ];
+            ",
+        );
+        assert_eq!(
+            result.unwrap_return().to_string(),
+            "[oslo,null,null,null,null,null,key not found,cannot assign through ?[ or ?.,]"
+        );
+    }
+
+    #[test]
+    fn test_unicode_names_and_strings() {
+        let result = get_result(
+            "\
+            let größe = 3;
+            let 名前 = \"日本語テキスト\";
+            let привет = fn(имя) { \"привет, \" + имя };
+            let m = [ключ: größe];
+            return [名前.len(), 名前[1], 名前[-2], 名前[1:3], привет(\"мир\"), m.ключ];
+            ",
+        );
+        assert_eq!(
+            result.unwrap_return().to_string(),
+            "[7,本,ス,本語,привет, мир,3,]"
+        );
+    }
+
+    #[test]
+    fn test_null_literal() {
+        let result = get_result(
+            "\
+            let found = null;
+            for (x in [1, 2, 3]) {
+                if (x == 2) { found = x; };
+            };
+            let missing = null;
+            let describe = fn(pair) {
+                switch (pair) {
+                    case [null, y]: { y }
+                    default: { \"other\" }
+                }
+            };
+            return [found, missing, missing == null, found != null, 0 == null, describe([null, 5])];
+            ",
+        );
+        assert_eq!(
+            result.unwrap_return().to_string(),
+            "[2,null,true,true,false,5,]"
+        );
+    }
+
+    #[test]
+    fn test_conditional_expression() {
+        let result = get_result(
+            "\
+            let sign = fn(x) { x > 0 ? 1 : x < 0 ? -1 : 0 };
+            let flag = true;
+            let label = flag ? \"on\" : \"off\";
+            let count = 0;
+            flag ? count = count + 1 : count;
+            return [sign(5), sign(-2), sign(0), label, [key: flag ? 1 : 2, !flag ? 3 : 4], count];
+            ",
+        );
+        assert_eq!(
+            result.unwrap_return().to_string(),
+            "[1,-1,0,on,[key:1,4,],1,]"
+        );
+    }
+
+    #[test]
+    fn test_map_insertion_order() {
+        let result = get_result(
+            "\
+            let m = [zeta: 1, alpha: 2, zeta: 3];
+            m[\"mid\"] = 4;
+            m[\"alpha\"] = 5;
+            let order = 0;
+            for (value in m) {
+                order = order * 10 + value;
+            };
+            return [m, order];
+            ",
+        );
+        assert_eq!(
+            result.unwrap_return().to_string(),
+            "[[zeta:3,alpha:5,mid:4,],354,]"
+        );
+    }
+
+    #[test]
+    fn test_slicing() {
+        let result = get_result(
+            "\
+            let letters = [\"a\", \"b\", \"c\", \"d\"];
+            let text = \"héllo\";
+            let mid = 2;
+            return [
+                letters[1:3], letters[:mid], letters[mid:], letters[:], letters[3:1],
+                text[1:3], text[2:], text[:0], [a: 1, b: 2, 3][1:],
+            ];
+            ",
+        );
+        assert_eq!(
+            result.unwrap_return().to_string(),
+            "[[b,c,],[a,b,],[c,d,],[a,b,c,d,],[],él,llo,,[b:2,3,],]"
+        );
+
+        for (source, message) in [
+            ("[1, 2][1:3];", "index out of bounds"),
+            ("\"ab\"[1:3];", "index out of bounds"),
+            ("[1][\"a\":];", "slice bounds must be integers, got a"),
+            ("let x = 1; x[0:1];", "cannot slice identifier x"),
+        ] {
+            let mut lexer = Peekable::new(source);
+            let program = parse(&mut lexer).unwrap();
+            let env = Rc::new(RefCell::new(Environment::new(None)));
+            let error = program.eval(env, &mut EvalOption::new()).unwrap_err();
+            assert_eq!(error.message, message, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_ranges() {
+        let mut lexer = Peekable::new(
+            "\
+            let r = 1..4;
+            let sum = 0;
+            for (i in r) { sum = sum + i; };
+            let down = range(10, 0, -3);
+            let last = for (i in 0..1000000000) { if (i == 3) { break i; }; };
+            return [
+                sum, r[\"start\"], r[\"end\"], r[\"step\"], r[2], r == 1..4, down,
+                toArray(down), contains(down, 4), contains(down, 5), contains(r, 4),
+                [\"a\", \"b\", \"c\", \"d\"][1..3], [1, 2, 3][range(2, -1, -1)], last,
+            ];
+            ",
+        );
+        let program = parse(&mut lexer).unwrap();
+        let env = Rc::new(RefCell::new(get_builtin_environment()));
+        let val = program.eval(env, &mut EvalOption::new()).unwrap();
+        assert_eq!(
+            val.unwrap_return().to_string(),
+            "[6,1,4,1,3,true,10..0 step -3,[10,7,4,1,],true,false,false,[b,c,],[3,2,1,],3,]"
+        );
+
+        for (source, message) in [
+            ("[1, 2][1..3];", "index out of bounds"),
+            ("(1..3)[2];", "index out of bounds"),
+            ("1.5..3;", "range bounds must be integers"),
+            ("range(0, 1, 0);", "range step cannot be 0"),
+        ] {
+            let mut lexer = Peekable::new(source);
+            let program = parse(&mut lexer).unwrap();
+            let env = Rc::new(RefCell::new(get_builtin_environment()));
+            let error = program.eval(env, &mut EvalOption::new()).unwrap_err();
+            assert_eq!(error.message, message, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_import_data_files() {
+        let dir = std::env::temp_dir().join(format!("ankara-import-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("config.json"),
+            r#"{"port": 80, "hosts": ["a", "b"]}"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("users.csv"), "name,age\nann,31\nbob,42\n").unwrap();
+        std::fs::write(dir.join("broken.json"), "{\n  \"port\": }").unwrap();
+
+        let result = get_result(&format!(
+            r#"
+            import config from "{}";
+            import users from "{}";
+            return [config["port"], config["hosts"][1], users[1]["name"], users[0]["age"]];
+            "#,
+            dir.join("config.json").display(),
+            dir.join("users.csv").display()
+        ));
+        assert_eq!(result.unwrap_return().to_string(), "[80,b,bob,31,]");
+
+        let broken = dir.join("broken.json").display().to_string();
+        let source = format!(r#"import config from "{}";"#, broken);
+        let mut lexer = Peekable::new(&source);
+        let program = parse(&mut lexer).unwrap();
+        let env = Rc::new(RefCell::new(Environment::new(None)));
+        let error = program.eval(env, &mut EvalOption::new()).unwrap_err();
+        assert_eq!(error.message, format!("cannot import {}", broken));
+        assert_eq!(
+            error.child.unwrap().message,
+            "expected a value at line 2 column 11"
+        );
+
+        let mut lexer = Peekable::new(r#"import notes from "notes.txt";"#);
+        let program = parse(&mut lexer).unwrap();
+        let env = Rc::new(RefCell::new(Environment::new(None)));
+        let error = program.eval(env, &mut EvalOption::new()).unwrap_err();
+        assert_eq!(
+            error.message,
+            "cannot import notes.txt, only .ank, .json and .csv files can be imported"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_import_modules() {
+        let dir = std::env::temp_dir().join(format!("ankara-modules-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let math = dir.join("math.ank").display().to_string();
+        std::fs::write(
+            &math,
+            r#"
+            export const PI = 3;
+            export let square = fn(x) { x * x }, area = fn(r) { PI * square(r) };
+            let helper = 1;
+            "#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("broken.ank"),
+            "export let ok = 1;\nthrow \"broken\";",
+        )
+        .unwrap();
+
+        let source = format!(
+            r#"
+            import math from "{0}";
+            import again from "{0}";
+            let helper = try {{ math.helper }} catch (e) {{ e.message }};
+            return [math.PI, math.area(2), helper, math.keys(), again.square(5)];
+            "#,
+            math
+        );
+        let program = parse(&mut Peekable::new(&source)).unwrap();
+        let env = Rc::new(RefCell::new(Environment::new(None)));
+        let mut option = EvalOption::new();
+        let result = program.eval(env, &mut option).unwrap();
+        assert_eq!(
+            result.unwrap_return().to_string(),
+            "[3,12,key not found,[PI,square,area,],25,]"
+        );
+        assert_eq!(option.modules.borrow().len(), 1);
+
+        let broken = dir.join("broken.ank").display().to_string();
+        let source = format!(r#"import broken from "{}";"#, broken);
+        let program = parse(&mut Peekable::new(&source)).unwrap();
+        let env = Rc::new(RefCell::new(Environment::new(None)));
+        let error = program.eval(env, &mut EvalOption::new()).unwrap_err();
+        assert_eq!(error.message, format!("cannot import {}", broken));
+        assert_eq!(error.child.unwrap().message, "uncaught broken");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_circular_import() {
+        let dir = std::env::temp_dir().join(format!("ankara-cycle-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).display().to_string();
+        for (name, imports) in [("a.ank", "b.ank"), ("b.ank", "c.ank"), ("c.ank", "b.ank")] {
+            let source = format!("import next from \"{}\";", path(imports));
+            std::fs::write(dir.join(name), source).unwrap();
+        }
+        std::fs::write(dir.join("main.ank"), "").unwrap();
+        std::fs::write(
+            dir.join("self.ank"),
+            format!("import me from \"{}\";", path("main.ank")),
+        )
+        .unwrap();
+
+        let run = |source: String| {
+            let program = parse(&mut Peekable::new(&source)).unwrap();
+            let env = Rc::new(RefCell::new(Environment::new(None)));
+            let mut option = EvalOption::new();
+            option.file = Some(path("main.ank"));
+            let mut error = program.eval(env, &mut option).unwrap_err();
+            assert!(option.importing.is_empty());
+            while let Some(child) = error.child {
+                error = *child;
+            }
+            error.message
+        };
+        assert_eq!(
+            run(format!("import a from \"{}\";", path("a.ank"))),
+            format!(
+                "circular import: {} -> {} -> {}",
+                path("b.ank"),
+                path("c.ank"),
+                path("b.ank")
+            )
+        );
+        assert_eq!(
+            run(format!("import a from \"{}\";", path("self.ank"))),
+            format!(
+                "circular import: {} -> {} -> {}",
+                path("main.ank"),
+                path("self.ank"),
+                path("main.ank")
+            )
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_module_search_path() {
+        let dir = std::env::temp_dir().join(format!("ankara-search-{}", std::process::id()));
+        let extra = dir.join("extra");
+        std::fs::create_dir_all(&extra).unwrap();
+        std::fs::create_dir_all(dir.join("lib")).unwrap();
+        std::fs::write(dir.join("util.ank"), "export let name = \"util\";").unwrap();
+        std::fs::write(
+            dir.join("lib").join("shape.ank"),
+            "export let name = \"shape\";",
+        )
+        .unwrap();
+        std::fs::write(extra.join("text.ank"), "export let name = \"text\";").unwrap();
+        std::fs::write(extra.join("util.ank"), "export let name = \"shadowed\";").unwrap();
+        std::env::set_var("ANKARA_PATH", &extra);
+
+        let run = |source: &str| {
+            let program = parse(&mut Peekable::new(source)).unwrap();
+            let env = Rc::new(RefCell::new(Environment::new(None)));
+            let mut option = EvalOption::new();
+            option.file = Some(dir.join("main.ank").display().to_string());
+            program.eval(env, &mut option)
+        };
+        let result = run(
+            "import \"util\"; import \"text\"; import \"shape\"; return [util.name, text.name, shape.name];",
+        );
+        assert_eq!(
+            result.unwrap().unwrap_return().to_string(),
+            "[util,text,shape,]"
+        );
+
+        let error = run("import \"missing\";").unwrap_err();
+        assert_eq!(
+            error.message,
+            format!(
+                "module not found: missing, searched: {}, {}, {}",
+                dir.join("missing.ank").display(),
+                dir.join("lib").join("missing.ank").display(),
+                extra.join("missing.ank").display()
+            )
+        );
+        std::env::remove_var("ANKARA_PATH");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_fork_isolated() {
+        let builtins = Rc::new(RefCell::new(get_builtin_environment()));
+        let run = |source: &str| {
+            let mut lexer = Peekable::new(source);
+            let program = parse(&mut lexer).unwrap();
+            let env = Rc::new(RefCell::new(Environment::fork_isolated(&builtins)));
+            program.eval(env, &mut EvalOption::new())
+        };
+
+        let error = run("print = 1;").unwrap_err();
+        assert_eq!(error.message, "cannot assign to builtin print");
+        let val = run("let print = 1; let x = 2; return print;").unwrap();
+        assert_eq!(val.unwrap_return().to_string(), "1");
+
+        // the first run's globals and shadowing are gone for the next one
+        let error = run("return x;").unwrap_err();
+        assert_eq!(error.message, "variable not found x");
+        let val = run("return print;").unwrap();
+        assert_eq!(val.unwrap_return().to_string(), "builtin function");
+        assert!((*builtins).borrow().frozen);
+    }
+
+    #[test]
+    fn test_output_limit() {
+        let mut lexer = Peekable::new("for (i in [1, 2, 3, 4]) { print(\"12345\"); };");
+        let program = parse(&mut lexer).unwrap();
+        let env = Rc::new(RefCell::new(get_builtin_environment()));
+        let mut option = EvalOption::new();
+        option.set_output_limit(15);
+        let error = program.eval(env, &mut option).unwrap_err();
+        assert_eq!(error.message, "output limit of 15 bytes exceeded");
+        assert_eq!(option.output_limit.unwrap().borrow().written, 12);
+    }
+
+    #[test]
+    fn test_template_literal() {
+        let result = get_result(
+            r#"
+            let name = "ankara";
+            let age = 2;
+            return "hello ${name}, you are ${age + 1} \${not} ${[1, 2.5]}";
+            "#,
+        );
+        assert_eq!(
+            result,
+            get_return_object(Object::StringLiteral(
+                "hello ankara, you are 3 ${not} [1,2.5,]".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_watch_updated_from_inner_scopes() {
+        let result = get_result(
+            "
+            let x = 1;
+            watch double = { x * 2 };
+            let setX = fn(value) { x = value; };
+            setX(5);
+            let fromFunction = double;
+            for (i in [1, 2]) { x = i * 10; };
+            return [fromFunction, double];
+            ",
+        );
+        assert_eq!(result.unwrap_return().to_string(), "[10,40,]");
+    }
+
+    #[test]
+    fn test_watch_ends_with_its_scope() {
+        let result = get_result(
+            "
+            let x = 1;
+            let calls = [0];
+            let f = fn() {
+                watch w = { calls[0] = calls[0] + 1; x };
+            };
+            f();
+            for (i in [1, 2]) {
+                watch w = { calls[0] = calls[0] + 1; x + i };
+            };
+            let afterDeclaring = calls[0];
+            x = 5;
+            return [afterDeclaring, calls[0]];
+            ",
+        );
+        assert_eq!(result.unwrap_return().to_string(), "[3,3,]");
+    }
+
+    #[test]
+    fn test_watch_lives_as_long_as_a_closure() {
+        let result = get_result(
+            "
+            let x = 1;
+            let make = fn() {
+                watch w = { x + 1 };
+                fn() { w }
+            };
+            let first = make();
+            let second = make();
+            x = 10;
+            return [first(), second()];
+            ",
+        );
+        assert_eq!(result.unwrap_return().to_string(), "[11,11,]");
+    }
+
+    #[test]
+    fn test_increment_and_decrement() {
+        let result = get_result(
+            "
+            let i = 1;
+            let counts = [0, 1.5];
+            let old = i++;
+            let new = ++i;
+            counts[0]++;
+            --counts[1];
+            i--;
+            return [old, new, i, counts[0], counts[1]];
+            ",
+        );
+        assert_eq!(result.unwrap_return().to_string(), "[1,3,2,1,0.5,]");
+    }
+
+    #[test]
+    fn test_caller_location() {
+        let mut lexer = Peekable::new(
+            "let log = fn(message) {
+    let line = callerLocation()[\"line\"];
+    \"${__file}:${line} ${message}\"
+};
+
+return [log(\"start\"), __line, callerLocation(), \"\"\"${
+    log(\"inside\")
+}\"\"\"];",
+        );
+        let program = parse(&mut lexer).unwrap();
+        let env = Rc::new(RefCell::new(get_builtin_environment()));
+        let mut option = EvalOption::new();
+        option.file = Some("main.ank".to_string());
+        let result = program.eval(env, &mut option).unwrap();
+        assert_eq!(
+            result.unwrap_return().to_string(),
+            "[main.ank:6 start,6,null,main.ank:7 inside,]"
+        );
+        assert!(option.call_lines.is_empty());
+    }
+
+    #[test]
+    fn test_negative_indexing() {
+        let result = get_result(
+            "
+            let arr = [1, 2, key: 3];
+            let last = arr[-1];
+            arr[-3] = 10;
+            arr[-2]++;
+            return [last, arr[-3], arr];
+            ",
+        );
+        assert_eq!(result.unwrap_return().to_string(), "[3,10,[10,3,key:3,],]");
+
+        for (source, message) in [
+            ("let arr = [1, 2]; arr[-3];", "index out of bounds"),
+            ("let arr = [1, 2]; arr[-3] = 0;", "index out of range: -3"),
+        ] {
+            let mut lexer = Peekable::new(source);
+            let program = parse(&mut lexer).unwrap();
+            let env = Rc::new(RefCell::new(Environment::new(None)));
+            let error = program.eval(env, &mut EvalOption::new()).unwrap_err();
+            assert_eq!(error.message, message);
+        }
+    }
+
+    #[test]
+    fn test_string_comparison() {
+        let result = get_result(
+            "
+            return [
+                \"apple\" < \"banana\", \"apple\" < \"app\", \"b\" > \"abc\",
+                \"a\" <= \"a\", \"Z\" < \"a\", \"\" >= \"a\",
+            ];
+            ",
+        );
+        assert_eq!(
+            result.unwrap_return().to_string(),
+            "[true,false,true,true,true,false,]"
+        );
+    }
+
+    #[test]
+    fn test_array_concatenation_and_repetition() {
+        let result = get_result(
+            "
+            let left = [1, 2];
+            let joined = left + [3];
+            joined.push(4);
+            let inner = [0];
+            let grid = [inner] * 2;
+            inner.push(1);
+            return [
+                joined, left, [a: 1, b: 2] + [b: 3, c: 4], [0] * 3, [1] * 0,
+                \"ab\" * 3, \"ab\" * 0, grid,
+            ];
+            ",
+        );
+        assert_eq!(
+            result.unwrap_return().to_string(),
+            "[[1,2,3,4,],[1,2,],[a:1,b:3,c:4,],[0,0,0,],[],ababab,,[[0,1,],[0,1,],],]"
+        );
+
+        for (source, message) in [
+            ("[1] * -1;", "cannot repeat a value -1 times"),
+            ("\"a\" * -2;", "cannot repeat a value -2 times"),
+            ("[a: 1] * 2;", "cannot repeat an array with keys"),
+            ("[1] - [1];", "invalid operator"),
+        ] {
+            let mut lexer = Peekable::new(source);
+            let program = parse(&mut lexer).unwrap();
+            let env = Rc::new(RefCell::new(Environment::new(None)));
+            let error = program.eval(env, &mut EvalOption::new()).unwrap_err();
+            assert_eq!(error.message, message);
+        }
+    }
+
+    #[test]
+    fn test_mixed_string_and_number() {
+        let mut lexer = Peekable::new(
+            "
+            return [
+                \"count: \" + str(3), str(1.5) + \"!\", str([1, 2]), int(2.9), int(-2.9),
+                int(\"42\"), int(true), float(2), float(\"1.5\"), str(\"a\") == \"a\",
+            ];
+            ",
+        );
+        let program = parse(&mut lexer).unwrap();
+        let env = Rc::new(RefCell::new(get_builtin_environment()));
+        let result = program.eval(env, &mut EvalOption::new()).unwrap();
+        assert_eq!(
+            result.unwrap_return().to_string(),
+            "[count: 3,1.5!,[1,2,],2,-2,42,1,2.0,1.5,true,]"
+        );
+
+        for (source, message) in [
+            (
+                "\"count: \" + 3;",
+                "cannot add string and number, convert the number with str(3) first",
+            ),
+            (
+                "1.5 + \"x\";",
+                "cannot add float and string, convert the number with str(1.5) first",
+            ),
+            ("\"a\" - 1;", "invalid operator"),
+            ("int(\"1.5\");", "cannot convert string \"1.5\" to int"),
+            ("float(null);", "cannot convert null to float"),
+        ] {
+            let mut lexer = Peekable::new(source);
+            let program = parse(&mut lexer).unwrap();
+            let env = Rc::new(RefCell::new(get_builtin_environment()));
+            let error = program.eval(env, &mut EvalOption::new()).unwrap_err();
+            assert_eq!(error.message, message);
+        }
+    }
+
+    #[test]
+    fn test_float_precision() {
+        let source = "
+            return [
+                3.14159, str(3.14159, 2), str([1.5, 2.25], 1), str(7, 3), str(2.5, 0),
+                1234567.5, 0.00012,
+            ];
+            ";
+        let run = || {
+            let program = parse(&mut Peekable::new(source)).unwrap();
+            let env = Rc::new(RefCell::new(get_builtin_environment()));
+            let result = program.eval(env, &mut EvalOption::new()).unwrap();
+            result.unwrap_return().to_string()
+        };
+        assert_eq!(run(), "[3.14159,3.14,[1.5,2.2,],7,2,1234567.5,0.00012,]");
+        let format = object::FloatFormat {
+            precision: Some(3),
+            exponent_threshold: Some(3),
+        };
+        assert_eq!(
+            object::with_float_format(format, run),
+            "[3.142,3.14,[1.5,2.2,],7,2,1.235e6,1.200e-4,]"
+        );
+        assert_eq!(object::get_float_format(), object::FloatFormat::default());
+
+        let program = parse(&mut Peekable::new("str(1.5, -1);")).unwrap();
+        let env = Rc::new(RefCell::new(get_builtin_environment()));
+        let error = program.eval(env, &mut EvalOption::new()).unwrap_err();
+        assert_eq!(
+            error.message,
+            "str expects from 0 to 20 digits after the decimal point, got -1"
+        );
+    }
+
+    #[test]
+    fn test_literal_pool() {
+        let result = get_result(
+            "
+            let results = [];
+            for (i in 0..3) {
+                let values = [1, \"two\", 3.0];
+                values.push(i);
+                values[0] = i * 10;
+                results.push(values);
+            };
+            return [results, [1, [2]], [i: 1]];
+            ",
+        );
+        assert_eq!(
+            result.unwrap_return().to_string(),
+            "[[[0,two,3.0,0,],[10,two,3.0,1,],[20,two,3.0,2,],],[1,[2,],],[i:1,],]"
+        );
+
+        // arrays made from the same literal share its elements until one of them changes
+        let mut lexer = Peekable::new("[1, 2, 3]");
+        let literal = parse_expression(&mut lexer, Precedence::Lowest).unwrap();
+        let env = Rc::new(RefCell::new(Environment::new(None)));
+        let make = || match literal.eval(env.clone(), &mut EvalOption::new()).unwrap() {
+            Object::Array(array) => array,
+            obj => panic!("expected an array, got {}", obj),
+        };
+        let (first, second) = (make(), make());
+        assert!(Rc::ptr_eq(
+            &first.elements.borrow(),
+            &second.elements.borrow()
+        ));
+        first.elements_mut().pop();
+        assert!(!Rc::ptr_eq(
+            &first.elements.borrow(),
+            &second.elements.borrow()
+        ));
+        assert_eq!(Object::Array(second).to_string(), "[1,2,3,]");
+    }
+
+    #[test]
+    fn test_recursive_closure() {
+        let result = get_result(
+            "
+            let fact = fn(n) { if (n == 0) { return 1; } return n * fact(n - 1); };
+            let outer = fn() {
+                let isEven = fn(n) { if (n == 0) { return true; } isOdd(n - 1) };
+                let isOdd = fn(n) { if (n == 0) { return false; } isEven(n - 1) };
+                isEven(7)
+            };
+            let count = 0;
+            if (count > 0) { count = 10; }
+            count = count + 1;
+            return [fact(5), outer(), count];
+            ",
+        );
+        assert_eq!(result.unwrap_return().to_string(), "[120,false,1,]");
+    }
+
+    #[test]
+    fn test_max_call_depth() {
+        let mut lexer = Peekable::new(
+            "
+            let down = fn(n) { if (n == 0) { return 0; } down(n - 1) };
+            let forever = fn(n) { forever(n + 1) };
+            let caught = try { forever(0) } catch (e) { e[\"calls\"].len() };
+            return [down(9), caught, down(9)];
+            ",
+        );
+        let program = parse(&mut lexer).unwrap();
+        let env = Rc::new(RefCell::new(Environment::new(None)));
+        let mut option = EvalOption::new();
+        option.max_depth = 10;
+        let result = program.eval(env, &mut option).unwrap();
+        assert_eq!(result.unwrap_return().to_string(), "[0,11,0,]");
+        assert_eq!(option.depth, 0);
+
+        let mut lexer = Peekable::new(
+            "
+            let forever = fn(n) { forever(n + 1) };
+            let start = fn() { forever(0) };
+            start();
+            ",
+        );
+        let program = parse(&mut lexer).unwrap();
+        let env = Rc::new(RefCell::new(Environment::new(None)));
+        let mut option = EvalOption::new();
+        option.max_depth = 10;
+        let error = program.eval(env, &mut option).unwrap_err();
+        assert_eq!(
+            error.message,
+            "maximum call depth 10 exceeded, calls from lines 4, 3, 2 (x9)"
+        );
+
+        let mut lexer = Peekable::new(
+            "
+            struct Loop { fn __add(other) { self + other } }
+            Loop() + 1;
+            ",
+        );
+        let program = parse(&mut lexer).unwrap();
+        let env = Rc::new(RefCell::new(Environment::new(None)));
+        let mut option = EvalOption::new();
+        option.max_depth = 10;
+        let error = program.eval(env, &mut option).unwrap_err();
+        assert_eq!(error.message, "maximum call depth 10 exceeded");
+    }
+
+    #[test]
+    fn test_string_indexing() {
+        let result = get_result(
+            "
+            let word = \"héllo\";
+            return [word[0], word[1], word[-1], word[1] == \"é\"];
+            ",
+        );
+        assert_eq!(result.unwrap_return().to_string(), "[h,é,o,true,]");
+
+        for (source, message) in [
+            ("\"abc\"[3];", "index out of bounds"),
+            ("\"abc\"[-4];", "index out of bounds"),
+        ] {
+            let mut lexer = Peekable::new(source);
+            let program = parse(&mut lexer).unwrap();
+            let env = Rc::new(RefCell::new(Environment::new(None)));
+            let error = program.eval(env, &mut EvalOption::new()).unwrap_err();
+            assert_eq!(error.message, message);
+        }
+    }
+
+    #[test]
+    fn test_member_access() {
+        let result = get_result(
+            "
+            let point = [x: 1, y: 2];
+            point.x = 5;
+            point.z = point.x + point.y;
+            point.y++;
+            return [point.x, point[\"z\"], point];
+            ",
+        );
+        assert_eq!(result.unwrap_return().to_string(), "[5,7,[x:5,y:3,z:7,],]");
+
+        for (source, message) in [
+            ("let point = [x: 1]; point.y;", "key not found"),
+            ("let n = 1; n.x = 2;", "1 is not an array"),
+        ] {
+            let mut lexer = Peekable::new(source);
+            let program = parse(&mut lexer).unwrap();
+            let env = Rc::new(RefCell::new(Environment::new(None)));
+            let error = program.eval(env, &mut EvalOption::new()).unwrap_err();
+            assert_eq!(error.message, message);
+        }
+    }
+
+    #[test]
+    fn test_methods() {
+        let result = get_result(
+            "
+            let arr = [1, 2];
+            arr.push(3);
+            let last = arr.pop();
+            let m = [a: 1, b: 2, keys: fn() { 0 }, twice: fn(x) { x * 2 }];
+            return [
+                arr.len(), last, arr, m.keys(), m.values().len(), m.twice(4),
+                \" Hi There \".trim().lower().split(\" \"), \"héllo\".len(),
+                (1..10).contains(3), (0..3).toArray()
+            ];
+            ",
+        );
+        assert_eq!(
+            result.unwrap_return().to_string(),
+            "[2,3,[1,2,],[a,b,keys,twice,],4,8,[hi,there,],5,true,[0,1,2,],]"
+        );
+
+        for (source, message) in [
+            (
+                "[1].push();",
+                "wrong number of arguments to push. got=0, want=1",
+            ),
+            ("[].pop();", "cannot pop from an empty array"),
+            ("let m = [a: 1]; m.missing();", "key not found"),
+            ("\"a\".split(1);", "split expects a string separator, got 1"),
+        ] {
+            let mut lexer = Peekable::new(source);
+            let program = parse(&mut lexer).unwrap();
+            let env = Rc::new(RefCell::new(Environment::new(None)));
+            let error = program.eval(env, &mut EvalOption::new()).unwrap_err();
+            assert_eq!(error.message, message);
+        }
+    }
+
+    #[test]
+    fn test_watches() {
+        let mut lexer = Peekable::new(
+            "
+            let x = 1;
+            let y = 2;
+            watch sum = { x + y };
+            watch double = { x * 2 };
+            let inner = fn() {
+                watch local = { y + 10 };
+                watches()
+            };
+            x = 5;
+            return [watches(), inner()];
+            ",
+        );
+        let program = parse(&mut lexer).unwrap();
+        let builtins = Rc::new(RefCell::new(get_builtin_environment()));
+        let env = Rc::new(RefCell::new(Environment::fork_isolated(&builtins)));
+        let result = program.eval(env, &mut EvalOption::new()).unwrap();
+        assert_eq!(
+            result.unwrap_return().to_string(),
+            "[[[name:double,dependencies:[x,],value:10,],[name:sum,dependencies:[x,y,],value:7,],],\
+             [[name:double,dependencies:[x,],value:10,],[name:local,dependencies:[y,],value:12,],\
+             [name:sum,dependencies:[x,y,],value:7,],],]"
+        );
+    }
+
+    #[test]
+    fn test_arrow_function() {
+        let input = r#"
+            let add = (a, b) => a + b;
+            let twice = (f, x) => f(f(x));
+            let offset = 10;
+            let shift = (x) => { let y = x + offset; y };
+            return [add(1, 2), twice((x) => x * 3, 2), shift(5), (() => "none")()];
+        "#;
+        assert_eq!(
+            get_result(input).unwrap_return().to_string(),
+            "[3,18,15,none,]"
+        );
+    }
+
+    #[test]
+    fn test_rest_parameters() {
+        let input = r#"
+            let count = fn(first, ...rest) { [first, rest] };
+            let sum = (...numbers) => {
+                let total = 0;
+                for (n in numbers) { total = total + n; };
+                total
+            };
+            return [count(1), count(1, 2, 3), sum(), sum(1, 2, 3)];
+        "#;
+        assert_eq!(
+            get_result(input).unwrap_return().to_string(),
+            "[[1,[],],[1,[2,3,],],0,6,]"
+        );
+    }
+
+    #[test]
+    fn test_destructuring_rest() {
+        let val = get_result(
+            "\
+            let [head, ...tail] = [1, 2, 3];
+            let [...all] = [];
+            let [a, [b, ...inner], ...outer] = [1, [2, 3, 4], 5];
+            return [head, tail, all, inner, outer];
+            ",
+        );
+        assert_eq!(
+            val.unwrap_return().to_string(),
+            "[1,[2,3,],[],[3,4,],[5,],]"
+        );
+
+        for (input, message) in [
+            (
+                "let [a, b, ...rest] = [1];",
+                "expected at least 2 elements, got 1",
+            ),
+            ("let [a, b] = [1, 2, 3];", "expected 2 elements, got 3"),
+        ] {
+            let mut lexer = Peekable::new(input);
+            let program = parse(&mut lexer).unwrap();
+            let error = program
+                .eval(
+                    Rc::new(RefCell::new(Environment::new(None))),
+                    &mut EvalOption::new(),
+                )
+                .unwrap_err();
+            assert_eq!(error.message, message);
+        }
+    }
+
+    #[test]
+    fn test_destructuring_keys() {
+        let val = get_result(
+            "\
+            let point = [x: 1, y: 2, label: \"p\"];
+            let [y: py, x: px] = point;
+            let [pos: [first, second], name: name] = [name: \"a\", pos: [3, 4]];
+            let origin = fn(p) {
+                switch (p) {
+                    case [x: 0, y: 0]: { true }
+                    default: { false }
+                }
+            };
+            return [px, py, first, second, name, origin([y: 0, x: 0]), origin(point)];
+            ",
+        );
+        assert_eq!(val.unwrap_return().to_string(), "[1,2,3,4,a,true,false,]");
+
+        for (input, message) in [
+            ("let [x: a, y: b] = [x: 1];", "key not found: y"),
+            ("let [x: a] = 1;", "expected an array with keys, got 1"),
+        ] {
+            let mut lexer = Peekable::new(input);
+            let program = parse(&mut lexer).unwrap();
+            let error = program
+                .eval(
+                    Rc::new(RefCell::new(Environment::new(None))),
+                    &mut EvalOption::new(),
+                )
+                .unwrap_err();
+            assert_eq!(error.message, message);
+        }
+    }
+
+    #[test]
+    fn test_multiple_assign() {
+        let val = get_result(
+            "\
+            let a = 1, b = a + 1;
+            a, b = b, a;
+            let point = [x: 1, y: 2];
+            let list = [10, 20, 30];
+            point.x, point.y, list[0], list[2] = point.y, point.x, list[2], list[0];
+            return [a, b, point, list];
+            ",
+        );
+        assert_eq!(
+            val.unwrap_return().to_string(),
+            "[2,1,[x:2,y:1,],[30,20,10,],]"
+        );
+    }
+
+    #[test]
+    fn test_match_expression() {
+        let val = get_result(
+            "\
+            let describe = fn(value) {
+                match (value) {
+                    0 => \"zero\",
+                    \"hi\" => \"greeting\",
+                    [0, _] => \"on the y axis\",
+                    [x, y] => { let sum = x + y; sum },
+                    [x: px] => px,
+                    n => n * 2,
+                }
+            };
+            return [describe(0), describe(\"hi\"), describe([0, 5]), describe([1, 2]), describe([x: 7]), describe(4)];
+            ",
+        );
+        assert_eq!(
+            val.unwrap_return().to_string(),
+            "[zero,greeting,on the y axis,3,7,8,]"
+        );
+
+        let mut lexer = Peekable::new("match (3) { 1 => 1, [a] => a }");
+        let program = parse(&mut lexer).unwrap();
+        let error = program
+            .eval(
+                Rc::new(RefCell::new(Environment::new(None))),
+                &mut EvalOption::new(),
+            )
+            .unwrap_err();
+        assert_eq!(error.message, "no match arm for 3");
+    }
+
+    #[test]
+    fn test_switch_fallthrough() {
+        let val = get_result(
+            "\
+            let log = [];
+            let visit = fn(n) {
+                switch (n) {
+                    case 1: { log.push(1); fallthrough; }
+                    case 2: { log.push(2); }
+                    case 3: { 30 }
+                    case 4: { fallthrough; }
+                    default: { \"default\" }
+                }
+            };
+            return [visit(1), visit(3), visit(4), visit(5), log];
+            ",
+        );
+        // a case without a value still ends the switch
+        assert_eq!(
+            val.unwrap_return().to_string(),
+            "[none,30,default,default,[1,2,],]"
+        );
+    }
+
+    #[test]
+    fn test_for_index() {
+        let val = get_result(
+            "\
+            let pairs = [];
+            for (i, v in [\"a\", \"b\"]) {
+                pairs.push([i, v]);
+            };
+            for (k, v in [x: 1, y: 2]) {
+                pairs.push([k, v]);
+            };
+            for (i, n in 5..7) {
+                pairs.push([i, n]);
+            };
+            return pairs;
+            ",
+        );
+        assert_eq!(
+            val.unwrap_return().to_string(),
+            "[[0,a,],[1,b,],[x,1,],[y,2,],[0,5,],[1,6,],]"
+        );
+    }
+
+    #[test]
+    fn test_do_while() {
+        let val = get_result(
+            "\
+            let log = [];
+            let i = 0;
+            do {
+                i = i + 1;
+                if (i == 2) { continue; };
+                log.push(i);
+            } while (i < 4);
+            do { log.push(\"once\"); } while (false);
+            let found = do {
+                if (i == 6) { break i; };
+                i = i + 1;
+            } while (true);
+            let f = fn() { do { return \"returned\"; } while (true); };
+            return [log, found, f()];
+            ",
+        );
+        assert_eq!(
+            val.unwrap_return().to_string(),
+            "[[1,3,4,once,],6,returned,]"
+        );
+    }
+
+    #[test]
+    fn test_loop() {
+        let val = get_result(
+            "\
+            let i = 0;
+            let found = loop {
+                i = i + 1;
+                if (i % 2 == 1) { continue; };
+                if (i > 5) { break i * 10; };
+            };
+            let nothing = loop { break; };
+            let f = fn() { loop { return \"returned\"; }; };
+            return [found, nothing, f()];
+            ",
+        );
+        assert_eq!(val.unwrap_return().to_string(), "[60,none,returned,]");
+    }
+
+    #[test]
+    fn test_loop_labels() {
+        let val = get_result(
+            "\
+            let pairs = [];
+            outer: for (i in 0..3) {
+                for (j in 0..3) {
+                    if (j == 1) { continue outer; };
+                    if (i == 2) { break outer; };
+                    pairs.push([i, j]);
+                };
+            };
+            let n = 0;
+            search: loop {
+                n = n + 1;
+                do {
+                    if (n == 3) { break search; };
+                } while (false);
+            };
+            let f = fn() {
+                outer: loop { loop { break outer n * 10; }; }
+            };
+            return [pairs, f()];
+            ",
+        );
+        assert_eq!(val.unwrap_return().to_string(), "[[[0,0,],[1,0,],],30,]");
+    }
+
+    #[test]
+    fn test_computed_map_keys() {
+        let val = get_result(
+            "\
+            let prefix = \"user_\";
+            let id = 7;
+            let user = [(prefix + \"name\"): \"ann\", (id): \"seven\", (prefix + \"name\"): \"bob\"];
+            return [user.user_name, user[\"7\"], user];
+            ",
+        );
+        assert_eq!(
+            val.unwrap_return().to_string(),
+            "[bob,seven,[user_name:bob,7:seven,],]"
+        );
+
+        let mut lexer = Peekable::new("[(true): 1]");
+        let program = parse(&mut lexer).unwrap();
+        let error = program
+            .eval(
+                Rc::new(RefCell::new(Environment::new(None))),
+                &mut EvalOption::new(),
+            )
+            .unwrap_err();
+        assert_eq!(
+            error.message,
+            "map keys must be strings or numbers, got true"
+        );
+    }
+
+    #[test]
+    fn test_empty_map() {
+        let val = get_result(
+            "\
+            let counts = [:];
+            for (word in [\"a\", \"b\"]) {
+                counts[word] = 1;
+            };
+            counts[\"a\"] = 2;
+            return [[:], [], counts];
+            ",
+        );
+        assert_eq!(val.unwrap_return().to_string(), "[[:],[],[a:2,b:1,],]");
+    }
+
+    #[test]
+    fn test_struct_declaration() {
+        let result = get_result(
+            "\
+            struct Point {
+                x, y,
+                fn sum() { self.x + self.y }
+                fn moveBy(dx) { self.x = self.x + dx; self }
+                fn len() { 2 }
+            }
+            let p = Point(1, 2);
+            let q = Point(5);
+            p.moveBy(10);
+            let sum = p.sum;
+            let tooMany = try { Point(1, 2, 3) } catch (e) { e.message };
+            return [p, q, sum(), p.len(), Point, tooMany];
+            ",
+        );
+        assert_eq!(
+            result.unwrap_return().to_string(),
+            "[Point[x:11,y:2,],Point[x:5,y:null,],13,2,struct Point,Point has 2 fields, got 3 arguments,]"
+        );
+    }
+
+    // one call site that gets receivers of different shapes looks their methods up again
+    #[test]
+    fn test_method_cache() {
+        let result = get_result(
+            "\
+            struct Square { side, fn area() { self.side * self.side } }
+            struct Circle { r, fn area() { 3 * self.r * self.r } }
+            let areas = [];
+            for (shape in [Square(2), Circle(1), Square(3), [area: fn() { 7 }], Circle(2)]) {
+                areas.push(shape.area());
+            }
+            let sizes = [];
+            for (value in [[1, 2], \"abc\", 0..4, [1]]) {
+                sizes.push(value.len());
+            }
+            return [areas, sizes];
+            ",
+        );
+        assert_eq!(
+            result.unwrap_return().to_string(),
+            "[[4,3,9,7,12,],[2,3,4,1,],]"
+        );
+    }
+
+    #[test]
+    fn test_operator_methods() {
+        let result = get_result(
+            "\
+            struct Vec2 {
+                x, y,
+                fn __add(other) { Vec2(self.x + other.x, self.y + other.y) }
+                fn __mul(factor) { Vec2(self.x * factor, self.y * factor) }
+                fn __eq(other) { if (self.x == other.x) { self.y == other.y } else { false } }
+                fn __index(i) { if (i == 0) { self.x } else { self.y } }
+            }
+            let a = Vec2(1, 2);
+            let b = Vec2(3, 4);
+            let invalid = try { a - b } catch (e) { e.message };
+            return [a + b, a * 3, a == Vec2(1, 2), a != b, b[0], b[1], a == null, invalid];
+            ",
+        );
+        assert_eq!(
+            result.unwrap_return().to_string(),
+            "[Vec2[x:4,y:6,],Vec2[x:3,y:6,],true,true,3,4,false,invalid operator,]"
+        );
+
+        let result = get_result(
+            "\
+            struct Pair {
+                a,
+                fn __add(left, right) { left }
+                fn __index() { self.a }
+                fn __next(step) { null }
+            }
+            let p = Pair(1);
+            let add = try { p + p } catch (e) { e.message };
+            let index = try { p[0] } catch (e) { e.message };
+            let next = try { for (x in p) {} } catch (e) { e.message };
+            let call = try { fn(a, b) { a }(1) } catch (e) { e.message };
+            return [add, index, next, call];
+            ",
+        );
+        assert_eq!(
+            result.unwrap_return().to_string(),
+            "[__add must take 1 parameter, not 2,__index must take 1 parameter, not 0,\
+             __next must take 0 parameters, not 1,wrong number of arguments. got=1, want=2,]"
+        );
+    }
+
+    #[test]
+    fn test_for_iterator_protocol() {
+        let result = get_result(
+            "\
+            struct Countdown {
+                from,
+                fn __next() {
+                    if (self.from == 0) { return null; };
+                    self.from = self.from - 1;
+                    self.from + 1
+                }
+            }
+            struct Bag {
+                items,
+                fn __iter() { self.items }
+            }
+            let seen = [];
+            for (c in \"héllo\") { seen.push(c); };
+            for (i, n in Countdown(3)) { seen.push(i * 10 + n); };
+            for (item in Bag([\"a\", \"b\"])) { seen.push(item); };
+            let grown = [1];
+            for (n in grown) { if (n < 3) { grown.push(n + 1); } };
+            let invalid = try { for (x in 5) {} } catch (e) { e.message };
+            return [seen, grown, invalid];
+            ",
+        );
+        assert_eq!(
+            result.unwrap_return().to_string(),
+            "[[h,é,l,l,o,3,12,21,a,b,],[1,2,3,],cannot iterate over 5,]"
+        );
+    }
+
+    #[test]
+    fn test_array_comprehension() {
+        let result = get_result(
+            "\
+            let nums = [3, -1, 4, 0, 5];
+            let x = \"outer\";
+            return [
+                [x * 2 for x in nums if x > 0],
+                [[n, n * n] for n in 1..4],
+                [c for c in \"abc\" if c != \"b\"],
+                [x for x in []],
+                x,
+            ];
+            ",
+        );
+        assert_eq!(
+            result.unwrap_return().to_string(),
+            "[[6,8,10,],[[1,1,],[2,4,],[3,9,],],[a,c,],[],outer,]"
+        );
+    }
+
+    #[test]
+    fn test_strict_mode() {
+        let result = get_result(
+            "\
+            \"use strict\";
+            let point = [x: 1];
+            let found = if (point.x == 1) { \"yes\" } else { \"no\" };
+            let f = fn(x) { let y = x * 2; y };
+            return [found, f(2), point?.x, !true];
+            ",
+        );
+        assert_eq!(result.unwrap_return().to_string(), "[yes,4,1,false,]");
+
+        for (source, message) in [
+            (
+                "if (1) { 2 };",
+                "strict mode: 1 is a number, not a condition",
+            ),
+            ("!0;", "strict mode: 0 is a number, not a condition"),
+            ("1 && 1;", "strict mode: && does not take numbers"),
+            ("assert 2;", "strict mode: 2 is a number, not a condition"),
+            (
+                "let x = 1; let f = fn() { let x = 2; x }; f();",
+                "strict mode: x shadows a variable of an enclosing scope",
+            ),
+            ("let point = [x: 1]; point?.y;", "key not found"),
+            ("let point = [x: 1]; point?[\"y\"];", "key not found"),
+            (
+                "let x = 1; x + 1\nx",
+                "strict mode: expected a semicolon after x + 1",
+            ),
+        ] {
+            let mut lexer = Peekable::new(source);
+            let program = parse(&mut lexer).unwrap();
+            let env = Rc::new(RefCell::new(Environment::new(None)));
+            let mut option = EvalOption::new();
+            option.strict = true;
+            let error = program.eval(env, &mut option).unwrap_err();
+            assert_eq!(error.message, message);
+        }
+    }
 }
@@ -41,4 +41,20 @@ mod tests {
             Object::Number(2)
         );
     }
+
+    #[test]
+    fn test_top_level_return_stops_execution() {
+        let source_code = "\
+            let x = 1;
+            return x + 1;
+            x = 100;
+            ";
+        let mut lexer = Peekable::new(source_code);
+        let program = parse(&mut lexer).unwrap();
+        assert_eq!(program.get_unreachable_statements().len(), 1);
+        let env = Rc::new(RefCell::new(Environment::new(None)));
+        let result = program.eval(env.clone(), &mut EvalOption::new()).unwrap();
+        assert_eq!(result.unwrap_return(), Object::Number(2));
+        assert_eq!(env.borrow().get("x"), Some(Object::Number(1)));
+    }
 }
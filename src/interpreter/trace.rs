@@ -0,0 +1,73 @@
+use std::fmt::Display;
+use std::rc::Rc;
+
+use super::evaluator::Error;
+use super::object::{Array, Object};
+
+// Where a runtime error came from, recorded by the innermost statement or call it goes up
+// through.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ErrorTrace {
+    pub kind: ErrorKind,
+    // The line of the statement that failed, or of the call to the builtin that failed, in
+    // the script being run then.
+    pub line: Option<usize>,
+    pub file: Option<String>,
+    // Lines of the calls being run when the error happened, outermost first. For a builtin,
+    // the last one is the call that failed.
+    pub calls: Vec<usize>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ErrorKind {
+    // raised by the evaluator itself, such as a missing variable or a division by zero
+    Runtime,
+    // returned by a builtin function or method
+    Builtin,
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorKind::Runtime => write!(f, "runtime"),
+            ErrorKind::Builtin => write!(f, "builtin"),
+        }
+    }
+}
+
+// `[message: ..., kind: ..., file: ..., line: ..., calls: [...]]`, what `catch` binds for a
+// runtime error. `line` and `file` are `null` when nothing recorded where the error happened.
+pub fn error_object(error: Error, trace: Option<ErrorTrace>) -> Object {
+    let trace = trace.unwrap_or(ErrorTrace {
+        kind: ErrorKind::Runtime,
+        line: None,
+        file: None,
+        calls: Vec::new(),
+    });
+    let line = match trace.line {
+        Some(line) => Object::Number(line as i64),
+        None => Object::Null,
+    };
+    let file = match trace.file {
+        Some(file) if trace.line.is_some() => Object::StringLiteral(file),
+        _ => Object::Null,
+    };
+    let calls = trace
+        .calls
+        .iter()
+        .map(|line| Object::Number(*line as i64))
+        .collect();
+    Object::Array(Rc::new(Array::from_entries(vec![
+        ("message".to_string(), Object::StringLiteral(error.message)),
+        (
+            "kind".to_string(),
+            Object::StringLiteral(trace.kind.to_string()),
+        ),
+        ("file".to_string(), file),
+        ("line".to_string(), line),
+        (
+            "calls".to_string(),
+            Object::Array(Rc::new(Array::from_objects(calls))),
+        ),
+    ])))
+}
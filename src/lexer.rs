@@ -1,28 +1,83 @@
+use std::cell::Cell;
+
 use crate::Token;
 use logos::Lexer;
 use logos::Logos;
 
+// How deeply the parser may recurse into nested expressions before giving up.
+pub const DEFAULT_MAX_NESTING_DEPTH: usize = 256;
+
+#[derive(Clone)]
 pub struct Peekable<'source> {
     lexer: Lexer<'source, Token>,
     pub peeked: Option<Token>,
     pub peeked_slice: Option<&'source str>,
     pub current: Option<Token>,
     pub current_slice: Option<&'source str>,
+    // byte offset just past the last token returned by `next`
+    pub consumed: usize,
+    // byte offset of the peeked token
+    pub peeked_start: usize,
     pub is_newline: bool,
+    // current and maximum nesting of expressions being parsed
+    pub depth: usize,
+    pub max_depth: usize,
+    // labels of the loops being parsed in the current function, innermost last
+    pub labels: Vec<String>,
+    // how many brackets, parentheses and braces read are still open
+    pub brackets: usize,
+    // The bracket nesting at which the construct being parsed takes the next `:`, like a case
+    // or a map key, so a `?[` there is not the start of a conditional.
+    pub colon_taken_at: Option<usize>,
+    // byte offset and line `line` last counted up to
+    line_count: Cell<(usize, usize)>,
 }
 
 impl<'source> Peekable<'source> {
     pub fn new(source: &'source str) -> Self {
+        Self::with_max_depth(source, DEFAULT_MAX_NESTING_DEPTH)
+    }
+
+    pub fn with_max_depth(source: &'source str, max_depth: usize) -> Self {
         Self {
             lexer: Token::lexer(source),
             peeked: None,
             peeked_slice: None,
             current: None,
             current_slice: None,
+            consumed: 0,
+            peeked_start: 0,
             is_newline: false,
+            depth: 0,
+            max_depth,
+            labels: Vec::new(),
+            brackets: 0,
+            colon_taken_at: None,
+            line_count: Cell::new((0, 1)),
         }
     }
 
+    // Starts lexing at byte `offset` of the source. Only valid before anything is peeked.
+    pub fn start_at(&mut self, offset: usize) {
+        self.lexer.bump(offset);
+        self.consumed = self.lexer.span().end;
+    }
+
+    // Numbers the lines of the source from `line`, for source embedded in a larger one.
+    pub fn start_at_line(&mut self, line: usize) {
+        self.line_count.set((0, line));
+    }
+
+    // 1-based line of the last token read from the source, peeked or not. The lexer only
+    // moves forward, so lines are counted on from the previous call.
+    pub fn line(&self) -> usize {
+        let offset = self.lexer.span().start;
+        let (counted, line) = self.line_count.get();
+        let line = line + self.lexer.source()[counted..offset].matches('\n').count();
+        self.line_count.set((offset, line));
+        line
+    }
+
     pub fn peek(&mut self) -> Option<&Token> {
         if self.peeked.is_none() {
             let mut next = self.lexer.next();
@@ -66,9 +121,35 @@ impl<'source> Peekable<'source> {
                 Some(_) => Some(self.lexer.slice()),
                 _ => None,
             };
+            self.peeked_start = self.lexer.span().start;
         }
         self.peeked.as_ref()
     }
+
+    // Hands back the `[` of the `?[` just read, to be read again as the start of the first
+    // branch of a conditional like `c ?[a] : [b]`.
+    pub fn unread_bracket(&mut self) {
+        self.peeked = Some(Token::LBracket);
+        self.peeked_slice = self.current_slice.map(|slice| &slice[1..]);
+        self.peeked_start = self.consumed - 1;
+        self.brackets -= 1;
+    }
+
+    // The token after the peeked one, found on a copy of the lexer so nothing is consumed.
+    pub fn peek_second(&mut self) -> Option<Token> {
+        self.peek()?;
+        let mut lexer = self.lexer.clone();
+        let mut in_comment = false;
+        loop {
+            match lexer.next()? {
+                Ok(Token::Newline) => in_comment = false,
+                Ok(Token::Comment) => in_comment = true,
+                _ if in_comment => {}
+                Ok(token) => return Some(token),
+                Err(_) => return None,
+            }
+        }
+    }
 }
 
 impl<'source> Iterator for Peekable<'source> {
@@ -80,8 +161,18 @@ impl<'source> Iterator for Peekable<'source> {
         }
         match self.peeked.take() {
             Some(token) => {
+                match token {
+                    Token::LParen | Token::LBracket | Token::LBrace | Token::QuestionBracket => {
+                        self.brackets += 1
+                    }
+                    Token::RParen | Token::RBracket | Token::RBrace => {
+                        self.brackets = self.brackets.saturating_sub(1)
+                    }
+                    _ => {}
+                }
                 self.current = Some(token);
                 self.current_slice = self.peeked_slice.take();
+                self.consumed = self.lexer.span().end;
                 self.current.clone()
             }
             _ => None,
@@ -121,4 +212,24 @@ mod tests {
         assert_eq!(peekable.peek(), None);
         assert_eq!(peekable.next(), None);
     }
+
+    #[test]
+    fn test_identifier_underscores() {
+        let mut peekable = Peekable::new("let my_var _tmp let_x _ for");
+        let mut tokens = Vec::new();
+        while let Some(token) = peekable.next() {
+            tokens.push((token, peekable.current_slice.unwrap()));
+        }
+        assert_eq!(
+            tokens,
+            vec![
+                (Token::Let, "let"),
+                (Token::Identifier, "my_var"),
+                (Token::Identifier, "_tmp"),
+                (Token::Identifier, "let_x"),
+                (Token::Identifier, "_"),
+                (Token::For, "for"),
+            ]
+        );
+    }
 }
@@ -0,0 +1,16 @@
+pub mod analyze;
+pub mod ast;
+pub mod builtin;
+pub mod check;
+pub mod incremental;
+pub mod interpreter;
+pub mod lexer;
+pub mod parser;
+pub mod precedence;
+pub mod printer;
+pub mod read_file;
+pub mod repl;
+pub mod server;
+pub mod token;
+
+use token::Token;
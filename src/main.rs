@@ -1,47 +1,270 @@
-mod ast;
-mod builtin;
-mod interpreter;
-mod lexer;
-mod parser;
-mod precedence;
-mod read_file;
-mod token;
-use std::{cell::RefCell, rc::Rc};
-
-use interpreter::evaluator::{self, EvalOption, Evaluator};
-use lexer::Peekable;
-use logos::{source, Logos};
-use parser::parse;
-use token::Token;
+use std::{cell::RefCell, collections::HashSet, process, rc::Rc, thread};
+
+use ankara::analyze::analyze;
+use ankara::check::check;
+use ankara::interpreter::environment::Environment;
+use ankara::interpreter::evaluator::{
+    call_stack_size, EvalOption, Evaluator, DEFAULT_MAX_CALL_DEPTH,
+};
+use ankara::interpreter::history::WATCH_HISTORY_CAPACITY;
+use ankara::interpreter::object::{set_float_format, FloatFormat, Object, MAX_PRECISION};
+use ankara::interpreter::output::TEST_MAX_OUTPUT_BYTES;
+use ankara::interpreter::stats::get_mem_stats;
+use ankara::interpreter::test_runner::run_tests;
+use ankara::interpreter::trace::ErrorTrace;
+use ankara::lexer::{Peekable, DEFAULT_MAX_NESTING_DEPTH};
+use ankara::parser::parse;
 extern crate clap;
-use builtin::get_builtin_environment::get_builtin_environment;
-use clap::{App, Arg};
-use read_file::read_file;
+use ankara::builtin::get_builtin_environment::get_builtin_environment_with;
+use ankara::read_file::read_file;
+use ankara::repl::{run_repl, SharedStdin};
+use ankara::server;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 
 fn main() {
     let matches = App::new("ankara")
         .version("1.0")
         .author("Your Name")
         .about("Description about your application")
+        .setting(AppSettings::SubcommandsNegateReqs)
         .arg(
             Arg::with_name("file")
                 .help("The input file to use")
                 .required(true)
                 .index(1),
         ) // 1つ目のフリーアーギュメントとして受け取る
+        .arg(
+            Arg::with_name("record-watch")
+                .long("record-watch")
+                .help("Records every value of watched variables and prints them at exit"),
+        )
+        .arg(
+            Arg::with_name("no-flat-builtins")
+                .long("no-flat-builtins")
+                .help("Only defines builtins inside their namespaces, e.g. io[\"print\"]"),
+        )
+        .arg(
+            Arg::with_name("max-nesting-depth")
+                .long("max-nesting-depth")
+                .takes_value(true)
+                .help("How deeply expressions may nest before parsing fails (default 256)"),
+        )
+        .arg(
+            Arg::with_name("max-depth")
+                .long("max-depth")
+                .takes_value(true)
+                .help("How deeply function calls may nest before the script fails (default 10000)"),
+        )
+        .arg(
+            Arg::with_name("max-output")
+                .long("max-output")
+                .takes_value(true)
+                .help("Aborts the script once print has written this many bytes"),
+        )
+        .arg(
+            Arg::with_name("precision")
+                .long("precision")
+                .takes_value(true)
+                .help("How many digits after the decimal point print shows for floats"),
+        )
+        .arg(
+            Arg::with_name("exponent-threshold")
+                .long("exponent-threshold")
+                .takes_value(true)
+                .help("Prints floats of 10^N and up, or below 10^-N, like 1.5e21"),
+        )
+        .arg(
+            Arg::with_name("strict")
+                .long("strict")
+                .help("Runs in strict mode, as if the script started with \"use strict\";"),
+        )
+        .arg(
+            Arg::with_name("deny-warnings")
+                .long("deny-warnings")
+                .help("Fails without running the script if checking it printed any warning"),
+        )
+        .arg(
+            Arg::with_name("mem-report")
+                .long("mem-report")
+                .help("Prints the number of live environments, arrays and functions at exit"),
+        )
+        .subcommand(
+            SubCommand::with_name("test")
+                .about("Runs the test blocks of a script")
+                .arg(
+                    Arg::with_name("file")
+                        .help("The script whose tests are run")
+                        .required(true)
+                        .index(1),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("analyze")
+                .about("Reports functions that are never called and code that never runs")
+                .arg(
+                    Arg::with_name("file")
+                        .help("The script to analyze")
+                        .required(true)
+                        .index(1),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("repl")
+                .about("Evaluates lines typed at a prompt; :paste reads a block until a lone ."),
+        )
+        .subcommand(
+            SubCommand::with_name("serve")
+                .about("Evaluates newline-delimited requests and answers each with a JSON line")
+                .arg(
+                    Arg::with_name("socket")
+                        .long("socket")
+                        .takes_value(true)
+                        .help("Path of the Unix domain socket to listen on"),
+                )
+                .arg(
+                    Arg::with_name("tcp")
+                        .long("tcp")
+                        .takes_value(true)
+                        .conflicts_with("socket")
+                        .help("Address to listen on, e.g. 127.0.0.1:7878"),
+                ),
+        )
         .get_matches();
 
+    let max_depth = match matches.value_of("max-depth") {
+        Some(depth) => match depth.parse::<usize>() {
+            Ok(depth) => depth,
+            Err(_) => {
+                println!("--max-depth expects a number, got {}", depth);
+                return;
+            }
+        },
+        None => DEFAULT_MAX_CALL_DEPTH,
+    };
+    // the main thread's stack would run out long before the call depth limit is reached
+    let runner = match thread::Builder::new()
+        .stack_size(call_stack_size(max_depth))
+        .spawn(move || run(matches, max_depth))
+    {
+        Ok(runner) => runner,
+        Err(error) => {
+            println!(
+                "cannot reserve the stack for --max-depth {}: {}",
+                max_depth, error
+            );
+            process::exit(1);
+        }
+    };
+    if runner.join().is_err() {
+        process::exit(101);
+    }
+}
+
+fn run(matches: ArgMatches, max_depth: usize) {
+    let precision = match matches.value_of("precision") {
+        Some(digits) => match digits.parse::<usize>() {
+            Ok(digits) if digits <= MAX_PRECISION => Some(digits),
+            _ => {
+                println!(
+                    "--precision expects a number from 0 to {}, got {}",
+                    MAX_PRECISION, digits
+                );
+                return;
+            }
+        },
+        None => None,
+    };
+    let exponent_threshold = match matches.value_of("exponent-threshold") {
+        Some(exponent) => match exponent.parse::<i32>() {
+            Ok(exponent) if exponent > 0 => Some(exponent),
+            _ => {
+                println!(
+                    "--exponent-threshold expects a positive number, got {}",
+                    exponent
+                );
+                return;
+            }
+        },
+        None => None,
+    };
+    set_float_format(FloatFormat {
+        precision,
+        exponent_threshold,
+    });
+
+    if let Some(serve_matches) = matches.subcommand_matches("serve") {
+        if let Err(error) = serve(serve_matches) {
+            println!("{:?}", error);
+        }
+        return;
+    }
+
+    if matches.subcommand_matches("repl").is_some() {
+        if let Err(error) = run_repl(SharedStdin::default(), std::io::stdout()) {
+            println!("{:?}", error);
+        }
+        return;
+    }
+
+    if let Some(test_matches) = matches.subcommand_matches("test") {
+        let file_name = test_matches.value_of("file").unwrap();
+        if !test(file_name, max_depth) {
+            process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(analyze_matches) = matches.subcommand_matches("analyze") {
+        let file_name = analyze_matches.value_of("file").unwrap();
+        let source_code = match read_file(file_name) {
+            Ok(source_code) => source_code,
+            Err(error) => {
+                println!("{}", error);
+                process::exit(1);
+            }
+        };
+        let mut lexer = Peekable::new(&source_code);
+        match parse(&mut lexer) {
+            Ok(program) => print!("{}", analyze(&program)),
+            Err(error) => {
+                println!("{:?}", error);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
     let file_name = matches.value_of("file").unwrap();
+    let max_nesting_depth = match matches.value_of("max-nesting-depth") {
+        Some(depth) => match depth.parse::<usize>() {
+            Ok(depth) => depth,
+            Err(_) => {
+                println!("--max-nesting-depth expects a number, got {}", depth);
+                return;
+            }
+        },
+        None => DEFAULT_MAX_NESTING_DEPTH,
+    };
+    let max_output = match matches.value_of("max-output") {
+        Some(bytes) => match bytes.parse::<usize>() {
+            Ok(bytes) => Some(bytes),
+            Err(_) => {
+                println!("--max-output expects a number of bytes, got {}", bytes);
+                return;
+            }
+        },
+        None => None,
+    };
 
     let source_code = match read_file(file_name) {
         Ok(source_code) => source_code,
         Err(error) => {
-            println!("{:?}", error);
+            println!("{}", error);
             return;
         }
     };
 
-    let mut lexer = Peekable::new(&source_code);
+    let mut lexer = Peekable::with_max_depth(&source_code, max_nesting_depth);
     let program = match parse(&mut lexer) {
         Ok(program) => program,
         Err(error) => {
@@ -49,12 +272,137 @@ fn main() {
             return;
         }
     };
-    let mut env = get_builtin_environment();
-    match program.eval(Rc::new(RefCell::new(env)), &mut EvalOption::new()) {
+    let builtins = Rc::new(RefCell::new(get_builtin_environment_with(
+        !matches.is_present("no-flat-builtins"),
+    )));
+    let globals: HashSet<String> = builtins.borrow().visible_names().into_iter().collect();
+    let report = check(&program, &globals);
+    let unreachable_statements = program.get_unreachable_statements();
+    if !unreachable_statements.is_empty() {
+        eprintln!(
+            "warning: {} statement(s) after the top-level return are unreachable",
+            unreachable_statements.len()
+        );
+    }
+    for warning in &report.warnings {
+        eprintln!(
+            "warning: {}:{}: {}",
+            file_name, warning.line, warning.message
+        );
+    }
+    for error in &report.errors {
+        eprintln!("error: {}", error);
+    }
+    let warned = !unreachable_statements.is_empty() || !report.warnings.is_empty();
+    if !report.errors.is_empty() || (warned && matches.is_present("deny-warnings")) {
+        process::exit(1);
+    }
+    let env = Environment::fork_isolated(&builtins);
+    let mut option = if matches.is_present("record-watch") {
+        EvalOption::with_watch_history(WATCH_HISTORY_CAPACITY)
+    } else {
+        EvalOption::new()
+    };
+    option.file = Some(file_name.to_string());
+    option.strict = matches.is_present("strict");
+    option.max_depth = max_depth;
+    if let Some(max_output) = max_output {
+        option.set_output_limit(max_output);
+    }
+    let result = program.eval(Rc::new(RefCell::new(env)), &mut option);
+    if let Some(watch_history) = &option.watch_history {
+        eprint!("watch history:\n{}", watch_history.borrow());
+    }
+    if matches.is_present("mem-report") {
+        eprint!("memory report:\n{}", get_mem_stats());
+    }
+    let result = match result {
         Ok(obj) => obj,
         Err(error) => {
             println!("{:?}", error);
+            if let Some(ErrorTrace {
+                line: Some(line),
+                file: Some(file),
+                ..
+            }) = &option.error_trace
+            {
+                println!("  at {}:{}", file, line);
+            }
             return;
         }
     };
+    // A top-level `return` ends the script, and a number it returns becomes the exit code.
+    // Exit codes are a byte, so a number outside of one fails rather than wrapping around.
+    if let Object::Return(return_value) = result {
+        if let Object::Number(code) = return_value.value {
+            if !(0..=255).contains(&code) {
+                println!("exit code must be from 0 to 255, got {}", code);
+                process::exit(1);
+            }
+            process::exit(code as i32);
+        }
+    }
+}
+
+// Returns whether every test passed.
+fn test(file_name: &str, max_depth: usize) -> bool {
+    let source_code = match read_file(file_name) {
+        Ok(source_code) => source_code,
+        Err(error) => {
+            println!("{}", error);
+            return false;
+        }
+    };
+    let mut lexer = Peekable::new(&source_code);
+    let program = match parse(&mut lexer) {
+        Ok(program) => program,
+        Err(error) => {
+            println!("{:?}", error);
+            return false;
+        }
+    };
+    let builtins = Rc::new(RefCell::new(get_builtin_environment_with(true)));
+    let env = Rc::new(RefCell::new(Environment::fork_isolated(&builtins)));
+    let mut option = EvalOption::new();
+    option.file = Some(file_name.to_string());
+    option.set_output_limit(TEST_MAX_OUTPUT_BYTES);
+    option.max_depth = max_depth;
+    let results = match run_tests(&program, env, &mut option) {
+        Ok(results) => results,
+        Err(error) => {
+            println!("{:?}", error);
+            return false;
+        }
+    };
+    let mut failed = 0;
+    for result in &results {
+        match &result.error {
+            None => println!("test {} ... ok", result.name),
+            Some(error) => {
+                failed += 1;
+                println!("test {} ... FAILED: {}", result.name, error.message);
+            }
+        }
+    }
+    println!("\n{} passed; {} failed", results.len() - failed, failed);
+    failed == 0
+}
+
+fn serve(matches: &clap::ArgMatches) -> std::io::Result<()> {
+    if let Some(address) = matches.value_of("tcp") {
+        return server::serve_tcp(address);
+    }
+    match matches.value_of("socket") {
+        #[cfg(unix)]
+        Some(path) => server::serve_unix(path),
+        #[cfg(not(unix))]
+        Some(_) => Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "unix sockets are not supported on this platform, use --tcp",
+        )),
+        None => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "either --socket or --tcp is required",
+        )),
+    }
 }
@@ -4,9 +4,13 @@ use std::fmt::Display;
 use crate::ast;
 use crate::ast::Identifier;
 use crate::ast::Operator;
+use crate::interpreter::inline_cache::MethodCache;
+use crate::interpreter::object::format_float;
+use crate::interpreter::pool::LiteralPool;
 use crate::lexer::Peekable;
 use crate::precedence;
 use crate::precedence::Precedence;
+use crate::token::interpolation_end;
 use crate::token::Token;
 use logos::Lexer;
 use logos::Logos;
@@ -17,6 +21,38 @@ pub struct ParseError {
     child: Option<Box<ParseError>>,
 }
 
+impl ParseError {
+    /// The error that caused this one, such as the error inside a `${...}` interpolation.
+    pub fn child(&self) -> Option<&ParseError> {
+        self.child.as_deref()
+    }
+}
+
+/// Byte range of the source that a parse function left unconsumed.
+pub type Span = std::ops::Range<usize>;
+
+/// Parses one expression from the start of `source`, for tools such as editors and the REPL
+/// that work with fragments rather than whole programs.
+///
+/// Returns the expression together with the span of the input after it, which may begin
+/// with whitespace, a `;` or whatever else the expression did not need. Fails when the
+/// source does not start with a complete expression; the error carries no position and
+/// nothing parsed before it is returned.
+pub fn parse_expression_public(source: &str) -> Result<(ast::Expression, Span), ParseError> {
+    let mut lexer = Peekable::new(source);
+    let expression = parse_expression(&mut lexer, Precedence::Lowest)?;
+    Ok((expression, lexer.consumed..source.len()))
+}
+
+/// Parses one statement from the start of `source`, with the same contract as
+/// [`parse_expression_public`]. The statement's `;` is consumed; an expression without one
+/// becomes a block level return, as it would at the end of a block.
+pub fn parse_statement_public(source: &str) -> Result<(ast::Statement, Span), ParseError> {
+    let mut lexer = Peekable::new(source);
+    let statement = parse_statement(&mut lexer)?;
+    Ok((statement, lexer.consumed..source.len()))
+}
+
 impl Error for ParseError {
     fn description(&self) -> &str {
         &self.message
@@ -31,7 +67,9 @@ impl Display for ParseError {
 
 pub fn parse(lexer: &mut Peekable<'_>) -> Result<ast::Program, ParseError> {
     let mut statements: Vec<ast::Statement> = vec![];
+    let mut lines = vec![];
     while lexer.peek().is_some() {
+        lines.push(lexer.line());
         let statement = match parse_statement(lexer) {
             Ok(statement) => statement,
             Err(error) => return Err(error),
@@ -40,10 +78,14 @@ pub fn parse(lexer: &mut Peekable<'_>) -> Result<ast::Program, ParseError> {
     }
     return Ok(ast::Program {
         statements: statements,
+        lines: ast::StatementLines(lines),
     });
 }
 
 pub fn parse_statement(lexer: &mut Peekable<'_>) -> Result<ast::Statement, ParseError> {
+    if lexer.peek() == Some(&Token::Identifier) && lexer.peek_second() == Some(Token::Colon) {
+        return parse_labeled_statement(lexer);
+    }
     let token = match lexer.peek() {
         Some(token) => token,
         _ => {
@@ -54,8 +96,25 @@ pub fn parse_statement(lexer: &mut Peekable<'_>) -> Result<ast::Statement, Parse
         }
     };
     match token {
-        Token::Let => match parse_variable_declaration(lexer) {
-            Ok(variable_declaration) => {
+        Token::Let => match parse_let_statement(lexer) {
+            Ok(statement) => {
+                match lexer.peek() {
+                    Some(Token::Semicolon) => {
+                        lexer.next();
+                    }
+                    _ => {
+                        return Err(ParseError {
+                            message: "expected semicolon".to_string(),
+                            child: None,
+                        })
+                    }
+                };
+                return Ok(statement);
+            }
+            Err(error) => return Err(error),
+        },
+        Token::Const => match parse_constant_declaration(lexer) {
+            Ok(constant_declaration) => {
                 match lexer.peek() {
                     Some(Token::Semicolon) => {
                         lexer.next();
@@ -67,7 +126,7 @@ pub fn parse_statement(lexer: &mut Peekable<'_>) -> Result<ast::Statement, Parse
                         })
                     }
                 };
-                return Ok(ast::Statement::VariableDeclaration(variable_declaration));
+                return Ok(ast::Statement::ConstantDeclaration(constant_declaration));
             }
             Err(error) => return Err(error),
         },
@@ -88,6 +147,43 @@ pub fn parse_statement(lexer: &mut Peekable<'_>) -> Result<ast::Statement, Parse
             }
             Err(error) => return Err(error),
         },
+        Token::Throw | Token::Defer | Token::Export | Token::Struct | Token::Assert => {
+            parse_keyword_statement(lexer)
+        }
+        Token::Break => match parse_break_statement(lexer) {
+            Ok(break_statement) => {
+                match lexer.peek() {
+                    Some(Token::Semicolon) => {
+                        lexer.next();
+                    }
+                    _ => {
+                        return Err(ParseError {
+                            message: "expected semicolon".to_string(),
+                            child: None,
+                        })
+                    }
+                };
+                return Ok(ast::Statement::BreakStatement(break_statement));
+            }
+            Err(error) => return Err(error),
+        },
+        Token::Continue => match parse_continue_statement(lexer) {
+            Ok(continue_statement) => {
+                match lexer.peek() {
+                    Some(Token::Semicolon) => {
+                        lexer.next();
+                    }
+                    _ => {
+                        return Err(ParseError {
+                            message: "expected semicolon".to_string(),
+                            child: None,
+                        })
+                    }
+                };
+                return Ok(ast::Statement::ContinueStatement(continue_statement));
+            }
+            Err(error) => return Err(error),
+        },
         Token::Watch => match parse_watch_declaration(lexer) {
             Ok(watch_statement) => {
                 match lexer.peek() {
@@ -105,7 +201,41 @@ pub fn parse_statement(lexer: &mut Peekable<'_>) -> Result<ast::Statement, Parse
             }
             Err(error) => return Err(error),
         },
+        Token::Import => match parse_import_statement(lexer) {
+            Ok(import_statement) => {
+                match lexer.peek() {
+                    Some(Token::Semicolon) => {
+                        lexer.next();
+                    }
+                    _ => {
+                        return Err(ParseError {
+                            message: "expected semicolon".to_string(),
+                            child: None,
+                        })
+                    }
+                };
+                Ok(ast::Statement::ImportStatement(import_statement))
+            }
+            Err(error) => Err(error),
+        },
+        Token::Fallthrough => Err(ParseError {
+            message: "fallthrough is only allowed at the end of a case".to_string(),
+            child: None,
+        }),
+        Token::Test => match parse_test_block(lexer) {
+            Ok(test_block) => {
+                // like other blocks, a test does not need a trailing semicolon
+                if lexer.peek() == Some(&Token::Semicolon) {
+                    lexer.next();
+                }
+                return Ok(ast::Statement::TestBlock(test_block));
+            }
+            Err(error) => return Err(error),
+        },
         _ => match parse_expression(lexer, Precedence::Lowest) {
+            Ok(expression) if lexer.peek() == Some(&Token::Comma) => {
+                parse_multiple_assign(lexer, expression)
+            }
             Ok(expression) => {
                 let peeked = lexer.peek().cloned();
                 if peeked.is_some() && peeked.as_ref().unwrap() == &Token::Semicolon {
@@ -122,9 +252,116 @@ pub fn parse_statement(lexer: &mut Peekable<'_>) -> Result<ast::Statement, Parse
     }
 }
 
-fn parse_variable_declaration(
+// `outer: for (...) { ... }`, a loop that `break outer;` and `continue outer;` can refer to
+// from the loops inside it.
+fn parse_labeled_statement(lexer: &mut Peekable<'_>) -> Result<ast::Statement, ParseError> {
+    lexer.next();
+    let label = lexer.current_slice.unwrap().to_string();
+    lexer.next();
+    if lexer.labels.contains(&label) {
+        return Err(ParseError {
+            message: format!("label {} is already in use", label),
+            child: None,
+        });
+    }
+    lexer.labels.push(label.clone());
+    let expression = match lexer.peek() {
+        Some(Token::For) => parse_for_expression(lexer).map(|mut for_expression| {
+            for_expression.label = Some(label);
+            ast::Expression::ForExpression(for_expression)
+        }),
+        Some(Token::Do) => parse_do_while_expression(lexer).map(|mut expression| {
+            if let ast::Expression::DoWhileExpression(do_while) = &mut expression {
+                do_while.label = Some(label);
+            }
+            expression
+        }),
+        Some(Token::Loop) => parse_loop_expression(lexer).map(|mut expression| {
+            if let ast::Expression::LoopExpression(loop_expression) = &mut expression {
+                loop_expression.label = Some(label);
+            }
+            expression
+        }),
+        _ => Err(ParseError {
+            message: "only loops can have labels".to_string(),
+            child: None,
+        }),
+    }?;
+    lexer.labels.pop();
+    if lexer.peek() == Some(&Token::Semicolon) {
+        lexer.next();
+        return Ok(ast::Statement::Expression(expression));
+    }
+    Ok(ast::Statement::BlockReturnStatement(
+        ast::BlockReturnStatement { value: expression },
+    ))
+}
+
+// `a, b = b, a;` after its first target, with as many values as targets.
+fn parse_multiple_assign(
     lexer: &mut Peekable<'_>,
-) -> Result<ast::VariableDeclaration, ParseError> {
+    first: ast::Expression,
+) -> Result<ast::Statement, ParseError> {
+    let position = ast::Position { line: lexer.line() };
+    let mut targets = vec![first];
+    while lexer.peek() == Some(&Token::Comma) {
+        lexer.next();
+        targets.push(parse_expression(lexer, Precedence::Assign)?);
+    }
+    for target in &targets {
+        match target {
+            ast::Expression::Identifier(_)
+            | ast::Expression::ElementAccessExpression(_)
+            | ast::Expression::MemberAccessExpression(_) => {}
+            _ => {
+                return Err(ParseError {
+                    message: "invalid assignment target".to_string(),
+                    child: None,
+                })
+            }
+        }
+    }
+    match lexer.next() {
+        Some(Token::Assign) => {}
+        _ => {
+            return Err(ParseError {
+                message: "expected = after the assignment targets".to_string(),
+                child: None,
+            })
+        }
+    };
+    let mut values = vec![parse_expression(lexer, Precedence::Lowest)?];
+    while lexer.peek() == Some(&Token::Comma) {
+        lexer.next();
+        values.push(parse_expression(lexer, Precedence::Lowest)?);
+    }
+    if values.len() != targets.len() {
+        return Err(ParseError {
+            message: format!(
+                "expected {} values to assign, got {}",
+                targets.len(),
+                values.len()
+            ),
+            child: None,
+        });
+    }
+    match lexer.next() {
+        Some(Token::Semicolon) => {}
+        _ => {
+            return Err(ParseError {
+                message: "expected semicolon".to_string(),
+                child: None,
+            })
+        }
+    };
+    Ok(ast::Statement::MultipleAssign(ast::MultipleAssign {
+        targets,
+        values,
+        position,
+    }))
+}
+
+fn parse_let_statement(lexer: &mut Peekable<'_>) -> Result<ast::Statement, ParseError> {
     match lexer.next() {
         Some(Token::Let) => {}
         _ => {
@@ -134,6 +371,31 @@ fn parse_variable_declaration(
             })
         }
     };
+    match lexer.peek() {
+        Some(Token::LBracket) => match parse_destructuring_declaration(lexer) {
+            Ok(declaration) => Ok(ast::Statement::DestructuringDeclaration(declaration)),
+            Err(error) => Err(error),
+        },
+        _ => {
+            let declaration = parse_variable_declaration_body(lexer)?;
+            if lexer.peek() != Some(&Token::Comma) {
+                return Ok(ast::Statement::VariableDeclaration(declaration));
+            }
+            let mut declarations = vec![declaration];
+            while lexer.peek() == Some(&Token::Comma) {
+                lexer.next();
+                declarations.push(parse_variable_declaration_body(lexer)?);
+            }
+            Ok(ast::Statement::DeclarationList(ast::DeclarationList {
+                declarations,
+            }))
+        }
+    }
+}
+
+fn parse_variable_declaration_body(
+    lexer: &mut Peekable<'_>,
+) -> Result<ast::VariableDeclaration, ParseError> {
     match lexer.next() {
         Some(Token::Identifier) => {}
         _ => {
@@ -144,6 +406,8 @@ fn parse_variable_declaration(
         }
     };
     let name = lexer.current_slice.unwrap().to_string();
+    let position = ast::Position { line: lexer.line() };
+    let type_annotation = parse_type_annotation(lexer)?;
     match lexer.next() {
         Some(Token::Assign) => {}
         Some(token) => {
@@ -169,145 +433,541 @@ fn parse_variable_declaration(
     return Ok({
         ast::VariableDeclaration {
             name: name,
+            type_annotation,
             value: value,
+            position,
         }
     });
 }
 
-pub fn parse_expression(
-    lexer: &mut Peekable,
-    precedence: Precedence,
-) -> Result<ast::Expression, ParseError> {
-    let next = lexer.peek();
-    let mut left = match next {
-        Some(Token::Number) => {
-            lexer.next();
-            ast::Expression::NumberLiteral(ast::NumberLiteral {
-                value: lexer.current_slice.unwrap().parse::<i32>().unwrap(),
+fn parse_destructuring_declaration(
+    lexer: &mut Peekable<'_>,
+) -> Result<ast::DestructuringDeclaration, ParseError> {
+    let pattern = match parse_array_pattern(lexer) {
+        Ok(pattern) => pattern,
+        Err(error) => return Err(error),
+    };
+    match lexer.next() {
+        Some(Token::Assign) => {}
+        _ => {
+            return Err(ParseError {
+                message: "expected assign after pattern".to_string(),
+                child: None,
             })
         }
-        Some(Token::Identifier) => {
+    };
+    let value = match parse_expression(lexer, Precedence::Lowest) {
+        Ok(expression) => expression,
+        Err(error) => return Err(error),
+    };
+    let alternative = match lexer.peek() {
+        Some(Token::Else) => {
             lexer.next();
-            ast::Expression::Identifier(ast::Identifier {
-                value: lexer.current_slice.unwrap().to_string(),
-            })
+            match parse_block_statement(lexer) {
+                Ok(block) => Some(block),
+                Err(error) => return Err(error),
+            }
         }
-        Some(Token::Function) => match parse_function_expression(lexer) {
-            Ok(function_declaration) => ast::Expression::FunctionLiteral(function_declaration),
-            Err(error) => return Err(error),
-        },
-        Some(Token::If) => match parse_if_expression(lexer) {
-            Ok(if_expression) => ast::Expression::IfExpression(Box::new(if_expression)),
-            Err(error) => return Err(error),
-        },
-        Some(Token::True) => {
-            lexer.next();
-            ast::Expression::BooleanLiteral(ast::BooleanLiteral { value: true })
+        _ => None,
+    };
+    return Ok(ast::DestructuringDeclaration {
+        pattern,
+        value,
+        alternative,
+    });
+}
+
+fn parse_array_pattern(lexer: &mut Peekable<'_>) -> Result<ast::ArrayPattern, ParseError> {
+    enter_nesting(lexer)?;
+    let pattern = parse_nested_array_pattern(lexer);
+    lexer.depth -= 1;
+    pattern
+}
+
+fn parse_nested_array_pattern(lexer: &mut Peekable<'_>) -> Result<ast::ArrayPattern, ParseError> {
+    match lexer.next() {
+        Some(Token::LBracket) => {}
+        _ => {
+            return Err(ParseError {
+                message: "expected [".to_string(),
+                child: None,
+            })
         }
-        Some(Token::False) => {
-            lexer.next();
-            ast::Expression::BooleanLiteral(ast::BooleanLiteral { value: false })
+    };
+    let mut elements = vec![];
+    if lexer.peek() == Some(&Token::RBracket) {
+        lexer.next();
+        return Ok(ast::ArrayPattern {
+            elements,
+            rest: None,
+        });
+    }
+    let mut rest = None;
+    loop {
+        if lexer.peek() == Some(&Token::Ellipsis) {
+            rest = Some(parse_rest_pattern(lexer)?);
+            break;
         }
-        Some(Token::String) => {
-            lexer.next();
-            let value = lexer.current_slice.unwrap().to_string();
-            //  unwrap double quotes
-            let value = value[1..value.len() - 1].to_string();
-            ast::Expression::StringLiteral(ast::StringLiteral { value: value })
+        elements.push(parse_pattern_element(lexer)?);
+        match lexer.next() {
+            Some(Token::Comma) if lexer.peek() == Some(&Token::RBracket) => {
+                lexer.next();
+                break;
+            }
+            Some(Token::Comma) => {}
+            Some(Token::RBracket) => break,
+            _ => {
+                return Err(ParseError {
+                    message: "expected , or ] in pattern".to_string(),
+                    child: None,
+                })
+            }
         }
-        Some(Token::LBracket) => match parse_array_literal(lexer) {
-            Ok(array_literal) => ast::Expression::ArrayLiteral(array_literal),
-            Err(error) => return Err(error),
-        },
-        Some(Token::LParen) => {
+    }
+    // keyed elements are looked up by key, which leaves no position for the others
+    let keyed = elements
+        .iter()
+        .filter(|element| matches!(element, ast::Pattern::Keyed(_)))
+        .count();
+    if keyed > 0 && (keyed < elements.len() || rest.is_some()) {
+        return Err(ParseError {
+            message: "a pattern with keys cannot have positional elements".to_string(),
+            child: None,
+        });
+    }
+    return Ok(ast::ArrayPattern { elements, rest });
+}
+
+// An identifier, a literal, a nested `[...]`, or `key: pattern` for the value of a key.
+fn parse_pattern_element(lexer: &mut Peekable<'_>) -> Result<ast::Pattern, ParseError> {
+    let element = match lexer.peek() {
+        Some(Token::LBracket) => ast::Pattern::Array(parse_array_pattern(lexer)?),
+        Some(Token::Identifier) => {
             lexer.next();
-            let expression = match parse_expression(lexer, Precedence::Lowest) {
-                Ok(expression) => expression,
-                Err(error) => return Err(error),
-            };
-            match lexer.next() {
-                Some(Token::RParen) => {}
-                _ => {
-                    return Err(ParseError {
-                        message: "expected )".to_string(),
-                        child: None,
-                    })
-                }
-            };
-            expression
+            let name = lexer.current_slice.unwrap().to_string();
+            if name == "_" {
+                ast::Pattern::Wildcard
+            } else if lexer.peek() == Some(&Token::Colon) {
+                lexer.next();
+                ast::Pattern::Keyed(Box::new(ast::KeyedPattern {
+                    key: name,
+                    value: parse_pattern_element(lexer)?,
+                }))
+            } else {
+                ast::Pattern::Identifier(ast::Identifier { value: name })
+            }
         }
-        Some(Token::For) => match parse_for_expression(lexer) {
-            Ok(for_expression) => ast::Expression::ForExpression(Box::new(for_expression)),
-            Err(error) => return Err(error),
-        },
-        Some(Token::Switch) => match parse_switch_expression(lexer) {
-            Ok(switch_expression) => ast::Expression::SwitchExpression(Box::new(switch_expression)),
-            Err(error) => return Err(error),
-        },
-        Some(Token::LBrace) => match parse_block_statement(lexer) {
-            Ok(block_statement) => ast::Expression::BlockExpression(block_statement),
-            Err(error) => return Err(error),
-        },
+        Some(Token::Number)
+        | Some(Token::Float)
+        | Some(Token::String)
+        | Some(Token::MultilineString)
+        | Some(Token::RawString)
+        | Some(Token::True)
+        | Some(Token::False)
+        | Some(Token::Null)
+        | Some(Token::Minus) => ast::Pattern::Literal(parse_expression(lexer, Precedence::Lowest)?),
         _ => {
-            print!("unexpected token: {:?}", lexer.peek());
             return Err(ParseError {
-                message: "unexpected token".to_string(),
+                message: "expected identifier, literal or [ in pattern".to_string(),
                 child: None,
-            });
+            })
         }
     };
-    let mut peeked = lexer.peek().cloned();
-
-    while peeked.is_some()
-        && peeked.as_ref().unwrap() != &Token::Semicolon
-        && precedence.is_lower_than(&Precedence::get_precedence(peeked.as_ref().unwrap()))
-    {
-        let expression = match peeked.as_ref().unwrap() {
-            Token::LParen => match parse_call_expression(lexer, left) {
-                Ok(call_expression) => ast::Expression::CallExpression(Box::new(call_expression)),
-                Err(error) => return Err(error),
-            },
-            Token::LBracket => match parse_element_access_expression(lexer, left) {
-                Ok(element_access_expression) => {
-                    ast::Expression::ElementAccessExpression(Box::new(element_access_expression))
-                }
-                Err(error) => return Err(error),
-            },
-            Token::Assign => match parse_assign(lexer, left) {
-                Ok(assign) => ast::Expression::Assign(Box::new(assign)),
-                Err(error) => return Err(error),
-            },
-            _ => match parse_infix_expression(lexer, left) {
-                Ok(infix_expression) => {
-                    ast::Expression::InfixExpression(Box::new(infix_expression))
-                }
-                Err(error) => return Err(error),
-            },
-        };
-        left = expression;
-        peeked = lexer.peek().cloned();
-    }
-
-    Ok(left)
+    Ok(element)
 }
 
-fn parse_infix_expression(
-    lexer: &mut Peekable,
-    left: ast::Expression,
-) -> Result<ast::InfixExpression, ParseError> {
-    let token = match lexer.next() {
-        Some(token) => token,
+// `...name]`, which ends the pattern, though a trailing comma may come before the `]`.
+fn parse_rest_pattern(lexer: &mut Peekable<'_>) -> Result<ast::Identifier, ParseError> {
+    lexer.next();
+    match lexer.next() {
+        Some(Token::Identifier) => {}
         _ => {
             return Err(ParseError {
-                message: "unexpected end of file".to_string(),
+                message: "expected identifier after ...".to_string(),
                 child: None,
             })
         }
     };
-    let precedence = Precedence::get_precedence(&token);
-    let right = match parse_expression(lexer, precedence) {
-        Ok(expression) => expression,
-        Err(error) => return Err(error),
+    let rest = ast::Identifier {
+        value: lexer.current_slice.unwrap().to_string(),
+    };
+    if lexer.peek() == Some(&Token::Comma) {
+        lexer.next();
+    }
+    match lexer.next() {
+        Some(Token::RBracket) => Ok(rest),
+        _ => Err(ParseError {
+            message: "the rest pattern must be last".to_string(),
+            child: None,
+        }),
+    }
+}
+
+fn parse_constant_declaration(
+    lexer: &mut Peekable<'_>,
+) -> Result<ast::ConstantDeclaration, ParseError> {
+    match lexer.next() {
+        Some(Token::Const) => {}
+        _ => {
+            return Err(ParseError {
+                message: "expected const".to_string(),
+                child: None,
+            })
+        }
+    };
+    match lexer.next() {
+        Some(Token::Identifier) => {}
+        _ => {
+            return Err(ParseError {
+                message: "expected identifier".to_string(),
+                child: None,
+            })
+        }
+    };
+    let name = lexer.current_slice.unwrap().to_string();
+    let position = ast::Position { line: lexer.line() };
+    let type_annotation = parse_type_annotation(lexer)?;
+    match lexer.next() {
+        Some(Token::Assign) => {}
+        _ => {
+            return Err(ParseError {
+                message: "expected assign after ".to_string() + &name,
+                child: None,
+            })
+        }
+    };
+    let value = match parse_expression(lexer, Precedence::Lowest) {
+        Ok(expression) => expression,
+        Err(error) => return Err(error),
+    };
+    return Ok(ast::ConstantDeclaration {
+        name,
+        type_annotation,
+        value,
+        position,
+    });
+}
+
+// `: name`, the type annotated after a declared name or a parameter list, if there is one.
+fn parse_type_annotation(lexer: &mut Peekable) -> Result<Option<String>, ParseError> {
+    if lexer.peek() != Some(&Token::Colon) {
+        return Ok(None);
+    }
+    lexer.next();
+    match lexer.next() {
+        Some(Token::Identifier) => Ok(Some(lexer.current_slice.unwrap().to_string())),
+        _ => Err(ParseError {
+            message: "expected a type name after :".to_string(),
+            child: None,
+        }),
+    }
+}
+
+/// Parses the expression at the lexer's position, stopping before the first operator that
+/// binds no tighter than `precedence`. Pass `Precedence::Lowest` for a whole expression.
+pub fn parse_expression(
+    lexer: &mut Peekable,
+    precedence: Precedence,
+) -> Result<ast::Expression, ParseError> {
+    enter_nesting(lexer)?;
+    let expression = parse_nested_expression(lexer, precedence);
+    lexer.depth -= 1;
+    expression
+}
+
+// Every construct that can contain itself is parsed through `parse_expression` or
+// `parse_array_pattern`, so counting there bounds the recursion of the whole parser.
+fn enter_nesting(lexer: &mut Peekable) -> Result<(), ParseError> {
+    if lexer.depth >= lexer.max_depth {
+        lexer.peek();
+        return Err(ParseError {
+            message: format!("expression too deeply nested at line {}", lexer.line()),
+            child: None,
+        });
+    }
+    lexer.depth += 1;
+    Ok(())
+}
+
+fn parse_nested_expression(
+    lexer: &mut Peekable,
+    precedence: Precedence,
+) -> Result<ast::Expression, ParseError> {
+    let next = lexer.peek();
+    let mut left = match next {
+        Some(Token::Number) | Some(Token::Float) => parse_number_literal(lexer)?,
+        Some(Token::Identifier) => {
+            lexer.next();
+            match lexer.current_slice.unwrap() {
+                "__line" => ast::Expression::LineLiteral(ast::Position { line: lexer.line() }),
+                "__file" => ast::Expression::FileLiteral,
+                name => ast::Expression::Identifier(ast::Identifier {
+                    value: name.to_string(),
+                }),
+            }
+        }
+        Some(Token::Minus) | Some(Token::Bang) => match parse_prefix_expression(lexer) {
+            Ok(prefix_expression) => ast::Expression::PrefixExpression(Box::new(prefix_expression)),
+            Err(error) => return Err(error),
+        },
+        Some(Token::Increment) | Some(Token::Decrement) => {
+            let operator = get_update_operator(&lexer.next().unwrap());
+            let target = parse_expression(lexer, Precedence::Prefix)?;
+            match parse_update_expression(operator, true, target, lexer.line()) {
+                Ok(update_expression) => {
+                    ast::Expression::UpdateExpression(Box::new(update_expression))
+                }
+                Err(error) => return Err(error),
+            }
+        }
+        Some(Token::Function) => match parse_function_expression(lexer) {
+            Ok(function_declaration) => ast::Expression::FunctionLiteral(function_declaration),
+            Err(error) => return Err(error),
+        },
+        Some(Token::If) => match parse_if_expression(lexer) {
+            Ok(if_expression) => ast::Expression::IfExpression(Box::new(if_expression)),
+            Err(error) => return Err(error),
+        },
+        Some(Token::True) => {
+            lexer.next();
+            ast::Expression::BooleanLiteral(ast::BooleanLiteral { value: true })
+        }
+        Some(Token::False) => {
+            lexer.next();
+            ast::Expression::BooleanLiteral(ast::BooleanLiteral { value: false })
+        }
+        Some(Token::Null) => {
+            lexer.next();
+            ast::Expression::NullLiteral
+        }
+        Some(Token::String) | Some(Token::MultilineString) | Some(Token::RawString) => {
+            let token = lexer.next().unwrap();
+            let max_depth = lexer.max_depth - lexer.depth;
+            let parts = match parse_string_token(
+                &token,
+                lexer.current_slice.unwrap(),
+                max_depth,
+                lexer.line(),
+            ) {
+                Ok(parts) => parts,
+                Err(error) => return Err(error),
+            };
+            match parts.as_slice() {
+                [ast::TemplatePart::String(value)] => {
+                    ast::Expression::StringLiteral(ast::StringLiteral {
+                        value: value.clone(),
+                    })
+                }
+                _ => ast::Expression::TemplateLiteral(ast::TemplateLiteral { parts }),
+            }
+        }
+        Some(Token::LBracket) => parse_array_literal(lexer)?,
+        Some(Token::LParen) => parse_parenthesized_expression(lexer)?,
+        Some(Token::For) => match parse_for_expression(lexer) {
+            Ok(for_expression) => ast::Expression::ForExpression(for_expression),
+            Err(error) => return Err(error),
+        },
+        Some(Token::Switch) => match parse_switch_expression(lexer) {
+            Ok(switch_expression) => ast::Expression::SwitchExpression(Box::new(switch_expression)),
+            Err(error) => return Err(error),
+        },
+        Some(Token::Do | Token::Loop | Token::Match) => parse_keyword_expression(lexer)?,
+        Some(Token::Try) => match parse_try_expression(lexer) {
+            Ok(try_expression) => ast::Expression::TryExpression(Box::new(try_expression)),
+            Err(error) => return Err(error),
+        },
+        Some(Token::LBrace) => match parse_block_statement(lexer) {
+            Ok(block_statement) => ast::Expression::BlockExpression(block_statement),
+            Err(error) => return Err(error),
+        },
+        _ => {
+            print!("unexpected token: {:?}", lexer.peek());
+            return Err(ParseError {
+                message: "unexpected token".to_string(),
+                child: None,
+            });
+        }
+    };
+    let mut peeked = lexer.peek().cloned();
+
+    while peeked.is_some()
+        && peeked.as_ref().unwrap() != &Token::Semicolon
+        && precedence.is_lower_than(&Precedence::get_precedence(peeked.as_ref().unwrap()))
+    {
+        let token = match peeked.unwrap() {
+            Token::QuestionBracket if is_bracket_conditional(lexer) => Token::Question,
+            token => token,
+        };
+        if !precedence.is_lower_than(&Precedence::get_precedence(&token)) {
+            break;
+        }
+        left = parse_operator_expression(lexer, left, &token)?;
+        peeked = lexer.peek().cloned();
+    }
+
+    Ok(left)
+}
+
+// Whether the peeked `?[` starts a conditional, as in `c ?[a] : [b]`, rather than an optional
+// element access. It does when a `:` follows the bracketed branch and the construct around it,
+// like a case or a map key, does not take that `:` itself.
+fn is_bracket_conditional(lexer: &mut Peekable) -> bool {
+    if lexer.colon_taken_at == Some(lexer.brackets) {
+        return false;
+    }
+    let mut lookahead = lexer.clone();
+    lookahead.next();
+    lookahead.unread_bracket();
+    parse_taking_colon(&mut lookahead, |lookahead| {
+        parse_expression(lookahead, Precedence::Lowest)
+    })
+    .is_ok()
+        && lookahead.peek() == Some(&Token::Colon)
+}
+
+// Parses what comes before a `:` that belongs to the construct being parsed, so a `?[` right
+// before that `:` is read as an optional element access.
+fn parse_taking_colon<T>(
+    lexer: &mut Peekable,
+    parse: impl FnOnce(&mut Peekable) -> Result<T, ParseError>,
+) -> Result<T, ParseError> {
+    let outer = lexer.colon_taken_at.replace(lexer.brackets);
+    let result = parse(lexer);
+    lexer.colon_taken_at = outer;
+    result
+}
+
+// The expression an operator token after `left` builds with it. Kept out of
+// `parse_nested_expression` so the frames of that recursion stay small.
+fn parse_operator_expression(
+    lexer: &mut Peekable,
+    left: ast::Expression,
+    token: &Token,
+) -> Result<ast::Expression, ParseError> {
+    let expression = match token {
+        Token::LParen => match parse_call_expression(lexer, left) {
+            Ok(call_expression) => ast::Expression::CallExpression(Box::new(call_expression)),
+            Err(error) => return Err(error),
+        },
+        Token::LBracket | Token::QuestionBracket => parse_element_access_expression(lexer, left)?,
+        Token::Dot | Token::QuestionDot => parse_member_access_expression(lexer, left)?,
+        Token::Assign => match parse_assign(lexer, left) {
+            Ok(assign) => ast::Expression::Assign(Box::new(assign)),
+            Err(error) => return Err(error),
+        },
+        Token::Pipe => {
+            ast::Expression::CallExpression(Box::new(parse_pipe_expression(lexer, left)?))
+        }
+        Token::Question => match parse_conditional_expression(lexer, left) {
+            Ok(if_expression) => ast::Expression::IfExpression(Box::new(if_expression)),
+            Err(error) => return Err(error),
+        },
+        Token::Increment | Token::Decrement => {
+            let operator = get_update_operator(&lexer.next().unwrap());
+            match parse_update_expression(operator, false, left, lexer.line()) {
+                Ok(update_expression) => {
+                    ast::Expression::UpdateExpression(Box::new(update_expression))
+                }
+                Err(error) => return Err(error),
+            }
+        }
+        _ => match parse_infix_expression(lexer, left) {
+            Ok(infix_expression) => ast::Expression::InfixExpression(Box::new(infix_expression)),
+            Err(error) => return Err(error),
+        },
+    };
+    Ok(expression)
+}
+
+// Kept out of `parse_nested_expression`, like the other helpers, to keep its frames small.
+fn parse_number_literal(lexer: &mut Peekable) -> Result<ast::Expression, ParseError> {
+    let token = lexer.next();
+    let literal = lexer.current_slice.unwrap().replace('_', "");
+    if token == Some(Token::Number) {
+        // the lexer only matches digits, so the value not fitting is the only way to fail
+        return match literal.parse::<i64>() {
+            Ok(value) => Ok(ast::Expression::NumberLiteral(ast::NumberLiteral { value })),
+            Err(_) => Err(ParseError {
+                message: "number literal out of range".to_string(),
+                child: None,
+            }),
+        };
+    }
+    let value = literal.parse::<f64>().unwrap();
+    // an exponent can take a literal past the largest float
+    if !value.is_finite() {
+        return Err(ParseError {
+            message: "float literal out of range".to_string(),
+            child: None,
+        });
+    }
+    Ok(ast::Expression::FloatLiteral(ast::FloatLiteral { value }))
+}
+
+fn parse_prefix_expression(lexer: &mut Peekable) -> Result<ast::PrefixExpression, ParseError> {
+    let operator = match lexer.next() {
+        Some(Token::Minus) => ast::Operator::Minus,
+        Some(Token::Bang) => ast::Operator::Bang,
+        _ => {
+            return Err(ParseError {
+                message: "expected - or !".to_string(),
+                child: None,
+            })
+        }
+    };
+    let right = match parse_expression(lexer, Precedence::Prefix) {
+        Ok(expression) => expression,
+        Err(error) => return Err(error),
+    };
+    return Ok(ast::PrefixExpression { operator, right });
+}
+
+fn get_update_operator(token: &Token) -> ast::UpdateOperator {
+    match token {
+        Token::Increment => ast::UpdateOperator::Increment,
+        _ => ast::UpdateOperator::Decrement,
+    }
+}
+
+fn parse_update_expression(
+    operator: ast::UpdateOperator,
+    prefix: bool,
+    target: ast::Expression,
+    line: usize,
+) -> Result<ast::UpdateExpression, ParseError> {
+    match target {
+        ast::Expression::Identifier(_)
+        | ast::Expression::ElementAccessExpression(_)
+        | ast::Expression::MemberAccessExpression(_) => Ok(ast::UpdateExpression {
+            operator,
+            prefix,
+            target,
+            position: ast::Position { line },
+        }),
+        _ => Err(ParseError {
+            message: format!("{} needs a variable or an element to update", operator),
+            child: None,
+        }),
+    }
+}
+
+fn parse_infix_expression(
+    lexer: &mut Peekable,
+    left: ast::Expression,
+) -> Result<ast::InfixExpression, ParseError> {
+    let token = match lexer.next() {
+        Some(token) => token,
+        _ => {
+            return Err(ParseError {
+                message: "unexpected end of file".to_string(),
+                child: None,
+            })
+        }
+    };
+    let precedence = Precedence::get_precedence(&token);
+    let right = match parse_expression(lexer, precedence) {
+        Ok(expression) => expression,
+        Err(error) => return Err(error),
     };
     return Ok(ast::InfixExpression {
         left: left,
@@ -316,8 +976,67 @@ fn parse_infix_expression(
     });
 }
 
+// `condition ? consequence : alternative`, read as an `if` whose blocks hold just the
+// two expressions. The alternative takes everything to its right, so a chain of them
+// groups as `a ? b : (c ? d : e)`.
+fn parse_conditional_expression(
+    lexer: &mut Peekable,
+    condition: ast::Expression,
+) -> Result<ast::IfExpression, ParseError> {
+    // the `[` of `?[` starts the first branch
+    if lexer.next() == Some(Token::QuestionBracket) {
+        lexer.unread_bracket();
+    }
+    let consequence =
+        parse_taking_colon(lexer, |lexer| parse_expression(lexer, Precedence::Lowest))?;
+    match lexer.next() {
+        Some(Token::Colon) => {}
+        _ => {
+            return Err(ParseError {
+                message: "expected : in conditional expression".to_string(),
+                child: None,
+            })
+        }
+    };
+    let alternative = parse_expression(lexer, Precedence::Lowest)?;
+    let block = |value| ast::BlockExpression {
+        statements: vec![ast::Statement::BlockReturnStatement(
+            ast::BlockReturnStatement { value },
+        )],
+        lines: ast::StatementLines::default(),
+    };
+    Ok(ast::IfExpression {
+        condition,
+        consequence: block(consequence),
+        alternative: Some(block(alternative)),
+    })
+}
+
+// `value |> f` is read as `f(value)` and `value |> f(a, b)` as `f(value, a, b)`, so a chain
+// of them calls its functions from left to right.
+fn parse_pipe_expression(
+    lexer: &mut Peekable,
+    value: ast::Expression,
+) -> Result<ast::CallExpression, ParseError> {
+    let position = ast::Position { line: lexer.line() };
+    lexer.next();
+    match parse_expression(lexer, Precedence::Pipe)? {
+        ast::Expression::CallExpression(mut call) => {
+            call.arguments.insert(0, value);
+            Ok(*call)
+        }
+        function => Ok(ast::CallExpression {
+            left: function,
+            arguments: vec![value],
+            position,
+            cache: MethodCache::default(),
+        }),
+    }
+}
+
 fn parse_assign(lexer: &mut Peekable, left: ast::Expression) -> Result<ast::Assign, ParseError> {
     lexer.next();
+    let position = ast::Position { line: lexer.line() };
     let right = match parse_expression(lexer, Precedence::Lowest) {
         Ok(expression) => expression,
         Err(error) => return Err(error),
@@ -325,10 +1044,13 @@ fn parse_assign(lexer: &mut Peekable, left: ast::Expression) -> Result<ast::Assi
     return Ok(ast::Assign {
         left: left,
         right: right,
+        position,
     });
 }
 
-fn parse_function_expression(lexer: &mut Peekable) -> Result<ast::FunctionLiteral, ParseError> {
+fn parse_function_expression(
+    lexer: &mut Peekable,
+) -> Result<Box<ast::FunctionLiteral>, ParseError> {
     match lexer.next() {
         Some(Token::Function) => {}
         _ => {
@@ -338,6 +1060,11 @@ fn parse_function_expression(lexer: &mut Peekable) -> Result<ast::FunctionLitera
             })
         }
     };
+    parse_function_definition(lexer).map(Box::new)
+}
+
+// The parameters and body of a function, from the `(` on.
+fn parse_function_definition(lexer: &mut Peekable) -> Result<ast::FunctionLiteral, ParseError> {
     match lexer.next() {
         Some(Token::LParen) => {}
         _ => {
@@ -348,8 +1075,14 @@ fn parse_function_expression(lexer: &mut Peekable) -> Result<ast::FunctionLitera
         }
     };
     let mut parameters: Vec<ast::Identifier> = vec![];
+    let mut parameter_types = vec![];
+    let mut rest = None;
     let mut peeked = lexer.peek().cloned();
     while peeked.is_some() && peeked.as_ref().unwrap() != &Token::RParen {
+        if peeked == Some(Token::Ellipsis) {
+            rest = Some(parse_rest_parameter(lexer)?);
+            break;
+        }
         match lexer.next() {
             Some(Token::Identifier) => {}
             _ => {
@@ -362,6 +1095,7 @@ fn parse_function_expression(lexer: &mut Peekable) -> Result<ast::FunctionLitera
         parameters.push(ast::Identifier {
             value: lexer.current_slice.unwrap().to_string(),
         });
+        parameter_types.push(parse_type_annotation(lexer)?);
         peeked = lexer.peek().cloned();
         if peeked.is_some() && peeked.as_ref().unwrap() == &Token::Comma {
             lexer.next();
@@ -377,6 +1111,7 @@ fn parse_function_expression(lexer: &mut Peekable) -> Result<ast::FunctionLitera
             })
         }
     };
+    let return_type = parse_type_annotation(lexer)?;
     match lexer.next() {
         Some(Token::LBrace) => {}
         _ => {
@@ -387,8 +1122,12 @@ fn parse_function_expression(lexer: &mut Peekable) -> Result<ast::FunctionLitera
         }
     };
     let mut statements: Vec<ast::Statement> = vec![];
+    let mut lines = vec![];
+    // a function cannot break out of the loops around it
+    let labels = std::mem::take(&mut lexer.labels);
     peeked = lexer.peek().cloned();
     while peeked.is_some() && peeked.as_ref().unwrap() != &Token::RBrace {
+        lines.push(lexer.line());
         let statement = match parse_statement(lexer) {
             Ok(statement) => statement,
             Err(error) => return Err(error),
@@ -396,6 +1135,7 @@ fn parse_function_expression(lexer: &mut Peekable) -> Result<ast::FunctionLitera
         statements.push(statement);
         peeked = lexer.peek().cloned();
     }
+    lexer.labels = labels;
     match lexer.next() {
         Some(Token::RBrace) => {}
         _ => {
@@ -407,38 +1147,60 @@ fn parse_function_expression(lexer: &mut Peekable) -> Result<ast::FunctionLitera
     };
     return Ok(ast::FunctionLiteral {
         parameters: parameters,
+        parameter_types,
+        return_type,
+        rest,
         body: ast::BlockExpression {
             statements: statements,
+            lines: ast::StatementLines(lines),
         },
     });
 }
 
-fn parse_call_expression(
-    lexer: &mut Peekable,
-    left: ast::Expression,
-) -> Result<ast::CallExpression, ParseError> {
+// `...name`, which has to be the last parameter, though a trailing comma may follow it.
+fn parse_rest_parameter(lexer: &mut Peekable) -> Result<ast::Identifier, ParseError> {
+    lexer.next();
     match lexer.next() {
-        Some(Token::LParen) => {}
+        Some(Token::Identifier) => {}
         _ => {
             return Err(ParseError {
-                message: "expected (".to_string(),
+                message: "expected identifier after ...".to_string(),
                 child: None,
             })
         }
     };
-    let mut arguments: Vec<ast::Expression> = vec![];
-    let mut peeked = lexer.peek().cloned();
-    while peeked.is_some() && peeked.as_ref().unwrap() != &Token::RParen {
-        let expression = match parse_expression(lexer, Precedence::Lowest) {
-            Ok(expression) => expression,
-            Err(error) => return Err(error),
-        };
-        arguments.push(expression);
-        peeked = lexer.peek().cloned();
-        if peeked.is_some() && peeked.as_ref().unwrap() == &Token::Comma {
+    let rest = ast::Identifier {
+        value: lexer.current_slice.unwrap().to_string(),
+    };
+    if lexer.peek() == Some(&Token::Comma) {
+        lexer.next();
+    }
+    if lexer.peek() != Some(&Token::RParen) {
+        return Err(ParseError {
+            message: "the rest parameter must be last".to_string(),
+            child: None,
+        });
+    }
+    Ok(rest)
+}
+
+// `(expression)`, or an arrow function `(a, b) => a + b` whose body is an expression or a
+// block. Arrow functions become the same `FunctionLiteral` as `fn(a, b) { a + b }`.
+fn parse_parenthesized_expression(lexer: &mut Peekable) -> Result<ast::Expression, ParseError> {
+    lexer.next();
+    let mut expressions = Vec::new();
+    let mut rest = None;
+    while lexer.peek().is_some() && lexer.peek() != Some(&Token::RParen) {
+        if lexer.peek() == Some(&Token::Ellipsis) {
+            rest = Some(parse_rest_parameter(lexer)?);
+            break;
+        }
+        expressions.push(parse_expression(lexer, Precedence::Lowest)?);
+        if lexer.peek() == Some(&Token::Comma) {
             lexer.next();
+        } else {
+            break;
         }
-        peeked = lexer.peek().cloned();
     }
     match lexer.next() {
         Some(Token::RParen) => {}
@@ -449,13 +1211,108 @@ fn parse_call_expression(
             })
         }
     };
-    return Ok(ast::CallExpression { left, arguments });
-}
-
-fn parse_return_statement(lexer: &mut Peekable) -> Result<ast::ReturnStatement, ParseError> {
-    match lexer.next() {
-        Some(Token::Return) => {}
-        _ => {
+    if lexer.peek() != Some(&Token::FatArrow) {
+        return match expressions.len() {
+            1 if rest.is_none() => Ok(expressions.pop().unwrap()),
+            0 if rest.is_none() => Err(ParseError {
+                message: "expected expression in ()".to_string(),
+                child: None,
+            }),
+            _ => Err(ParseError {
+                message: "expected => after the parameters".to_string(),
+                child: None,
+            }),
+        };
+    }
+    lexer.next();
+    let mut parameters = Vec::new();
+    for expression in expressions {
+        match expression {
+            ast::Expression::Identifier(identifier) => parameters.push(identifier),
+            _ => {
+                return Err(ParseError {
+                    message: "arrow function parameters must be identifiers".to_string(),
+                    child: None,
+                })
+            }
+        }
+    }
+    let labels = std::mem::take(&mut lexer.labels);
+    let body = match lexer.peek() {
+        Some(Token::LBrace) => parse_block_statement(lexer)?,
+        _ => {
+            let line = lexer.line();
+            ast::BlockExpression {
+                statements: vec![ast::Statement::BlockReturnStatement(
+                    ast::BlockReturnStatement {
+                        value: parse_expression(lexer, Precedence::Lowest)?,
+                    },
+                )],
+                lines: ast::StatementLines(vec![line]),
+            }
+        }
+    };
+    lexer.labels = labels;
+    Ok(ast::Expression::FunctionLiteral(Box::new(
+        ast::FunctionLiteral {
+            parameter_types: vec![None; parameters.len()],
+            parameters,
+            return_type: None,
+            rest,
+            body,
+        },
+    )))
+}
+
+fn parse_call_expression(
+    lexer: &mut Peekable,
+    left: ast::Expression,
+) -> Result<ast::CallExpression, ParseError> {
+    let position = ast::Position { line: lexer.line() };
+    match lexer.next() {
+        Some(Token::LParen) => {}
+        _ => {
+            return Err(ParseError {
+                message: "expected (".to_string(),
+                child: None,
+            })
+        }
+    };
+    let mut arguments: Vec<ast::Expression> = vec![];
+    let mut peeked = lexer.peek().cloned();
+    while peeked.is_some() && peeked.as_ref().unwrap() != &Token::RParen {
+        let expression = match parse_expression(lexer, Precedence::Lowest) {
+            Ok(expression) => expression,
+            Err(error) => return Err(error),
+        };
+        arguments.push(expression);
+        peeked = lexer.peek().cloned();
+        if peeked.is_some() && peeked.as_ref().unwrap() == &Token::Comma {
+            lexer.next();
+        }
+        peeked = lexer.peek().cloned();
+    }
+    match lexer.next() {
+        Some(Token::RParen) => {}
+        _ => {
+            return Err(ParseError {
+                message: "expected )".to_string(),
+                child: None,
+            })
+        }
+    };
+    return Ok(ast::CallExpression {
+        left,
+        arguments,
+        position,
+        cache: MethodCache::default(),
+    });
+}
+
+fn parse_return_statement(lexer: &mut Peekable) -> Result<ast::ReturnStatement, ParseError> {
+    match lexer.next() {
+        Some(Token::Return) => {}
+        _ => {
             return Err(ParseError {
                 message: "expected return".to_string(),
                 child: None,
@@ -469,6 +1326,155 @@ fn parse_return_statement(lexer: &mut Peekable) -> Result<ast::ReturnStatement,
     return Ok(ast::ReturnStatement { value: expression });
 }
 
+// Kept out of `parse_statement`, as every arm there grows the stack frame of each level of
+// nested blocks.
+fn parse_keyword_statement(lexer: &mut Peekable) -> Result<ast::Statement, ParseError> {
+    let statement = match lexer.peek() {
+        Some(Token::Throw) => ast::Statement::ThrowStatement(parse_throw_statement(lexer)?),
+        Some(Token::Defer) => ast::Statement::DeferStatement(parse_defer_statement(lexer)?),
+        Some(Token::Export) => ast::Statement::ExportStatement(parse_export_statement(lexer)?),
+        Some(Token::Struct) => {
+            let declaration = parse_struct_declaration(lexer)?;
+            // like other blocks, a struct does not need a trailing semicolon
+            if lexer.peek() == Some(&Token::Semicolon) {
+                lexer.next();
+            }
+            return Ok(ast::Statement::StructDeclaration(declaration));
+        }
+        _ => ast::Statement::AssertStatement(parse_assert_statement(lexer)?),
+    };
+    match lexer.peek() {
+        Some(Token::Semicolon) => {
+            lexer.next();
+        }
+        _ => {
+            return Err(ParseError {
+                message: "expected semicolon".to_string(),
+                child: None,
+            })
+        }
+    };
+    Ok(statement)
+}
+
+fn parse_export_statement(lexer: &mut Peekable) -> Result<ast::ExportStatement, ParseError> {
+    lexer.next();
+    if lexer.depth > 0 {
+        return Err(ParseError {
+            message: "export is only allowed at the top level".to_string(),
+            child: None,
+        });
+    }
+    let declaration = match lexer.peek() {
+        Some(Token::Let) => Some(parse_let_statement(lexer)?),
+        Some(Token::Const) => Some(ast::Statement::ConstantDeclaration(
+            parse_constant_declaration(lexer)?,
+        )),
+        _ => None,
+    };
+    match declaration {
+        Some(
+            declaration @ (ast::Statement::VariableDeclaration(_)
+            | ast::Statement::DeclarationList(_)
+            | ast::Statement::ConstantDeclaration(_)),
+        ) => Ok(ast::ExportStatement {
+            declaration: Box::new(declaration),
+        }),
+        _ => Err(ParseError {
+            message: "only let and const declarations of names can be exported".to_string(),
+            child: None,
+        }),
+    }
+}
+
+// `struct Name { x, y, fn method() { ... } }`, where commas between members are optional.
+fn parse_struct_declaration(lexer: &mut Peekable) -> Result<ast::StructDeclaration, ParseError> {
+    lexer.next();
+    if lexer.next() != Some(Token::Identifier) {
+        return Err(ParseError {
+            message: "expected struct name".to_string(),
+            child: None,
+        });
+    }
+    let name = lexer.current_slice.unwrap().to_string();
+    if lexer.next() != Some(Token::LBrace) {
+        return Err(ParseError {
+            message: "expected {".to_string(),
+            child: None,
+        });
+    }
+    let mut fields = Vec::new();
+    let mut methods: Vec<ast::StructMethod> = Vec::new();
+    while lexer.peek() != Some(&Token::RBrace) {
+        let method = match lexer.next() {
+            Some(Token::Identifier) => false,
+            Some(Token::Function) if lexer.next() == Some(Token::Identifier) => true,
+            None => {
+                return Err(ParseError {
+                    message: "expected }".to_string(),
+                    child: None,
+                })
+            }
+            _ => {
+                return Err(ParseError {
+                    message: "expected a field or a method".to_string(),
+                    child: None,
+                })
+            }
+        };
+        let member = lexer.current_slice.unwrap().to_string();
+        if fields.contains(&member) || methods.iter().any(|method| method.name == member) {
+            return Err(ParseError {
+                message: format!("{} is declared twice in struct {}", member, name),
+                child: None,
+            });
+        }
+        if method {
+            let function = parse_function_definition(lexer)?;
+            methods.push(ast::StructMethod {
+                name: member,
+                function,
+            });
+        } else {
+            fields.push(member);
+        }
+        if lexer.peek() == Some(&Token::Comma) {
+            lexer.next();
+        }
+    }
+    lexer.next();
+    Ok(ast::StructDeclaration {
+        name,
+        fields,
+        methods,
+    })
+}
+
+fn parse_throw_statement(lexer: &mut Peekable) -> Result<ast::ThrowStatement, ParseError> {
+    lexer.next();
+    let value = parse_expression(lexer, Precedence::Lowest)?;
+    Ok(ast::ThrowStatement { value })
+}
+
+fn parse_assert_statement(lexer: &mut Peekable) -> Result<ast::AssertStatement, ParseError> {
+    lexer.next();
+    let condition = parse_expression(lexer, Precedence::Lowest)?;
+    let message = match lexer.peek() {
+        Some(Token::Comma) => {
+            lexer.next();
+            Some(parse_expression(lexer, Precedence::Lowest)?)
+        }
+        _ => None,
+    };
+    Ok(ast::AssertStatement { condition, message })
+}
+
+fn parse_defer_statement(lexer: &mut Peekable) -> Result<ast::DeferStatement, ParseError> {
+    lexer.next();
+    let value = parse_expression(lexer, Precedence::Lowest)?;
+    Ok(ast::DeferStatement { value })
+}
+
 fn parse_if_expression(lexer: &mut Peekable) -> Result<ast::IfExpression, ParseError> {
     match lexer.next() {
         Some(Token::If) => {}
@@ -510,12 +1516,32 @@ fn parse_if_expression(lexer: &mut Peekable) -> Result<ast::IfExpression, ParseE
             })
         }
     };
-    let consequence = parse_block_statement(lexer);
+    let consequence = parse_block_statement(lexer)?;
     match lexer.peek() {
         Some(Token::Else) => {
             lexer.next();
             match lexer.peek() {
                 Some(Token::LBrace) => {}
+                // `else if` is an `else` block holding only the next `if`
+                Some(Token::If) => {
+                    let line = lexer.line();
+                    let alternative = match parse_if_expression(lexer) {
+                        Ok(if_expression) => ast::BlockExpression {
+                            statements: vec![ast::Statement::BlockReturnStatement(
+                                ast::BlockReturnStatement {
+                                    value: ast::Expression::IfExpression(Box::new(if_expression)),
+                                },
+                            )],
+                            lines: ast::StatementLines(vec![line]),
+                        },
+                        Err(error) => return Err(error),
+                    };
+                    return Ok(ast::IfExpression {
+                        condition: condition,
+                        consequence,
+                        alternative: Some(alternative),
+                    });
+                }
                 _ => {
                     return Err(ParseError {
                         message: "expected {".to_string(),
@@ -523,26 +1549,20 @@ fn parse_if_expression(lexer: &mut Peekable) -> Result<ast::IfExpression, ParseE
                     })
                 }
             };
-            let alternative = parse_block_statement(lexer);
+            let alternative = parse_block_statement(lexer)?;
             return Ok(ast::IfExpression {
                 condition: condition,
-                consequence: consequence.unwrap(),
-                alternative: Some(alternative.unwrap()),
+                consequence,
+                alternative: Some(alternative),
             });
         }
-        Some(_) => {
+        _ => {
             return Ok(ast::IfExpression {
                 condition: condition,
-                consequence: consequence.unwrap(),
+                consequence,
                 alternative: None,
             });
         }
-        _ => {
-            return Err(ParseError {
-                message: "expected {".to_string(),
-                child: None,
-            });
-        }
     };
 }
 
@@ -557,8 +1577,10 @@ fn parse_block_statement(lexer: &mut Peekable) -> Result<ast::BlockExpression, P
         }
     };
     let mut statements: Vec<ast::Statement> = vec![];
+    let mut lines = vec![];
     let mut peeked = lexer.peek().cloned();
     while peeked.is_some() && peeked.as_ref().unwrap() != &Token::RBrace {
+        lines.push(lexer.line());
         let statement = match parse_statement(lexer) {
             Ok(statement) => statement,
             Err(error) => return Err(error),
@@ -577,10 +1599,12 @@ fn parse_block_statement(lexer: &mut Peekable) -> Result<ast::BlockExpression, P
     };
     return Ok(ast::BlockExpression {
         statements: statements,
+        lines: ast::StatementLines(lines),
     });
 }
 
-fn parse_array_literal(lexer: &mut Peekable) -> Result<ast::ArrayLiteral, ParseError> {
+// `[1, 2]`, or a map when every element has a key, with `[:]` as the empty map.
+fn parse_array_literal(lexer: &mut Peekable) -> Result<ast::Expression, ParseError> {
     match lexer.next() {
         Some(Token::LBracket) => {}
         _ => {
@@ -590,10 +1614,19 @@ fn parse_array_literal(lexer: &mut Peekable) -> Result<ast::ArrayLiteral, ParseE
             })
         }
     };
-    let elements = match parse_comma_separated(lexer) {
-        Ok(elements) => elements,
-        Err(error) => return Err(error),
+    let is_empty_map = lexer.peek() == Some(&Token::Colon);
+    let elements = if is_empty_map {
+        lexer.next();
+        vec![]
+    } else {
+        match parse_comma_separated(lexer) {
+            Ok(elements) => elements,
+            Err(error) => return Err(error),
+        }
     };
+    if lexer.peek() == Some(&Token::For) {
+        return parse_array_comprehension(lexer, elements);
+    }
     match lexer.next() {
         Some(Token::RBracket) => {}
         _ => {
@@ -603,24 +1636,101 @@ fn parse_array_literal(lexer: &mut Peekable) -> Result<ast::ArrayLiteral, ParseE
             })
         }
     };
-    return Ok(ast::ArrayLiteral { elements });
+    let is_map = !elements.is_empty()
+        && elements
+            .iter()
+            .all(|element| !matches!(element, ast::ArrayMapValue::Value(_)));
+    if is_empty_map || is_map {
+        return Ok(ast::Expression::MapLiteral(ast::MapLiteral {
+            entries: elements,
+        }));
+    }
+    return Ok(ast::Expression::ArrayLiteral(ast::ArrayLiteral {
+        pool: LiteralPool::new(&elements),
+        elements,
+    }));
+}
+
+// The rest of `[element for name in iterable if condition]`, from `for` on.
+fn parse_array_comprehension(
+    lexer: &mut Peekable,
+    elements: Vec<ast::ArrayMapValue>,
+) -> Result<ast::Expression, ParseError> {
+    let element = match <[_; 1]>::try_from(elements) {
+        Ok([ast::ArrayMapValue::Value(element)]) => element,
+        _ => {
+            return Err(ParseError {
+                message: "a comprehension takes a single value before for".to_string(),
+                child: None,
+            })
+        }
+    };
+    lexer.next();
+    lexer.next();
+    let variable = ast::Identifier {
+        value: lexer.current_slice.unwrap().to_string(),
+    };
+    if lexer.next() != Some(Token::In) {
+        return Err(ParseError {
+            message: "expected in".to_string(),
+            child: None,
+        });
+    }
+    let iterable = parse_expression(lexer, Precedence::Lowest)?;
+    let condition = match lexer.peek() {
+        Some(Token::If) => {
+            lexer.next();
+            Some(parse_expression(lexer, Precedence::Lowest)?)
+        }
+        _ => None,
+    };
+    if lexer.next() != Some(Token::RBracket) {
+        return Err(ParseError {
+            message: "expected ]".to_string(),
+            child: None,
+        });
+    }
+    Ok(ast::Expression::ArrayComprehension(Box::new(
+        ast::ArrayComprehension {
+            element,
+            variable,
+            iterable,
+            condition,
+        },
+    )))
 }
 
 fn parse_comma_separated(lexer: &mut Peekable<'_>) -> Result<Vec<ast::ArrayMapValue>, ParseError> {
     let mut elements: Vec<ast::ArrayMapValue> = vec![];
     let mut peeked = lexer.peek().cloned();
     while peeked.is_some() && peeked.as_ref().unwrap() != &Token::RBracket {
-        let expression = match parse_expression(lexer, Precedence::Lowest) {
-            Ok(expression) => expression,
-            Err(error) => return Err(error),
-        };
+        // a key in parentheses is computed, so `(name): 1` uses the value of `name`
+        let is_computed = peeked == Some(Token::LParen);
+        let expression =
+            match parse_taking_colon(lexer, |lexer| parse_expression(lexer, Precedence::Lowest)) {
+                Ok(expression) => expression,
+                Err(error) => return Err(error),
+            };
         peeked = lexer.peek().cloned();
-        if peeked.is_some() && peeked.as_ref().unwrap() == &Token::Colon {
+        if is_computed && peeked == Some(Token::Colon) {
+            lexer.next();
+            let value = parse_expression(lexer, Precedence::Lowest)?;
+            elements.push(ast::ArrayMapValue::ComputedKeyValue(
+                ast::ComputedKeyValue {
+                    key: expression,
+                    value,
+                },
+            ));
+            peeked = lexer.peek().cloned();
+        } else if peeked.is_some() && peeked.as_ref().unwrap() == &Token::Colon {
             let key = match expression {
                 ast::Expression::Identifier(identifier) => identifier.value,
+                ast::Expression::StringLiteral(string) => string.value,
+                ast::Expression::NumberLiteral(number) => number.value.to_string(),
+                ast::Expression::FloatLiteral(float) => format_float(float.value),
                 _ => {
                     return Err(ParseError {
-                        message: "expected string literal".to_string(),
+                        message: "computed map keys must be in parentheses".to_string(),
                         child: None,
                     })
                 }
@@ -637,6 +1747,10 @@ fn parse_comma_separated(lexer: &mut Peekable<'_>) -> Result<Vec<ast::ArrayMapVa
             peeked = lexer.peek().cloned();
         } else {
             elements.push(ast::ArrayMapValue::Value(expression));
+            // `for name` without parentheses turns the array into a comprehension
+            if peeked == Some(Token::For) && lexer.peek_second() == Some(Token::Identifier) {
+                break;
+            }
         }
 
         if peeked.is_some() && peeked.as_ref().unwrap() == &Token::Comma {
@@ -647,12 +1761,15 @@ fn parse_comma_separated(lexer: &mut Peekable<'_>) -> Result<Vec<ast::ArrayMapVa
     return Ok(elements);
 }
 
+// `left[index]` or `left?[index]`, or a slice `left[start:end]` where either bound may be
+// left out.
 fn parse_element_access_expression(
     lexer: &mut Peekable,
     left: ast::Expression,
-) -> Result<ast::ElementAccessExpression, ParseError> {
-    match lexer.next() {
-        Some(Token::LBracket) => {}
+) -> Result<ast::Expression, ParseError> {
+    let optional = match lexer.next() {
+        Some(Token::LBracket) => false,
+        Some(Token::QuestionBracket) => true,
         _ => {
             return Err(ParseError {
                 message: "expected [".to_string(),
@@ -660,9 +1777,22 @@ fn parse_element_access_expression(
             })
         }
     };
-    let index = match parse_expression(lexer, Precedence::Lowest) {
-        Ok(expression) => expression,
-        Err(error) => return Err(error),
+    let index = match lexer.peek() {
+        Some(Token::Colon) => None,
+        _ => match parse_taking_colon(lexer, |lexer| parse_expression(lexer, Precedence::Lowest)) {
+            Ok(expression) => Some(expression),
+            Err(error) => return Err(error),
+        },
+    };
+    let is_slice = lexer.peek() == Some(&Token::Colon);
+    let end = if is_slice {
+        lexer.next();
+        match lexer.peek() {
+            Some(Token::RBracket) => None,
+            _ => Some(parse_expression(lexer, Precedence::Lowest)?),
+        }
+    } else {
+        None
     };
     match lexer.next() {
         Some(Token::RBracket) => {}
@@ -673,10 +1803,54 @@ fn parse_element_access_expression(
             })
         }
     };
-    return Ok(ast::ElementAccessExpression { left, index });
+    if is_slice && optional {
+        return Err(ParseError {
+            message: "?[ cannot take a slice".to_string(),
+            child: None,
+        });
+    }
+    if is_slice {
+        return Ok(ast::Expression::SliceExpression(Box::new(
+            ast::SliceExpression {
+                left,
+                start: index,
+                end,
+            },
+        )));
+    }
+    Ok(ast::Expression::ElementAccessExpression(Box::new(
+        ast::ElementAccessExpression {
+            left,
+            index: index.unwrap(),
+            optional,
+        },
+    )))
+}
+
+fn parse_member_access_expression(
+    lexer: &mut Peekable,
+    left: ast::Expression,
+) -> Result<ast::Expression, ParseError> {
+    let optional = lexer.next() == Some(Token::QuestionDot);
+    match lexer.next() {
+        Some(Token::Identifier) => {}
+        _ => {
+            return Err(ParseError {
+                message: "expected identifier after .".to_string(),
+                child: None,
+            })
+        }
+    };
+    Ok(ast::Expression::MemberAccessExpression(Box::new(
+        ast::MemberAccessExpression {
+            left,
+            member: lexer.current_slice.unwrap().to_string(),
+            optional,
+        },
+    )))
 }
 
-fn parse_for_expression(lexer: &mut Peekable) -> Result<ast::ForExpression, ParseError> {
+fn parse_for_expression(lexer: &mut Peekable) -> Result<Box<ast::ForExpression>, ParseError> {
     match lexer.next() {
         Some(Token::For) => {}
         _ => {
@@ -704,7 +1878,22 @@ fn parse_for_expression(lexer: &mut Peekable) -> Result<ast::ForExpression, Pars
             })
         }
     };
-    let name = lexer.current_slice.unwrap().to_string();
+    let mut name = lexer.current_slice.unwrap().to_string();
+    let mut index = None;
+    if lexer.peek() == Some(&Token::Comma) {
+        lexer.next();
+        match lexer.next() {
+            Some(Token::Identifier) => {}
+            _ => {
+                return Err(ParseError {
+                    message: "expected identifier".to_string(),
+                    child: None,
+                })
+            }
+        };
+        index = Some(ast::Identifier { value: name });
+        name = lexer.current_slice.unwrap().to_string();
+    }
     match lexer.next() {
         Some(Token::In) => {}
         _ => {
@@ -731,23 +1920,52 @@ fn parse_for_expression(lexer: &mut Peekable) -> Result<ast::ForExpression, Pars
         Ok(block_statement) => block_statement,
         Err(error) => return Err(error),
     };
-    return Ok(ast::ForExpression {
+    return Ok(Box::new(ast::ForExpression {
+        label: None,
+        index,
         variable: ast::Identifier { value: name },
         iterable: array,
         body: block_statement,
-    });
+    }));
 }
 
-fn parse_switch_expression(lexer: &mut Peekable) -> Result<ast::SwitchExpression, ParseError> {
+fn parse_try_expression(lexer: &mut Peekable) -> Result<ast::TryExpression, ParseError> {
     match lexer.next() {
-        Some(Token::Switch) => {}
+        Some(Token::Try) => {}
         _ => {
             return Err(ParseError {
-                message: "expected switch".to_string(),
+                message: "expected try".to_string(),
                 child: None,
             })
         }
     };
+    let body = parse_block_statement(lexer)?;
+    let handler = match lexer.peek() {
+        Some(Token::Catch) => Some(parse_catch_handler(lexer)?),
+        _ => None,
+    };
+    let finalizer = match lexer.peek() {
+        Some(Token::Finally) => {
+            lexer.next();
+            Some(parse_block_statement(lexer)?)
+        }
+        _ => None,
+    };
+    if handler.is_none() && finalizer.is_none() {
+        return Err(ParseError {
+            message: "expected catch or finally".to_string(),
+            child: None,
+        });
+    }
+    Ok(ast::TryExpression {
+        body,
+        handler,
+        finalizer,
+    })
+}
+
+fn parse_catch_handler(lexer: &mut Peekable) -> Result<ast::CatchHandler, ParseError> {
+    lexer.next();
     match lexer.next() {
         Some(Token::LParen) => {}
         _ => {
@@ -757,10 +1975,16 @@ fn parse_switch_expression(lexer: &mut Peekable) -> Result<ast::SwitchExpression
             })
         }
     };
-    let expression = match parse_expression(lexer, Precedence::Lowest) {
-        Ok(expression) => expression,
-        Err(error) => return Err(error),
+    match lexer.next() {
+        Some(Token::Identifier) => {}
+        _ => {
+            return Err(ParseError {
+                message: "expected identifier".to_string(),
+                child: None,
+            })
+        }
     };
+    let name = lexer.current_slice.unwrap().to_string();
     match lexer.next() {
         Some(Token::RParen) => {}
         _ => {
@@ -770,23 +1994,62 @@ fn parse_switch_expression(lexer: &mut Peekable) -> Result<ast::SwitchExpression
             })
         }
     };
+    let body = parse_block_statement(lexer)?;
+    Ok(ast::CatchHandler {
+        parameter: ast::Identifier { value: name },
+        body,
+    })
+}
+
+fn parse_switch_expression(lexer: &mut Peekable) -> Result<ast::SwitchExpression, ParseError> {
     match lexer.next() {
-        Some(Token::LBrace) => {}
+        Some(Token::Switch) => {}
         _ => {
             return Err(ParseError {
-                message: "expected {".to_string(),
+                message: "expected switch".to_string(),
                 child: None,
             })
         }
     };
-    let mut cases: Vec<ast::Case> = vec![];
-    let mut peeked = lexer.peek().cloned();
-    while peeked.is_some()
-        && peeked.as_ref().unwrap() != &Token::RBrace
-        && peeked.as_ref().unwrap() != &Token::Default
-    {
-        let case = match parse_case(lexer) {
-            Ok(case) => case,
+    match lexer.next() {
+        Some(Token::LParen) => {}
+        _ => {
+            return Err(ParseError {
+                message: "expected (".to_string(),
+                child: None,
+            })
+        }
+    };
+    let expression = match parse_expression(lexer, Precedence::Lowest) {
+        Ok(expression) => expression,
+        Err(error) => return Err(error),
+    };
+    match lexer.next() {
+        Some(Token::RParen) => {}
+        _ => {
+            return Err(ParseError {
+                message: "expected )".to_string(),
+                child: None,
+            })
+        }
+    };
+    match lexer.next() {
+        Some(Token::LBrace) => {}
+        _ => {
+            return Err(ParseError {
+                message: "expected {".to_string(),
+                child: None,
+            })
+        }
+    };
+    let mut cases: Vec<ast::Case> = vec![];
+    let mut peeked = lexer.peek().cloned();
+    while peeked.is_some()
+        && peeked.as_ref().unwrap() != &Token::RBrace
+        && peeked.as_ref().unwrap() != &Token::Default
+    {
+        let case = match parse_case(lexer) {
+            Ok(case) => case,
             Err(error) => return Err(error),
         };
         cases.push(case);
@@ -800,6 +2063,30 @@ fn parse_switch_expression(lexer: &mut Peekable) -> Result<ast::SwitchExpression
         },
         _ => None,
     };
+    // the next body would run without its pattern binding anything
+    for (index, case) in cases.iter().enumerate() {
+        if !case.fallthrough {
+            continue;
+        }
+        match cases.get(index + 1) {
+            Some(ast::Case {
+                condition: ast::CaseCondition::Pattern(_),
+                ..
+            }) => {
+                return Err(ParseError {
+                    message: "cannot fallthrough into a case with a pattern".to_string(),
+                    child: None,
+                })
+            }
+            None if default.is_none() => {
+                return Err(ParseError {
+                    message: "cannot fallthrough from the last case".to_string(),
+                    child: None,
+                })
+            }
+            _ => {}
+        }
+    }
 
     match lexer.next() {
         Some(Token::RBrace) => {}
@@ -817,6 +2104,141 @@ fn parse_switch_expression(lexer: &mut Peekable) -> Result<ast::SwitchExpression
     });
 }
 
+// Kept out of `parse_nested_expression`, as every arm there grows the stack frame of each
+// level of nesting.
+fn parse_keyword_expression(lexer: &mut Peekable) -> Result<ast::Expression, ParseError> {
+    match lexer.peek() {
+        Some(Token::Do) => parse_do_while_expression(lexer),
+        Some(Token::Loop) => parse_loop_expression(lexer),
+        _ => parse_match_expression(lexer),
+    }
+}
+
+fn parse_do_while_expression(lexer: &mut Peekable) -> Result<ast::Expression, ParseError> {
+    lexer.next();
+    let body = parse_block_statement(lexer)?;
+    match lexer.next() {
+        Some(Token::While) => {}
+        _ => {
+            return Err(ParseError {
+                message: "expected while after the do block".to_string(),
+                child: None,
+            })
+        }
+    };
+    match lexer.next() {
+        Some(Token::LParen) => {}
+        _ => {
+            return Err(ParseError {
+                message: "expected (".to_string(),
+                child: None,
+            })
+        }
+    };
+    let condition = parse_expression(lexer, Precedence::Lowest)?;
+    match lexer.next() {
+        Some(Token::RParen) => {}
+        _ => {
+            return Err(ParseError {
+                message: "expected )".to_string(),
+                child: None,
+            })
+        }
+    };
+    Ok(ast::Expression::DoWhileExpression(Box::new(
+        ast::DoWhileExpression {
+            label: None,
+            body,
+            condition,
+        },
+    )))
+}
+
+fn parse_loop_expression(lexer: &mut Peekable) -> Result<ast::Expression, ParseError> {
+    lexer.next();
+    let body = parse_block_statement(lexer)?;
+    Ok(ast::Expression::LoopExpression(Box::new(
+        ast::LoopExpression { label: None, body },
+    )))
+}
+
+fn parse_match_expression(lexer: &mut Peekable) -> Result<ast::Expression, ParseError> {
+    lexer.next();
+    match lexer.next() {
+        Some(Token::LParen) => {}
+        _ => {
+            return Err(ParseError {
+                message: "expected (".to_string(),
+                child: None,
+            })
+        }
+    };
+    let expression = parse_expression(lexer, Precedence::Lowest)?;
+    match lexer.next() {
+        Some(Token::RParen) => {}
+        _ => {
+            return Err(ParseError {
+                message: "expected )".to_string(),
+                child: None,
+            })
+        }
+    };
+    match lexer.next() {
+        Some(Token::LBrace) => {}
+        _ => {
+            return Err(ParseError {
+                message: "expected {".to_string(),
+                child: None,
+            })
+        }
+    };
+    let mut arms = vec![];
+    while lexer.peek().is_some() && lexer.peek() != Some(&Token::RBrace) {
+        let pattern = parse_pattern_element(lexer)?;
+        if let ast::Pattern::Keyed(_) = pattern {
+            return Err(ParseError {
+                message: "a key: pattern has to be inside [ ]".to_string(),
+                child: None,
+            });
+        }
+        match lexer.next() {
+            Some(Token::FatArrow) => {}
+            _ => {
+                return Err(ParseError {
+                    message: "expected => after the pattern".to_string(),
+                    child: None,
+                })
+            }
+        };
+        let body = parse_expression(lexer, Precedence::Lowest)?;
+        arms.push(ast::MatchArm { pattern, body });
+        match lexer.peek() {
+            Some(Token::Comma) => {
+                lexer.next();
+            }
+            Some(Token::RBrace) => {}
+            _ => {
+                return Err(ParseError {
+                    message: "expected , or } after a match arm".to_string(),
+                    child: None,
+                })
+            }
+        }
+    }
+    match lexer.next() {
+        Some(Token::RBrace) => {}
+        _ => {
+            return Err(ParseError {
+                message: "expected }".to_string(),
+                child: None,
+            })
+        }
+    };
+    Ok(ast::Expression::MatchExpression(Box::new(
+        ast::MatchExpression { expression, arms },
+    )))
+}
+
 fn parse_case(lexer: &mut Peekable) -> Result<ast::Case, ParseError> {
     match lexer.next() {
         Some(Token::Case) => {}
@@ -827,9 +2249,15 @@ fn parse_case(lexer: &mut Peekable) -> Result<ast::Case, ParseError> {
             })
         }
     };
-    let expression = match parse_expression(lexer, Precedence::Lowest) {
-        Ok(expression) => expression,
-        Err(error) => return Err(error),
+    let condition = match lexer.peek() {
+        Some(Token::LBracket) => match parse_array_pattern(lexer) {
+            Ok(pattern) => ast::CaseCondition::Pattern(pattern),
+            Err(error) => return Err(error),
+        },
+        _ => match parse_taking_colon(lexer, |lexer| parse_expression(lexer, Precedence::Lowest)) {
+            Ok(expression) => ast::CaseCondition::Value(expression),
+            Err(error) => return Err(error),
+        },
     };
     match lexer.next() {
         Some(Token::Colon) => {}
@@ -840,16 +2268,62 @@ fn parse_case(lexer: &mut Peekable) -> Result<ast::Case, ParseError> {
             })
         }
     };
-    let block_statement = match parse_block_statement(lexer) {
-        Ok(block_statement) => block_statement,
-        Err(error) => return Err(error),
-    };
+    let (body, fallthrough) = parse_case_body(lexer)?;
     return Ok(ast::Case {
-        condition: expression,
-        body: block_statement,
+        condition,
+        body,
+        fallthrough,
     });
 }
 
+// Like a block, except that it may end with `fallthrough;`.
+fn parse_case_body(lexer: &mut Peekable) -> Result<(ast::BlockExpression, bool), ParseError> {
+    match lexer.next() {
+        Some(Token::LBrace) => {}
+        _ => {
+            return Err(ParseError {
+                message: "expected {".to_string(),
+                child: None,
+            })
+        }
+    };
+    let mut statements = vec![];
+    let mut lines = vec![];
+    let mut fallthrough = false;
+    while lexer.peek().is_some() && lexer.peek() != Some(&Token::RBrace) {
+        if lexer.peek() != Some(&Token::Fallthrough) {
+            lines.push(lexer.line());
+            statements.push(parse_statement(lexer)?);
+            continue;
+        }
+        lexer.next();
+        if lexer.next() != Some(Token::Semicolon) {
+            return Err(ParseError {
+                message: "expected semicolon".to_string(),
+                child: None,
+            });
+        }
+        if lexer.peek() != Some(&Token::RBrace) {
+            return Err(ParseError {
+                message: "fallthrough is only allowed at the end of a case".to_string(),
+                child: None,
+            });
+        }
+        fallthrough = true;
+    }
+    match lexer.next() {
+        Some(Token::RBrace) => {}
+        _ => {
+            return Err(ParseError {
+                message: "expected }".to_string(),
+                child: None,
+            })
+        }
+    };
+    let lines = ast::StatementLines(lines);
+    Ok((ast::BlockExpression { statements, lines }, fallthrough))
+}
+
 fn parse_default(lexer: &mut Peekable) -> Result<ast::Default, ParseError> {
     match lexer.next() {
         Some(Token::Default) => {}
@@ -917,55 +2391,474 @@ fn parse_watch_declaration(lexer: &mut Peekable) -> Result<ast::WatchDeclaration
     });
 }
 
-// test parser
-#[cfg(test)]
-mod tests {
-    use crate::ast::{Expression, VariableDeclaration};
-
-    use super::*;
-
-    #[test]
-    fn test_parse() {
-        let mut lexer = Peekable::new("let x = 1;");
-        let program = parse(&mut lexer).unwrap();
-        assert_eq!(
-            program,
-            ast::Program {
-                statements: vec![ast::Statement::VariableDeclaration(
-                    ast::VariableDeclaration {
-                        name: "x".to_string(),
-                        value: ast::Expression::NumberLiteral(ast::NumberLiteral { value: 1 }),
-                    }
-                )],
+// `import name from "path"`, or `import "path"` named after the file. `from` is only a
+// keyword here, so it stays usable as a name.
+fn parse_import_statement(lexer: &mut Peekable) -> Result<ast::ImportStatement, ParseError> {
+    match lexer.next() {
+        Some(Token::Import) => {}
+        _ => {
+            return Err(ParseError {
+                message: "expected import".to_string(),
+                child: None,
+            })
+        }
+    };
+    let name = match lexer.peek() {
+        Some(Token::Identifier) => {
+            lexer.next();
+            Some(lexer.current_slice.unwrap().to_string())
+        }
+        Some(Token::String | Token::MultilineString | Token::RawString) => None,
+        _ => {
+            return Err(ParseError {
+                message: "expected identifier".to_string(),
+                child: None,
+            })
+        }
+    };
+    if name.is_some() {
+        match lexer.next() {
+            Some(Token::Identifier) if lexer.current_slice == Some("from") => {}
+            _ => {
+                return Err(ParseError {
+                    message: "expected from".to_string(),
+                    child: None,
+                })
             }
-        );
+        };
     }
-    #[test]
-    fn test_infix_expression() {
-        let mut lexer = Peekable::new("1 + 2;");
-        let expression = parse_expression(&mut lexer, Precedence::Lowest).unwrap();
-        assert_eq!(
-            expression,
-            Expression::InfixExpression(Box::new(ast::InfixExpression {
-                left: Expression::NumberLiteral(ast::NumberLiteral { value: 1 }),
-                operator: Operator::Plus,
-                right: Expression::NumberLiteral(ast::NumberLiteral { value: 2 }),
-            }))
-        );
-
-        let mut lexer = Peekable::new("1 + 2 * 3;");
-        let expression = parse_expression(&mut lexer, Precedence::Lowest).unwrap();
-        assert_eq!(
-            expression,
-            Expression::InfixExpression(Box::new(ast::InfixExpression {
-                left: Expression::NumberLiteral(ast::NumberLiteral { value: 1 }),
-                operator: Operator::Plus,
-                right: Expression::InfixExpression(Box::new(ast::InfixExpression {
-                    left: Expression::NumberLiteral(ast::NumberLiteral { value: 2 }),
-                    operator: Operator::Asterisk,
-                    right: Expression::NumberLiteral(ast::NumberLiteral { value: 3 }),
-                })),
-            }))
+    let token = match lexer.next() {
+        Some(token @ Token::String)
+        | Some(token @ Token::MultilineString)
+        | Some(token @ Token::RawString) => token,
+        _ => {
+            return Err(ParseError {
+                message: "expected the path to import as a string".to_string(),
+                child: None,
+            })
+        }
+    };
+    let path = match parse_string_token(
+        &token,
+        lexer.current_slice.unwrap(),
+        lexer.max_depth,
+        lexer.line(),
+    ) {
+        Ok(mut parts) => match parts.pop() {
+            Some(ast::TemplatePart::String(path)) if parts.is_empty() => path,
+            _ => {
+                return Err(ParseError {
+                    message: "import paths cannot contain ${...}".to_string(),
+                    child: None,
+                })
+            }
+        },
+        Err(error) => return Err(error),
+    };
+    let name = match name {
+        Some(name) => name,
+        None => get_import_name(&path)?,
+    };
+    Ok(ast::ImportStatement { name, path })
+}
+
+// `json` for `import "json"` and `math` for `import "lib/math.ank"`.
+fn get_import_name(path: &str) -> Result<String, ParseError> {
+    let name = std::path::Path::new(path)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("");
+    let mut tokens = Token::lexer(name);
+    if tokens.next() == Some(Ok(Token::Identifier)) && tokens.span() == (0..name.len()) {
+        return Ok(name.to_string());
+    }
+    Err(ParseError {
+        message: format!(
+            "cannot name the import of {}, write import name from \"{}\"",
+            path, path
+        ),
+        child: None,
+    })
+}
+
+fn parse_break_statement(lexer: &mut Peekable) -> Result<ast::BreakStatement, ParseError> {
+    match lexer.next() {
+        Some(Token::Break) => {}
+        _ => {
+            return Err(ParseError {
+                message: "expected break".to_string(),
+                child: None,
+            })
+        }
+    };
+    // a name is a label when an enclosing loop has it, and a value otherwise
+    let is_label = lexer.peek() == Some(&Token::Identifier)
+        && lexer
+            .labels
+            .iter()
+            .any(|label| Some(label.as_str()) == lexer.peeked_slice);
+    let label = if is_label {
+        lexer.next();
+        Some(lexer.current_slice.unwrap().to_string())
+    } else {
+        None
+    };
+    if lexer.peek() == Some(&Token::Semicolon) {
+        return Ok(ast::BreakStatement { label, value: None });
+    }
+    let value = match parse_expression(lexer, Precedence::Lowest) {
+        Ok(expression) => expression,
+        Err(error) => return Err(error),
+    };
+    return Ok(ast::BreakStatement {
+        label,
+        value: Some(value),
+    });
+}
+
+fn parse_continue_statement(lexer: &mut Peekable) -> Result<ast::ContinueStatement, ParseError> {
+    lexer.next();
+    if lexer.peek() != Some(&Token::Identifier) {
+        return Ok(ast::ContinueStatement { label: None });
+    }
+    lexer.next();
+    let label = lexer.current_slice.unwrap().to_string();
+    if !lexer.labels.contains(&label) {
+        return Err(ParseError {
+            message: format!("no enclosing loop is labeled {}", label),
+            child: None,
+        });
+    }
+    Ok(ast::ContinueStatement { label: Some(label) })
+}
+
+fn parse_test_block(lexer: &mut Peekable) -> Result<ast::TestBlock, ParseError> {
+    match lexer.next() {
+        Some(Token::Test) => {}
+        _ => {
+            return Err(ParseError {
+                message: "expected test".to_string(),
+                child: None,
+            })
+        }
+    };
+    let token = match lexer.next() {
+        Some(token @ Token::String)
+        | Some(token @ Token::MultilineString)
+        | Some(token @ Token::RawString) => token,
+        _ => {
+            return Err(ParseError {
+                message: "expected test name".to_string(),
+                child: None,
+            })
+        }
+    };
+    let name = match parse_string_token(
+        &token,
+        lexer.current_slice.unwrap(),
+        lexer.max_depth,
+        lexer.line(),
+    ) {
+        Ok(mut parts) => match parts.pop() {
+            Some(ast::TemplatePart::String(name)) if parts.is_empty() => name,
+            _ => {
+                return Err(ParseError {
+                    message: "test names cannot contain ${...}".to_string(),
+                    child: None,
+                })
+            }
+        },
+        Err(error) => return Err(error),
+    };
+    let body = match parse_block_statement(lexer) {
+        Ok(body) => body,
+        Err(error) => return Err(error),
+    };
+    return Ok(ast::TestBlock { name, body });
+}
+
+// Strips the quotes of a string token and, unless it is raw, resolves its escape sequences
+// and `${...}` interpolations. A multi-line string drops the line break right after its
+// opening quotes.
+fn parse_string_token(
+    token: &Token,
+    slice: &str,
+    max_depth: usize,
+    line: usize,
+) -> Result<Vec<ast::TemplatePart>, ParseError> {
+    let mut line = line;
+    let content = match token {
+        Token::RawString => {
+            let raw = &slice[1..];
+            let raw = if raw.len() >= 6 && raw.starts_with("\"\"\"") {
+                &raw[3..raw.len() - 3]
+            } else {
+                &raw[1..raw.len() - 1]
+            };
+            return Ok(vec![ast::TemplatePart::String(raw.to_string())]);
+        }
+        Token::MultilineString => {
+            let content = &slice[3..slice.len() - 3];
+            match content
+                .strip_prefix("\r\n")
+                .or_else(|| content.strip_prefix('\n'))
+            {
+                Some(stripped) => {
+                    line += 1;
+                    stripped
+                }
+                None => content,
+            }
+        }
+        _ => &slice[1..slice.len() - 1],
+    };
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut index = 0;
+    while let Some(c) = content[index..].chars().next() {
+        index += c.len_utf8();
+        if c == '$' && content[index..].starts_with('{') {
+            let start = index + 1;
+            let end = match interpolation_end(&content[start..]) {
+                Some(length) => start + length,
+                None => {
+                    return Err(ParseError {
+                        message: "unterminated ${ in string".to_string(),
+                        child: None,
+                    })
+                }
+            };
+            let expression_line = line + content[..start].matches('\n').count();
+            let expression =
+                match parse_interpolation(&content[start..end], max_depth, expression_line) {
+                    Ok(expression) => expression,
+                    Err(error) => return Err(error),
+                };
+            if !text.is_empty() {
+                parts.push(ast::TemplatePart::String(std::mem::take(&mut text)));
+            }
+            parts.push(ast::TemplatePart::Expression(expression));
+            // skip the closing brace
+            index = end + 1;
+            continue;
+        }
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        let escaped = match content[index..].chars().next() {
+            Some(escaped) => escaped,
+            None => {
+                return Err(ParseError {
+                    message: "unterminated escape sequence".to_string(),
+                    child: None,
+                })
+            }
+        };
+        index += escaped.len_utf8();
+        match escaped {
+            'n' => text.push('\n'),
+            't' => text.push('\t'),
+            'r' => text.push('\r'),
+            '0' => text.push('\0'),
+            '"' | '\\' | '$' => text.push(escaped),
+            other => {
+                return Err(ParseError {
+                    message: format!("unknown escape sequence \\{}", other),
+                    child: None,
+                })
+            }
+        }
+    }
+    if !text.is_empty() || parts.is_empty() {
+        parts.push(ast::TemplatePart::String(text));
+    }
+    Ok(parts)
+}
+
+fn parse_interpolation(
+    source: &str,
+    max_depth: usize,
+    line: usize,
+) -> Result<ast::Expression, ParseError> {
+    let mut lexer = Peekable::with_max_depth(source, max_depth);
+    lexer.start_at_line(line);
+    let expression = match parse_expression(&mut lexer, Precedence::Lowest) {
+        Ok(expression) => expression,
+        Err(error) => {
+            return Err(ParseError {
+                message: format!("invalid expression in ${{{}}}", source),
+                child: Some(Box::new(error)),
+            })
+        }
+    };
+    match lexer.peek() {
+        None => Ok(expression),
+        Some(token) => Err(ParseError {
+            message: format!("unexpected {} in ${{{}}}", token, source),
+            child: None,
+        }),
+    }
+}
+
+// test parser
+#[cfg(test)]
+mod tests {
+    use crate::ast::{Expression, VariableDeclaration};
+
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let mut lexer = Peekable::new("let x = 1;");
+        let program = parse(&mut lexer).unwrap();
+        assert_eq!(
+            program,
+            ast::Program {
+                lines: ast::StatementLines::default(),
+                statements: vec![ast::Statement::VariableDeclaration(
+                    ast::VariableDeclaration {
+                        name: "x".to_string(),
+                        type_annotation: None,
+                        value: ast::Expression::NumberLiteral(ast::NumberLiteral { value: 1 }),
+                        position: ast::Position { line: 1 },
+                    }
+                )],
+            }
+        );
+    }
+    #[test]
+    fn test_nesting_depth_limit() {
+        let source = format!(
+            "let x = 1;\nlet y = {}1{};",
+            "(".repeat(100_000),
+            ")".repeat(100_000)
+        );
+        let mut lexer = Peekable::new(&source);
+        assert_eq!(
+            parse(&mut lexer).unwrap_err().message,
+            "expression too deeply nested at line 2"
+        );
+
+        let source = format!("{}1{};", "{[-".repeat(80), "]}".repeat(80));
+        let mut lexer = Peekable::new(&source);
+        assert!(parse(&mut lexer).is_ok());
+
+        let mut lexer = Peekable::with_max_depth("[[1]];", 2);
+        assert!(parse(&mut lexer).is_err());
+    }
+
+    #[test]
+    fn test_digit_separators() {
+        let mut lexer = Peekable::new("1_000_000 + 0.000_5;");
+        let expression = parse_expression(&mut lexer, Precedence::Lowest).unwrap();
+        assert_eq!(
+            expression,
+            Expression::InfixExpression(Box::new(ast::InfixExpression {
+                left: Expression::NumberLiteral(ast::NumberLiteral { value: 1000000 }),
+                operator: Operator::Plus,
+                right: Expression::FloatLiteral(ast::FloatLiteral { value: 0.0005 }),
+            }))
+        );
+    }
+
+    #[test]
+    fn test_float_literal() {
+        let mut lexer = Peekable::new("3.25;");
+        let expression = parse_expression(&mut lexer, Precedence::Lowest).unwrap();
+        assert_eq!(
+            expression,
+            Expression::FloatLiteral(ast::FloatLiteral { value: 3.25 })
+        );
+
+        for (source, value) in [
+            ("1e6", 1e6),
+            ("2.5e-3", 2.5e-3),
+            ("1_000E+2", 1e5),
+            ("0e0", 0.0),
+        ] {
+            let mut lexer = Peekable::new(source);
+            let expression = parse_expression(&mut lexer, Precedence::Lowest).unwrap();
+            assert_eq!(
+                expression,
+                Expression::FloatLiteral(ast::FloatLiteral { value }),
+                "{}",
+                source
+            );
+        }
+
+        let mut lexer = Peekable::new("1e999");
+        assert_eq!(
+            parse_expression(&mut lexer, Precedence::Lowest)
+                .unwrap_err()
+                .message,
+            "float literal out of range"
+        );
+
+        let mut lexer = Peekable::new("let x = 99999999999999999999;");
+        assert_eq!(
+            parse(&mut lexer).unwrap_err().message,
+            "number literal out of range"
+        );
+    }
+
+    #[test]
+    fn test_prefix_expression() {
+        let mut lexer = Peekable::new("-a * 2;");
+        let expression = parse_expression(&mut lexer, Precedence::Lowest).unwrap();
+        assert_eq!(
+            expression,
+            Expression::InfixExpression(Box::new(ast::InfixExpression {
+                left: Expression::PrefixExpression(Box::new(ast::PrefixExpression {
+                    operator: Operator::Minus,
+                    right: Expression::Identifier(ast::Identifier {
+                        value: "a".to_string()
+                    }),
+                })),
+                operator: Operator::Asterisk,
+                right: Expression::NumberLiteral(ast::NumberLiteral { value: 2 }),
+            }))
+        );
+
+        let mut lexer = Peekable::new("!flag;");
+        let expression = parse_expression(&mut lexer, Precedence::Lowest).unwrap();
+        assert_eq!(
+            expression,
+            Expression::PrefixExpression(Box::new(ast::PrefixExpression {
+                operator: Operator::Bang,
+                right: Expression::Identifier(ast::Identifier {
+                    value: "flag".to_string()
+                }),
+            }))
+        );
+    }
+
+    #[test]
+    fn test_infix_expression() {
+        let mut lexer = Peekable::new("1 + 2;");
+        let expression = parse_expression(&mut lexer, Precedence::Lowest).unwrap();
+        assert_eq!(
+            expression,
+            Expression::InfixExpression(Box::new(ast::InfixExpression {
+                left: Expression::NumberLiteral(ast::NumberLiteral { value: 1 }),
+                operator: Operator::Plus,
+                right: Expression::NumberLiteral(ast::NumberLiteral { value: 2 }),
+            }))
+        );
+
+        let mut lexer = Peekable::new("1 + 2 * 3;");
+        let expression = parse_expression(&mut lexer, Precedence::Lowest).unwrap();
+        assert_eq!(
+            expression,
+            Expression::InfixExpression(Box::new(ast::InfixExpression {
+                left: Expression::NumberLiteral(ast::NumberLiteral { value: 1 }),
+                operator: Operator::Plus,
+                right: Expression::InfixExpression(Box::new(ast::InfixExpression {
+                    left: Expression::NumberLiteral(ast::NumberLiteral { value: 2 }),
+                    operator: Operator::Asterisk,
+                    right: Expression::NumberLiteral(ast::NumberLiteral { value: 3 }),
+                })),
+            }))
         );
 
         let mut lexer = Peekable::new("1 * 2 + 3;");
@@ -1010,14 +2903,19 @@ mod tests {
         assert_eq!(
             program,
             ast::Program {
+                lines: ast::StatementLines::default(),
                 statements: vec![
                     ast::Statement::VariableDeclaration(ast::VariableDeclaration {
                         name: "x".to_string(),
+                        type_annotation: None,
                         value: ast::Expression::NumberLiteral(ast::NumberLiteral { value: 1 }),
+                        position: ast::Position { line: 1 },
                     }),
                     ast::Statement::VariableDeclaration(ast::VariableDeclaration {
                         name: "y".to_string(),
+                        type_annotation: None,
                         value: ast::Expression::NumberLiteral(ast::NumberLiteral { value: 2 }),
+                        position: ast::Position { line: 1 },
                     }),
                     ast::Statement::Expression(ast::Expression::InfixExpression(Box::new(
                         ast::InfixExpression {
@@ -1043,12 +2941,13 @@ mod tests {
             };\
         ",
         );
-        let variableDeclaration = parse_variable_declaration(&mut lexer).unwrap();
+        let statement = parse_let_statement(&mut lexer).unwrap();
         assert_eq!(
-            variableDeclaration,
-            VariableDeclaration {
+            statement,
+            ast::Statement::VariableDeclaration(VariableDeclaration {
                 name: "a".to_string(),
-                value: Expression::FunctionLiteral(ast::FunctionLiteral {
+                type_annotation: None,
+                value: Expression::FunctionLiteral(Box::new(ast::FunctionLiteral {
                     parameters: vec![
                         ast::Identifier {
                             value: "x".to_string(),
@@ -1057,7 +2956,11 @@ mod tests {
                             value: "y".to_string(),
                         }
                     ],
+                    parameter_types: vec![None, None],
+                    return_type: None,
+                    rest: None,
                     body: ast::BlockExpression {
+                        lines: ast::StatementLines::default(),
                         statements: vec![ast::Statement::Expression(
                             ast::Expression::InfixExpression(Box::new(ast::InfixExpression {
                                 left: ast::Expression::Identifier(ast::Identifier {
@@ -1070,8 +2973,9 @@ mod tests {
                             }))
                         )],
                     },
-                }),
-            }
+                })),
+                position: ast::Position { line: 1 },
+            })
         )
     }
     #[test]
@@ -1092,6 +2996,8 @@ mod tests {
                     ast::Expression::NumberLiteral(ast::NumberLiteral { value: 1 }),
                     ast::Expression::NumberLiteral(ast::NumberLiteral { value: 2 }),
                 ],
+                position: ast::Position { line: 1 },
+                cache: MethodCache::default(),
             }))
         );
     }
@@ -1120,6 +3026,7 @@ mod tests {
                     }),
                 })),
                 consequence: ast::BlockExpression {
+                    lines: ast::StatementLines::default(),
                     statements: vec![ast::Statement::Expression(ast::Expression::Identifier(
                         ast::Identifier {
                             value: "x".to_string(),
@@ -1127,6 +3034,7 @@ mod tests {
                     ))],
                 },
                 alternative: Some(ast::BlockExpression {
+                    lines: ast::StatementLines::default(),
                     statements: vec![ast::Statement::Expression(ast::Expression::Identifier(
                         ast::Identifier {
                             value: "y".to_string(),
@@ -1162,10 +3070,99 @@ mod tests {
                         value: ast::Expression::NumberLiteral(ast::NumberLiteral { value: 4 }),
                     }),
                 ],
+                pool: LiteralPool::default(),
             })
         );
     }
 
+    #[test]
+    fn test_parse_map_keys() {
+        let mut lexer = Peekable::new("[\"my key\": 1, 2: 3, 0.5: 4]");
+        let expression = parse_expression(&mut lexer, Precedence::Lowest).unwrap();
+        let keys: Vec<String> = match expression {
+            Expression::MapLiteral(map) => map
+                .entries
+                .into_iter()
+                .map(|element| match element {
+                    ast::ArrayMapValue::MapKeyValue(key_value) => key_value.key,
+                    element => panic!("expected a key and value, got {:?}", element),
+                })
+                .collect(),
+            expression => panic!("expected a map literal, got {}", expression),
+        };
+        assert_eq!(keys, vec!["my key", "2", "0.5"]);
+
+        let mut lexer = Peekable::new("[(key): 1]");
+        match parse_expression(&mut lexer, Precedence::Lowest).unwrap() {
+            Expression::MapLiteral(map) => assert_eq!(
+                map.entries,
+                vec![ast::ArrayMapValue::ComputedKeyValue(
+                    ast::ComputedKeyValue {
+                        key: Expression::Identifier(ast::Identifier {
+                            value: "key".to_string()
+                        }),
+                        value: Expression::NumberLiteral(ast::NumberLiteral { value: 1 }),
+                    }
+                )]
+            ),
+            expression => panic!("expected a map literal, got {}", expression),
+        }
+
+        let mut lexer = Peekable::new("[f(): 1]");
+        assert_eq!(
+            parse_expression(&mut lexer, Precedence::Lowest)
+                .unwrap_err()
+                .message,
+            "computed map keys must be in parentheses"
+        );
+    }
+
+    #[test]
+    fn test_parse_map_literal() {
+        for (source, expected) in [
+            (
+                "[:]",
+                Expression::MapLiteral(ast::MapLiteral { entries: vec![] }),
+            ),
+            (
+                "[]",
+                Expression::ArrayLiteral(ast::ArrayLiteral {
+                    elements: vec![],
+                    pool: LiteralPool::default(),
+                }),
+            ),
+            (
+                "[1, a: 2]",
+                Expression::ArrayLiteral(ast::ArrayLiteral {
+                    elements: vec![
+                        ast::ArrayMapValue::Value(Expression::NumberLiteral(ast::NumberLiteral {
+                            value: 1,
+                        })),
+                        ast::ArrayMapValue::MapKeyValue(ast::MapKeyValue {
+                            key: "a".to_string(),
+                            value: Expression::NumberLiteral(ast::NumberLiteral { value: 2 }),
+                        }),
+                    ],
+                    pool: LiteralPool::default(),
+                }),
+            ),
+        ] {
+            let mut lexer = Peekable::new(source);
+            assert_eq!(
+                parse_expression(&mut lexer, Precedence::Lowest).unwrap(),
+                expected
+            );
+        }
+
+        let mut lexer = Peekable::new("[:1]");
+        assert_eq!(
+            parse_expression(&mut lexer, Precedence::Lowest)
+                .unwrap_err()
+                .message,
+            "expected ]"
+        );
+    }
+
     #[test]
     fn test_comment() {
         let mut lexer = Peekable::new(
@@ -1178,13 +3175,806 @@ mod tests {
         assert_eq!(
             program,
             ast::Program {
+                lines: ast::StatementLines::default(),
                 statements: vec![ast::Statement::VariableDeclaration(
                     ast::VariableDeclaration {
                         name: "x".to_string(),
+                        type_annotation: None,
                         value: ast::Expression::NumberLiteral(ast::NumberLiteral { value: 1 }),
+                        position: ast::Position { line: 1 },
                     }
                 )],
             }
         );
     }
+
+    #[test]
+    fn test_parse_constant_declaration() {
+        let mut lexer = Peekable::new("const PI = 3;");
+        let program = parse(&mut lexer).unwrap();
+        assert_eq!(
+            program,
+            ast::Program {
+                lines: ast::StatementLines::default(),
+                statements: vec![ast::Statement::ConstantDeclaration(
+                    ast::ConstantDeclaration {
+                        name: "PI".to_string(),
+                        type_annotation: None,
+                        value: ast::Expression::NumberLiteral(ast::NumberLiteral { value: 3 }),
+                        position: ast::Position { line: 1 },
+                    }
+                )],
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_string_literals() {
+        let cases = vec![
+            (r#""a\tb \"c\" \\""#, "a\tb \"c\" \\"),
+            (
+                "\"\"\"\nfirst\n  \"second\"\n\"\"\"",
+                "first\n  \"second\"\n",
+            ),
+            (r#"r"C:\path\n""#, "C:\\path\\n"),
+            ("r\"\"\"\\d \"x\" y\"\"\"", "\\d \"x\" y"),
+        ];
+        for (source, expected) in cases {
+            let mut lexer = Peekable::new(source);
+            assert_eq!(
+                parse_expression(&mut lexer, Precedence::Lowest).unwrap(),
+                ast::Expression::StringLiteral(ast::StringLiteral {
+                    value: expected.to_string()
+                }),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_unknown_escape() {
+        let mut lexer = Peekable::new(r#""\q""#);
+        assert_eq!(
+            parse_expression(&mut lexer, Precedence::Lowest)
+                .unwrap_err()
+                .message,
+            "unknown escape sequence \\q"
+        );
+    }
+
+    #[test]
+    fn test_parse_template_literal() {
+        let mut lexer = Peekable::new("\"\"\"a ${f(\"}\") + 1}\"\"\"");
+        assert_eq!(
+            parse_expression(&mut lexer, Precedence::Lowest).unwrap(),
+            ast::Expression::TemplateLiteral(ast::TemplateLiteral {
+                parts: vec![
+                    ast::TemplatePart::String("a ".to_string()),
+                    ast::TemplatePart::Expression(ast::Expression::InfixExpression(Box::new(
+                        ast::InfixExpression {
+                            left: ast::Expression::CallExpression(Box::new(ast::CallExpression {
+                                left: ast::Expression::Identifier(ast::Identifier {
+                                    value: "f".to_string()
+                                }),
+                                arguments: vec![ast::Expression::StringLiteral(
+                                    ast::StringLiteral {
+                                        value: "}".to_string()
+                                    }
+                                )],
+                                position: ast::Position { line: 1 },
+                                cache: MethodCache::default(),
+                            })),
+                            operator: ast::Operator::Plus,
+                            right: ast::Expression::NumberLiteral(ast::NumberLiteral { value: 1 }),
+                        }
+                    ))),
+                ],
+            })
+        );
+
+        let mut lexer = Peekable::new("\"${1\"");
+        assert_eq!(
+            parse_expression(&mut lexer, Precedence::Lowest)
+                .unwrap_err()
+                .message,
+            "unterminated ${ in string"
+        );
+    }
+
+    #[test]
+    fn test_parse_public() {
+        let source = "a + 1; rest";
+        let (expression, rest) = parse_expression_public(source).unwrap();
+        assert_eq!(expression.to_string(), "identifier a+number Literal 1");
+        assert_eq!(&source[rest], "; rest");
+
+        let (statement, rest) = parse_statement_public(source).unwrap();
+        assert!(matches!(statement, ast::Statement::Expression(_)));
+        assert_eq!(&source[rest], " rest");
+
+        assert_eq!(
+            parse_statement_public("let = 1;").unwrap_err().message,
+            "expected identifier"
+        );
+    }
+
+    #[test]
+    fn test_parse_update_expression() {
+        let mut lexer = Peekable::new("-a[0]++");
+        assert_eq!(
+            parse_expression(&mut lexer, Precedence::Lowest)
+                .unwrap()
+                .to_string(),
+            "-element access identifier a++"
+        );
+        let mut lexer = Peekable::new("1++");
+        assert_eq!(
+            parse_expression(&mut lexer, Precedence::Lowest)
+                .unwrap_err()
+                .message,
+            "++ needs a variable or an element to update"
+        );
+    }
+
+    #[test]
+    fn test_parse_import_statement() {
+        let mut lexer = Peekable::new("import config from \"data/config.json\"; let from = 1;");
+        let program = parse(&mut lexer).unwrap();
+        assert_eq!(
+            program.statements[0],
+            ast::Statement::ImportStatement(ast::ImportStatement {
+                name: "config".to_string(),
+                path: "data/config.json".to_string(),
+            })
+        );
+        assert_eq!(program.statements.len(), 2);
+
+        let mut lexer = Peekable::new("import \"json\"; import \"lib/math.ank\";");
+        let names: Vec<String> = parse(&mut lexer)
+            .unwrap()
+            .statements
+            .into_iter()
+            .map(|statement| match statement {
+                ast::Statement::ImportStatement(import) => import.name,
+                _ => String::new(),
+            })
+            .collect();
+        assert_eq!(names, vec!["json", "math"]);
+
+        let mut lexer = Peekable::new("import \"my-lib\";");
+        assert_eq!(
+            parse(&mut lexer).unwrap_err().message,
+            "cannot name the import of my-lib, write import name from \"my-lib\""
+        );
+        let mut lexer = Peekable::new("import config \"config.json\";");
+        assert_eq!(parse(&mut lexer).unwrap_err().message, "expected from");
+        let mut lexer = Peekable::new("import config from \"${name}.json\";");
+        assert_eq!(
+            parse(&mut lexer).unwrap_err().message,
+            "import paths cannot contain ${...}"
+        );
+    }
+
+    #[test]
+    fn test_parse_type_annotations() {
+        let mut lexer = Peekable::new(
+            "let x: number = 1; const NAME: string = \"a\"; let add = fn(a: number, b): number { a + b };",
+        );
+        let program = parse(&mut lexer).unwrap();
+        match &program.statements[..] {
+            [ast::Statement::VariableDeclaration(x), ast::Statement::ConstantDeclaration(name), ast::Statement::VariableDeclaration(add)] =>
+            {
+                assert_eq!(x.type_annotation.as_deref(), Some("number"));
+                assert_eq!(name.type_annotation.as_deref(), Some("string"));
+                match &add.value {
+                    ast::Expression::FunctionLiteral(function) => {
+                        assert_eq!(
+                            function.parameter_types,
+                            vec![Some("number".to_string()), None]
+                        );
+                        assert_eq!(function.return_type.as_deref(), Some("number"));
+                    }
+                    value => panic!("expected a function, got {:?}", value),
+                }
+            }
+            statements => panic!("unexpected statements {:?}", statements),
+        }
+
+        let mut lexer = Peekable::new("let x: 1 = 1;");
+        assert_eq!(
+            parse(&mut lexer).unwrap_err().message,
+            "expected a type name after :"
+        );
+    }
+
+    #[test]
+    fn test_parse_array_comprehension() {
+        let mut lexer = Peekable::new("[x * 2 for x in nums if x > 0]; [a for (x in b) {}];");
+        let program = parse(&mut lexer).unwrap();
+        assert!(matches!(
+            &program.statements[0],
+            ast::Statement::Expression(ast::Expression::ArrayComprehension(comprehension))
+                if comprehension.variable.value == "x" && comprehension.condition.is_some()
+        ));
+        // a `for` loop in parentheses is still an element of its own
+        assert!(matches!(
+            &program.statements[1],
+            ast::Statement::Expression(ast::Expression::ArrayLiteral(array))
+                if array.elements.len() == 2
+        ));
+
+        for (source, message) in [
+            (
+                "[a, b for x in c]",
+                "a comprehension takes a single value before for",
+            ),
+            ("[a for x of c]", "expected in"),
+            ("[a for x in c d]", "expected ]"),
+        ] {
+            let mut lexer = Peekable::new(source);
+            assert_eq!(parse(&mut lexer).unwrap_err().message, message);
+        }
+    }
+
+    #[test]
+    fn test_parse_struct_declaration() {
+        let mut lexer = Peekable::new("struct Point { x, y fn sum() { self.x + self.y } }");
+        let program = parse(&mut lexer).unwrap();
+        match &program.statements[..] {
+            [ast::Statement::StructDeclaration(declaration)] => {
+                assert_eq!(declaration.name, "Point");
+                assert_eq!(declaration.fields, vec!["x", "y"]);
+                assert_eq!(declaration.methods[0].name, "sum");
+            }
+            statements => panic!("expected a struct declaration, got {:?}", statements),
+        }
+
+        for (source, message) in [
+            (
+                "struct Point { x, x }",
+                "x is declared twice in struct Point",
+            ),
+            ("struct Point { 1 }", "expected a field or a method"),
+            ("struct { x }", "expected struct name"),
+        ] {
+            let mut lexer = Peekable::new(source);
+            assert_eq!(parse(&mut lexer).unwrap_err().message, message);
+        }
+    }
+
+    #[test]
+    fn test_parse_export_statement() {
+        let mut lexer = Peekable::new("export let a = 1, b = 2; export const C = 3;");
+        let program = parse(&mut lexer).unwrap();
+        let names: Vec<Vec<&str>> = program
+            .statements
+            .iter()
+            .map(|statement| match statement {
+                ast::Statement::ExportStatement(export) => export.names(),
+                _ => Vec::new(),
+            })
+            .collect();
+        assert_eq!(names, vec![vec!["a", "b"], vec!["C"]]);
+
+        for (source, message) in [
+            (
+                "export x = 1;",
+                "only let and const declarations of names can be exported",
+            ),
+            (
+                "export let [a, b] = [1, 2];",
+                "only let and const declarations of names can be exported",
+            ),
+            (
+                "if (x) { export let a = 1; }",
+                "export is only allowed at the top level",
+            ),
+        ] {
+            let mut lexer = Peekable::new(source);
+            assert_eq!(
+                parse(&mut lexer).unwrap_err().message,
+                message,
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_conditional_expression() {
+        let mut lexer = Peekable::new("a || b ? 1 : c ? 2 : 3");
+        let mut desugared = Peekable::new("if (a || b) { 1 } else { if (c) { 2 } else { 3 } }");
+        assert_eq!(
+            parse_expression(&mut lexer, Precedence::Lowest).unwrap(),
+            parse_expression(&mut desugared, Precedence::Lowest).unwrap()
+        );
+
+        let mut lexer = Peekable::new("a ? 1");
+        assert_eq!(
+            parse_expression(&mut lexer, Precedence::Lowest)
+                .unwrap_err()
+                .message,
+            "expected : in conditional expression"
+        );
+    }
+
+    #[test]
+    fn test_parse_pipe_expression() {
+        for (piped, call) in [
+            ("x |> f |> g(2)", "g(f(x), 2)"),
+            ("a + 1 |> list.push", "list.push(a + 1)"),
+            ("x |> fn(v) { v }", "fn(v) { v }(x)"),
+            ("y = x |> f", "y = f(x)"),
+        ] {
+            let piped = parse_expression(&mut Peekable::new(piped), Precedence::Lowest);
+            let call = parse_expression(&mut Peekable::new(call), Precedence::Lowest);
+            assert_eq!(piped, call);
+        }
+    }
+
+    #[test]
+    fn test_parse_optional_chaining() {
+        let mut lexer = Peekable::new("a?.b?[0]");
+        let expression = parse_expression(&mut lexer, Precedence::Lowest).unwrap();
+        assert_eq!(
+            expression,
+            Expression::ElementAccessExpression(Box::new(ast::ElementAccessExpression {
+                left: Expression::MemberAccessExpression(Box::new(ast::MemberAccessExpression {
+                    left: Expression::Identifier(ast::Identifier {
+                        value: "a".to_string(),
+                    }),
+                    member: "b".to_string(),
+                    optional: true,
+                })),
+                index: Expression::NumberLiteral(ast::NumberLiteral { value: 0 }),
+                optional: true,
+            }))
+        );
+
+        let mut lexer = Peekable::new("a?[1:2]");
+        assert_eq!(
+            parse_expression(&mut lexer, Precedence::Lowest)
+                .unwrap_err()
+                .message,
+            "?[ cannot take a slice"
+        );
+    }
+
+    #[test]
+    fn test_parse_member_access_expression() {
+        let mut lexer = Peekable::new("a.b[0].c = 1");
+        let expression = parse_expression(&mut lexer, Precedence::Lowest).unwrap();
+        assert_eq!(
+            expression,
+            Expression::Assign(Box::new(ast::Assign {
+                left: Expression::MemberAccessExpression(Box::new(ast::MemberAccessExpression {
+                    left: Expression::ElementAccessExpression(Box::new(
+                        ast::ElementAccessExpression {
+                            left: Expression::MemberAccessExpression(Box::new(
+                                ast::MemberAccessExpression {
+                                    left: Expression::Identifier(ast::Identifier {
+                                        value: "a".to_string(),
+                                    }),
+                                    member: "b".to_string(),
+                                    optional: false,
+                                }
+                            )),
+                            index: Expression::NumberLiteral(ast::NumberLiteral { value: 0 }),
+                            optional: false,
+                        }
+                    )),
+                    member: "c".to_string(),
+                    optional: false,
+                })),
+                right: Expression::NumberLiteral(ast::NumberLiteral { value: 1 }),
+                position: ast::Position { line: 1 },
+            }))
+        );
+
+        let mut lexer = Peekable::new("a.1");
+        assert_eq!(
+            parse_expression(&mut lexer, Precedence::Lowest)
+                .unwrap_err()
+                .message,
+            "expected identifier after ."
+        );
+    }
+
+    #[test]
+    fn test_parse_arrow_function() {
+        for (arrow, function) in [
+            ("(x, y) => x + y", "fn(x, y) { x + y }"),
+            ("() => 1", "fn() { 1 }"),
+            ("(x) => { let y = x; y * 2 }", "fn(x) { let y = x; y * 2 }"),
+        ] {
+            let mut lexer = Peekable::new(arrow);
+            let mut expected = Peekable::new(function);
+            assert_eq!(
+                parse_expression(&mut lexer, Precedence::Lowest).unwrap(),
+                parse_expression(&mut expected, Precedence::Lowest).unwrap()
+            );
+        }
+
+        for (source, message) in [
+            (
+                "(x + 1) => x",
+                "arrow function parameters must be identifiers",
+            ),
+            ("(x, y)", "expected => after the parameters"),
+            ("()", "expected expression in ()"),
+        ] {
+            let mut lexer = Peekable::new(source);
+            assert_eq!(
+                parse_expression(&mut lexer, Precedence::Lowest)
+                    .unwrap_err()
+                    .message,
+                message
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_rest_parameter() {
+        for source in ["fn(first, ...rest) { rest }", "(first, ...rest) => rest"] {
+            let mut lexer = Peekable::new(source);
+            match parse_expression(&mut lexer, Precedence::Lowest).unwrap() {
+                Expression::FunctionLiteral(function) => {
+                    assert_eq!(
+                        function.parameters,
+                        vec![ast::Identifier {
+                            value: "first".to_string()
+                        }]
+                    );
+                    assert_eq!(
+                        function.rest,
+                        Some(ast::Identifier {
+                            value: "rest".to_string()
+                        })
+                    );
+                }
+                expression => panic!("expected a function, got {}", expression),
+            }
+        }
+
+        for (source, message) in [
+            ("fn(...rest, last) {}", "the rest parameter must be last"),
+            ("fn(...) {}", "expected identifier after ..."),
+            ("(...rest)", "expected => after the parameters"),
+        ] {
+            let mut lexer = Peekable::new(source);
+            assert_eq!(
+                parse_expression(&mut lexer, Precedence::Lowest)
+                    .unwrap_err()
+                    .message,
+                message
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_trailing_commas() {
+        for (with_comma, without_comma) in [
+            ("[1, 2, 3,]", "[1, 2, 3]"),
+            ("[a: 1, b: 2,]", "[a: 1, b: 2]"),
+            ("f(1, 2,)", "f(1, 2)"),
+            ("fn(a, b,) { a }", "fn(a, b) { a }"),
+            ("fn(a, ...rest,) { a }", "fn(a, ...rest) { a }"),
+            ("(a, b,) => a", "(a, b) => a"),
+            (
+                "switch (x) { case [a, [b,],]: { a } }",
+                "switch (x) { case [a, [b]]: { a } }",
+            ),
+        ] {
+            let mut lexer = Peekable::new(with_comma);
+            let mut expected = Peekable::new(without_comma);
+            assert_eq!(
+                parse_expression(&mut lexer, Precedence::Lowest).unwrap(),
+                parse_expression(&mut expected, Precedence::Lowest).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_rest_pattern() {
+        let mut lexer = Peekable::new("let [a, ...rest] = x;");
+        match parse_statement(&mut lexer).unwrap() {
+            ast::Statement::DestructuringDeclaration(declaration) => {
+                assert_eq!(declaration.pattern.elements.len(), 1);
+                assert_eq!(
+                    declaration.pattern.rest,
+                    Some(ast::Identifier {
+                        value: "rest".to_string()
+                    })
+                );
+            }
+            statement => panic!("expected a destructuring declaration, got {:?}", statement),
+        }
+
+        for (source, message) in [
+            ("let [...rest, a] = x;", "the rest pattern must be last"),
+            ("let [a, ...] = x;", "expected identifier after ..."),
+        ] {
+            let mut lexer = Peekable::new(source);
+            assert_eq!(parse_statement(&mut lexer).unwrap_err().message, message);
+        }
+    }
+
+    #[test]
+    fn test_parse_keyed_pattern() {
+        let mut lexer = Peekable::new("let [x: px, y: [a, b]] = point;");
+        match parse_statement(&mut lexer).unwrap() {
+            ast::Statement::DestructuringDeclaration(declaration) => assert_eq!(
+                declaration.pattern.elements[0],
+                ast::Pattern::Keyed(Box::new(ast::KeyedPattern {
+                    key: "x".to_string(),
+                    value: ast::Pattern::Identifier(ast::Identifier {
+                        value: "px".to_string()
+                    }),
+                }))
+            ),
+            statement => panic!("expected a destructuring declaration, got {:?}", statement),
+        }
+
+        for source in ["let [x: a, b] = p;", "let [x: a, ...rest] = p;"] {
+            let mut lexer = Peekable::new(source);
+            assert_eq!(
+                parse_statement(&mut lexer).unwrap_err().message,
+                "a pattern with keys cannot have positional elements"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_multiple_assign() {
+        let mut lexer = Peekable::new("a, b[0] = b, a;");
+        assert_eq!(
+            parse_statement(&mut lexer).unwrap(),
+            ast::Statement::MultipleAssign(ast::MultipleAssign {
+                targets: vec![
+                    Expression::Identifier(ast::Identifier {
+                        value: "a".to_string()
+                    }),
+                    Expression::ElementAccessExpression(Box::new(ast::ElementAccessExpression {
+                        left: Expression::Identifier(ast::Identifier {
+                            value: "b".to_string()
+                        }),
+                        index: Expression::NumberLiteral(ast::NumberLiteral { value: 0 }),
+                        optional: false,
+                    })),
+                ],
+                values: vec![
+                    Expression::Identifier(ast::Identifier {
+                        value: "b".to_string()
+                    }),
+                    Expression::Identifier(ast::Identifier {
+                        value: "a".to_string()
+                    }),
+                ],
+                position: ast::Position { line: 1 },
+            })
+        );
+
+        let mut lexer = Peekable::new("let x = 1, y = 2;");
+        assert_eq!(
+            parse_statement(&mut lexer).unwrap(),
+            ast::Statement::DeclarationList(ast::DeclarationList {
+                declarations: vec![
+                    ast::VariableDeclaration {
+                        name: "x".to_string(),
+                        type_annotation: None,
+                        value: Expression::NumberLiteral(ast::NumberLiteral { value: 1 }),
+                        position: ast::Position { line: 1 },
+                    },
+                    ast::VariableDeclaration {
+                        name: "y".to_string(),
+                        type_annotation: None,
+                        value: Expression::NumberLiteral(ast::NumberLiteral { value: 2 }),
+                        position: ast::Position { line: 1 },
+                    },
+                ],
+            })
+        );
+
+        for (source, message) in [
+            ("a, b = 1;", "expected 2 values to assign, got 1"),
+            ("a, f() = 1, 2;", "invalid assignment target"),
+            ("a, b;", "expected = after the assignment targets"),
+            ("a, b = 1, 2", "expected semicolon"),
+        ] {
+            let mut lexer = Peekable::new(source);
+            assert_eq!(parse_statement(&mut lexer).unwrap_err().message, message);
+        }
+    }
+
+    #[test]
+    fn test_parse_match_expression() {
+        let mut lexer = Peekable::new("match (x) { [_, y] => y, _ => 0 }");
+        assert_eq!(
+            parse_expression(&mut lexer, Precedence::Lowest).unwrap(),
+            Expression::MatchExpression(Box::new(ast::MatchExpression {
+                expression: Expression::Identifier(ast::Identifier {
+                    value: "x".to_string()
+                }),
+                arms: vec![
+                    ast::MatchArm {
+                        pattern: ast::Pattern::Array(ast::ArrayPattern {
+                            elements: vec![
+                                ast::Pattern::Wildcard,
+                                ast::Pattern::Identifier(ast::Identifier {
+                                    value: "y".to_string()
+                                }),
+                            ],
+                            rest: None,
+                        }),
+                        body: Expression::Identifier(ast::Identifier {
+                            value: "y".to_string()
+                        }),
+                    },
+                    ast::MatchArm {
+                        pattern: ast::Pattern::Wildcard,
+                        body: Expression::NumberLiteral(ast::NumberLiteral { value: 0 }),
+                    },
+                ],
+            }))
+        );
+
+        for (source, message) in [
+            ("match (x) { 1 2 }", "expected => after the pattern"),
+            (
+                "match (x) { 1 => 1 2 => 2 }",
+                "expected , or } after a match arm",
+            ),
+            (
+                "match (x) { a: 1 => 1 }",
+                "a key: pattern has to be inside [ ]",
+            ),
+        ] {
+            let mut lexer = Peekable::new(source);
+            assert_eq!(
+                parse_expression(&mut lexer, Precedence::Lowest)
+                    .unwrap_err()
+                    .message,
+                message
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_fallthrough() {
+        let mut lexer = Peekable::new("switch (x) { case 1: { y; fallthrough; } default: {} }");
+        match parse_expression(&mut lexer, Precedence::Lowest).unwrap() {
+            Expression::SwitchExpression(switch_expression) => {
+                assert!(switch_expression.cases[0].fallthrough);
+                assert_eq!(switch_expression.cases[0].body.statements.len(), 1);
+            }
+            expression => panic!("expected a switch expression, got {}", expression),
+        }
+
+        for (source, message) in [
+            (
+                "switch (x) { case 1: { fallthrough; y; } default: {} }",
+                "fallthrough is only allowed at the end of a case",
+            ),
+            (
+                "if (x) { fallthrough; }",
+                "fallthrough is only allowed at the end of a case",
+            ),
+            (
+                "switch (x) { case 1: { fallthrough; } case [a]: {} }",
+                "cannot fallthrough into a case with a pattern",
+            ),
+            (
+                "switch (x) { case 1: { fallthrough; } }",
+                "cannot fallthrough from the last case",
+            ),
+        ] {
+            let mut lexer = Peekable::new(source);
+            assert_eq!(
+                parse_expression(&mut lexer, Precedence::Lowest)
+                    .unwrap_err()
+                    .message,
+                message
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_for_index() {
+        let mut lexer = Peekable::new("for (i, v in x) {}");
+        let for_expression = parse_for_expression(&mut lexer).unwrap();
+        assert_eq!(for_expression.index.unwrap().value, "i");
+        assert_eq!(for_expression.variable.value, "v");
+
+        let mut lexer = Peekable::new("for (i, in x) {}");
+        assert_eq!(
+            parse_for_expression(&mut lexer).unwrap_err().message,
+            "expected identifier"
+        );
+    }
+
+    #[test]
+    fn test_parse_do_while() {
+        let mut lexer = Peekable::new("do { x; } while (x < 3)");
+        match parse_expression(&mut lexer, Precedence::Lowest).unwrap() {
+            Expression::DoWhileExpression(do_while) => {
+                assert_eq!(do_while.body.statements.len(), 1);
+                assert!(matches!(do_while.condition, Expression::InfixExpression(_)));
+            }
+            expression => panic!("expected a do while expression, got {}", expression),
+        }
+
+        let mut lexer = Peekable::new("do { x; } (x < 3)");
+        assert_eq!(
+            parse_expression(&mut lexer, Precedence::Lowest)
+                .unwrap_err()
+                .message,
+            "expected while after the do block"
+        );
+    }
+
+    #[test]
+    fn test_parse_loop() {
+        let mut lexer = Peekable::new("let x = loop { break 1; };");
+        match parse_statement(&mut lexer).unwrap() {
+            ast::Statement::VariableDeclaration(declaration) => match declaration.value {
+                Expression::LoopExpression(loop_expression) => {
+                    assert_eq!(loop_expression.body.statements.len(), 1);
+                }
+                expression => panic!("expected a loop expression, got {}", expression),
+            },
+            statement => panic!("expected a let statement, got {:?}", statement),
+        }
+    }
+
+    #[test]
+    fn test_parse_loop_labels() {
+        let mut lexer = Peekable::new("outer: for (x in xs) { loop { break outer x; }; }");
+        match parse_statement(&mut lexer).unwrap() {
+            ast::Statement::BlockReturnStatement(block_return) => match block_return.value {
+                Expression::ForExpression(for_expression) => {
+                    assert_eq!(for_expression.label, Some("outer".to_string()));
+                }
+                expression => panic!("expected a for expression, got {}", expression),
+            },
+            statement => panic!("expected a labeled loop, got {:?}", statement),
+        }
+
+        // outside of a loop with the label, the name is the value to break with
+        let mut lexer = Peekable::new("loop { break outer; }");
+        let expression = parse_expression(&mut lexer, Precedence::Lowest).unwrap();
+        match expression {
+            Expression::LoopExpression(loop_expression) => {
+                assert_eq!(
+                    loop_expression.body.statements[0],
+                    ast::Statement::BreakStatement(ast::BreakStatement {
+                        label: None,
+                        value: Some(Expression::Identifier(ast::Identifier {
+                            value: "outer".to_string()
+                        })),
+                    })
+                );
+            }
+            expression => panic!("expected a loop expression, got {}", expression),
+        }
+
+        for (source, message) in [
+            ("outer: x;", "only loops can have labels"),
+            (
+                "loop { continue outer; }",
+                "no enclosing loop is labeled outer",
+            ),
+            (
+                "outer: loop { outer: loop {} }",
+                "label outer is already in use",
+            ),
+            (
+                "outer: loop { let f = fn() { continue outer; }; }",
+                "no enclosing loop is labeled outer",
+            ),
+        ] {
+            let mut lexer = Peekable::new(source);
+            assert_eq!(parse_statement(&mut lexer).unwrap_err().message, message);
+        }
+    }
 }
@@ -1,13 +1,18 @@
+use crate::ast::Operator;
 use crate::token::Token;
 
 #[derive(Debug, PartialEq, PartialOrd, Ord, Eq, Clone, Copy)]
 pub enum Precedence {
     Lowest,      // 最低優先度
     Assign,      // =
+    Pipe,        // |>
+    Ternary,     // ? :
+    Coalesce,    // ??
     LogicalOr,   // ||
     LogicalAnd,  // &&
     Equals,      // ==, !=
-    LessGreater, // <, >, <=, >=
+    LessGreater, // <, >, <=, >=, in
+    Range,       // ..
     Sum,         // +, -
     Product,     // *, /, %
     Prefix,      // -x, !x
@@ -26,19 +31,46 @@ impl Precedence {
     pub fn get_precedence(token: &Token) -> Precedence {
         match token {
             Token::Assign => Precedence::Assign,
+            Token::Pipe => Precedence::Pipe,
+            Token::Question => Precedence::Ternary,
+            Token::QuestionQuestion => Precedence::Coalesce,
             Token::Or => Precedence::LogicalOr,
             Token::And => Precedence::LogicalAnd,
             Token::Equal | Token::NotEqual => Precedence::Equals,
             Token::LessThan
             | Token::LessThanOrEqual
             | Token::GreaterThan
-            | Token::GreaterThanOrEqual => Precedence::LessGreater,
+            | Token::GreaterThanOrEqual
+            | Token::In => Precedence::LessGreater,
+            Token::DotDot => Precedence::Range,
             Token::Plus | Token::Minus => Precedence::Sum,
             Token::Asterisk | Token::Slash | Token::Percent => Precedence::Product,
             Token::Bang | Token::Minus => Precedence::Prefix,
             Token::LParen => Precedence::Call,
-            Token::LBracket => Precedence::Index,
+            Token::LBracket
+            | Token::Dot
+            | Token::QuestionBracket
+            | Token::QuestionDot
+            | Token::Increment
+            | Token::Decrement => Precedence::Index,
             _ => Precedence::Lowest,
         }
     }
+    pub fn get_operator_precedence(operator: &Operator) -> Precedence {
+        match operator {
+            Operator::Coalesce => Precedence::Coalesce,
+            Operator::Or => Precedence::LogicalOr,
+            Operator::And => Precedence::LogicalAnd,
+            Operator::Equal | Operator::NotEqual => Precedence::Equals,
+            Operator::LessThan
+            | Operator::LessThanOrEqual
+            | Operator::GreaterThan
+            | Operator::GreaterThanOrEqual
+            | Operator::In => Precedence::LessGreater,
+            Operator::Range => Precedence::Range,
+            Operator::Plus | Operator::Minus => Precedence::Sum,
+            Operator::Asterisk | Operator::Slash | Operator::Percent => Precedence::Product,
+            Operator::Bang => Precedence::Prefix,
+        }
+    }
 }
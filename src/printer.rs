@@ -0,0 +1,1260 @@
+use crate::ast::{self, Expression, Statement};
+use crate::interpreter::object::format_float;
+use crate::precedence::Precedence;
+use crate::token::Token;
+use logos::Logos;
+
+const INDENT: &str = "    ";
+
+// Prints an AST back to Ankara source code.
+// Parentheses are only emitted where the parser needs them, so `parse(print(ast)) == ast`.
+pub fn print_program(program: &ast::Program) -> String {
+    let mut result = String::new();
+    for statement in &program.statements {
+        result.push_str(&print_statement(statement, 0));
+        result.push('\n');
+    }
+    result
+}
+
+pub fn print_statement(statement: &Statement, depth: usize) -> String {
+    match statement {
+        Statement::VariableDeclaration(variable_declaration) => format!(
+            "let {}{} = {};",
+            variable_declaration.name,
+            print_type_annotation(&variable_declaration.type_annotation),
+            print_expression(&variable_declaration.value, depth)
+        ),
+        Statement::DeclarationList(declaration_list) => {
+            let declarations: Vec<String> = declaration_list
+                .declarations
+                .iter()
+                .map(|declaration| {
+                    format!(
+                        "{}{} = {}",
+                        declaration.name,
+                        print_type_annotation(&declaration.type_annotation),
+                        print_expression(&declaration.value, depth)
+                    )
+                })
+                .collect();
+            format!("let {};", declarations.join(", "))
+        }
+        Statement::DestructuringDeclaration(destructuring_declaration) => {
+            let value = &destructuring_declaration.value;
+            match &destructuring_declaration.alternative {
+                // an `if` without `else`, also on the right of an assignment, would take the
+                // let's `else` as its own
+                Some(alternative) => format!(
+                    "let {} = {} else {};",
+                    print_array_pattern(&destructuring_declaration.pattern),
+                    print_operand(value, depth, |child| *child <= Precedence::Assign),
+                    print_block(alternative, depth)
+                ),
+                None => format!(
+                    "let {} = {};",
+                    print_array_pattern(&destructuring_declaration.pattern),
+                    print_expression(value, depth)
+                ),
+            }
+        }
+        Statement::ConstantDeclaration(constant_declaration) => format!(
+            "const {}{} = {};",
+            constant_declaration.name,
+            print_type_annotation(&constant_declaration.type_annotation),
+            print_expression(&constant_declaration.value, depth)
+        ),
+        Statement::Expression(expression) => format!("{};", print_expression(expression, depth)),
+        Statement::MultipleAssign(multiple_assign) => {
+            let print_all = |expressions: &[Expression]| {
+                expressions
+                    .iter()
+                    .map(|expression| print_expression(expression, depth))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            };
+            format!(
+                "{} = {};",
+                print_all(&multiple_assign.targets),
+                print_all(&multiple_assign.values)
+            )
+        }
+        Statement::ReturnStatement(return_statement) => {
+            format!(
+                "return {};",
+                print_expression(&return_statement.value, depth)
+            )
+        }
+        Statement::ThrowStatement(throw_statement) => {
+            format!("throw {};", print_expression(&throw_statement.value, depth))
+        }
+        Statement::DeferStatement(defer_statement) => {
+            format!("defer {};", print_expression(&defer_statement.value, depth))
+        }
+        Statement::AssertStatement(assert_statement) => match &assert_statement.message {
+            Some(message) => format!(
+                "assert {}, {};",
+                print_expression(&assert_statement.condition, depth),
+                print_expression(message, depth)
+            ),
+            None => format!(
+                "assert {};",
+                print_expression(&assert_statement.condition, depth)
+            ),
+        },
+        Statement::BlockReturnStatement(block_return) => {
+            print_expression(&block_return.value, depth)
+        }
+        Statement::BreakStatement(break_statement) => {
+            let label = match &break_statement.label {
+                Some(label) => format!(" {}", label),
+                None => String::new(),
+            };
+            match &break_statement.value {
+                Some(value) => format!("break{} {};", label, print_expression(value, depth)),
+                None => format!("break{};", label),
+            }
+        }
+        Statement::ContinueStatement(continue_statement) => match &continue_statement.label {
+            Some(label) => format!("continue {};", label),
+            None => "continue;".to_string(),
+        },
+        Statement::WatchDeclaration(watch_declaration) => format!(
+            "watch {} = {};",
+            watch_declaration.name,
+            print_block(&watch_declaration.block, depth)
+        ),
+        Statement::TestBlock(test_block) => format!(
+            "test {} {}",
+            print_string(&test_block.name),
+            print_block(&test_block.body, depth)
+        ),
+        Statement::ImportStatement(import_statement) => format!(
+            "import {} from {};",
+            import_statement.name,
+            print_string(&import_statement.path)
+        ),
+        Statement::ExportStatement(export_statement) => format!(
+            "export {}",
+            print_statement(&export_statement.declaration, depth)
+        ),
+        Statement::StructDeclaration(struct_declaration) => {
+            if struct_declaration.fields.is_empty() && struct_declaration.methods.is_empty() {
+                return format!("struct {} {{}}", struct_declaration.name);
+            }
+            let indent = INDENT.repeat(depth + 1);
+            let mut result = format!("struct {} {{\n", struct_declaration.name);
+            if !struct_declaration.fields.is_empty() {
+                result.push_str(&format!(
+                    "{}{},\n",
+                    indent,
+                    struct_declaration.fields.join(", ")
+                ));
+            }
+            for method in &struct_declaration.methods {
+                result.push_str(&format!(
+                    "{}fn {}({} {}\n",
+                    indent,
+                    method.name,
+                    print_parameters(&method.function),
+                    print_block(&method.function.body, depth + 1)
+                ));
+            }
+            result.push_str(&INDENT.repeat(depth));
+            result.push('}');
+            result
+        }
+    }
+}
+
+pub fn print_expression(expression: &Expression, depth: usize) -> String {
+    match expression {
+        Expression::InfixExpression(infix) => {
+            let precedence = Precedence::get_operator_precedence(&infix.operator);
+            // operators are left associative, so only a strictly lower left operand needs parentheses
+            let left = print_operand(&infix.left, depth, |child| child.is_lower_than(&precedence));
+            let right = print_operand(&infix.right, depth, |child| {
+                !child.is_higher_than(&precedence)
+            });
+            format!("{} {} {}", left, infix.operator, right)
+        }
+        Expression::PrefixExpression(prefix) => {
+            let right = print_operand(&prefix.right, depth, |child| {
+                child.is_lower_than(&Precedence::Prefix)
+            });
+            // `- -x` would lex as a decrement
+            if prefix.operator == ast::Operator::Minus && right.starts_with('-') {
+                format!("{}({})", prefix.operator, right)
+            } else {
+                format!("{}{}", prefix.operator, right)
+            }
+        }
+        Expression::UpdateExpression(update) => {
+            if update.prefix {
+                format!(
+                    "{}{}",
+                    update.operator,
+                    print_expression(&update.target, depth)
+                )
+            } else {
+                format!(
+                    "{}{}",
+                    print_expression(&update.target, depth),
+                    update.operator
+                )
+            }
+        }
+        Expression::NumberLiteral(number) => number.value.to_string(),
+        Expression::FloatLiteral(float) => format_float(float.value),
+        Expression::Identifier(identifier) => identifier.value.clone(),
+        Expression::FunctionLiteral(function) => format!(
+            "fn({} {}",
+            print_parameters(function),
+            print_block(&function.body, depth)
+        ),
+        Expression::CallExpression(call) => {
+            let arguments: Vec<String> = call
+                .arguments
+                .iter()
+                .map(|argument| print_expression(argument, depth))
+                .collect();
+            format!(
+                "{}({})",
+                print_postfix_operand(&call.left, depth),
+                arguments.join(", ")
+            )
+        }
+        Expression::IfExpression(if_expression) => {
+            let mut result = format!(
+                "if ({}) {}",
+                print_expression(&if_expression.condition, depth),
+                print_block(&if_expression.consequence, depth)
+            );
+            if let Some(alternative) = &if_expression.alternative {
+                match &alternative.statements[..] {
+                    // `else if` parses into an `else` block holding only the next `if`
+                    [Statement::BlockReturnStatement(block_return)]
+                        if matches!(block_return.value, Expression::IfExpression(_)) =>
+                    {
+                        result.push_str(&format!(
+                            " else {}",
+                            print_expression(&block_return.value, depth)
+                        ));
+                    }
+                    _ => result.push_str(&format!(" else {}", print_block(alternative, depth))),
+                }
+            }
+            result
+        }
+        Expression::BooleanLiteral(boolean) => boolean.value.to_string(),
+        Expression::NullLiteral => "null".to_string(),
+        Expression::LineLiteral(_) => "__line".to_string(),
+        Expression::FileLiteral => "__file".to_string(),
+        Expression::StringLiteral(string) => print_string(&string.value),
+        Expression::TemplateLiteral(template) => print_template(template),
+        Expression::ArrayLiteral(array) => format!("[{}]", print_elements(&array.elements, depth)),
+        Expression::ArrayComprehension(comprehension) => {
+            let condition = match &comprehension.condition {
+                Some(condition) => format!(" if {}", print_expression(condition, depth)),
+                None => String::new(),
+            };
+            format!(
+                "[{} for {} in {}{}]",
+                print_expression(&comprehension.element, depth),
+                comprehension.variable.value,
+                print_expression(&comprehension.iterable, depth),
+                condition
+            )
+        }
+        Expression::MapLiteral(map) if map.entries.is_empty() => "[:]".to_string(),
+        Expression::MapLiteral(map) => format!("[{}]", print_elements(&map.entries, depth)),
+        Expression::ElementAccessExpression(element_access) => format!(
+            "{}{}[{}]",
+            print_postfix_operand(&element_access.left, depth),
+            if element_access.optional { "?" } else { "" },
+            print_expression(&element_access.index, depth)
+        ),
+        Expression::MemberAccessExpression(member_access) => format!(
+            "{}{}.{}",
+            print_postfix_operand(&member_access.left, depth),
+            if member_access.optional { "?" } else { "" },
+            member_access.member
+        ),
+        Expression::SliceExpression(slice) => {
+            let bound = |bound: &Option<Expression>| match bound {
+                Some(bound) => print_expression(bound, depth),
+                None => String::new(),
+            };
+            format!(
+                "{}[{}:{}]",
+                print_postfix_operand(&slice.left, depth),
+                bound(&slice.start),
+                bound(&slice.end)
+            )
+        }
+        Expression::ForExpression(for_expression) => format!(
+            "{}for ({}{} in {}) {}",
+            print_label(&for_expression.label),
+            match &for_expression.index {
+                Some(index) => format!("{}, ", index.value),
+                None => String::new(),
+            },
+            for_expression.variable.value,
+            print_expression(&for_expression.iterable, depth),
+            print_block(&for_expression.body, depth)
+        ),
+        Expression::DoWhileExpression(do_while) => format!(
+            "{}do {} while ({})",
+            print_label(&do_while.label),
+            print_block(&do_while.body, depth),
+            print_expression(&do_while.condition, depth)
+        ),
+        Expression::LoopExpression(loop_expression) => {
+            format!(
+                "{}loop {}",
+                print_label(&loop_expression.label),
+                print_block(&loop_expression.body, depth)
+            )
+        }
+        Expression::TryExpression(try_expression) => {
+            let mut result = format!("try {}", print_block(&try_expression.body, depth));
+            if let Some(handler) = &try_expression.handler {
+                result.push_str(&format!(
+                    " catch ({}) {}",
+                    handler.parameter.value,
+                    print_block(&handler.body, depth)
+                ));
+            }
+            if let Some(finalizer) = &try_expression.finalizer {
+                result.push_str(&format!(" finally {}", print_block(finalizer, depth)));
+            }
+            result
+        }
+        Expression::MatchExpression(match_expression) => {
+            let indent = INDENT.repeat(depth + 1);
+            let mut result = format!(
+                "match ({}) {{\n",
+                print_expression(&match_expression.expression, depth)
+            );
+            for arm in &match_expression.arms {
+                result.push_str(&format!(
+                    "{}{} => {},\n",
+                    indent,
+                    print_pattern(&arm.pattern),
+                    print_expression(&arm.body, depth + 1)
+                ));
+            }
+            result.push_str(&format!("{}}}", INDENT.repeat(depth)));
+            result
+        }
+        Expression::SwitchExpression(switch_expression) => {
+            let indent = INDENT.repeat(depth + 1);
+            let mut result = format!(
+                "switch ({}) {{\n",
+                print_expression(&switch_expression.expression, depth)
+            );
+            for case in &switch_expression.cases {
+                let condition = match &case.condition {
+                    ast::CaseCondition::Value(condition) => {
+                        let printed = print_expression(condition, depth + 1);
+                        // a value starting with `[` would be read back as a pattern
+                        if printed.starts_with('[') {
+                            format!("({})", printed)
+                        } else {
+                            printed
+                        }
+                    }
+                    ast::CaseCondition::Pattern(pattern) => print_array_pattern(pattern),
+                };
+                let last = if case.fallthrough {
+                    Some("fallthrough;")
+                } else {
+                    None
+                };
+                result.push_str(&format!(
+                    "{}case {}: {}\n",
+                    indent,
+                    condition,
+                    print_block_with(&case.body, depth + 1, last)
+                ));
+            }
+            if let Some(default) = &switch_expression.default {
+                result.push_str(&format!(
+                    "{}default: {}\n",
+                    indent,
+                    print_block(&default.body, depth + 1)
+                ));
+            }
+            result.push_str(&INDENT.repeat(depth));
+            result.push('}');
+            result
+        }
+        Expression::Assign(assign) => format!(
+            "{} = {}",
+            print_postfix_operand(&assign.left, depth),
+            print_expression(&assign.right, depth)
+        ),
+        Expression::BlockExpression(block) => print_block(block, depth),
+    }
+}
+
+pub fn print_block(block: &ast::BlockExpression, depth: usize) -> String {
+    print_block_with(block, depth, None)
+}
+
+// A block with one more line printed after its statements, like the `fallthrough;` of a case.
+fn print_block_with(block: &ast::BlockExpression, depth: usize, last: Option<&str>) -> String {
+    if block.statements.is_empty() && last.is_none() {
+        return "{}".to_string();
+    }
+    let indent = INDENT.repeat(depth + 1);
+    let mut result = "{\n".to_string();
+    let statements = block
+        .statements
+        .iter()
+        .map(|statement| print_statement(statement, depth + 1));
+    for line in statements.chain(last.map(str::to_string)) {
+        result.push_str(&indent);
+        result.push_str(&line);
+        result.push('\n');
+    }
+    result.push_str(&INDENT.repeat(depth));
+    result.push('}');
+    result
+}
+
+// The parameter list of a function and its return type, `a: number, b): number` without the
+// opening parenthesis.
+fn print_parameters(function: &ast::FunctionLiteral) -> String {
+    let mut parameters: Vec<String> = function
+        .parameters
+        .iter()
+        .zip(&function.parameter_types)
+        .map(|(parameter, parameter_type)| {
+            format!(
+                "{}{}",
+                parameter.value,
+                print_type_annotation(parameter_type)
+            )
+        })
+        .collect();
+    if let Some(rest) = &function.rest {
+        parameters.push(format!("...{}", rest.value));
+    }
+    format!(
+        "{}){}",
+        parameters.join(", "),
+        print_type_annotation(&function.return_type)
+    )
+}
+
+fn print_type_annotation(type_annotation: &Option<String>) -> String {
+    match type_annotation {
+        Some(name) => format!(": {}", name),
+        None => String::new(),
+    }
+}
+
+// The precedence an expression binds with when it is used as an operand.
+fn print_elements(elements: &[ast::ArrayMapValue], depth: usize) -> String {
+    let elements: Vec<String> = elements
+        .iter()
+        .map(|element| match element {
+            ast::ArrayMapValue::Value(value) => print_expression(value, depth),
+            ast::ArrayMapValue::MapKeyValue(key_value) => format!(
+                "{}: {}",
+                print_key(&key_value.key),
+                print_expression(&key_value.value, depth)
+            ),
+            ast::ArrayMapValue::ComputedKeyValue(key_value) => format!(
+                "({}): {}",
+                print_expression(&key_value.key, depth),
+                print_expression(&key_value.value, depth)
+            ),
+        })
+        .collect();
+    elements.join(", ")
+}
+
+// Keys that do not lex as a single name, such as `my key` or `for`, are quoted.
+fn print_key(key: &str) -> String {
+    let mut tokens = Token::lexer(key);
+    if tokens.next() == Some(Ok(Token::Identifier)) && tokens.span() == (0..key.len()) {
+        key.to_string()
+    } else {
+        print_string(key)
+    }
+}
+
+fn print_string(value: &str) -> String {
+    format!("\"{}\"", escape_string(value))
+}
+
+// Expressions that contain strings themselves need the triple quoted form.
+fn print_template(template: &ast::TemplateLiteral) -> String {
+    let mut content = String::new();
+    for part in &template.parts {
+        match part {
+            ast::TemplatePart::String(value) => content.push_str(&escape_string(value)),
+            ast::TemplatePart::Expression(expression) => {
+                content.push_str(&format!("${{{}}}", print_expression(expression, 0)))
+            }
+        }
+    }
+    let has_strings = template.parts.iter().any(|part| match part {
+        ast::TemplatePart::Expression(expression) => print_expression(expression, 0).contains('"'),
+        ast::TemplatePart::String(_) => false,
+    });
+    if has_strings {
+        format!("\"\"\"{}\"\"\"", content)
+    } else {
+        format!("\"{}\"", content)
+    }
+}
+
+fn escape_string(value: &str) -> String {
+    let mut result = String::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '$' if chars.peek() == Some(&'{') => result.push_str("\\$"),
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            '\0' => result.push_str("\\0"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+fn print_array_pattern(pattern: &ast::ArrayPattern) -> String {
+    let mut elements: Vec<String> = pattern.elements.iter().map(print_pattern).collect();
+    if let Some(rest) = &pattern.rest {
+        elements.push(format!("...{}", rest.value));
+    }
+    format!("[{}]", elements.join(", "))
+}
+
+fn print_pattern(pattern: &ast::Pattern) -> String {
+    match pattern {
+        ast::Pattern::Identifier(identifier) => identifier.value.clone(),
+        ast::Pattern::Array(array) => print_array_pattern(array),
+        ast::Pattern::Literal(literal) => print_expression(literal, 0),
+        ast::Pattern::Keyed(keyed) => format!("{}: {}", keyed.key, print_pattern(&keyed.value)),
+        ast::Pattern::Wildcard => "_".to_string(),
+    }
+}
+
+fn get_expression_precedence(expression: &Expression) -> Precedence {
+    match expression {
+        Expression::InfixExpression(infix) => Precedence::get_operator_precedence(&infix.operator),
+        Expression::Assign(_) => Precedence::Assign,
+        Expression::PrefixExpression(_) => Precedence::Prefix,
+        Expression::UpdateExpression(update) if update.prefix => Precedence::Prefix,
+        Expression::UpdateExpression(_) => Precedence::Index,
+        Expression::NumberLiteral(_)
+        | Expression::FloatLiteral(_)
+        | Expression::Identifier(_)
+        | Expression::BooleanLiteral(_)
+        | Expression::NullLiteral
+        | Expression::LineLiteral(_)
+        | Expression::FileLiteral
+        | Expression::StringLiteral(_)
+        | Expression::TemplateLiteral(_)
+        | Expression::ArrayLiteral(_)
+        | Expression::ArrayComprehension(_)
+        | Expression::MapLiteral(_)
+        | Expression::CallExpression(_)
+        | Expression::ElementAccessExpression(_)
+        | Expression::MemberAccessExpression(_)
+        | Expression::SliceExpression(_) => Precedence::Index,
+        // keyword expressions read better wrapped when they are operands
+        Expression::FunctionLiteral(_)
+        | Expression::IfExpression(_)
+        | Expression::ForExpression(_)
+        | Expression::DoWhileExpression(_)
+        | Expression::LoopExpression(_)
+        | Expression::SwitchExpression(_)
+        | Expression::MatchExpression(_)
+        | Expression::TryExpression(_)
+        | Expression::BlockExpression(_) => Precedence::Lowest,
+    }
+}
+
+fn print_label(label: &Option<String>) -> String {
+    match label {
+        Some(label) => format!("{}: ", label),
+        None => String::new(),
+    }
+}
+
+fn print_operand(
+    expression: &Expression,
+    depth: usize,
+    needs_parentheses: impl Fn(&Precedence) -> bool,
+) -> String {
+    let printed = print_expression(expression, depth);
+    if needs_parentheses(&get_expression_precedence(expression)) {
+        format!("({})", printed)
+    } else {
+        printed
+    }
+}
+
+fn print_postfix_operand(expression: &Expression, depth: usize) -> String {
+    print_operand(expression, depth, |child| {
+        child.is_lower_than(&Precedence::Call)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use crate::ast::{self, Expression, Operator, Statement};
+    use crate::interpreter::inline_cache::MethodCache;
+    use crate::interpreter::pool::LiteralPool;
+    use crate::lexer::Peekable;
+    use crate::parser::parse;
+
+    use super::*;
+
+    fn arb_name() -> impl Strategy<Value = String> {
+        prop::sample::select(vec![
+            "a", "b", "foo", "bar", "baz2", "myKey", "my_var", "_tmp", "größe", "名前",
+        ])
+        .prop_map(|name| name.to_string())
+    }
+
+    fn arb_key() -> impl Strategy<Value = String> {
+        prop_oneof![
+            arb_name(),
+            prop::sample::select(vec!["my key", "a-b", "for", "1", "", "\\\""])
+                .prop_map(|key| key.to_string()),
+        ]
+    }
+
+    fn arb_type_annotation() -> impl Strategy<Value = Option<String>> {
+        prop::option::of(prop::sample::select(vec!["number", "string", "Point"]))
+            .prop_map(|name| name.map(str::to_string))
+    }
+
+    fn arb_identifier() -> impl Strategy<Value = ast::Identifier> {
+        arb_name().prop_map(|value| ast::Identifier { value })
+    }
+
+    fn arb_pattern_leaf() -> impl Strategy<Value = ast::Pattern> {
+        prop_oneof![
+            arb_identifier().prop_map(ast::Pattern::Identifier),
+            Just(ast::Pattern::Wildcard),
+            (0..100i64).prop_map(|value| {
+                ast::Pattern::Literal(Expression::NumberLiteral(ast::NumberLiteral { value }))
+            }),
+            "[a-z]{0,3}".prop_map(|value| {
+                ast::Pattern::Literal(Expression::StringLiteral(ast::StringLiteral { value }))
+            }),
+        ]
+    }
+
+    fn arb_array_pattern() -> impl Strategy<Value = ast::ArrayPattern> {
+        let leaf = (
+            prop::collection::vec(arb_pattern_leaf(), 0..3),
+            prop::option::of(arb_identifier()),
+        )
+            .prop_map(|(elements, rest)| ast::ArrayPattern { elements, rest });
+        leaf.prop_recursive(2, 8, 3, |inner| {
+            let element = prop_oneof![arb_pattern_leaf(), inner.prop_map(ast::Pattern::Array)];
+            prop_oneof![
+                (
+                    prop::collection::vec(element.clone(), 0..3),
+                    prop::option::of(arb_identifier()),
+                )
+                    .prop_map(|(elements, rest)| ast::ArrayPattern { elements, rest }),
+                // keyed elements cannot be mixed with the others
+                prop::collection::vec((arb_name(), element), 1..3).prop_map(|keyed| {
+                    ast::ArrayPattern {
+                        elements: keyed
+                            .into_iter()
+                            .map(|(key, value)| {
+                                ast::Pattern::Keyed(Box::new(ast::KeyedPattern { key, value }))
+                            })
+                            .collect(),
+                        rest: None,
+                    }
+                }),
+            ]
+        })
+    }
+
+    fn arb_operator() -> impl Strategy<Value = Operator> {
+        prop::sample::select(vec![
+            Operator::Plus,
+            Operator::Minus,
+            Operator::Asterisk,
+            Operator::Slash,
+            Operator::Percent,
+            Operator::Equal,
+            Operator::NotEqual,
+            Operator::LessThan,
+            Operator::LessThanOrEqual,
+            Operator::GreaterThan,
+            Operator::GreaterThanOrEqual,
+            Operator::And,
+            Operator::Or,
+            Operator::Range,
+            Operator::In,
+            Operator::Coalesce,
+        ])
+    }
+
+    fn arb_block(
+        expression: BoxedStrategy<Expression>,
+    ) -> impl Strategy<Value = ast::BlockExpression> {
+        let statement = prop_oneof![
+            (arb_name(), expression.clone()).prop_map(|(name, value)| {
+                Statement::VariableDeclaration(ast::VariableDeclaration {
+                    name,
+                    type_annotation: None,
+                    value,
+                    position: ast::Position::default(),
+                })
+            }),
+            expression.clone().prop_map(Statement::Expression),
+            expression
+                .clone()
+                .prop_map(|value| Statement::ReturnStatement(ast::ReturnStatement { value })),
+            prop::option::of(expression.clone()).prop_map(|value| Statement::BreakStatement(
+                ast::BreakStatement { label: None, value }
+            )),
+            Just(Statement::ContinueStatement(ast::ContinueStatement {
+                label: None
+            })),
+            expression
+                .clone()
+                .prop_map(|value| Statement::ThrowStatement(ast::ThrowStatement { value })),
+            expression
+                .clone()
+                .prop_map(|value| Statement::DeferStatement(ast::DeferStatement { value })),
+            (expression.clone(), prop::option::of(expression.clone())).prop_map(
+                |(condition, message)| Statement::AssertStatement(ast::AssertStatement {
+                    condition,
+                    message
+                })
+            ),
+        ];
+        (
+            prop::collection::vec(statement, 0..3),
+            prop::option::of(expression),
+        )
+            .prop_map(|(mut statements, block_return)| {
+                // a block level return is only unambiguous as the last statement of a block
+                if let Some(value) = block_return {
+                    statements.push(Statement::BlockReturnStatement(ast::BlockReturnStatement {
+                        value,
+                    }));
+                }
+                ast::BlockExpression {
+                    statements,
+                    lines: ast::StatementLines::default(),
+                }
+            })
+    }
+
+    fn arb_template_literal() -> impl Strategy<Value = ast::TemplateLiteral> {
+        let expression = prop_oneof![
+            (0..i64::MAX).prop_map(|value| Expression::NumberLiteral(ast::NumberLiteral { value })),
+            arb_identifier().prop_map(Expression::Identifier),
+            "[a-z]{0,3}".prop_map(|value| Expression::StringLiteral(ast::StringLiteral { value })),
+        ];
+        (
+            prop::collection::vec(("[a-z \"${]{0,4}", expression), 1..3),
+            "[a-z \"${]{0,4}",
+        )
+            .prop_map(|(pieces, last)| {
+                let mut parts = Vec::new();
+                for (text, expression) in pieces {
+                    if !text.is_empty() {
+                        parts.push(ast::TemplatePart::String(text));
+                    }
+                    parts.push(ast::TemplatePart::Expression(expression));
+                }
+                if !last.is_empty() {
+                    parts.push(ast::TemplatePart::String(last));
+                }
+                ast::TemplateLiteral { parts }
+            })
+    }
+
+    fn arb_expression() -> impl Strategy<Value = Expression> {
+        let leaf = prop_oneof![
+            (0..i64::MAX).prop_map(|value| Expression::NumberLiteral(ast::NumberLiteral { value })),
+            (0.0..1e12f64).prop_map(|value| Expression::FloatLiteral(ast::FloatLiteral { value })),
+            arb_identifier().prop_map(Expression::Identifier),
+            any::<bool>()
+                .prop_map(|value| Expression::BooleanLiteral(ast::BooleanLiteral { value })),
+            Just(Expression::NullLiteral),
+            Just(Expression::LineLiteral(ast::Position::default())),
+            Just(Expression::FileLiteral),
+            "[a-z \"\\\\\n\t${]{0,6}"
+                .prop_map(|value| Expression::StringLiteral(ast::StringLiteral { value })),
+            arb_template_literal().prop_map(Expression::TemplateLiteral),
+        ];
+        leaf.prop_recursive(4, 48, 4, |inner| {
+            let assign_target = prop_oneof![
+                arb_identifier().prop_map(Expression::Identifier),
+                (inner.clone(), inner.clone()).prop_map(|(left, index)| {
+                    Expression::ElementAccessExpression(Box::new(ast::ElementAccessExpression {
+                        left,
+                        index,
+                        optional: false,
+                    }))
+                }),
+                (inner.clone(), arb_name()).prop_map(|(left, member)| {
+                    Expression::MemberAccessExpression(Box::new(ast::MemberAccessExpression {
+                        left,
+                        member,
+                        optional: false,
+                    }))
+                }),
+            ];
+            let array_element = prop_oneof![
+                inner.clone().prop_map(ast::ArrayMapValue::Value),
+                (arb_key(), inner.clone()).prop_map(|(key, value)| {
+                    ast::ArrayMapValue::MapKeyValue(ast::MapKeyValue { key, value })
+                }),
+                (inner.clone(), inner.clone()).prop_map(|(key, value)| {
+                    ast::ArrayMapValue::ComputedKeyValue(ast::ComputedKeyValue { key, value })
+                }),
+            ];
+            let case_condition = prop_oneof![
+                inner.clone().prop_map(ast::CaseCondition::Value),
+                arb_array_pattern().prop_map(ast::CaseCondition::Pattern),
+            ];
+            let case = (case_condition, arb_block(inner.clone()), any::<bool>()).prop_map(
+                |(condition, body, fallthrough)| ast::Case {
+                    condition,
+                    body,
+                    fallthrough,
+                },
+            );
+            prop_oneof![
+                (inner.clone(), arb_operator(), inner.clone()).prop_map(
+                    |(left, operator, right)| {
+                        Expression::InfixExpression(Box::new(ast::InfixExpression {
+                            left,
+                            operator,
+                            right,
+                        }))
+                    }
+                ),
+                (
+                    prop::sample::select(vec![Operator::Minus, Operator::Bang]),
+                    inner.clone()
+                )
+                    .prop_map(|(operator, right)| {
+                        Expression::PrefixExpression(Box::new(ast::PrefixExpression {
+                            operator,
+                            right,
+                        }))
+                    }),
+                (
+                    prop::sample::select(vec![
+                        ast::UpdateOperator::Increment,
+                        ast::UpdateOperator::Decrement
+                    ]),
+                    any::<bool>(),
+                    assign_target.clone()
+                )
+                    .prop_map(|(operator, prefix, target)| {
+                        Expression::UpdateExpression(Box::new(ast::UpdateExpression {
+                            operator,
+                            prefix,
+                            target,
+                            position: ast::Position::default(),
+                        }))
+                    }),
+                (inner.clone(), prop::collection::vec(inner.clone(), 0..3)).prop_map(
+                    |(left, arguments)| {
+                        Expression::CallExpression(Box::new(ast::CallExpression {
+                            left,
+                            arguments,
+                            position: ast::Position::default(),
+                            cache: MethodCache::default(),
+                        }))
+                    }
+                ),
+                (inner.clone(), inner.clone(), any::<bool>()).prop_map(
+                    |(left, index, optional)| {
+                        Expression::ElementAccessExpression(Box::new(
+                            ast::ElementAccessExpression {
+                                left,
+                                index,
+                                optional,
+                            },
+                        ))
+                    }
+                ),
+                (inner.clone(), arb_name(), any::<bool>()).prop_map(|(left, member, optional)| {
+                    Expression::MemberAccessExpression(Box::new(ast::MemberAccessExpression {
+                        left,
+                        member,
+                        optional,
+                    }))
+                }),
+                (
+                    inner.clone(),
+                    prop::option::of(inner.clone()),
+                    prop::option::of(inner.clone())
+                )
+                    .prop_map(|(left, start, end)| {
+                        Expression::SliceExpression(Box::new(ast::SliceExpression {
+                            left,
+                            start,
+                            end,
+                        }))
+                    }),
+                // elements that all have keys parse as a map literal
+                prop::collection::vec(array_element, 0..4).prop_map(|elements| {
+                    let is_map = !elements.is_empty()
+                        && elements
+                            .iter()
+                            .all(|element| !matches!(element, ast::ArrayMapValue::Value(_)));
+                    if is_map {
+                        Expression::MapLiteral(ast::MapLiteral { entries: elements })
+                    } else {
+                        Expression::ArrayLiteral(ast::ArrayLiteral {
+                            elements,
+                            pool: LiteralPool::default(),
+                        })
+                    }
+                }),
+                Just(Expression::MapLiteral(ast::MapLiteral { entries: vec![] })),
+                (
+                    inner.clone(),
+                    arb_identifier(),
+                    inner.clone(),
+                    prop::option::of(inner.clone())
+                )
+                    .prop_map(|(element, variable, iterable, condition)| {
+                        Expression::ArrayComprehension(Box::new(ast::ArrayComprehension {
+                            element,
+                            variable,
+                            iterable,
+                            condition,
+                        }))
+                    }),
+                (
+                    inner.clone(),
+                    arb_block(inner.clone()),
+                    prop::option::of(arb_block(inner.clone()))
+                )
+                    .prop_map(|(condition, consequence, alternative)| {
+                        Expression::IfExpression(Box::new(ast::IfExpression {
+                            condition,
+                            consequence,
+                            alternative,
+                        }))
+                    }),
+                (
+                    prop::option::of(arb_identifier()),
+                    arb_identifier(),
+                    inner.clone(),
+                    arb_block(inner.clone())
+                )
+                    .prop_map(|(index, variable, iterable, body)| {
+                        Expression::ForExpression(Box::new(ast::ForExpression {
+                            label: None,
+                            index,
+                            variable,
+                            iterable,
+                            body,
+                        }))
+                    }),
+                arb_block(inner.clone()).prop_map(|body| {
+                    Expression::LoopExpression(Box::new(ast::LoopExpression { label: None, body }))
+                }),
+                (arb_block(inner.clone()), inner.clone()).prop_map(|(body, condition)| {
+                    Expression::DoWhileExpression(Box::new(ast::DoWhileExpression {
+                        label: None,
+                        body,
+                        condition,
+                    }))
+                }),
+                (
+                    inner.clone(),
+                    prop::collection::vec(case, 0..3),
+                    prop::option::of(arb_block(inner.clone()))
+                )
+                    .prop_map(|(expression, mut cases, default)| {
+                        // only fall into a case without a pattern, or into the default
+                        for index in 0..cases.len() {
+                            let allowed = match cases.get(index + 1) {
+                                Some(next) => {
+                                    matches!(next.condition, ast::CaseCondition::Value(_))
+                                }
+                                None => default.is_some(),
+                            };
+                            cases[index].fallthrough &= allowed;
+                        }
+                        Expression::SwitchExpression(Box::new(ast::SwitchExpression {
+                            expression,
+                            cases,
+                            default: default.map(|body| ast::Default { body }),
+                        }))
+                    }),
+                (
+                    prop::collection::vec((arb_identifier(), arb_type_annotation()), 0..3),
+                    arb_type_annotation(),
+                    prop::option::of(arb_identifier()),
+                    arb_block(inner.clone())
+                )
+                    .prop_map(|(parameters, return_type, rest, body)| {
+                        let (parameters, parameter_types) = parameters.into_iter().unzip();
+                        Expression::FunctionLiteral(Box::new(ast::FunctionLiteral {
+                            parameters,
+                            parameter_types,
+                            return_type,
+                            rest,
+                            body,
+                        }))
+                    }),
+                (
+                    inner.clone(),
+                    prop::collection::vec(
+                        (
+                            prop_oneof![
+                                arb_pattern_leaf(),
+                                arb_array_pattern().prop_map(ast::Pattern::Array)
+                            ],
+                            inner.clone()
+                        ),
+                        0..3
+                    )
+                )
+                    .prop_map(|(expression, arms)| {
+                        Expression::MatchExpression(Box::new(ast::MatchExpression {
+                            expression,
+                            arms: arms
+                                .into_iter()
+                                .map(|(pattern, body)| ast::MatchArm { pattern, body })
+                                .collect(),
+                        }))
+                    }),
+                (
+                    arb_block(inner.clone()),
+                    prop::option::of((arb_identifier(), arb_block(inner.clone()))),
+                    prop::option::of(arb_block(inner.clone()))
+                )
+                    .prop_filter("try needs catch or finally", |(_, handler, finalizer)| {
+                        handler.is_some() || finalizer.is_some()
+                    })
+                    .prop_map(|(body, handler, finalizer)| {
+                        Expression::TryExpression(Box::new(ast::TryExpression {
+                            body,
+                            handler: handler
+                                .map(|(parameter, body)| ast::CatchHandler { parameter, body }),
+                            finalizer,
+                        }))
+                    }),
+                arb_block(inner.clone()).prop_map(Expression::BlockExpression),
+                (assign_target, inner.clone()).prop_map(|(left, right)| {
+                    Expression::Assign(Box::new(ast::Assign {
+                        left,
+                        right,
+                        position: ast::Position::default(),
+                    }))
+                }),
+            ]
+        })
+    }
+
+    fn arb_program() -> impl Strategy<Value = ast::Program> {
+        let expression = arb_expression().boxed();
+        let statement = prop_oneof![
+            (arb_name(), arb_type_annotation(), expression.clone()).prop_map(
+                |(name, type_annotation, value)| {
+                    Statement::VariableDeclaration(ast::VariableDeclaration {
+                        name,
+                        type_annotation,
+                        value,
+                        position: ast::Position::default(),
+                    })
+                }
+            ),
+            (arb_name(), arb_type_annotation(), expression.clone()).prop_map(
+                |(name, type_annotation, value)| {
+                    Statement::ConstantDeclaration(ast::ConstantDeclaration {
+                        name,
+                        type_annotation,
+                        value,
+                        position: ast::Position::default(),
+                    })
+                }
+            ),
+            prop::collection::vec((arb_name(), expression.clone()), 2..4).prop_map(
+                |declarations| {
+                    Statement::DeclarationList(ast::DeclarationList {
+                        declarations: declarations
+                            .into_iter()
+                            .map(|(name, value)| ast::VariableDeclaration {
+                                name,
+                                type_annotation: None,
+                                value,
+                                position: ast::Position::default(),
+                            })
+                            .collect(),
+                    })
+                }
+            ),
+            prop::collection::vec(
+                (
+                    prop_oneof![
+                        arb_identifier().prop_map(Expression::Identifier),
+                        (expression.clone(), expression.clone()).prop_map(|(left, index)| {
+                            Expression::ElementAccessExpression(Box::new(
+                                ast::ElementAccessExpression {
+                                    left,
+                                    index,
+                                    optional: false,
+                                },
+                            ))
+                        }),
+                    ],
+                    expression.clone()
+                ),
+                2..4
+            )
+            .prop_map(|pairs| {
+                let (targets, values) = pairs.into_iter().unzip();
+                Statement::MultipleAssign(ast::MultipleAssign {
+                    targets,
+                    values,
+                    position: ast::Position::default(),
+                })
+            }),
+            (
+                arb_array_pattern(),
+                expression.clone(),
+                prop::option::of(arb_block(expression.clone()))
+            )
+                .prop_map(|(pattern, value, alternative)| {
+                    Statement::DestructuringDeclaration(ast::DestructuringDeclaration {
+                        pattern,
+                        value,
+                        alternative,
+                    })
+                }),
+            expression.clone().prop_map(Statement::Expression),
+            expression
+                .clone()
+                .prop_map(|value| Statement::ReturnStatement(ast::ReturnStatement { value })),
+            (arb_name(), arb_block(expression.clone())).prop_map(|(name, block)| {
+                Statement::WatchDeclaration(ast::WatchDeclaration { name, block })
+            }),
+            ("[a-z \"\\\\\n]{0,6}", arb_block(expression.clone()))
+                .prop_map(|(name, body)| Statement::TestBlock(ast::TestBlock { name, body })),
+            (arb_name(), "[a-z./\\\\\"]{0,8}").prop_map(|(name, path)| {
+                Statement::ImportStatement(ast::ImportStatement { name, path })
+            }),
+            (arb_name(), expression.clone(), any::<bool>()).prop_map(|(name, value, constant)| {
+                let declaration = if constant {
+                    Statement::ConstantDeclaration(ast::ConstantDeclaration {
+                        name,
+                        type_annotation: None,
+                        value,
+                        position: ast::Position::default(),
+                    })
+                } else {
+                    Statement::VariableDeclaration(ast::VariableDeclaration {
+                        name,
+                        type_annotation: None,
+                        value,
+                        position: ast::Position::default(),
+                    })
+                };
+                Statement::ExportStatement(ast::ExportStatement {
+                    declaration: Box::new(declaration),
+                })
+            }),
+            (
+                arb_name(),
+                prop::collection::btree_set(arb_name(), 0..3),
+                prop::collection::vec(arb_block(expression.clone()), 0..2)
+            )
+                .prop_map(|(name, fields, bodies)| {
+                    Statement::StructDeclaration(ast::StructDeclaration {
+                        name,
+                        fields: fields.into_iter().collect(),
+                        methods: bodies
+                            .into_iter()
+                            .enumerate()
+                            .map(|(index, body)| ast::StructMethod {
+                                name: format!("method{}", index),
+                                function: ast::FunctionLiteral {
+                                    parameters: Vec::new(),
+                                    parameter_types: Vec::new(),
+                                    return_type: None,
+                                    rest: None,
+                                    body,
+                                },
+                            })
+                            .collect(),
+                    })
+                }),
+        ];
+        (
+            prop::collection::vec(statement, 0..4),
+            prop::option::of(expression),
+        )
+            .prop_map(|(mut statements, block_return)| {
+                if let Some(value) = block_return {
+                    statements.push(Statement::BlockReturnStatement(ast::BlockReturnStatement {
+                        value,
+                    }));
+                }
+                ast::Program {
+                    statements,
+                    lines: ast::StatementLines::default(),
+                }
+            })
+    }
+
+    fn round_trip(source: &str) -> ast::Program {
+        let mut lexer = Peekable::new(source);
+        parse(&mut lexer).unwrap()
+    }
+
+    #[test]
+    fn test_print_else_if() {
+        let program = round_trip("if (a) { 1 } else if (b) { 2 } else { 3 }");
+        assert_eq!(
+            print_program(&program),
+            "if (a) {\n    1\n} else if (b) {\n    2\n} else {\n    3\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_print_minimal_parentheses() {
+        let program = round_trip("let x = (1 + 2) * 3 - (4 - 5);");
+        assert_eq!(print_program(&program), "let x = (1 + 2) * 3 - (4 - 5);\n");
+    }
+
+    #[test]
+    fn test_print_if_without_else_at_end_of_program() {
+        let program = round_trip("if (x) { 1 }");
+        assert_eq!(print_program(&program), "if (x) {\n    1\n}\n");
+    }
+
+    proptest! {
+        #[test]
+        fn test_parse_print_round_trip(program in arb_program()) {
+            let source = print_program(&program);
+            let mut lexer = Peekable::new(&source);
+            let parsed = parse(&mut lexer);
+            prop_assert_eq!(parsed, Ok(program), "source:\n{}", source);
+        }
+    }
+}
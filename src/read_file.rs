@@ -1,10 +1,112 @@
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::Read;
+use std::path::{Path, PathBuf};
 
-pub fn read_file(file_name: &str) -> Result<String, std::io::Error> {
-    let mut file = File::open(file_name)?;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[error("could not read {} (working directory {}): {source}{}", .path.display(), .cwd.display(), format_hint(.hint))]
+pub struct ReadFileError {
+    pub path: PathBuf,
+    pub cwd: PathBuf,
+    pub hint: Option<String>,
+    #[source]
+    pub source: std::io::Error,
+}
+
+pub fn read_file(file_name: &str) -> Result<String, ReadFileError> {
     let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
+    match File::open(file_name).and_then(|mut file| file.read_to_string(&mut contents)) {
+        Ok(_) => Ok(contents),
+        Err(source) => {
+            let path = PathBuf::from(file_name);
+            Err(ReadFileError {
+                cwd: std::env::current_dir().unwrap_or_default(),
+                hint: get_hint(&path),
+                path,
+                source,
+            })
+        }
+    }
+}
+
+fn format_hint(hint: &Option<String>) -> String {
+    match hint {
+        Some(hint) => format!("\nhint: {}", hint),
+        None => String::new(),
+    }
+}
+
+// Whether the path ends in .ank, in any case.
+pub fn has_ank_extension(path: &Path) -> bool {
+    match path.extension() {
+        Some(extension) => extension.eq_ignore_ascii_case("ank"),
+        None => false,
+    }
+}
+
+fn get_hint(path: &Path) -> Option<String> {
+    if path.is_dir() {
+        return Some(format!(
+            "{} is a directory, pass one of the .ank files in it",
+            path.display()
+        ));
+    }
+    let name = path.file_name()?.to_str()?.to_lowercase();
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    // `foo` for `foo.ank`, or `foo.ank` for `Foo.ANK` on a case-sensitive file system
+    let with_extension = format!("{}.ank", name);
+    for entry in fs::read_dir(parent).ok()? {
+        let candidate = entry.ok()?.path();
+        if candidate.is_dir() || !has_ank_extension(&candidate) {
+            continue;
+        }
+        let candidate_name = match candidate.file_name().and_then(|name| name.to_str()) {
+            Some(candidate_name) => candidate_name.to_lowercase(),
+            None => continue,
+        };
+        if candidate_name == name || candidate_name == with_extension {
+            return Some(format!(
+                "did you mean {}?",
+                parent.join(candidate.file_name()?).display()
+            ));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_file_hints() {
+        let dir = std::env::temp_dir().join(format!("ankara-read-file-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("Script.ANK"), "print(1);").unwrap();
+
+        let missing_extension = dir.join("script");
+        let error = read_file(missing_extension.to_str().unwrap()).unwrap_err();
+        assert_eq!(error.path, missing_extension);
+        assert_eq!(
+            error.hint,
+            Some(format!(
+                "did you mean {}?",
+                dir.join("Script.ANK").display()
+            ))
+        );
+
+        let error = read_file(dir.to_str().unwrap()).unwrap_err();
+        assert!(error.hint.unwrap().contains("is a directory"));
+
+        let error = read_file(dir.join("other.ank").to_str().unwrap()).unwrap_err();
+        assert_eq!(error.hint, None);
+        assert!(error.to_string().contains("working directory"));
 
-    Ok(contents)
+        assert!(has_ank_extension(&dir.join("Script.ANK")));
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,134 @@
+use std::io::{self, BufRead, Read, Write};
+use std::{cell::RefCell, rc::Rc};
+
+use crate::builtin::get_builtin_environment::get_builtin_environment;
+use crate::interpreter::environment::Environment;
+use crate::interpreter::evaluator::{EvalOption, Evaluator};
+use crate::interpreter::object::Object;
+use crate::lexer::Peekable;
+use crate::parser::parse;
+
+const PROMPT: &str = "> ";
+const PASTE_PROMPT: &str = "| ";
+
+// Evaluates every line read as a program in one environment that lasts for the session,
+// and writes its value. After `:paste`, lines are collected verbatim, blank ones included,
+// until a line with only `.`, and then evaluated together as one program.
+pub fn run_repl(reader: impl BufRead, mut writer: impl Write) -> std::io::Result<()> {
+    let builtins = Rc::new(RefCell::new(get_builtin_environment()));
+    let env = Rc::new(RefCell::new(Environment::fork_isolated(&builtins)));
+    let mut option = EvalOption::new();
+    let mut pasted: Option<String> = None;
+    write!(writer, "{}", PROMPT)?;
+    writer.flush()?;
+    for line in reader.lines() {
+        let line = line?;
+        match &mut pasted {
+            Some(_) if line.trim() == "." => {
+                let source = pasted.take().unwrap();
+                eval_input(&source, env.clone(), &mut option, &mut writer)?;
+            }
+            Some(buffer) => {
+                buffer.push_str(&line);
+                buffer.push('\n');
+            }
+            None if line.trim() == ":paste" => {
+                writeln!(writer, "// paste mode, end with a line containing only .")?;
+                pasted = Some(String::new());
+            }
+            None if line.trim().is_empty() => {}
+            None => eval_input(&line, env.clone(), &mut option, &mut writer)?,
+        }
+        let prompt = if pasted.is_some() {
+            PASTE_PROMPT
+        } else {
+            PROMPT
+        };
+        write!(writer, "{}", prompt)?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+fn eval_input(
+    source_code: &str,
+    env: Rc<RefCell<Environment>>,
+    option: &mut EvalOption,
+    writer: &mut impl Write,
+) -> std::io::Result<()> {
+    let mut lexer = Peekable::new(source_code);
+    let program = match parse(&mut lexer) {
+        Ok(program) => program,
+        Err(error) => return writeln!(writer, "{}", error),
+    };
+    match program.eval(env, option) {
+        Ok(obj) => match obj.unwrap_return().unwrap_block_return() {
+            Object::None | Object::Void => Ok(()),
+            value => writeln!(writer, "{}", value),
+        },
+        Err(error) => writeln!(writer, "Error: {}", error.message),
+    }
+}
+
+// Standard input for the REPL, locked only while a line is taken from it, so `stdinLines()`
+// in a line typed at the prompt can read the lines after it instead of waiting for the lock.
+#[derive(Default)]
+pub struct SharedStdin {
+    line: Vec<u8>,
+    position: usize,
+}
+
+impl Read for SharedStdin {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl BufRead for SharedStdin {
+    // Takes at most one line out of the buffer of standard input, leaving the rest there.
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.position == self.line.len() {
+            let stdin = io::stdin();
+            let mut lock = stdin.lock();
+            let buffered = lock.fill_buf()?;
+            let len = match buffered.iter().position(|byte| *byte == b'\n') {
+                Some(end) => end + 1,
+                None => buffered.len(),
+            };
+            self.line = buffered[..len].to_vec();
+            self.position = 0;
+            lock.consume(len);
+        }
+        Ok(&self.line[self.position..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.position = (self.position + amount).min(self.line.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_run_repl_paste_mode() {
+        let input = "let x = 40;\nx + 2\n:paste\nlet add = fn(a, b) {\n\n    a + b\n};\n\nadd(x, 2)\n.\nmissing\n";
+        let mut output = Vec::new();
+        run_repl(Cursor::new(input), &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "> > 42\n\
+             > // paste mode, end with a line containing only .\n\
+             | | | | | | | 42\n\
+             > Error: variable not found missing\n\
+             > "
+        );
+    }
+}
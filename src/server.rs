@@ -0,0 +1,173 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::thread;
+use std::{cell::RefCell, rc::Rc};
+
+use crate::builtin::get_builtin_environment::get_builtin_environment;
+use crate::interpreter::environment::Environment;
+use crate::interpreter::evaluator::{
+    call_stack_size, EvalOption, Evaluator, DEFAULT_MAX_CALL_DEPTH,
+};
+use crate::interpreter::object::{format_float, ArrayElement, Object};
+use crate::lexer::Peekable;
+use crate::parser::parse;
+
+// Serves newline-delimited eval requests on a TCP address, one thread per connection.
+pub fn serve_tcp(address: &str) -> std::io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    for stream in listener.incoming() {
+        let stream = stream?;
+        thread::Builder::new()
+            .stack_size(call_stack_size(DEFAULT_MAX_CALL_DEPTH))
+            .spawn(move || {
+                let reader = BufReader::new(stream.try_clone()?);
+                handle_connection(reader, stream)
+            })?;
+    }
+    Ok(())
+}
+
+// Serves newline-delimited eval requests on a Unix domain socket, one thread per connection.
+#[cfg(unix)]
+pub fn serve_unix(path: &str) -> std::io::Result<()> {
+    use std::os::unix::net::UnixListener;
+
+    let listener = UnixListener::bind(path)?;
+    for stream in listener.incoming() {
+        let stream = stream?;
+        thread::Builder::new()
+            .stack_size(call_stack_size(DEFAULT_MAX_CALL_DEPTH))
+            .spawn(move || {
+                let reader = BufReader::new(stream.try_clone()?);
+                handle_connection(reader, stream)
+            })?;
+    }
+    Ok(())
+}
+
+// Every line read is evaluated as a program in an environment that lives as long as the
+// connection, and answered with one line of JSON: `{"ok":true,"result":...}` or
+// `{"ok":false,"error":"..."}`.
+pub fn handle_connection(reader: impl BufRead, mut writer: impl Write) -> std::io::Result<()> {
+    let builtins = Rc::new(RefCell::new(get_builtin_environment()));
+    let env = Rc::new(RefCell::new(Environment::fork_isolated(&builtins)));
+    let mut option = EvalOption::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = eval_request(&line, env.clone(), &mut option);
+        writeln!(writer, "{}", response)?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+fn eval_request(
+    source_code: &str,
+    env: Rc<RefCell<Environment>>,
+    option: &mut EvalOption,
+) -> String {
+    let mut lexer = Peekable::new(source_code);
+    let program = match parse(&mut lexer) {
+        Ok(program) => program,
+        Err(error) => return error_response(&error.to_string()),
+    };
+    match program.eval(env, option) {
+        Ok(obj) => format!(
+            "{{\"ok\":true,\"result\":{}}}",
+            to_json(&obj.unwrap_return().unwrap_block_return())
+        ),
+        Err(error) => error_response(&error.message),
+    }
+}
+
+fn error_response(message: &str) -> String {
+    format!("{{\"ok\":false,\"error\":{}}}", quote_json(message))
+}
+
+// Arrays without keys become JSON arrays; arrays with keys become objects where the
+// positional elements are keyed by their index.
+pub fn to_json(obj: &Object) -> String {
+    match obj {
+        Object::Number(value) => value.to_string(),
+        Object::Float(value) if value.is_finite() => format_float(*value),
+        Object::Boolean(value) => value.to_string(),
+        Object::StringLiteral(value) => quote_json(value),
+        Object::Array(array) => {
+            let elements = array.elements.borrow();
+            let map = array.map.borrow();
+            let has_keys = array.is_map
+                || elements
+                    .iter()
+                    .any(|element| matches!(element, ArrayElement::Key(_)));
+            let mut values = Vec::new();
+            for (index, element) in elements.iter().enumerate() {
+                let (key, value) = match element {
+                    ArrayElement::Object(value) => (index.to_string(), to_json(value)),
+                    ArrayElement::Key(key) => match map.get(key) {
+                        Some(value) => (key.clone(), to_json(value)),
+                        None => (key.clone(), "null".to_string()),
+                    },
+                };
+                if has_keys {
+                    values.push(format!("{}:{}", quote_json(&key), value));
+                } else {
+                    values.push(value);
+                }
+            }
+            if has_keys {
+                format!("{{{}}}", values.join(","))
+            } else {
+                format!("[{}]", values.join(","))
+            }
+        }
+        Object::Null | Object::None | Object::Void => "null".to_string(),
+        obj => quote_json(&obj.to_string()),
+    }
+}
+
+fn quote_json(value: &str) -> String {
+    let mut result = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_handle_connection_keeps_environment() {
+        let input = "let x = 40;\nx + 2\n\n[1, key: \"a b\"]\nmissing\nlet = 1;\n";
+        let mut output = Vec::new();
+        handle_connection(Cursor::new(input), &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"ok\":true,\"result\":null}\n\
+             {\"ok\":true,\"result\":42}\n\
+             {\"ok\":true,\"result\":{\"0\":1,\"key\":\"a b\"}}\n\
+             {\"ok\":false,\"error\":\"variable not found missing\"}\n\
+             {\"ok\":false,\"error\":\"ParseError: expected identifier\"}\n"
+        );
+    }
+
+    #[test]
+    fn test_quote_json() {
+        assert_eq!(quote_json("a\"b\\c\nd"), "\"a\\\"b\\\\c\\nd\"");
+    }
+}
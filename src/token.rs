@@ -1,4 +1,4 @@
-use logos::Logos;
+use logos::{Lexer, Logos};
 use std::fmt;
 
 #[derive(Logos, Debug, PartialEq, Clone)]
@@ -8,18 +8,25 @@ pub enum Token {
     Newline,
     #[token("//")]
     Comment,
-    #[regex("[a-zA-Z][a-zA-Z0-9]*")]
+    // letters and digits of any script, so names can be written in any language
+    #[regex(r"[\p{XID_Start}_][\p{XID_Continue}]*")]
     Identifier,
     #[token("+")]
     Plus,
     #[token("-")]
     Minus,
+    #[token("++")]
+    Increment,
+    #[token("--")]
+    Decrement,
     #[token("*")]
     Asterisk,
     #[token("/")]
     Slash,
     #[token("||")]
     Or,
+    #[token("|>")]
+    Pipe,
     #[token("&&")]
     And,
     #[token("!=")]
@@ -38,8 +45,19 @@ pub enum Token {
     Bang,
     #[token("%")]
     Percent,
-    #[regex("[0-9]+")]
+    #[token("..")]
+    DotDot,
+    #[token("...")]
+    Ellipsis,
+    #[token(".")]
+    Dot,
+    // single underscores may separate digits, as in 1_000_000
+    #[regex("[0-9]+(_[0-9]+)*")]
     Number,
+    #[regex(r"[0-9]+(_[0-9]+)*\.[0-9]+(_[0-9]+)*")]
+    // scientific notation, as in 1e6 or 2.5e-3, is always a float
+    #[regex(r"[0-9]+(_[0-9]+)*(\.[0-9]+(_[0-9]+)*)?[eE][+-]?[0-9]+(_[0-9]+)*")]
+    Float,
     // if
     #[token("if")]
     If,
@@ -66,11 +84,23 @@ pub enum Token {
     Semicolon,
     #[token(":")]
     Colon,
+    #[token("?")]
+    Question,
+    #[token("??")]
+    QuestionQuestion,
+    #[token("?.")]
+    QuestionDot,
+    #[token("?[")]
+    QuestionBracket,
     // assignment
     #[token("=")]
     Assign,
+    #[token("=>")]
+    FatArrow,
     #[token("let")]
     Let,
+    #[token("const")]
+    Const,
     #[token(",")]
     Comma,
     #[token("fn")]
@@ -81,20 +111,64 @@ pub enum Token {
     True,
     #[token("false")]
     False,
-    #[regex(r#""[^"]*""#)]
+    #[token("null")]
+    Null,
+    // read on by `lex_string`, since a `${...}` inside may hold strings of its own
+    #[token("\"", lex_string)]
     String,
+    // keeps line breaks and needs no escaping for single quotes inside
+    #[regex(r#""{3}([^"\\]|"[^"\\]|""[^"\\]|"{0,2}\\(.|\n))*"{3}"#)]
+    MultilineString,
+    // no escape sequences, as in r"C:\path" or r"""..."""
+    #[regex(r#"r"[^"]*""#)]
+    #[regex(r#"r"{3}([^"]|"[^"]|""[^"])*"{3}"#)]
+    RawString,
     #[token("for")]
     For,
+    #[token("do")]
+    Do,
+    #[token("while")]
+    While,
+    #[token("loop")]
+    Loop,
     #[token("in")]
     In,
     #[token("switch")]
     Switch,
+    #[token("match")]
+    Match,
     #[token("case")]
     Case,
     #[token("default")]
     Default,
+    #[token("fallthrough")]
+    Fallthrough,
     #[token("watch")]
     Watch,
+    #[token("test")]
+    Test,
+    #[token("break")]
+    Break,
+    #[token("continue")]
+    Continue,
+    #[token("import")]
+    Import,
+    #[token("export")]
+    Export,
+    #[token("try")]
+    Try,
+    #[token("catch")]
+    Catch,
+    #[token("finally")]
+    Finally,
+    #[token("throw")]
+    Throw,
+    #[token("defer")]
+    Defer,
+    #[token("assert")]
+    Assert,
+    #[token("struct")]
+    Struct,
 }
 
 impl Token {
@@ -112,7 +186,8 @@ impl Token {
             | Token::LessThanOrEqual
             | Token::GreaterThan
             | Token::GreaterThanOrEqual
-            | Token::Percent => true,
+            | Token::Percent
+            | Token::DotDot => true,
             _ => false,
         }
     }
@@ -124,9 +199,12 @@ impl fmt::Display for Token {
             Token::Identifier => write!(f, "Identifier"),
             Token::Plus => write!(f, "Plus"),
             Token::Minus => write!(f, "Minus"),
+            Token::Increment => write!(f, "Increment"),
+            Token::Decrement => write!(f, "Decrement"),
             Token::Asterisk => write!(f, "Asterisk"),
             Token::Slash => write!(f, "Slash"),
             Token::Or => write!(f, "Or"),
+            Token::Pipe => write!(f, "Pipe"),
             Token::And => write!(f, "And"),
             Token::NotEqual => write!(f, "NotEqual"),
             Token::Equal => write!(f, "Equal"),
@@ -136,7 +214,11 @@ impl fmt::Display for Token {
             Token::GreaterThanOrEqual => write!(f, "GreaterThanOrEqual"),
             Token::Bang => write!(f, "Bang"),
             Token::Percent => write!(f, "Percent"),
+            Token::DotDot => write!(f, "DotDot"),
+            Token::Ellipsis => write!(f, "Ellipsis"),
+            Token::Dot => write!(f, "Dot"),
             Token::Number => write!(f, "Number"),
+            Token::Float => write!(f, "Float"),
             Token::If => write!(f, "If"),
             Token::Else => write!(f, "Else"),
             Token::LParen => write!(f, "LParen"),
@@ -147,22 +229,113 @@ impl fmt::Display for Token {
             Token::RBracket => write!(f, "RBracket"),
             Token::Semicolon => write!(f, "Semicolon"),
             Token::Colon => write!(f, "Colon"),
+            Token::Question => write!(f, "Question"),
+            Token::QuestionQuestion => write!(f, "QuestionQuestion"),
+            Token::QuestionDot => write!(f, "QuestionDot"),
+            Token::QuestionBracket => write!(f, "QuestionBracket"),
             Token::Assign => write!(f, "Assign"),
+            Token::FatArrow => write!(f, "FatArrow"),
             Token::Let => write!(f, "Let"),
+            Token::Const => write!(f, "Const"),
             Token::Comma => write!(f, "Comma"),
             Token::Function => write!(f, "Function"),
             Token::Return => write!(f, "Return"),
             Token::True => write!(f, "True"),
+            Token::Null => write!(f, "Null"),
             Token::False => write!(f, "False"),
             Token::String => write!(f, "String"),
+            Token::MultilineString => write!(f, "MultilineString"),
+            Token::RawString => write!(f, "RawString"),
             Token::Newline => write!(f, "Newline"),
             Token::For => write!(f, "For"),
+            Token::Do => write!(f, "Do"),
+            Token::While => write!(f, "While"),
+            Token::Loop => write!(f, "Loop"),
             Token::In => write!(f, "In"),
             Token::Switch => write!(f, "Switch"),
+            Token::Match => write!(f, "Match"),
             Token::Case => write!(f, "Case"),
             Token::Default => write!(f, "Default"),
+            Token::Fallthrough => write!(f, "Fallthrough"),
             Token::Watch => write!(f, "Watch"),
+            Token::Import => write!(f, "Import"),
+            Token::Export => write!(f, "Export"),
+            Token::Try => write!(f, "Try"),
+            Token::Catch => write!(f, "Catch"),
+            Token::Finally => write!(f, "Finally"),
+            Token::Throw => write!(f, "Throw"),
+            Token::Assert => write!(f, "Assert"),
+            Token::Defer => write!(f, "Defer"),
+            Token::Struct => write!(f, "Struct"),
+            Token::Test => write!(f, "Test"),
+            Token::Break => write!(f, "Break"),
+            Token::Continue => write!(f, "Continue"),
             Token::Comment => write!(f, "Comment"),
         }
     }
 }
+
+// Moves past the rest of a string literal after its opening quote. When a `${` is not closed,
+// the literal ends at the next quote, and the parser reports the `${`.
+fn lex_string(lex: &mut Lexer<Token>) -> bool {
+    let rest = lex.remainder();
+    match string_end(rest).or_else(|| quote_end(rest)) {
+        Some(length) => {
+            lex.bump(length);
+            true
+        }
+        None => false,
+    }
+}
+
+// The length of the rest of a string literal after its opening quote, closing quote included.
+// A quote inside `${...}` starts a string of the interpolated expression, so it does not end
+// the literal.
+fn string_end(source: &str) -> Option<usize> {
+    let mut index = 0;
+    while let Some(c) = source[index..].chars().next() {
+        index += c.len_utf8();
+        match c {
+            '\\' => index += source[index..].chars().next()?.len_utf8(),
+            '"' => return Some(index),
+            '$' if source[index..].starts_with('{') => {
+                index += 1;
+                index += interpolation_end(&source[index..])? + 1;
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// The length up to and including the first quote that is not escaped.
+fn quote_end(source: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (index, c) in source.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return Some(index + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+// The length of the expression of a `${...}` up to its closing brace, skipping the braces of
+// nested blocks and maps and whatever is inside the strings it holds.
+pub fn interpolation_end(source: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut index = 0;
+    while let Some(c) = source[index..].chars().next() {
+        index += c.len_utf8();
+        match c {
+            '"' => index += string_end(&source[index..])?,
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(index - 1),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}